@@ -1,5 +1,14 @@
-use bitceptron_retriever::{retriever::Retriever, setting::RetrieverSetting};
-use clap::{Arg, Command};
+use bitceptron_retriever::{
+    daemon::RetrieverDaemon,
+    error::RetrieverError,
+    retriever::Retriever,
+    run_history::RunHistory,
+    setting::RetrieverSetting,
+    sweep::{SweepGrouping, SweepOptions},
+};
+use bitcoin::absolute::LockTime;
+use clap::{Arg, ArgAction, Command};
+use tokio_util::sync::CancellationToken;
 use tracing_log::LogTracer;
 
 #[tokio::main]
@@ -16,42 +25,481 @@ async fn main() {
                 .short('c')
                 .help("Path to the config.toml file.")
                 .required(true)
+        )
+        .arg(
+            Arg::new("sparrow-export")
+                .long("sparrow-export")
+                .help("Writes a Sparrow importable wallet file into the data dir for each account with finds.")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("export-wifs")
+                .long("export-wifs")
+                .help("Prints the private key of each find in WIF, to sweep its funds with other wallet software. Anyone who sees them can spend the funds.")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("export-private-descriptors")
+                .long("export-private-descriptors")
+                .help("Prints the private ranged descriptor of each account with finds, to restore it in a descriptor wallet. Anyone who sees them can spend the funds.")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("wif-password")
+                .long("wif-password")
+                .help("Encrypts the exported private keys with BIP38 under the given password.")
+                .requires("export-wifs")
+        )
+        .arg(
+            Arg::new("sweep-to")
+                .long("sweep-to")
+                .value_name("ADDRESS")
+                .help("Writes an unsigned PSBT sweeping the unspent outputs of the finds to the given address into the data dir, for a wallet holding the keys to sign.")
+                .requires("fee-rate")
+        )
+        .arg(
+            Arg::new("fee-rate")
+                .long("fee-rate")
+                .value_name("SAT/VB")
+                .help("The fee rate of the sweep PSBT in sat/vB.")
+                .value_parser(clap::value_parser!(u64))
+                .requires("sweep-to")
+        )
+        .arg(
+            Arg::new("sweep-replaceable")
+                .long("sweep-replaceable")
+                .help("Signals replace-by-fee in the sweep, so its fee can be bumped if it does not confirm.")
+                .action(ArgAction::SetTrue)
+                .requires("sweep-to")
+        )
+        .arg(
+            Arg::new("sweep-per-find")
+                .long("sweep-per-find")
+                .help("Sweeps each find in a transaction of its own instead of consolidating them all, so the finds are not linked on chain.")
+                .action(ArgAction::SetTrue)
+                .requires("sweep-to")
+        )
+        .arg(
+            Arg::new("sweep-locktime")
+                .long("sweep-locktime")
+                .value_name("LOCKTIME")
+                .help("The locktime of the sweep, a block height or a unix time, e.g. the current height, or the height timelocked finds can be spent at.")
+                .value_parser(clap::value_parser!(u32))
+                .requires("sweep-to")
+        )
+        .arg(
+            Arg::new("sign-sweep")
+                .long("sign-sweep")
+                .help("Signs the sweep with the keys of the finds and writes the raw transaction into the data dir instead of the unsigned PSBT.")
+                .action(ArgAction::SetTrue)
+                .requires("sweep-to")
+        )
+        .arg(
+            Arg::new("broadcast-sweep")
+                .long("broadcast-sweep")
+                .help("Broadcasts the signed sweep through bitcoincore.")
+                .action(ArgAction::SetTrue)
+                .requires("sign-sweep")
+                .conflicts_with("offline")
+        )
+        .arg(
+            Arg::new("sweep-confirmations")
+                .long("sweep-confirmations")
+                .value_name("BLOCKS")
+                .help("Waits until the broadcast sweep has the given number of confirmations.")
+                .value_parser(clap::value_parser!(u64))
+                .requires("broadcast-sweep")
+        )
+        .arg(
+            Arg::new("rpc-user")
+                .long("rpc-user")
+                .help("The bitcoincore rpc user, used instead of the cookie file. Overrides bitcoincore_rpc_user of the config file.")
+                .requires("rpc-password")
+        )
+        .arg(
+            Arg::new("rpc-password")
+                .long("rpc-password")
+                .help("The bitcoincore rpc password. Overrides bitcoincore_rpc_password of the config file.")
+                .requires("rpc-user")
+        )
+        .arg(
+            Arg::new("daemon")
+                .long("daemon")
+                .value_name("SOCKET")
+                .help("Keeps the populated UTXO database in memory and serves exploration jobs on the given Unix socket.")
+        )
+        .arg(
+            Arg::new("core-wallet")
+                .long("core-wallet")
+                .value_name("NAME")
+                .help("Creates a watch-only wallet with the given name on bitcoincore and imports the descriptors of the finds into it.")
+                .conflicts_with("offline")
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .help("Resumes the search of each seed from its checkpoint in the data dir, skipping the paths an interrupted run had searched and the seeds it had finished.")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("daemon")
+        )
+        .arg(
+            Arg::new("estimate")
+                .long("estimate")
+                .help("Prints the paths to explore, the memory the UTXO database takes and the time the search is estimated to take, then exits without searching.")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("daemon")
+        )
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .help("Lists the runs recorded in the data dir, with the settings they searched with, how much they searched and their finds, then exits.")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["daemon", "estimate"])
+        )
+        .arg(
+            Arg::new("diff-runs")
+                .long("diff-runs")
+                .value_names(["EARLIER", "LATER"])
+                .help("Prints the finds the later of two runs recorded in the data dir added, lost or changed the amount of against the earlier one, then exits.")
+                .num_args(2)
+                .value_parser(clap::value_parser!(u64))
+                .conflicts_with_all(["daemon", "estimate", "history"])
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .help("Runs without connecting to bitcoincore, reading the UTXO set and the details of finds from the dump file in the data dir.")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("daemon")
         ).get_matches();
 
     let config_file_path_string = matches.get_one::<String>("conf").expect("required");
 
-    let setting = RetrieverSetting::from_config_file(config_file_path_string)
+    let mut setting = RetrieverSetting::from_config_file(config_file_path_string)
         .map_err(|err| panic!("Error while reading the config file: {:#?}", err))
         .unwrap();
-    let mut ret = Retriever::new(setting)
-        .await
-        .map_err(|err| panic!("Error while creating the retriever: {:#?}", err))
-        .unwrap();
-    ret.check_for_dump_in_data_dir_or_create_dump_file()
-        .await
-        .map_err(|err| {
-            panic!(
-                "Error while checking/creating dump file in data dir: {:#?}",
-                err
-            )
-        })
+    if let (Some(rpc_user), Some(rpc_password)) = (
+        matches.get_one::<String>("rpc-user"),
+        matches.get_one::<String>("rpc-password"),
+    ) {
+        setting.set_bitcoincore_rpc_user(Some(rpc_user.to_owned()));
+        setting.set_bitcoincore_rpc_password(Some(rpc_password.to_owned()));
+    }
+    let batch_explorer_settings = setting
+        .get_batch_explorer_settings()
+        .map_err(|err| panic!("Error while reading the seeds to explore: {:#?}", err))
         .unwrap();
-    ret.populate_uspk_set()
-        .await
-        .map_err(|err| panic!("Error while populating in-memory UTXO database: {:#?}", err))
+    if matches.get_flag("history") || matches.contains_id("diff-runs") {
+        let history = RunHistory::open(setting.get_data_dir())
+            .map_err(|err| panic!("Error while opening the run history: {:#?}", err))
+            .unwrap();
+        match matches.get_many::<u64>("diff-runs") {
+            Some(runs) => {
+                let runs = runs.copied().collect::<Vec<_>>();
+                let diff = history
+                    .diff(runs[0], runs[1])
+                    .map_err(|err| panic!("Error while comparing the runs: {:#?}", err))
+                    .unwrap();
+                println!("{}", diff);
+            }
+            None => {
+                let runs = history
+                    .runs()
+                    .map_err(|err| panic!("Error while reading the run history: {:#?}", err))
+                    .unwrap();
+                if runs.is_empty() {
+                    println!("No runs are recorded in the data dir.");
+                }
+                for run in runs.iter() {
+                    println!("{}", run);
+                }
+            }
+        }
+        return;
+    }
+    // Ctrl-C cancels the token, stopping whichever phase is running.
+    let cancellation_token = CancellationToken::new();
+    let ctrl_c_token = cancellation_token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            ctrl_c_token.cancel();
+        }
+    });
+    if let Some(socket_path) = matches.get_one::<String>("daemon") {
+        let daemon = match RetrieverDaemon::new(setting, socket_path, cancellation_token).await {
+            Err(RetrieverError::Cancelled) => {
+                println!("\nCancelled before the daemon started.");
+                return;
+            }
+            result => result,
+        }
+        .map_err(|err| panic!("Error while creating the retriever daemon: {:#?}", err))
         .unwrap();
-    ret.search_the_uspk_set()
-        .await
-        .map_err(|err| panic!("Error while searching in-memory UTXO database: {:#?}", err))
+        daemon
+            .run()
+            .await
+            .map_err(|err| panic!("Error while serving the control socket: {:#?}", err))
+            .unwrap();
+        return;
+    }
+    let retriever = if matches.get_flag("offline") {
+        Retriever::new_offline(setting).await
+    } else {
+        Retriever::new(setting).await
+    };
+    let mut ret = retriever
+        .map_err(|err| panic!("Error while creating the retriever: {:#?}", err))
         .unwrap();
-    ret.get_details_of_finds_from_bitcoincore()
-        .await
+    ret.set_cancellation_token(cancellation_token.clone());
+    if matches.get_flag("estimate") {
+        match batch_explorer_settings {
+            Some(explorer_settings) => {
+                for explorer_setting in explorer_settings {
+                    let label = explorer_setting.get_run_label().unwrap_or_default();
+                    ret.replace_explorer(explorer_setting)
+                        .map_err(|err| {
+                            panic!("Error while creating the explorer of {}: {:#?}", label, err)
+                        })
+                        .unwrap();
+                    println!("\nSeed {}", label);
+                    print_estimate(&ret);
+                }
+            }
+            None => print_estimate(&ret),
+        }
+        return;
+    }
+    let export_sparrow = matches.get_flag("sparrow-export");
+    let key_exports = KeyExports {
+        wifs: matches.get_flag("export-wifs"),
+        wif_password: matches.get_one::<String>("wif-password").cloned(),
+        private_descriptors: matches.get_flag("export-private-descriptors"),
+        sweep: matches
+            .get_one::<String>("sweep-to")
+            .cloned()
+            .zip(matches.get_one::<u64>("fee-rate").copied()),
+        sweep_options: SweepOptions::default()
+            .replaceable(matches.get_flag("sweep-replaceable"))
+            .grouping(match matches.get_flag("sweep-per-find") {
+                true => SweepGrouping::PerFind,
+                false => SweepGrouping::Consolidated,
+            })
+            .lock_time(LockTime::from_consensus(
+                matches
+                    .get_one::<u32>("sweep-locktime")
+                    .copied()
+                    .unwrap_or_default(),
+            )),
+        sign_sweep: matches.get_flag("sign-sweep"),
+        broadcast_sweep: matches.get_flag("broadcast-sweep").then(|| {
+            matches
+                .get_one::<u64>("sweep-confirmations")
+                .copied()
+                .unwrap_or_default()
+        }),
+    };
+    let core_wallet = matches.get_one::<String>("core-wallet").cloned();
+    let resume = matches.get_flag("resume");
+    // Cancelled before the search, there are no finds to report.
+    match ret.check_for_dump_in_data_dir_or_create_dump_file().await {
+        Err(RetrieverError::Cancelled) => {
+            println!("\nCancelled before the search.");
+            return;
+        }
+        result => result,
+    }
+    .map_err(|err| {
+        panic!(
+            "Error while checking/creating dump file in data dir: {:#?}",
+            err
+        )
+    })
+    .unwrap();
+    match ret.populate_uspk_set().await {
+        Err(RetrieverError::Cancelled) => {
+            println!("\nCancelled before the search.");
+            return;
+        }
+        result => result,
+    }
+    .map_err(|err| panic!("Error while populating in-memory UTXO database: {:#?}", err))
+    .unwrap();
+    if ret.watchlist().is_some() {
+        ret.search_watchlist();
+        match ret.get_details_of_watchlist_finds_from_bitcoincore().await {
+            Err(RetrieverError::Cancelled) => {
+                println!("\nCancelled before the amounts were fetched.");
+                Ok(())
+            }
+            result => result,
+        }
         .map_err(|err| {
             panic!(
-                "Error while fetching details of finds from bitcoincore: {:#?}",
+                "Error while fetching details of watchlist finds from bitcoincore: {:#?}",
                 err
             )
         })
         .unwrap();
+        ret.print_watchlist_finds_on_console();
+        return;
+    }
+    match batch_explorer_settings {
+        Some(explorer_settings) => {
+            let mut runs = 0;
+            let mut runs_with_finds = vec![];
+            for explorer_setting in explorer_settings {
+                runs += 1;
+                let label = explorer_setting.get_run_label().unwrap_or_default();
+                ret.replace_explorer(explorer_setting)
+                    .map_err(|err| {
+                        panic!("Error while creating the explorer of {}: {:#?}", label, err)
+                    })
+                    .unwrap();
+                // A new explorer starts with a new token, which Ctrl-C must still cancel.
+                ret.set_cancellation_token(cancellation_token.clone());
+                println!("\nFinds of {}", label);
+                let core_wallet = core_wallet
+                    .as_ref()
+                    .map(|core_wallet| format!("{}_{}", core_wallet, label));
+                explore(&mut ret, export_sparrow, core_wallet, resume, &key_exports).await;
+                let finds = ret.get_detailed_finds().map_or(0, |finds| finds.len());
+                if finds > 0 {
+                    runs_with_finds.push(format!("{} ({} finds)", label, finds));
+                }
+            }
+            if runs_with_finds.is_empty() {
+                println!("\nNone of the {} explored seeds has finds.", runs);
+            } else {
+                println!("\nSeeds with finds: {}", runs_with_finds.join(", "));
+            }
+        }
+        None => explore(&mut ret, export_sparrow, core_wallet, resume, &key_exports).await,
+    }
+}
+
+/// Runs the search of the populated UTXO database with the explorer of the retriever, from its
+/// checkpoint when resuming, then reports and exports the finds.
+async fn explore(
+    ret: &mut Retriever,
+    export_sparrow: bool,
+    core_wallet: Option<String>,
+    resume: bool,
+    key_exports: &KeyExports,
+) {
+    let run = match resume {
+        true => ret.run_resumed().await,
+        false => ret.run().await,
+    };
+    if let Err(err) = run {
+        finish_partially(
+            ret,
+            export_sparrow,
+            format!("Error while searching the UTXO database: {:#?}", err),
+        );
+    }
     let _ = ret.print_detailed_finds_on_console();
+    ret.print_silent_payment_finds_on_console();
+    let _ = ret.write_scan_report();
+    if export_sparrow {
+        ret.export_sparrow_wallets()
+            .map_err(|err| panic!("Error while exporting Sparrow wallets: {:#?}", err))
+            .unwrap();
+    }
+    if key_exports.wifs {
+        let wifs = ret
+            .export_wifs(key_exports.wif_password.as_deref())
+            .map_err(|err| panic!("Error while exporting the private keys: {:#?}", err))
+            .unwrap();
+        for wif in wifs.iter() {
+            println!(
+                "\nPath: {}\nDescriptor: {}\nPrivate key: {}",
+                wif.get_path(),
+                wif.get_descriptor(),
+                wif.get_wif()
+            );
+        }
+    }
+    if key_exports.private_descriptors {
+        let descriptors = ret
+            .export_private_descriptors()
+            .map_err(|err| panic!("Error while exporting the private descriptors: {:#?}", err))
+            .unwrap();
+        for descriptor in descriptors.iter() {
+            println!(
+                "\nAccount: {}\nPrivate descriptor: {}",
+                descriptor.get_account_path(),
+                descriptor.get_descriptor()
+            );
+        }
+    }
+    if let Some((destination, fee_rate)) = key_exports.sweep.as_ref() {
+        let sweep_options = &key_exports.sweep_options;
+        if !key_exports.sign_sweep {
+            ret.write_sweep_psbts(destination, *fee_rate, sweep_options)
+                .await
+                .map_err(|err| panic!("Error while building the sweep: {:#?}", err))
+                .unwrap();
+        } else {
+            let transactions = ret
+                .write_signed_sweeps(destination, *fee_rate, sweep_options)
+                .await
+                .map_err(|err| panic!("Error while building the sweep: {:#?}", err))
+                .unwrap();
+            if let Some(confirmations) = key_exports.broadcast_sweep {
+                for transaction in transactions.iter() {
+                    match ret.broadcast_sweep(transaction, confirmations).await {
+                        Ok(txid) => println!("\nSweep broadcast: {}", txid),
+                        Err(RetrieverError::Cancelled) => {
+                            println!(
+                                "\nSweep {} broadcast, stopped waiting for its confirmations.",
+                                transaction.txid()
+                            );
+                            break;
+                        }
+                        Err(err) => panic!("Error while broadcasting the sweep: {:#?}", err),
+                    }
+                }
+            }
+        }
+    }
+    if let Some(core_wallet) = core_wallet {
+        ret.export_finds_to_core_wallet(&core_wallet)
+            .await
+            .map_err(|err| panic!("Error while exporting finds to bitcoincore: {:#?}", err))
+            .unwrap();
+    }
+}
+
+/// The private keys of the finds to print, which are only exported when asked for, and the
+/// sweep of their funds.
+struct KeyExports {
+    wifs: bool,
+    /// Encrypts the WIFs with BIP38 under it.
+    wif_password: Option<String>,
+    private_descriptors: bool,
+    /// The destination address and the fee rate in sat/vB of the sweep PSBT.
+    sweep: Option<(String, u64)>,
+    sweep_options: SweepOptions,
+    /// Signs the sweep instead of writing it unsigned.
+    sign_sweep: bool,
+    /// Broadcasts the signed sweep and waits for this many confirmations.
+    broadcast_sweep: Option<u64>,
+}
+
+fn print_estimate(ret: &Retriever) {
+    let estimate = ret
+        .estimate()
+        .map_err(|err| panic!("Error while estimating the search: {:#?}", err))
+        .unwrap();
+    println!("{}", estimate);
+}
+
+/// Reports and exports whatever was found before the run stopped, then aborts with the cause.
+fn finish_partially(ret: &Retriever, export_sparrow: bool, cause: String) -> ! {
+    let _ = ret.write_partial_report(&cause);
+    let _ = ret.record_run();
+    if export_sparrow {
+        let _ = ret.export_sparrow_wallets();
+    }
+    panic!("{}", cause);
 }
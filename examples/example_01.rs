@@ -17,7 +17,6 @@ use bitcoin::{
 };
 use bitcoincore_rpc::{Auth, Client, RpcApi};
 use miniscript::Descriptor;
-use tokio::join;
 use tracing_log::LogTracer;
 
 const BITCOIND_PATH: &str = "tests/bitcoind";
@@ -188,20 +187,10 @@ async fn main() {
             .to_string_lossy()
             .to_string(),
     );
-    let mut ret = join!(Retriever::new(setting)).0.unwrap();
-    let _ = join!(ret.check_for_dump_in_data_dir_or_create_dump_file());
-    let _ = join!(ret.populate_uspk_set());
-    let _ = join!(ret.search_the_uspk_set());
-    let _ = join!(ret.get_details_of_finds_from_bitcoincore());
+    let mut ret = Retriever::new(setting).await.unwrap();
+    let report = ret.run().await.unwrap();
     let _ = ret.print_detailed_finds_on_console();
-    assert_eq!(
-        ret.get_detailed_finds()
-            .unwrap()
-            .iter()
-            .fold(0u64, |acc, trio| acc
-                + trio.get_scan_result().total_amount.to_sat()),
-        4200000000
-    );
+    assert_eq!(*report.get_total_amount_sats(), 4200000000);
     client.stop().unwrap();
     sleep(Duration::from_millis(1000));
 }
@@ -96,13 +96,7 @@ async fn main() {
             .to_string(),
     );
     let mut ret = Retriever::new(setting).await.unwrap();
-    let _ = ret
-        .check_for_dump_in_data_dir_or_create_dump_file()
-        .await
-        .unwrap();
-    let _ = ret.populate_uspk_set().await.unwrap();
-    let _ = ret.search_the_uspk_set().await.unwrap();
-    let _ = ret.get_details_of_finds_from_bitcoincore();
+    ret.run().await.unwrap();
     let _ = ret.print_detailed_finds_on_console();
 
     // Remove regtest from temp dir.
@@ -0,0 +1,529 @@
+//! A minimal read-only LevelDB reader, covering what is needed to iterate over Bitcoin Core's
+//! chainstate: the manifest (to find the live tables), uncompressed tables and the write-ahead
+//! log. The database must not be in use by a running node while it is read.
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tracing::error;
+
+use crate::error::RetrieverError;
+
+const TABLE_MAGIC: u64 = 0xdb4775248b80fb57;
+const TABLE_FOOTER_LEN: usize = 48;
+const BLOCK_TRAILER_LEN: usize = 5;
+const LOG_BLOCK_SIZE: usize = 32768;
+const LOG_HEADER_LEN: usize = 7;
+const WRITE_BATCH_HEADER_LEN: usize = 12;
+const VALUE_TYPE_DELETION: u8 = 0;
+const VALUE_TYPE_VALUE: u8 = 1;
+
+/// A key in its internal form (user key followed by sequence number and value type) and value.
+type InternalEntry = (Vec<u8>, Vec<u8>);
+/// Offset and size of a block in a table.
+type BlockHandle = (u64, u64);
+type EntrySource = Box<dyn Iterator<Item = Result<InternalEntry, RetrieverError>> + Send>;
+
+/// Iterates over the live user keys of a database in ascending order, with their latest values.
+pub struct DbIterator {
+    sources: Vec<EntrySource>,
+    heap: BinaryHeap<HeapEntry>,
+    last_user_key: Option<Vec<u8>>,
+}
+
+impl DbIterator {
+    pub fn open(db_dir: &Path) -> Result<Self, RetrieverError> {
+        let version = LiveVersion::read(db_dir)?;
+        let mut sources: Vec<EntrySource> = vec![Box::new(
+            read_write_ahead_logs(db_dir, version.min_log_number)?
+                .into_iter()
+                .map(Ok),
+        )];
+        for (level, files) in version.files {
+            let paths = files
+                .into_iter()
+                .map(|file| table_file_path(db_dir, file.number))
+                .collect::<Vec<PathBuf>>();
+            if level == 0 {
+                // Level 0 tables may overlap, so each one is merged on its own.
+                for path in paths {
+                    sources.push(Box::new(TableIterator::new(vec![path])));
+                }
+            } else {
+                sources.push(Box::new(TableIterator::new(paths)));
+            }
+        }
+        Self::merge(sources)
+    }
+
+    fn merge(mut sources: Vec<EntrySource>) -> Result<Self, RetrieverError> {
+        let mut heap = BinaryHeap::new();
+        for (source, entries) in sources.iter_mut().enumerate() {
+            if let Some(entry) = entries.next() {
+                let (key, value) = entry?;
+                heap.push(HeapEntry { key, value, source });
+            }
+        }
+        Ok(DbIterator {
+            sources,
+            heap,
+            last_user_key: None,
+        })
+    }
+}
+
+impl Iterator for DbIterator {
+    type Item = Result<(Vec<u8>, Vec<u8>), RetrieverError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let HeapEntry { key, value, source } = self.heap.pop()?;
+            match self.sources[source].next() {
+                Some(Ok((key, value))) => self.heap.push(HeapEntry { key, value, source }),
+                Some(Err(err)) => return Some(Err(err)),
+                None => {}
+            }
+            let (user_key, value_type) = split_internal_key(&key);
+            // Entries of a user key come newest first, so older ones are shadowed.
+            if self.last_user_key.as_deref() == Some(user_key) {
+                continue;
+            }
+            self.last_user_key = Some(user_key.to_vec());
+            if value_type == VALUE_TYPE_VALUE {
+                return Some(Ok((user_key.to_vec(), value)));
+            }
+        }
+    }
+}
+
+struct HeapEntry {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    source: usize,
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    /// Reversed, so the max-heap pops the smallest internal key first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_internal_keys(&other.key, &self.key)
+    }
+}
+
+/// Orders by user key ascending, then by sequence number descending.
+fn compare_internal_keys(a: &[u8], b: &[u8]) -> Ordering {
+    let (a_user_key, a_trailer) = a.split_at(a.len().saturating_sub(8));
+    let (b_user_key, b_trailer) = b.split_at(b.len().saturating_sub(8));
+    a_user_key
+        .cmp(b_user_key)
+        .then_with(|| decode_fixed64(b_trailer).cmp(&decode_fixed64(a_trailer)))
+}
+
+fn split_internal_key(key: &[u8]) -> (&[u8], u8) {
+    let (user_key, trailer) = key.split_at(key.len().saturating_sub(8));
+    (user_key, (decode_fixed64(trailer) & 0xff) as u8)
+}
+
+fn make_internal_key(user_key: &[u8], sequence: u64, value_type: u8) -> Vec<u8> {
+    let mut key = user_key.to_vec();
+    key.extend_from_slice(&((sequence << 8) | value_type as u64).to_le_bytes());
+    key
+}
+
+fn decode_fixed64(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf[..bytes.len().min(8)].copy_from_slice(&bytes[..bytes.len().min(8)]);
+    u64::from_le_bytes(buf)
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64, RetrieverError> {
+    let mut result = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data
+            .get(*pos)
+            .ok_or(RetrieverError::InvalidChainstateDatabase)?;
+        *pos += 1;
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    Err(RetrieverError::InvalidChainstateDatabase)
+}
+
+fn read_slice<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], RetrieverError> {
+    let slice = data
+        .get(*pos..*pos + len)
+        .ok_or(RetrieverError::InvalidChainstateDatabase)?;
+    *pos += len;
+    Ok(slice)
+}
+
+fn read_length_prefixed<'a>(data: &'a [u8], pos: &mut usize) -> Result<&'a [u8], RetrieverError> {
+    let len = read_varint(data, pos)? as usize;
+    read_slice(data, pos, len)
+}
+
+fn table_file_path(db_dir: &Path, number: u64) -> PathBuf {
+    let ldb_path = db_dir.join(format!("{:06}.ldb", number));
+    if ldb_path.exists() {
+        ldb_path
+    } else {
+        // Tables written by older LevelDB versions.
+        db_dir.join(format!("{:06}.sst", number))
+    }
+}
+
+struct TableFile {
+    number: u64,
+    smallest_key: Vec<u8>,
+}
+
+/// The set of live tables per level, as recorded by the current manifest.
+struct LiveVersion {
+    min_log_number: u64,
+    files: BTreeMap<u64, Vec<TableFile>>,
+}
+
+impl LiveVersion {
+    fn read(db_dir: &Path) -> Result<Self, RetrieverError> {
+        let current = fs::read_to_string(db_dir.join("CURRENT"))?;
+        let mut log_number = 0;
+        let mut prev_log_number = 0;
+        let mut files: BTreeMap<(u64, u64), Vec<u8>> = BTreeMap::new();
+        for record in read_log_records(&db_dir.join(current.trim()))? {
+            let mut pos = 0;
+            while pos < record.len() {
+                match read_varint(&record, &mut pos)? {
+                    // Comparator name.
+                    1 => {
+                        read_length_prefixed(&record, &mut pos)?;
+                    }
+                    2 => log_number = read_varint(&record, &mut pos)?,
+                    // Next file number and last sequence.
+                    3 | 4 => {
+                        read_varint(&record, &mut pos)?;
+                    }
+                    // Compaction pointer.
+                    5 => {
+                        read_varint(&record, &mut pos)?;
+                        read_length_prefixed(&record, &mut pos)?;
+                    }
+                    6 => {
+                        let level = read_varint(&record, &mut pos)?;
+                        let number = read_varint(&record, &mut pos)?;
+                        files.remove(&(level, number));
+                    }
+                    7 => {
+                        let level = read_varint(&record, &mut pos)?;
+                        let number = read_varint(&record, &mut pos)?;
+                        let _file_size = read_varint(&record, &mut pos)?;
+                        let smallest_key = read_length_prefixed(&record, &mut pos)?.to_vec();
+                        let _largest_key = read_length_prefixed(&record, &mut pos)?;
+                        files.insert((level, number), smallest_key);
+                    }
+                    9 => prev_log_number = read_varint(&record, &mut pos)?,
+                    tag => {
+                        error!("Unknown tag {} in the chainstate manifest.", tag);
+                        return Err(RetrieverError::InvalidChainstateDatabase);
+                    }
+                }
+            }
+        }
+        let mut levels: BTreeMap<u64, Vec<TableFile>> = BTreeMap::new();
+        for ((level, number), smallest_key) in files {
+            levels.entry(level).or_default().push(TableFile {
+                number,
+                smallest_key,
+            });
+        }
+        for tables in levels.values_mut() {
+            tables.sort_by(|a, b| compare_internal_keys(&a.smallest_key, &b.smallest_key));
+        }
+        Ok(LiveVersion {
+            min_log_number: match prev_log_number {
+                0 => log_number,
+                prev_log_number => prev_log_number.min(log_number),
+            },
+            files: levels,
+        })
+    }
+}
+
+/// Reads the logical records of a log file, the format shared by manifests and write-ahead logs.
+fn read_log_records(path: &Path) -> Result<Vec<Vec<u8>>, RetrieverError> {
+    let data = fs::read(path)?;
+    let mut records = vec![];
+    let mut fragments: Vec<u8> = vec![];
+    for block in data.chunks(LOG_BLOCK_SIZE) {
+        let mut pos = 0;
+        while pos + LOG_HEADER_LEN <= block.len() {
+            let len = u16::from_le_bytes([block[pos + 4], block[pos + 5]]) as usize;
+            let record_type = block[pos + 6];
+            pos += LOG_HEADER_LEN;
+            if record_type == 0 && len == 0 {
+                // Preallocated zero padding.
+                break;
+            }
+            let payload = read_slice(block, &mut pos, len)?;
+            match record_type {
+                // Full record.
+                1 => records.push(payload.to_vec()),
+                // First and middle fragments.
+                2 => fragments = payload.to_vec(),
+                3 => fragments.extend_from_slice(payload),
+                // Last fragment.
+                4 => {
+                    fragments.extend_from_slice(payload);
+                    records.push(std::mem::take(&mut fragments));
+                }
+                _ => return Err(RetrieverError::InvalidChainstateDatabase),
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Reads the entries of the write-ahead logs that were not yet compacted into tables.
+fn read_write_ahead_logs(
+    db_dir: &Path,
+    min_log_number: u64,
+) -> Result<Vec<InternalEntry>, RetrieverError> {
+    let mut log_numbers = vec![];
+    for dir_entry in fs::read_dir(db_dir)? {
+        let file_name = dir_entry?.file_name().to_string_lossy().to_string();
+        if let Some(number) = file_name
+            .strip_suffix(".log")
+            .and_then(|number| number.parse::<u64>().ok())
+        {
+            if number >= min_log_number {
+                log_numbers.push(number);
+            }
+        }
+    }
+    log_numbers.sort();
+    let mut entries = vec![];
+    for number in log_numbers {
+        for batch in read_log_records(&db_dir.join(format!("{:06}.log", number)))? {
+            if batch.len() < WRITE_BATCH_HEADER_LEN {
+                return Err(RetrieverError::InvalidChainstateDatabase);
+            }
+            let mut sequence = decode_fixed64(&batch[..8]);
+            let mut pos = WRITE_BATCH_HEADER_LEN;
+            while pos < batch.len() {
+                let value_type = batch[pos];
+                pos += 1;
+                let user_key = read_length_prefixed(&batch, &mut pos)?;
+                let value = match value_type {
+                    VALUE_TYPE_VALUE => read_length_prefixed(&batch, &mut pos)?.to_vec(),
+                    VALUE_TYPE_DELETION => vec![],
+                    _ => return Err(RetrieverError::InvalidChainstateDatabase),
+                };
+                entries.push((make_internal_key(user_key, sequence, value_type), value));
+                sequence += 1;
+            }
+        }
+    }
+    entries.sort_by(|a, b| compare_internal_keys(&a.0, &b.0));
+    Ok(entries)
+}
+
+/// Iterates over the entries of a sequence of non-overlapping tables, one block at a time.
+struct TableIterator {
+    pending_tables: std::vec::IntoIter<PathBuf>,
+    table: Option<(Vec<u8>, std::vec::IntoIter<BlockHandle>)>,
+    block_entries: std::vec::IntoIter<InternalEntry>,
+}
+
+impl TableIterator {
+    fn new(paths: Vec<PathBuf>) -> Self {
+        TableIterator {
+            pending_tables: paths.into_iter(),
+            table: None,
+            block_entries: vec![].into_iter(),
+        }
+    }
+
+    fn next_block(&mut self) -> Result<bool, RetrieverError> {
+        loop {
+            if let Some((data, handles)) = self.table.as_mut() {
+                if let Some((offset, size)) = handles.next() {
+                    self.block_entries = read_block(data, offset, size)?.into_iter();
+                    return Ok(true);
+                }
+            }
+            match self.pending_tables.next() {
+                Some(path) => {
+                    let data = fs::read(path)?;
+                    let handles = read_index(&data)?;
+                    self.table = Some((data, handles.into_iter()));
+                }
+                None => return Ok(false),
+            }
+        }
+    }
+}
+
+impl Iterator for TableIterator {
+    type Item = Result<InternalEntry, RetrieverError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.block_entries.next() {
+                return Some(Ok(entry));
+            }
+            match self.next_block() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Returns the handles (offset and size) of the data blocks of a table.
+fn read_index(table: &[u8]) -> Result<Vec<BlockHandle>, RetrieverError> {
+    if table.len() < TABLE_FOOTER_LEN {
+        return Err(RetrieverError::InvalidChainstateDatabase);
+    }
+    let footer = &table[table.len() - TABLE_FOOTER_LEN..];
+    if decode_fixed64(&footer[TABLE_FOOTER_LEN - 8..]) != TABLE_MAGIC {
+        return Err(RetrieverError::InvalidChainstateDatabase);
+    }
+    let mut pos = 0;
+    // The metaindex handle comes first and is not needed.
+    read_varint(footer, &mut pos)?;
+    read_varint(footer, &mut pos)?;
+    let index_offset = read_varint(footer, &mut pos)?;
+    let index_size = read_varint(footer, &mut pos)?;
+    let mut handles = vec![];
+    for (_, handle) in read_block(table, index_offset, index_size)? {
+        let mut pos = 0;
+        handles.push((
+            read_varint(&handle, &mut pos)?,
+            read_varint(&handle, &mut pos)?,
+        ));
+    }
+    Ok(handles)
+}
+
+fn read_block(table: &[u8], offset: u64, size: u64) -> Result<Vec<InternalEntry>, RetrieverError> {
+    let (offset, size) = (offset as usize, size as usize);
+    let block = table
+        .get(offset..offset + size + BLOCK_TRAILER_LEN)
+        .ok_or(RetrieverError::InvalidChainstateDatabase)?;
+    if block[size] != 0 {
+        error!("Compressed blocks in the chainstate are not supported.");
+        return Err(RetrieverError::InvalidChainstateDatabase);
+    }
+    let block = &block[..size];
+    if block.len() < 4 {
+        return Err(RetrieverError::InvalidChainstateDatabase);
+    }
+    let num_restarts = decode_fixed64(&block[block.len() - 4..]) as usize;
+    let entries_end = block
+        .len()
+        .checked_sub(4 + 4 * num_restarts)
+        .ok_or(RetrieverError::InvalidChainstateDatabase)?;
+    let mut entries = vec![];
+    let mut key: Vec<u8> = vec![];
+    let mut pos = 0;
+    while pos < entries_end {
+        let shared = read_varint(block, &mut pos)? as usize;
+        let non_shared = read_varint(block, &mut pos)? as usize;
+        let value_len = read_varint(block, &mut pos)? as usize;
+        if shared > key.len() {
+            return Err(RetrieverError::InvalidChainstateDatabase);
+        }
+        key.truncate(shared);
+        key.extend_from_slice(read_slice(block, &mut pos, non_shared)?);
+        entries.push((
+            key.clone(),
+            read_slice(block, &mut pos, value_len)?.to_vec(),
+        ));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(entries: Vec<(&[u8], u64, u8, &[u8])>) -> EntrySource {
+        Box::new(
+            entries
+                .into_iter()
+                .map(|(user_key, sequence, value_type, value)| {
+                    Ok((
+                        make_internal_key(user_key, sequence, value_type),
+                        value.to_vec(),
+                    ))
+                })
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    }
+
+    #[test]
+    fn db_iterator_merge_works_01() {
+        let newer = source(vec![
+            (b"a", 10, VALUE_TYPE_VALUE, b"a2"),
+            (b"b", 11, VALUE_TYPE_DELETION, b""),
+        ]);
+        let older = source(vec![
+            (b"a", 1, VALUE_TYPE_VALUE, b"a1"),
+            (b"b", 2, VALUE_TYPE_VALUE, b"b1"),
+            (b"c", 3, VALUE_TYPE_VALUE, b"c1"),
+        ]);
+        let entries = DbIterator::merge(vec![older, newer])
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (b"a".to_vec(), b"a2".to_vec()),
+                (b"c".to_vec(), b"c1".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn read_block_works_01() {
+        // Two entries sharing a 3 byte prefix, a single restart point and no compression.
+        let mut block = vec![0, 4, 1];
+        block.extend_from_slice(b"key1");
+        block.push(b'x');
+        block.extend_from_slice(&[3, 1, 1]);
+        block.extend_from_slice(b"2y");
+        let size = block.len() as u64;
+        block.extend_from_slice(&0u32.to_le_bytes());
+        block.extend_from_slice(&1u32.to_le_bytes());
+        block.extend_from_slice(&[0, 0, 0, 0, 0]);
+        let entries = read_block(&block, 0, size + 8).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (b"key1".to_vec(), b"x".to_vec()),
+                (b"key2".to_vec(), b"y".to_vec())
+            ]
+        );
+    }
+}
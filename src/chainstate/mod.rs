@@ -0,0 +1,171 @@
+pub mod leveldb;
+
+use std::path::Path;
+
+use bitcoin::{consensus::Decodable, hashes::Hash, OutPoint, ScriptBuf, Txid};
+use tracing::info;
+
+use crate::{
+    coin_arena::{decompress_script_into, CoinHeader, CoinSource},
+    error::RetrieverError,
+};
+
+use self::leveldb::DbIterator;
+
+/// Prefix of the keys of coins (unspent outputs) in the chainstate.
+const COIN_KEY_PREFIX: u8 = b'C';
+/// Key under which the obfuscation key of the chainstate values is stored.
+const OBFUSCATION_KEY_KEY: &[u8] = b"\x0e\x00obfuscate_key";
+
+/// An unspent output as stored in Bitcoin Core's chainstate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainstateCoin {
+    pub out_point: OutPoint,
+    pub height: u32,
+    pub is_coinbase: bool,
+    pub amount_sats: u64,
+    pub script_pubkey: ScriptBuf,
+}
+
+/// Iterates over the coins of Bitcoin Core's chainstate LevelDB, so the unspent scriptpubkey set
+/// can be populated without calling dumptxoutset. Bitcoin Core must not be running on the same
+/// chainstate directory while it is read.
+pub struct ChainstateReader {
+    entries: DbIterator,
+    obfuscation_key: Vec<u8>,
+    /// The value of the last coin, deobfuscated, reused from one coin to the next.
+    value: Vec<u8>,
+}
+
+impl ChainstateReader {
+    pub fn open(chainstate_dir: &str) -> Result<Self, RetrieverError> {
+        info!("Opening the chainstate database at {}.", chainstate_dir);
+        let chainstate_dir = Path::new(chainstate_dir);
+        let mut obfuscation_key = vec![];
+        // The obfuscation key sorts before all coins, so it is found by a short scan.
+        for entry in DbIterator::open(chainstate_dir)? {
+            let (key, value) = entry?;
+            if key == OBFUSCATION_KEY_KEY {
+                // Stored as a length prefixed byte vector.
+                obfuscation_key = value.get(1..).unwrap_or_default().to_vec();
+            }
+            if key.first() >= Some(&COIN_KEY_PREFIX) {
+                break;
+            }
+        }
+        Ok(ChainstateReader {
+            entries: DbIterator::open(chainstate_dir)?,
+            obfuscation_key,
+            value: vec![],
+        })
+    }
+}
+
+impl CoinSource for ChainstateReader {
+    fn read_coin_into(
+        &mut self,
+        script_pubkey: &mut Vec<u8>,
+    ) -> Option<Result<CoinHeader, RetrieverError>> {
+        loop {
+            let (key, value) = match self.entries.next()? {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(err)),
+            };
+            match key.first() {
+                Some(&COIN_KEY_PREFIX) => {
+                    deobfuscate_into(&value, &self.obfuscation_key, &mut self.value);
+                    return Some(decode_coin_into(&key, &self.value, script_pubkey));
+                }
+                Some(prefix) if *prefix > COIN_KEY_PREFIX => return None,
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl Iterator for ChainstateReader {
+    type Item = Result<ChainstateCoin, RetrieverError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_coin()
+    }
+}
+
+/// Replaces the buffer with the value xored with the obfuscation key, repeated, if there is one.
+fn deobfuscate_into(value: &[u8], obfuscation_key: &[u8], deobfuscated: &mut Vec<u8>) {
+    deobfuscated.clear();
+    if obfuscation_key.is_empty() {
+        deobfuscated.extend_from_slice(value);
+    } else {
+        deobfuscated.extend(
+            value
+                .iter()
+                .zip(obfuscation_key.iter().cycle())
+                .map(|(byte, key_byte)| byte ^ key_byte),
+        );
+    }
+}
+
+/// Decodes a coin entry, appending its script to the buffer: the key is the prefix, txid and
+/// vout, the deobfuscated value holds the height and coinbase flag, the compressed amount and
+/// the compressed script.
+fn decode_coin_into(
+    key: &[u8],
+    value: &[u8],
+    script_pubkey: &mut Vec<u8>,
+) -> Result<CoinHeader, RetrieverError> {
+    if key.len() < 34 {
+        return Err(RetrieverError::InvalidChainstateDatabase);
+    }
+    let txid =
+        Txid::from_slice(&key[1..33]).map_err(|_| RetrieverError::InvalidChainstateDatabase)?;
+    let vout = u64::from(txoutset::VarInt::consensus_decode(&mut &key[33..])?) as u32;
+    let mut reader = value;
+    let coin = CoinHeader::decode(OutPoint::new(txid, vout), &mut reader)?;
+    decompress_script_into(&mut reader, script_pubkey)?;
+    Ok(coin)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::consensus::Encodable;
+
+    use super::*;
+
+    #[test]
+    fn decode_coin_works_01() {
+        let obfuscation_key = [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0];
+        let mut key = vec![COIN_KEY_PREFIX];
+        key.extend_from_slice(&[7u8; 32]);
+        txoutset::VarInt::from(300u32)
+            .consensus_encode(&mut key)
+            .unwrap();
+        let mut value = vec![];
+        // Height 840,000, not coinbase.
+        txoutset::VarInt::from(840_000u64 * 2)
+            .consensus_encode(&mut value)
+            .unwrap();
+        txoutset::Amount::new(123_456)
+            .consensus_encode(&mut value)
+            .unwrap();
+        // Compressed p2pkh script.
+        value.push(0);
+        value.extend_from_slice(&[9u8; 20]);
+        let obfuscated = value
+            .iter()
+            .zip(obfuscation_key.iter().cycle())
+            .map(|(byte, key_byte)| byte ^ key_byte)
+            .collect::<Vec<u8>>();
+        let mut value = vec![];
+        deobfuscate_into(&obfuscated, &obfuscation_key, &mut value);
+        let mut script_pubkey = vec![];
+        let coin = decode_coin_into(&key, &value, &mut script_pubkey)
+            .unwrap()
+            .with_script_pubkey(script_pubkey);
+        assert_eq!(coin.out_point.vout, 300);
+        assert_eq!(coin.height, 840_000);
+        assert!(!coin.is_coinbase);
+        assert_eq!(coin.amount_sats, 123_456);
+        assert!(coin.script_pubkey.is_p2pkh());
+    }
+}
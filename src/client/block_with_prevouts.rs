@@ -0,0 +1,54 @@
+use bitcoin::{consensus::deserialize, ScriptBuf, Transaction};
+use serde::Deserialize;
+
+use crate::error::RetrieverError;
+
+/// This is to collect the json response for `getblock` with verbosity 3, which lists the
+/// scriptpubkeys the inputs of each transaction spend.
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlockWithPrevouts {
+    tx: Vec<TransactionWithPrevouts>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct TransactionWithPrevouts {
+    #[serde(with = "bitcoincore_rpc::json::serde_hex")]
+    hex: Vec<u8>,
+    vin: Vec<InputWithPrevout>,
+}
+
+/// The input of a coinbase transaction has no prevout.
+#[derive(Debug, Deserialize, Clone)]
+struct InputWithPrevout {
+    prevout: Option<Prevout>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct Prevout {
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: PrevoutScriptPubKey,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct PrevoutScriptPubKey {
+    hex: ScriptBuf,
+}
+
+impl BlockWithPrevouts {
+    /// The transactions of the block, each with the scriptpubkeys its inputs spend. Coinbase
+    /// transactions come without any.
+    pub fn into_transactions(self) -> Result<Vec<(Transaction, Vec<ScriptBuf>)>, RetrieverError> {
+        self.tx
+            .into_iter()
+            .map(|tx| {
+                let prevouts = tx
+                    .vin
+                    .into_iter()
+                    .filter_map(|input| input.prevout)
+                    .map(|prevout| prevout.script_pub_key.hex)
+                    .collect();
+                Ok((deserialize(&tx.hex)?, prevouts))
+            })
+            .collect()
+    }
+}
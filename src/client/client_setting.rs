@@ -12,6 +12,33 @@ pub struct ClientSetting {
     /// This is the time period in which the rpc connection stays alive despite not receiving a response from bitcoincore.
     /// It is important to set this high enough for creating a utxo set dump or scanning the utxo set takes more than the default 15 seconds.
     timeout_seconds: u64,
+    /// Credentials set by rpcuser/rpcpassword or rpcauth in bitcoin.conf. When set, these are
+    /// used instead of the cookie file.
+    rpc_user: Option<String>,
+    rpc_password: Option<String>,
+    /// A SOCKS5 proxy (host:port), e.g. Tor's 127.0.0.1:9050 for nodes behind hidden services.
+    proxy: Option<String>,
+    proxy_user: Option<String>,
+    proxy_password: Option<String>,
+    /// A PEM encoded CA certificate to trust for https rpc urls, e.g. of a self-managed CA.
+    tls_ca_cert_path: Option<String>,
+    /// Accepts any certificate (e.g. self-signed) for https rpc urls. The node is then not
+    /// authenticated, only the traffic is encrypted.
+    tls_accept_invalid_certs: bool,
+    /// Complete urls (including the port) of further nodes to fail over to, in order, when the
+    /// current node is unreachable or times out. Credentials, proxy and TLS settings are shared.
+    fallback_urls: Vec<String>,
+    /// How often an rpc call is retried after a transient error, waiting twice as long before
+    /// each retry, starting from retry_backoff_millis.
+    retries: u32,
+    retry_backoff_millis: u64,
+    /// The number of connections used for independent calls made in parallel.
+    pool_size: usize,
+    /// The url (including the port) of the REST interface of the node. When set, found outputs
+    /// are verified over REST instead of rpc.
+    rest_url: Option<String>,
+    /// The wallet the rpc calls are made on, for the wallet rpcs of the node.
+    wallet: Option<String>,
 }
 
 impl ClientSetting {
@@ -21,6 +48,109 @@ impl ClientSetting {
             rpc_port: rpc_port.to_string(),
             cookie_path: cookie_path.to_string(),
             timeout_seconds,
+            rpc_user: None,
+            rpc_password: None,
+            proxy: None,
+            proxy_user: None,
+            proxy_password: None,
+            tls_ca_cert_path: None,
+            tls_accept_invalid_certs: false,
+            fallback_urls: vec![],
+            retries: 0,
+            retry_backoff_millis: 0,
+            pool_size: 1,
+            rest_url: None,
+            wallet: None,
         }
     }
+
+    pub fn with_user_pass(mut self, rpc_user: &str, rpc_password: &str) -> Self {
+        self.rpc_user = Some(rpc_user.to_string());
+        self.rpc_password = Some(rpc_password.to_string());
+        self
+    }
+
+    pub fn with_socks5_proxy(
+        mut self,
+        proxy: &str,
+        proxy_user: Option<String>,
+        proxy_password: Option<String>,
+    ) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self.proxy_user = proxy_user;
+        self.proxy_password = proxy_password;
+        self
+    }
+
+    pub fn with_tls(
+        mut self,
+        tls_ca_cert_path: Option<String>,
+        accept_invalid_certs: bool,
+    ) -> Self {
+        self.tls_ca_cert_path = tls_ca_cert_path;
+        self.tls_accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    pub fn with_fallback_urls(mut self, fallback_urls: Vec<String>) -> Self {
+        self.fallback_urls = fallback_urls;
+        self
+    }
+
+    pub fn with_retry(mut self, retries: u32, retry_backoff_millis: u64) -> Self {
+        self.retries = retries;
+        self.retry_backoff_millis = retry_backoff_millis;
+        self
+    }
+
+    pub fn with_pool_size(mut self, pool_size: usize) -> Self {
+        self.pool_size = pool_size.max(1);
+        self
+    }
+
+    pub fn with_rest_url(mut self, rest_url: &str) -> Self {
+        self.rest_url = Some(rest_url.to_string());
+        self
+    }
+
+    pub fn with_wallet(mut self, wallet: &str) -> Self {
+        self.wallet = Some(wallet.to_string());
+        self
+    }
+
+    /// The url of the primary node. A url pointing at a Unix socket (unix:///path/to/socket) is
+    /// used as is, otherwise the rpc port is appended.
+    pub fn get_primary_url(&self) -> String {
+        if self.rpc_url.starts_with("unix://") {
+            self.rpc_url.clone()
+        } else {
+            format!("{}:{}", self.rpc_url, self.rpc_port)
+        }
+    }
+
+    /// The urls of all nodes, the primary one first followed by the fallbacks, pointing at the
+    /// wallet if one is set.
+    pub fn get_endpoint_urls(&self) -> Vec<String> {
+        let mut endpoint_urls = vec![self.get_primary_url()];
+        endpoint_urls.extend(self.fallback_urls.iter().cloned());
+        match &self.wallet {
+            Some(wallet) => endpoint_urls
+                .iter()
+                .map(|url| format!("{}/wallet/{}", url.trim_end_matches('/'), wallet))
+                .collect(),
+            None => endpoint_urls,
+        }
+    }
+
+    /// Whether the connection to the given url needs the retriever's own http transport instead
+    /// of the simple http transport of jsonrpc. Fallback nodes need it too, as only it tells an
+    /// unreachable node from a request that timed out, which must not be sent to another node.
+    pub fn needs_custom_transport(&self, url: &str) -> bool {
+        self.proxy.is_some()
+            || !self.fallback_urls.is_empty()
+            || url.starts_with("unix://")
+            || url.starts_with("https://")
+            || self.tls_ca_cert_path.is_some()
+            || self.tls_accept_invalid_certs
+    }
 }
@@ -1,28 +1,111 @@
+pub mod block_with_prevouts;
 pub mod client_setting;
 pub mod dump_utxout_set_result;
+pub mod rest;
+pub mod retry;
+pub mod scan_in_progress_policy;
+pub mod transport;
 
 use std::{fs, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 
-use bitcoincore_rpc::{jsonrpc::serde_json::Value, Auth, RpcApi};
-use tracing::{error, info};
+use bitcoin::{BlockHash, OutPoint, ScriptBuf, Transaction, TxOut, Txid};
+use bitcoincore_rpc::{
+    json::{GetTxOutResult, ScanTxOutRequest, Utxo},
+    jsonrpc::{
+        self,
+        serde_json::{self, Value},
+        Transport,
+    },
+    Auth, RpcApi,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::{
+    data::defaults::SCANTXOUTSET_BATCH_SIZE,
+    descriptor_checksum::raw_descriptor_with_checksum,
     error::RetrieverError,
-    path_pairs::{PathScanRequestDescriptorTrio, PathScanResultDescriptorTrio},
+    event_log::{EventLog, LoggedEvent},
+    path_pairs::{
+        split_batch_scan_result, PathScanRequestDescriptorTrio, PathScanResultDescriptorTrio,
+    },
+};
+
+use self::{
+    block_with_prevouts::BlockWithPrevouts,
+    client_setting::ClientSetting,
+    dump_utxout_set_result::DumpTxoutSetResult,
+    rest::RestClient,
+    retry::RetryPolicy,
+    transport::{FailoverTransport, HttpTransport},
 };
 
-use self::{client_setting::ClientSetting, dump_utxout_set_result::DumpTxoutSetResult};
+/// The jsonrpc error code of calls to methods the node does not know.
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+/// The first bitcoincore versions offering the rpc capabilities the retriever relies on.
+const SCANTXOUTSET_MIN_VERSION: u64 = 170_000;
+const DUMPTXOUTSET_MIN_VERSION: u64 = 200_000;
+const GETTXSPENDINGPREVOUT_MIN_VERSION: u64 = 240_000;
+/// From this version on, getblock with verbosity 3 lists the prevouts of the inputs.
+const GETBLOCK_PREVOUTS_MIN_VERSION: u64 = 250_000;
+/// From this version on, dumptxoutset takes the type of dump ("latest" or "rollback") as its
+/// second argument, and requires it.
+const DUMPTXOUTSET_TYPE_MIN_VERSION: u64 = 280_000;
+
+/// The outcome of a scan of the utxo set for the details of finds. When bitcoincore fails on a
+/// batch, the results of the batches before it are kept, and the requests left unscanned are
+/// returned with the error so they alone can be retried.
+#[derive(Debug)]
+pub struct UtxoSetScan {
+    pub results: Vec<PathScanResultDescriptorTrio>,
+    pub unscanned: Vec<PathScanRequestDescriptorTrio>,
+    pub error: Option<RetrieverError>,
+}
+
+/// Formats a version as reported by getnetworkinfo, e.g. 270100 as v27.1. Versions before v22
+/// were numbered 0.x.
+fn format_version(version: u64) -> String {
+    let (major, minor) = (version / 10_000, version / 100 % 100);
+    if major < 22 {
+        format!("v0.{}.{}", major, minor)
+    } else {
+        format!("v{}.{}", major, minor)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct BitcoincoreRpcClient {
     client: Arc<bitcoincore_rpc::Client>,
+    /// A second connection for scan status and abort calls, which would otherwise queue behind
+    /// the running scan.
+    control_client: Arc<bitcoincore_rpc::Client>,
+    /// Connections for many independent calls made in parallel, the main one among them.
+    pool: Vec<Arc<bitcoincore_rpc::Client>>,
+    /// The REST interface of the node, used instead of rpc for verifying outputs when set.
+    rest: Option<RestClient>,
+    retry: RetryPolicy,
+    /// The version of the node as reported by getnetworkinfo, e.g. 270100 for v27.1.
+    node_version: u64,
+    event_log: EventLog,
+    /// Kept for opening connections to wallets of the node later on.
+    setting: ClientSetting,
+    credentials: (Option<String>, Option<String>),
 }
 
 impl Default for BitcoincoreRpcClient {
     fn default() -> Self {
+        let client = Arc::new(bitcoincore_rpc::Client::new("0.0.0.0", Auth::None).unwrap());
         Self {
-            client: Arc::new(bitcoincore_rpc::Client::new("0.0.0.0", Auth::None).unwrap()),
+            pool: vec![client.clone()],
+            client,
+            control_client: Arc::new(bitcoincore_rpc::Client::new("0.0.0.0", Auth::None).unwrap()),
+            rest: None,
+            retry: RetryPolicy::default(),
+            node_version: 0,
+            setting: ClientSetting::default(),
+            credentials: (None, None),
+            event_log: EventLog::default(),
         }
     }
 }
@@ -32,24 +115,82 @@ impl BitcoincoreRpcClient {
         info!("Creation of bitcoincore rpc client started.");
         let (client_result_sender, mut client_result_receiver) =
             tokio::sync::mpsc::unbounded_channel();
-        let (user, pass) = Auth::CookieFile(PathBuf::from_str(setting.get_cookie_path()).unwrap())
-            .get_user_pass()?;
+        let (user, pass) = match setting.get_rpc_user() {
+            Some(user) => (Some(user.to_owned()), setting.get_rpc_password().to_owned()),
+            None if setting.get_cookie_path().is_empty() => {
+                error!("Neither a cookie file nor an rpc user is set for bitcoincore rpc.");
+                return Err(RetrieverError::NoRpcCredentials);
+            }
+            None => Auth::CookieFile(PathBuf::from_str(setting.get_cookie_path()).unwrap())
+                .get_user_pass()?,
+        };
+        let retry = RetryPolicy::new(
+            *setting.get_retries(),
+            Duration::from_millis(*setting.get_retry_backoff_millis()),
+        );
+        let rest = match setting.get_rest_url() {
+            Some(rest_url) => {
+                info!("Found outputs are verified over REST at {}.", rest_url);
+                let transport = HttpTransport::new(
+                    rest_url,
+                    None,
+                    None,
+                    Duration::from_secs(*setting.get_timeout_seconds()),
+                )?;
+                Some(RestClient::new(Self::configure_transport(
+                    &setting, transport,
+                )?))
+            }
+            None => None,
+        };
         tokio::task::spawn_blocking(move || {
-            let jsonrpc_build = bitcoincore_rpc::jsonrpc::simple_http::Builder::new()
-                .timeout(Duration::from_secs(*setting.get_timeout_seconds()))
-                .auth(user.unwrap(), pass)
-                .url(format!("{}:{}", setting.get_rpc_url(), setting.get_rpc_port()).as_str())
-                .map_err(|err| client_result_sender.send(Err(RetrieverError::from(err))))
-                .unwrap()
-                .build();
-            let jsonrpc_client = bitcoincore_rpc::jsonrpc::Client::from(jsonrpc_build);
-            let client = bitcoincore_rpc::Client::from_jsonrpc(jsonrpc_client);
+            // The main and the control connection, plus the further connections of the pool.
+            let jsonrpc_clients = (0..2 + setting.get_pool_size().saturating_sub(1))
+                .map(|_| Self::build_jsonrpc_client(&setting, user.clone(), pass.clone()))
+                .collect::<Result<Vec<_>, _>>();
+            let mut jsonrpc_clients = match jsonrpc_clients {
+                Ok(jsonrpc_clients) => jsonrpc_clients.into_iter(),
+                Err(err) => {
+                    let _ = client_result_sender.send(Err(err));
+                    return;
+                }
+            };
+            let client = bitcoincore_rpc::Client::from_jsonrpc(jsonrpc_clients.next().unwrap());
+            let control_jsonrpc_client = jsonrpc_clients.next().unwrap();
             info!("Creation of bitcoincore rpc client finished successfully.");
             match client.ping() {
                 Ok(_) => {
                     info!("Bitcoincore rpc client responded successfully to ping.");
+                    let node_version = match retry.call("getnetworkinfo", |_| {
+                        client.call::<Value>("getnetworkinfo", &[])
+                    }) {
+                        Ok(network_info) => network_info["version"].as_u64().unwrap_or_default(),
+                        Err(err) => {
+                            let _ = client_result_sender.send(Err(err));
+                            return;
+                        }
+                    };
+                    info!(
+                        "Bitcoincore version {} detected.",
+                        format_version(node_version)
+                    );
+                    let client = Arc::new(client);
+                    let mut pool = vec![client.clone()];
+                    pool.extend(jsonrpc_clients.map(|jsonrpc_client| {
+                        Arc::new(bitcoincore_rpc::Client::from_jsonrpc(jsonrpc_client))
+                    }));
                     let _ = client_result_sender.send(Ok(BitcoincoreRpcClient {
-                        client: Arc::new(client),
+                        client,
+                        pool,
+                        control_client: Arc::new(bitcoincore_rpc::Client::from_jsonrpc(
+                            control_jsonrpc_client,
+                        )),
+                        rest,
+                        retry,
+                        node_version,
+                        event_log: EventLog::default(),
+                        credentials: (user, pass),
+                        setting,
                     }));
                 }
                 Err(_) => {
@@ -63,10 +204,213 @@ impl BitcoincoreRpcClient {
         client_result_receiver.recv().await.unwrap()
     }
 
+    fn build_jsonrpc_client(
+        setting: &ClientSetting,
+        user: Option<String>,
+        pass: Option<String>,
+    ) -> Result<bitcoincore_rpc::jsonrpc::Client, RetrieverError> {
+        let endpoint_urls = setting.get_endpoint_urls();
+        if endpoint_urls.len() > 1 {
+            info!(
+                "Bitcoincore rpc fails over through {} nodes.",
+                endpoint_urls.len()
+            );
+        }
+        let transports = endpoint_urls
+            .iter()
+            .map(|url| Self::build_transport(setting, url, user.clone(), pass.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(bitcoincore_rpc::jsonrpc::Client::with_transport(
+            FailoverTransport::new(transports),
+        ))
+    }
+
+    fn build_transport(
+        setting: &ClientSetting,
+        url: &str,
+        user: Option<String>,
+        pass: Option<String>,
+    ) -> Result<Box<dyn Transport>, RetrieverError> {
+        let timeout = Duration::from_secs(*setting.get_timeout_seconds());
+        if !setting.needs_custom_transport(url) {
+            return Ok(Box::new(
+                bitcoincore_rpc::jsonrpc::simple_http::Builder::new()
+                    .timeout(timeout)
+                    .auth(user.unwrap(), pass)
+                    .url(url)?
+                    .build(),
+            ));
+        }
+        let transport = HttpTransport::new(url, user, pass, timeout)?;
+        Ok(Box::new(Self::configure_transport(setting, transport)?))
+    }
+
+    /// Routes the transport through the proxy and secures it with the TLS settings, if any.
+    fn configure_transport(
+        setting: &ClientSetting,
+        mut transport: HttpTransport,
+    ) -> Result<HttpTransport, RetrieverError> {
+        if let Some(proxy) = setting.get_proxy() {
+            info!(
+                "Connecting to bitcoincore rpc through socks5 proxy {}.",
+                proxy
+            );
+            let proxy_auth = setting.get_proxy_user().to_owned().map(|user| {
+                (
+                    user,
+                    setting.get_proxy_password().to_owned().unwrap_or_default(),
+                )
+            });
+            transport = transport.with_socks5_proxy(proxy, proxy_auth);
+        }
+        if setting.get_tls_ca_cert_path().is_some() || *setting.get_tls_accept_invalid_certs() {
+            if *setting.get_tls_accept_invalid_certs() {
+                warn!("Accepting invalid certificates, bitcoincore rpc is not authenticated.");
+            }
+            let ca_cert_pem = match setting.get_tls_ca_cert_path() {
+                Some(ca_cert_path) => Some(fs::read(ca_cert_path)?),
+                None => None,
+            };
+            transport = transport.with_tls(
+                ca_cert_pem.as_deref(),
+                *setting.get_tls_accept_invalid_certs(),
+            )?;
+        }
+        Ok(transport)
+    }
+
+    pub fn get_node_version(&self) -> u64 {
+        self.node_version
+    }
+
+    /// Fails with the given error if the node is older than the version a capability needs.
+    fn require_version(
+        &self,
+        min_version: u64,
+        capability: &str,
+        err: RetrieverError,
+    ) -> Result<(), RetrieverError> {
+        if self.node_version < min_version {
+            error!(
+                "Bitcoincore {} does not support {}, {} or later is needed.",
+                format_version(self.node_version),
+                capability,
+                format_version(min_version)
+            );
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Makes the client record its rpc calls in the given event log.
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = event_log;
+        self
+    }
+
+    /// Checks that the node can serve the run before any long work starts: it must be on the
+    /// given network, be done with the initial block download, and keep the blocks from the
+    /// required height on if it is pruned (e.g. those above the height of a rolled back dump).
+    pub async fn preflight(
+        &self,
+        network: bitcoin::Network,
+        required_height: Option<u64>,
+    ) -> Result<(), RetrieverError> {
+        info!("Running preflight checks on bitcoincore.");
+        let client = self.client.clone();
+        let retry = self.retry;
+        let (blockchain_info, network_info) = tokio::task::spawn_blocking(move || {
+            Ok::<_, RetrieverError>((
+                retry.call("getblockchaininfo", |_| {
+                    client.call::<Value>("getblockchaininfo", &[])
+                })?,
+                retry.call("getnetworkinfo", |_| {
+                    client.call::<Value>("getnetworkinfo", &[])
+                })?,
+            ))
+        })
+        .await??;
+        info!(
+            "Connected to bitcoincore {} on chain {} at height {}.",
+            network_info["subversion"].as_str().unwrap_or_default(),
+            blockchain_info["chain"].as_str().unwrap_or_default(),
+            blockchain_info["blocks"].as_u64().unwrap_or_default()
+        );
+        if blockchain_info["chain"].as_str() != Some(network.to_core_arg()) {
+            error!(
+                "Bitcoincore is on chain {}, but the retriever is set to {}.",
+                blockchain_info["chain"],
+                network.to_core_arg()
+            );
+            return Err(RetrieverError::NodeNetworkMismatch);
+        }
+        if blockchain_info["initialblockdownload"].as_bool() == Some(true) {
+            error!("Bitcoincore is still in initial block download.");
+            return Err(RetrieverError::NodeInInitialBlockDownload);
+        }
+        if network_info["networkactive"].as_bool() == Some(false) {
+            warn!("Network activity of bitcoincore is disabled, its chain tip may be stale.");
+        }
+        if let (Some(required_height), Some(true)) =
+            (required_height, blockchain_info["pruned"].as_bool())
+        {
+            let prune_height = blockchain_info["pruneheight"].as_u64().unwrap_or_default();
+            if prune_height > required_height {
+                error!(
+                    "Bitcoincore is pruned up to height {}, but blocks from height {} are required.",
+                    prune_height, required_height
+                );
+                return Err(RetrieverError::NodePrunedBelowRequiredHeight);
+            }
+        }
+        info!("Preflight checks on bitcoincore passed.");
+        Ok(())
+    }
+
     pub async fn dump_utxo_set(
         &self,
         data_dump_dir_path: &str,
+        cancellation_token: &CancellationToken,
+    ) -> Result<DumpTxoutSetResult, RetrieverError> {
+        self.dump(data_dump_dir_path, None, cancellation_token)
+            .await
+    }
+
+    /// Dumps the utxo set as of the given block height using the rollback option of
+    /// `dumptxoutset` (bitcoincore v28 and later). The node rolls its chainstate back to the
+    /// height and then forward again, and does not take part in the network meanwhile.
+    pub async fn dump_utxo_set_at_height(
+        &self,
+        data_dump_dir_path: &str,
+        height: u64,
+        cancellation_token: &CancellationToken,
+    ) -> Result<DumpTxoutSetResult, RetrieverError> {
+        self.dump(data_dump_dir_path, Some(height), cancellation_token)
+            .await
+    }
+
+    /// Asks bitcoincore for the dump and waits for it unless cancelled. The node has no call to
+    /// abort a dump, so a cancelled one is still written, under a temporary name until complete,
+    /// and is found in datadir by the next run.
+    async fn dump(
+        &self,
+        data_dump_dir_path: &str,
+        rollback_height: Option<u64>,
+        cancellation_token: &CancellationToken,
     ) -> Result<DumpTxoutSetResult, RetrieverError> {
+        self.require_version(
+            DUMPTXOUTSET_MIN_VERSION,
+            "dumptxoutset",
+            RetrieverError::NodeTooOldForDump,
+        )?;
+        if rollback_height.is_some() {
+            self.require_version(
+                DUMPTXOUTSET_TYPE_MIN_VERSION,
+                "dumping at a height",
+                RetrieverError::NodeTooOldForDumpHeight,
+            )?;
+        }
+        let typed = self.node_version >= DUMPTXOUTSET_TYPE_MIN_VERSION;
         let dir_path = PathBuf::from_str(data_dump_dir_path).unwrap();
         let mut file_path = dir_path.clone();
         file_path.extend(["utxo_dump.dat"]);
@@ -76,58 +420,616 @@ impl BitcoincoreRpcClient {
         }
         fs::create_dir_all(&dir_path)?;
         let client = self.client.clone();
-        let (response_sender, response_receiver) = tokio::sync::oneshot::channel();
-        tokio::task::spawn_blocking(move || {
-            info!("Requesting the utxo dump file from bitcoincore.");
-            let response = client.call::<DumpTxoutSetResult>(
-                "dumptxoutset",
-                &[Value::String(file_path.to_str().unwrap().to_string())],
-            );
-            info!("Utxo dump file fetched from bitcoincore successfully.");
-            let _ = response_sender.send(response);
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
+        let dump = tokio::task::spawn_blocking(move || {
+            let mut params = vec![Value::String(file_path.to_str().unwrap().to_string())];
+            match rollback_height {
+                Some(height) => {
+                    info!(
+                        "Requesting the utxo dump file at height {} from bitcoincore.",
+                        height
+                    );
+                    params.push(Value::String("rollback".to_string()));
+                    params.push(serde_json::json!({ "rollback": height }));
+                }
+                None => {
+                    info!("Requesting the utxo dump file from bitcoincore.");
+                    if typed {
+                        params.push(Value::String("latest".to_string()));
+                    }
+                }
+            }
+            let response = retry.call_non_idempotent("dumptxoutset", |_| {
+                client.call::<DumpTxoutSetResult>("dumptxoutset", &params)
+            });
+            event_log.log(LoggedEvent::RpcCall {
+                method: "dumptxoutset".to_string(),
+                success: response.is_ok(),
+            });
+            if response.is_ok() {
+                info!("Utxo dump file fetched from bitcoincore successfully.");
+            }
+            response
         });
-
-        Ok(response_receiver.await.unwrap()?)
+        tokio::select! {
+            response = dump => response?,
+            _ = cancellation_token.cancelled() => {
+                warn!("Waiting for the utxo dump cancelled, bitcoincore goes on writing it.");
+                Err(RetrieverError::Cancelled)
+            }
+        }
     }
 
+    /// Scans the utxo set for the unspent outputs of the descriptors of the requests, in batches.
+    /// Only a node too old for scantxoutset fails the whole scan, see `UtxoSetScan`.
     pub async fn scan_utxo_set(
         &self,
         scan_requests: Vec<PathScanRequestDescriptorTrio>,
-    ) -> Result<Vec<PathScanResultDescriptorTrio>, RetrieverError> {
+    ) -> Result<UtxoSetScan, RetrieverError> {
+        self.require_version(
+            SCANTXOUTSET_MIN_VERSION,
+            "scantxoutset",
+            RetrieverError::NodeTooOldForScan,
+        )?;
         info!("Scanning the utxo set for details of non-empty ScriptPubKeys.");
-        let (results_sender, mut results_receiver) = tokio::sync::mpsc::unbounded_channel();
         let client = self.client.clone();
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
         tokio::task::spawn_blocking(move || {
             let mut results = vec![];
-            for PathScanRequestDescriptorTrio(path, request, descriptor) in scan_requests {
-                info!("Scan request sent to bitcoincore.");
-                results.push(PathScanResultDescriptorTrio::new(
-                    path,
-                    client
-                        .scan_tx_out_set_blocking(&[request])
-                        .map_err(|err| results_sender.send(Err(RetrieverError::from(err))))
-                        .unwrap(),
-                    descriptor,
-                ));
+            let mut scan_requests = scan_requests.into_iter().peekable();
+            // All descriptors of a batch are scanned in one pass over the utxo set.
+            while scan_requests.peek().is_some() {
+                let batch = scan_requests
+                    .by_ref()
+                    .take(SCANTXOUTSET_BATCH_SIZE)
+                    .collect::<Vec<_>>();
+                let requests = batch
+                    .iter()
+                    .map(|PathScanRequestDescriptorTrio(_, request, _)| request.clone())
+                    .collect::<Vec<_>>();
+                info!(
+                    descriptors = requests.len(),
+                    "Scan request sent to bitcoincore."
+                );
+                let response = retry.call_non_idempotent("scantxoutset", |_| {
+                    client.scan_tx_out_set_blocking(&requests)
+                });
+                event_log.log(LoggedEvent::RpcCall {
+                    method: "scantxoutset".to_string(),
+                    success: response.is_ok(),
+                });
+                let batch_result = match response {
+                    Ok(batch_result) => batch_result,
+                    Err(err) => {
+                        error!("Bitcoincore failed to scan the utxo set for details.");
+                        return Ok(UtxoSetScan {
+                            results,
+                            unscanned: batch.into_iter().chain(scan_requests).collect(),
+                            error: Some(err),
+                        });
+                    }
+                };
+                results.extend(split_batch_scan_result(batch, &batch_result));
                 info!("Scan result received from bitcoincore.");
             }
             info!("Bitcoincore scan for details completed.");
-            let _ = results_sender.send(Ok(results));
-        });
-        let results = results_receiver.recv().await.unwrap();
-        results
+            Ok(UtxoSetScan {
+                results,
+                unscanned: vec![],
+                error: None,
+            })
+        })
+        .await?
+    }
+
+    /// Scans the utxo set for the unspent outputs of raw scriptpubkeys, which have no descriptor
+    /// of their own, as raw() descriptors.
+    pub async fn scan_utxo_set_for_script_pubkeys(
+        &self,
+        script_pubkeys: Vec<ScriptBuf>,
+    ) -> Result<Vec<Utxo>, RetrieverError> {
+        self.require_version(
+            SCANTXOUTSET_MIN_VERSION,
+            "scantxoutset",
+            RetrieverError::NodeTooOldForScan,
+        )?;
+        info!("Scanning the utxo set for details of watchlist ScriptPubKeys.");
+        let requests = script_pubkeys
+            .iter()
+            .map(|script_pubkey| {
+                Ok(ScanTxOutRequest::Single(raw_descriptor_with_checksum(
+                    script_pubkey,
+                )?))
+            })
+            .collect::<Result<Vec<_>, RetrieverError>>()?;
+        let client = self.client.clone();
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut unspents = vec![];
+            for batch in requests.chunks(SCANTXOUTSET_BATCH_SIZE) {
+                let response = retry.call_non_idempotent("scantxoutset", |_| {
+                    client.scan_tx_out_set_blocking(batch)
+                });
+                event_log.log(LoggedEvent::RpcCall {
+                    method: "scantxoutset".to_string(),
+                    success: response.is_ok(),
+                });
+                unspents.extend(
+                    response
+                        .inspect_err(|_| {
+                            error!("Bitcoincore failed to scan the utxo set for details.")
+                        })?
+                        .unspents,
+                );
+            }
+            Ok(unspents)
+        })
+        .await?
+    }
+
+    /// Returns the progress (in percent) of the utxo set scan bitcoincore is running, if any.
+    pub async fn get_utxo_set_scan_progress(&self) -> Result<Option<f64>, RetrieverError> {
+        let client = self.control_client.clone();
+        let retry = self.retry;
+        let status = tokio::task::spawn_blocking(move || {
+            retry.call("scantxoutset status", |_| {
+                client.call::<Value>("scantxoutset", &[Value::String("status".to_string())])
+            })
+        })
+        .await??;
+        Ok(status.get("progress").and_then(Value::as_f64))
+    }
+
+    /// Aborts the utxo set scan bitcoincore is running. Returns false if there was none.
+    pub async fn abort_utxo_set_scan(&self) -> Result<bool, RetrieverError> {
+        let client = self.control_client.clone();
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
+        let aborted = tokio::task::spawn_blocking(move || {
+            let response = retry.call("scantxoutset abort", |_| {
+                client.call::<bool>("scantxoutset", &[Value::String("abort".to_string())])
+            });
+            event_log.log(LoggedEvent::RpcCall {
+                method: "scantxoutset abort".to_string(),
+                success: response.is_ok(),
+            });
+            response
+        })
+        .await??;
+        if aborted {
+            warn!("Utxo set scan of bitcoincore aborted.");
+        }
+        Ok(aborted)
+    }
+
+    /// Looks up the given outputs in the utxo set of the node (without the mempool), spreading
+    /// the calls over the connections of the pool. Outputs that are spent are returned as None.
+    pub async fn get_tx_outs(
+        &self,
+        out_points: Vec<OutPoint>,
+    ) -> Result<Vec<Option<GetTxOutResult>>, RetrieverError> {
+        let chunk_size = out_points.len().div_ceil(self.pool.len()).max(1);
+        let mut handles = vec![];
+        for (client, chunk) in self.pool.iter().zip(out_points.chunks(chunk_size)) {
+            let client = client.clone();
+            let chunk = chunk.to_vec();
+            let retry = self.retry;
+            let event_log = self.event_log.clone();
+            handles.push(tokio::task::spawn_blocking(move || {
+                chunk
+                    .iter()
+                    .map(|out_point| {
+                        let response = retry.call("gettxout", |_| {
+                            client.get_tx_out(&out_point.txid, out_point.vout, Some(false))
+                        });
+                        event_log.log(LoggedEvent::RpcCall {
+                            method: "gettxout".to_string(),
+                            success: response.is_ok(),
+                        });
+                        response
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            }));
+        }
+        let mut tx_outs = vec![];
+        for handle in handles {
+            tx_outs.extend(handle.await??);
+        }
+        Ok(tx_outs)
+    }
+
+    /// The txids of the mempool transactions spending the given outputs, if any. Nodes older than
+    /// bitcoincore v24 lack gettxspendingprevout, for them no output is reported as spent.
+    pub async fn get_mempool_spenders(
+        &self,
+        out_points: Vec<OutPoint>,
+    ) -> Result<Vec<Option<Txid>>, RetrieverError> {
+        if self.node_version < GETTXSPENDINGPREVOUT_MIN_VERSION {
+            warn!(
+                "Bitcoincore {} does not support gettxspendingprevout, spends in the mempool are not checked.",
+                format_version(self.node_version)
+            );
+            return Ok(vec![None; out_points.len()]);
+        }
+        let client = self.client.clone();
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
+        tokio::task::spawn_blocking(move || {
+            let prevouts = out_points
+                .iter()
+                .map(|out_point| serde_json::json!({ "txid": out_point.txid, "vout": out_point.vout }))
+                .collect::<Vec<_>>();
+            let response = retry.call("gettxspendingprevout", |_| {
+                client.call::<Vec<Value>>("gettxspendingprevout", &[Value::Array(prevouts.clone())])
+            });
+            event_log.log(LoggedEvent::RpcCall {
+                method: "gettxspendingprevout".to_string(),
+                success: response.is_ok(),
+            });
+            match response {
+                Ok(spenders) => Ok(spenders
+                    .iter()
+                    .map(|spender| {
+                        spender["spendingtxid"]
+                            .as_str()
+                            .and_then(|txid| Txid::from_str(txid).ok())
+                    })
+                    .collect()),
+                Err(RetrieverError::BitcoincoreRpcCrateError(bitcoincore_rpc::Error::JsonRpc(
+                    jsonrpc::Error::Rpc(err),
+                ))) if err.code == RPC_METHOD_NOT_FOUND => {
+                    warn!("Bitcoincore does not support gettxspendingprevout, spends in the mempool are not checked.");
+                    Ok(vec![None; out_points.len()])
+                }
+                Err(err) => Err(err),
+            }
+        })
+        .await?
+    }
+
+    /// Looks for unconfirmed outputs paying to any of the given scriptpubkeys by fetching the
+    /// transactions in the mempool, spreading the calls over the connections of the pool.
+    /// Transactions leaving the mempool meanwhile are skipped.
+    pub async fn get_mempool_outputs_to(
+        &self,
+        script_pubkeys: hashbrown::HashSet<ScriptBuf>,
+    ) -> Result<Vec<(OutPoint, TxOut)>, RetrieverError> {
+        let client = self.client.clone();
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
+        let txids = tokio::task::spawn_blocking(move || {
+            let response = retry.call("getrawmempool", |_| client.get_raw_mempool());
+            event_log.log(LoggedEvent::RpcCall {
+                method: "getrawmempool".to_string(),
+                success: response.is_ok(),
+            });
+            response
+        })
+        .await??;
+        info!("Looking through {} mempool transactions.", txids.len());
+        let script_pubkeys = Arc::new(script_pubkeys);
+        let chunk_size = txids.len().div_ceil(self.pool.len()).max(1);
+        let mut handles = vec![];
+        for (client, chunk) in self.pool.iter().zip(txids.chunks(chunk_size)) {
+            let client = client.clone();
+            let chunk = chunk.to_vec();
+            let retry = self.retry;
+            let script_pubkeys = script_pubkeys.clone();
+            handles.push(tokio::task::spawn_blocking(move || {
+                let mut outputs = vec![];
+                for txid in chunk {
+                    let tx = match retry.call("getrawtransaction", |_| {
+                        client.get_raw_transaction(&txid, None)
+                    }) {
+                        Ok(tx) => tx,
+                        Err(RetrieverError::BitcoincoreRpcCrateError(
+                            bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(_)),
+                        )) => continue,
+                        Err(err) => return Err(err),
+                    };
+                    outputs.extend(
+                        tx.output
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(_, tx_out)| script_pubkeys.contains(&tx_out.script_pubkey))
+                            .map(|(vout, tx_out)| (OutPoint::new(txid, vout as u32), tx_out)),
+                    );
+                }
+                Ok(outputs)
+            }));
+        }
+        let mut outputs = vec![];
+        for handle in handles {
+            outputs.extend(handle.await??);
+        }
+        Ok(outputs)
+    }
+
+    /// The times of the blocks at the given heights.
+    pub async fn get_block_times(&self, heights: Vec<u64>) -> Result<Vec<u32>, RetrieverError> {
+        let client = self.client.clone();
+        let retry = self.retry;
+        tokio::task::spawn_blocking(move || {
+            heights
+                .iter()
+                .map(|height| {
+                    let block_hash =
+                        retry.call("getblockhash", |_| client.get_block_hash(*height))?;
+                    let header =
+                        retry.call("getblockheader", |_| client.get_block_header(&block_hash))?;
+                    Ok(header.time)
+                })
+                .collect()
+        })
+        .await?
+    }
+
+    /// The transactions of the given txids, each looked up in the block at its height, so the
+    /// node needs no txindex.
+    pub async fn get_transactions_at_heights(
+        &self,
+        txids: Vec<(Txid, u64)>,
+    ) -> Result<Vec<Transaction>, RetrieverError> {
+        let client = self.client.clone();
+        let retry = self.retry;
+        tokio::task::spawn_blocking(move || {
+            txids
+                .iter()
+                .map(|(txid, height)| {
+                    let block_hash =
+                        retry.call("getblockhash", |_| client.get_block_hash(*height))?;
+                    retry.call("getrawtransaction", |_| {
+                        client.get_raw_transaction(txid, Some(&block_hash))
+                    })
+                })
+                .collect()
+        })
+        .await?
+    }
+
+    /// Sends the signed transaction to the node with sendrawtransaction, which relays it.
+    pub async fn broadcast(&self, transaction: &Transaction) -> Result<Txid, RetrieverError> {
+        let client = self.client.clone();
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
+        let transaction = transaction.clone();
+        tokio::task::spawn_blocking(move || {
+            // A retried broadcast of a transaction the node did accept is accepted again.
+            let response = retry.call("sendrawtransaction", |_| {
+                client.send_raw_transaction(&transaction)
+            });
+            event_log.log(LoggedEvent::RpcCall {
+                method: "sendrawtransaction".to_string(),
+                success: response.is_ok(),
+            });
+            match &response {
+                Ok(txid) => info!("Transaction {} broadcast by bitcoincore.", txid),
+                Err(err) => error!("Bitcoincore rejected the transaction: {:?}", err),
+            }
+            response
+        })
+        .await?
+    }
+
+    pub async fn get_block_count(&self) -> Result<u64, RetrieverError> {
+        let client = self.client.clone();
+        let retry = self.retry;
+        tokio::task::spawn_blocking(move || {
+            retry.call("getblockcount", |_| client.get_block_count())
+        })
+        .await?
+    }
+
+    /// Waits until the transaction has the given number of confirmations, looking for it in each
+    /// block from the given height on, every poll interval, and returns the height of its block.
+    /// Blocks are read instead of the transaction, so the node needs no txindex. A block of the
+    /// transaction that is reorged out is looked for again.
+    pub async fn wait_for_confirmations(
+        &self,
+        txid: Txid,
+        from_height: u64,
+        confirmations: u64,
+        poll_interval: Duration,
+        cancellation_token: &CancellationToken,
+    ) -> Result<u64, RetrieverError> {
+        let mut next_height = from_height;
+        let mut confirmed_at: Option<(u64, BlockHash)> = None;
+        loop {
+            let client = self.client.clone();
+            let retry = self.retry;
+            let (tip, found, next) = tokio::task::spawn_blocking(move || {
+                let tip = retry.call("getblockcount", |_| client.get_block_count())?;
+                let mut found = confirmed_at;
+                if let Some((height, block_hash)) = found {
+                    if retry.call("getblockhash", |_| client.get_block_hash(height))? != block_hash
+                    {
+                        warn!("The block of {} was reorged out.", txid);
+                        found = None;
+                        next_height = height;
+                    }
+                }
+                while found.is_none() && next_height <= tip {
+                    let block_hash =
+                        retry.call("getblockhash", |_| client.get_block_hash(next_height))?;
+                    let block = retry.call("getblock", |_| client.get_block_info(&block_hash))?;
+                    if block.tx.contains(&txid) {
+                        found = Some((next_height, block_hash));
+                    }
+                    next_height += 1;
+                }
+                Ok::<_, RetrieverError>((tip, found, next_height))
+            })
+            .await??;
+            confirmed_at = found;
+            next_height = next;
+            if let Some((height, _)) = confirmed_at {
+                info!(
+                    "Transaction {} has {} confirmations.",
+                    txid,
+                    tip + 1 - height
+                );
+                if tip + 1 - height >= confirmations {
+                    return Ok(height);
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(poll_interval) => {}
+                _ = cancellation_token.cancelled() => {
+                    warn!("Waiting for the confirmations of {} cancelled.", txid);
+                    return Err(RetrieverError::Cancelled);
+                }
+            }
+        }
+    }
+
+    /// The transactions of the block at the given height, each with the scriptpubkeys its inputs
+    /// spend, which the utxo set does not hold. The node must still have the undo data of the
+    /// block, which pruned nodes lose.
+    pub async fn get_block_transactions_with_prevouts(
+        &self,
+        height: u64,
+    ) -> Result<Vec<(Transaction, Vec<ScriptBuf>)>, RetrieverError> {
+        self.require_version(
+            GETBLOCK_PREVOUTS_MIN_VERSION,
+            "getblock with prevouts",
+            RetrieverError::NodeTooOldForSilentPayments,
+        )?;
+        let client = self.client.clone();
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
+        tokio::task::spawn_blocking(move || {
+            let block_hash = retry.call("getblockhash", |_| client.get_block_hash(height))?;
+            let params = [Value::String(block_hash.to_string()), Value::from(3)];
+            let response = retry.call("getblock", |_| {
+                client.call::<BlockWithPrevouts>("getblock", &params)
+            });
+            event_log.log(LoggedEvent::RpcCall {
+                method: "getblock".to_string(),
+                success: response.is_ok(),
+            });
+            response?.into_transactions()
+        })
+        .await?
+    }
+
+    /// Creates a blank descriptor wallet without private keys on the node and imports the given
+    /// descriptors into it with importdescriptors. Returns the result of each import.
+    pub async fn import_descriptors_into_new_wallet(
+        &self,
+        wallet_name: &str,
+        imports: Vec<Value>,
+    ) -> Result<Vec<Value>, RetrieverError> {
+        if self.setting.get_rpc_url().starts_with("unix://") {
+            error!("Wallet rpcs cannot be reached over a Unix socket url.");
+            return Err(RetrieverError::InvalidRpcUrl);
+        }
+        let client = self.client.clone();
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
+        let wallet_setting = self.setting.clone().with_wallet(wallet_name);
+        let (user, pass) = self.credentials.clone();
+        let wallet_name = wallet_name.to_string();
+        tokio::task::spawn_blocking(move || {
+            info!(
+                "Creating the watch-only wallet {} on bitcoincore.",
+                wallet_name
+            );
+            // Name, disable private keys, blank, passphrase, avoid reuse and descriptors.
+            let params = [
+                Value::String(wallet_name.clone()),
+                Value::Bool(true),
+                Value::Bool(true),
+                Value::String(String::new()),
+                Value::Bool(false),
+                Value::Bool(true),
+            ];
+            let response = retry.call_non_idempotent("createwallet", |_| {
+                client.call::<Value>("createwallet", &params)
+            });
+            event_log.log(LoggedEvent::RpcCall {
+                method: "createwallet".to_string(),
+                success: response.is_ok(),
+            });
+            response?;
+            let wallet_client = bitcoincore_rpc::Client::from_jsonrpc(Self::build_jsonrpc_client(
+                &wallet_setting,
+                user,
+                pass,
+            )?);
+            info!(
+                "Importing {} descriptors into wallet {}, the node rescans for them.",
+                imports.len(),
+                wallet_name
+            );
+            let imports = Value::Array(imports);
+            let response = retry.call_non_idempotent("importdescriptors", |_| {
+                wallet_client
+                    .call::<Vec<Value>>("importdescriptors", std::slice::from_ref(&imports))
+            });
+            event_log.log(LoggedEvent::RpcCall {
+                method: "importdescriptors".to_string(),
+                success: response.is_ok(),
+            });
+            response
+        })
+        .await?
+    }
+
+    /// Whether each of the given outputs is in the utxo set of the node (without the mempool),
+    /// asked over REST if it is set up and with gettxout over the pool otherwise.
+    pub async fn are_unspent(
+        &self,
+        out_points: Vec<OutPoint>,
+    ) -> Result<Vec<bool>, RetrieverError> {
+        let Some(rest) = self.rest.clone() else {
+            return Ok(self
+                .get_tx_outs(out_points)
+                .await?
+                .iter()
+                .map(Option::is_some)
+                .collect());
+        };
+        let retry = self.retry;
+        let event_log = self.event_log.clone();
+        tokio::task::spawn_blocking(move || {
+            let response = retry.call("rest getutxos", |_| rest.get_utxos(&out_points));
+            event_log.log(LoggedEvent::RpcCall {
+                method: "rest getutxos".to_string(),
+                success: response.is_ok(),
+            });
+            response
+        })
+        .await?
     }
 }
 
 impl Zeroize for BitcoincoreRpcClient {
     fn zeroize(&mut self) {
-        let client = bitcoincore_rpc::Client::new(
-            "0.0.0.0:0000",
-            Auth::CookieFile(PathBuf::from_str("/cookie/jar/obviously").unwrap()),
-        )
-        .unwrap();
+        // No cookie file is read with no auth, so the placeholder client is always created.
+        let client = bitcoincore_rpc::Client::new("0.0.0.0:0000", Auth::None).unwrap();
         self.client = Arc::new(client);
+        self.control_client = self.client.clone();
+        self.pool = vec![self.client.clone()];
+        self.rest = None;
+        self.setting.zeroize();
+        self.credentials.0.zeroize();
+        self.credentials.1.zeroize();
     }
 }
 
 impl ZeroizeOnDrop for BitcoincoreRpcClient {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_version_works_01() {
+        assert_eq!(format_version(270100), "v27.1");
+        assert_eq!(format_version(DUMPTXOUTSET_TYPE_MIN_VERSION), "v28.0");
+        assert_eq!(format_version(SCANTXOUTSET_MIN_VERSION), "v0.17.0");
+    }
+}
@@ -0,0 +1,87 @@
+use bitcoin::OutPoint;
+use bitcoincore_rpc::jsonrpc::{self, serde_json};
+use serde::Deserialize;
+
+use super::transport::HttpTransport;
+
+/// The most outpoints bitcoincore answers in a single getutxos request.
+pub const REST_GETUTXOS_MAX_OUTPOINTS: usize = 15;
+
+/// A client of the REST interface of bitcoincore (-rest), which needs no rpc credentials. It
+/// looks up outputs in the utxo set with getutxos.
+#[derive(Debug, Clone)]
+pub struct RestClient {
+    transport: HttpTransport,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetUtxosResult {
+    /// One character per requested outpoint, 1 if it is unspent and 0 otherwise.
+    bitmap: String,
+}
+
+impl RestClient {
+    pub fn new(transport: HttpTransport) -> Self {
+        RestClient { transport }
+    }
+
+    /// Whether each of the given outputs is in the utxo set of the node (without the mempool).
+    /// Errors reaching the node are transport errors, so they are retried like rpc calls.
+    pub fn get_utxos(&self, out_points: &[OutPoint]) -> Result<Vec<bool>, bitcoincore_rpc::Error> {
+        let mut unspent = vec![];
+        for chunk in out_points.chunks(REST_GETUTXOS_MAX_OUTPOINTS) {
+            let (status, body) = self
+                .transport
+                .get(&get_utxos_path(chunk))
+                .map_err(|err| jsonrpc::Error::Transport(Box::new(err)))?;
+            if !(200..300).contains(&status) {
+                return Err(bitcoincore_rpc::Error::ReturnedError(format!(
+                    "getutxos answered with status {}: {}",
+                    status,
+                    String::from_utf8_lossy(&body).trim()
+                )));
+            }
+            unspent.extend(parse_bitmap(&body, chunk.len())?);
+        }
+        Ok(unspent)
+    }
+}
+
+fn get_utxos_path(out_points: &[OutPoint]) -> String {
+    let out_points = out_points
+        .iter()
+        .map(|out_point| format!("/{}-{}", out_point.txid, out_point.vout))
+        .collect::<String>();
+    format!("/rest/getutxos{}.json", out_points)
+}
+
+fn parse_bitmap(body: &[u8], out_points: usize) -> Result<Vec<bool>, bitcoincore_rpc::Error> {
+    let result: GetUtxosResult = serde_json::from_slice(body)?;
+    if result.bitmap.len() != out_points {
+        return Err(bitcoincore_rpc::Error::UnexpectedStructure);
+    }
+    Ok(result.bitmap.chars().map(|bit| bit == '1').collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::Txid;
+
+    use super::*;
+
+    #[test]
+    fn rest_client_works_01() {
+        let txid =
+            Txid::from_str("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+                .unwrap();
+        assert_eq!(
+            get_utxos_path(&[OutPoint::new(txid, 0), OutPoint::new(txid, 3)]),
+            format!("/rest/getutxos/{}-0/{}-3.json", txid, txid)
+        );
+        let body = br#"{"chainHeight":840000,"chaintipHash":"00","bitmap":"101","utxos":[]}"#;
+        assert_eq!(parse_bitmap(body, 3).unwrap(), vec![true, false, true]);
+        assert!(parse_bitmap(body, 2).is_err());
+    }
+}
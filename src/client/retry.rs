@@ -0,0 +1,176 @@
+use std::{thread, time::Duration};
+
+use bitcoincore_rpc::jsonrpc;
+use tracing::{error, warn};
+
+use crate::{data::defaults::RPC_RETRY_MAX_BACKOFF_MILLIS, error::RetrieverError};
+
+use super::transport::is_unsent_request;
+
+/// How often and how patiently rpc calls are retried after transient errors, i.e. the node not
+/// being reachable, the connection dropping or timing out. The wait before each retry doubles,
+/// starting from the initial backoff. Calls that must not run twice are only retried when the
+/// node was not reachable, see `call_non_idempotent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetryPolicy {
+    retries: u32,
+    initial_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(retries: u32, initial_backoff: Duration) -> Self {
+        RetryPolicy {
+            retries,
+            initial_backoff,
+        }
+    }
+
+    /// The wait before the given retry (starting from 1).
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(Duration::from_millis(RPC_RETRY_MAX_BACKOFF_MILLIS))
+    }
+
+    /// Makes the call, which gets the number of the attempt (starting from 0), until it succeeds,
+    /// fails with an error that is not transient, or all retries failed. Blocks while backing off.
+    pub fn call<T>(
+        &self,
+        method: &str,
+        call: impl FnMut(u32) -> Result<T, bitcoincore_rpc::Error>,
+    ) -> Result<T, RetrieverError> {
+        self.call_retrying(method, call, is_transient)
+    }
+
+    /// Makes a call that must not run twice on the node, such as one starting a dump or a scan,
+    /// like `call`, but only retries it when it could not be sent. After a timeout or a dropped
+    /// connection the node may still be running it, and sending it again would fail on the dump
+    /// file it is writing or on the scan in progress.
+    pub fn call_non_idempotent<T>(
+        &self,
+        method: &str,
+        call: impl FnMut(u32) -> Result<T, bitcoincore_rpc::Error>,
+    ) -> Result<T, RetrieverError> {
+        self.call_retrying(
+            method,
+            call,
+            |err| matches!(err, bitcoincore_rpc::Error::JsonRpc(err) if is_unsent_request(err)),
+        )
+    }
+
+    fn call_retrying<T>(
+        &self,
+        method: &str,
+        mut call: impl FnMut(u32) -> Result<T, bitcoincore_rpc::Error>,
+        is_retryable: fn(&bitcoincore_rpc::Error) -> bool,
+    ) -> Result<T, RetrieverError> {
+        let mut attempt = 0;
+        loop {
+            match call(attempt) {
+                Err(err) if is_retryable(&err) => {
+                    if attempt == self.retries {
+                        error!(
+                            "Rpc call {} failed after {} attempts: {}",
+                            method,
+                            attempt + 1,
+                            err
+                        );
+                        return Err(RetrieverError::RpcRetriesExhausted);
+                    }
+                    attempt += 1;
+                    let backoff = self.backoff(attempt);
+                    warn!(
+                        "Rpc call {} failed ({}), retrying in {} ms.",
+                        method,
+                        err,
+                        backoff.as_millis()
+                    );
+                    thread::sleep(backoff);
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+}
+
+/// Whether the error lies in the connection rather than in the request, so retrying may help.
+fn is_transient(err: &bitcoincore_rpc::Error) -> bool {
+    matches!(
+        err,
+        bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn retry_policy_works_01() {
+        let retry = RetryPolicy::new(3, Duration::from_millis(1));
+        assert_eq!(retry.backoff(1), Duration::from_millis(1));
+        assert_eq!(retry.backoff(3), Duration::from_millis(4));
+        assert_eq!(
+            RetryPolicy::new(100, Duration::from_secs(1)).backoff(100),
+            Duration::from_millis(RPC_RETRY_MAX_BACKOFF_MILLIS)
+        );
+        let transient = || {
+            bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(Box::new(io::Error::from(
+                io::ErrorKind::ConnectionReset,
+            ))))
+        };
+        let mut attempts = 0;
+        let result = retry.call("getblockcount", |attempt| {
+            attempts += 1;
+            if attempt < 2 {
+                Err(transient())
+            } else {
+                Ok(attempt)
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(attempts, 3);
+        let result = retry.call::<()>("getblockcount", |_| Err(transient()));
+        assert!(matches!(result, Err(RetrieverError::RpcRetriesExhausted)));
+        let mut attempts = 0;
+        let result = retry.call::<()>("getblockcount", |_| {
+            attempts += 1;
+            Err(bitcoincore_rpc::Error::ReturnedError("bad".to_string()))
+        });
+        assert!(matches!(
+            result,
+            Err(RetrieverError::BitcoincoreRpcCrateError(_))
+        ));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn retry_policy_works_02() {
+        let retry = RetryPolicy::new(3, Duration::from_millis(1));
+        let mut attempts = 0;
+        let result = retry.call_non_idempotent::<()>("dumptxoutset", |_| {
+            attempts += 1;
+            Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(
+                Box::new(io::Error::from(io::ErrorKind::TimedOut)),
+            )))
+        });
+        assert!(matches!(
+            result,
+            Err(RetrieverError::BitcoincoreRpcCrateError(_))
+        ));
+        assert_eq!(attempts, 1);
+        let mut attempts = 0;
+        let result = retry.call_non_idempotent::<()>("dumptxoutset", |_| {
+            attempts += 1;
+            Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(
+                Box::new(jsonrpc::simple_http::Error::SocketError(io::Error::from(
+                    io::ErrorKind::ConnectionRefused,
+                ))),
+            )))
+        });
+        assert!(matches!(result, Err(RetrieverError::RpcRetriesExhausted)));
+        assert_eq!(attempts, 4);
+    }
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// What to do when bitcoincore is already scanning the utxo set as the details of finds are
+/// about to be fetched, e.g. a scan left behind by a retriever that died mid-scan. Bitcoincore
+/// runs one scantxoutset at a time.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScanInProgressPolicy {
+    /// Wait for the running scan to finish.
+    #[default]
+    Wait,
+    /// Abort the running scan.
+    Abort,
+}
@@ -0,0 +1,675 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use base64::Engine;
+use bitcoincore_rpc::jsonrpc::{self, serde_json, simple_http, Request, Response, Transport};
+use native_tls::{Certificate, TlsConnector};
+use tracing::warn;
+
+use crate::error::RetrieverError;
+
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+/// The error of a request that failed while connecting to the node, before any of it was sent,
+/// so the node never saw it and it can be sent again without running it twice.
+#[derive(Debug)]
+pub struct UnsentRequestError(io::Error);
+
+impl fmt::Display for UnsentRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not connect to the node: {}", self.0)
+    }
+}
+
+impl Error for UnsentRequestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Whether the transport error is known to have happened before the request was sent. A request
+/// that timed out or whose connection dropped may still be running on the node, as a dump or a
+/// scan goes on after its connection is gone. The simple http transport of jsonrpc does not tell
+/// a failed connection from a timed out request, so only its refused connections count.
+pub fn is_unsent_request(err: &jsonrpc::Error) -> bool {
+    let jsonrpc::Error::Transport(err) = err else {
+        return false;
+    };
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        return err
+            .get_ref()
+            .is_some_and(|inner| inner.is::<UnsentRequestError>());
+    }
+    matches!(
+        err.downcast_ref::<simple_http::Error>(),
+        Some(simple_http::Error::SocketError(err)) if err.kind() == io::ErrorKind::ConnectionRefused
+    )
+}
+
+/// A SOCKS5 proxy (e.g. Tor) through which the rpc connections are made.
+#[derive(Debug, Clone)]
+pub struct Socks5Proxy {
+    addr: String,
+    auth: Option<(String, String)>,
+}
+
+/// A minimal HTTP/1.1 jsonrpc transport used when the connection to bitcoincore needs more than
+/// what the simple http transport of jsonrpc offers, i.e. going through a SOCKS5 proxy, TLS or a
+/// Unix socket. Host names are resolved by the proxy, so Tor hidden services can be reached.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    host: String,
+    port: u16,
+    /// When set, connections are made to this Unix socket instead of host and port.
+    unix_socket: Option<PathBuf>,
+    path: String,
+    authorization: Option<String>,
+    timeout: Duration,
+    proxy: Option<Socks5Proxy>,
+    tls: Option<TlsConnector>,
+}
+
+impl HttpTransport {
+    pub fn new(
+        url: &str,
+        user: Option<String>,
+        password: Option<String>,
+        timeout: Duration,
+    ) -> Result<Self, RetrieverError> {
+        let RpcUrl {
+            https,
+            host,
+            port,
+            unix_socket,
+            path,
+        } = split_url(url)?;
+        let authorization = user.map(|user| {
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(format!(
+                    "{}:{}",
+                    user,
+                    password.unwrap_or_default()
+                ))
+            )
+        });
+        Ok(HttpTransport {
+            host,
+            port,
+            unix_socket,
+            path,
+            authorization,
+            timeout,
+            proxy: None,
+            tls: if https {
+                Some(TlsConnector::new()?)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Secures the connection with TLS, trusting the given PEM encoded CA certificate in addition
+    /// to the system roots. Accepting invalid certificates allows self-signed ones, at the cost
+    /// of not authenticating the node.
+    pub fn with_tls(
+        mut self,
+        ca_cert_pem: Option<&[u8]>,
+        accept_invalid_certs: bool,
+    ) -> Result<Self, RetrieverError> {
+        let mut builder = TlsConnector::builder();
+        if let Some(ca_cert_pem) = ca_cert_pem {
+            builder.add_root_certificate(Certificate::from_pem(ca_cert_pem)?);
+        }
+        builder.danger_accept_invalid_certs(accept_invalid_certs);
+        self.tls = Some(builder.build()?);
+        Ok(self)
+    }
+
+    pub fn with_socks5_proxy(mut self, addr: &str, auth: Option<(String, String)>) -> Self {
+        self.proxy = Some(Socks5Proxy {
+            addr: addr.to_string(),
+            auth,
+        });
+        self
+    }
+
+    fn connect(&self) -> io::Result<Box<dyn Stream>> {
+        if let Some(unix_socket) = &self.unix_socket {
+            return self.connect_unix_socket(unix_socket);
+        }
+        let target = (self.host.as_str(), self.port);
+        let stream = match &self.proxy {
+            Some(Socks5Proxy {
+                addr,
+                auth: Some((user, password)),
+            }) => {
+                socks::Socks5Stream::connect_with_password(addr.as_str(), target, user, password)?
+                    .into_inner()
+            }
+            Some(Socks5Proxy { addr, auth: None }) => {
+                socks::Socks5Stream::connect(addr.as_str(), target)?.into_inner()
+            }
+            None => TcpStream::connect(target)?,
+        };
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        self.secure(stream)
+    }
+
+    #[cfg(unix)]
+    fn connect_unix_socket(&self, unix_socket: &PathBuf) -> io::Result<Box<dyn Stream>> {
+        let stream = std::os::unix::net::UnixStream::connect(unix_socket)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        self.secure(stream)
+    }
+
+    #[cfg(not(unix))]
+    fn connect_unix_socket(&self, _unix_socket: &PathBuf) -> io::Result<Box<dyn Stream>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Unix sockets are not supported on this platform.",
+        ))
+    }
+
+    fn secure<S: Read + Write + fmt::Debug + 'static>(
+        &self,
+        stream: S,
+    ) -> io::Result<Box<dyn Stream>> {
+        match &self.tls {
+            Some(tls) => Ok(Box::new(
+                tls.connect(&self.host, stream)
+                    .map_err(|err| io::Error::other(err.to_string()))?,
+            )),
+            None => Ok(Box::new(stream)),
+        }
+    }
+
+    /// Makes an http request and returns the raw response.
+    fn request(&self, method: &str, path: &str, body: &[u8]) -> io::Result<Vec<u8>> {
+        let mut stream = self.send_http_request(method, path, body)?;
+        let mut response = vec![];
+        stream.read_to_end(&mut response)?;
+        Ok(response)
+    }
+
+    /// Connects and writes an http request, returning the stream to read the response from.
+    fn send_http_request(
+        &self,
+        method: &str,
+        path: &str,
+        body: &[u8],
+    ) -> io::Result<Box<dyn Stream>> {
+        let mut stream = self
+            .connect()
+            .map_err(|err| io::Error::new(err.kind(), UnsentRequestError(err)))?;
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            method,
+            path,
+            self.host,
+            self.port,
+            body.len()
+        );
+        if let Some(authorization) = &self.authorization {
+            request.push_str(&format!("Authorization: {}\r\n", authorization));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+        Ok(stream)
+    }
+
+    fn post(&self, body: &[u8]) -> io::Result<Vec<u8>> {
+        http_response_body(self.request("POST", &self.path, body)?)
+    }
+
+    /// Streams the body of the resource at the url into the writer, for files too large to hold
+    /// in memory. Returns the number of bytes written.
+    pub fn download(&self, writer: &mut dyn Write) -> io::Result<u64> {
+        let mut stream = BufReader::new(self.send_http_request("GET", &self.path, &[])?);
+        let mut head = vec![];
+        while !head.ends_with(b"\r\n\r\n") {
+            if stream.read_until(b'\n', &mut head)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Malformed http response.",
+                ));
+            }
+        }
+        let head = String::from_utf8_lossy(&head).to_ascii_lowercase();
+        let (status, _) = split_http_response(head.as_bytes().to_vec())?;
+        if !(200..300).contains(&status) {
+            return Err(io::Error::other(format!("Http error status {}.", status)));
+        }
+        if head.contains("transfer-encoding: chunked") {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Chunked http responses are not supported.",
+            ));
+        }
+        let content_length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length:"))
+            .and_then(|content_length| content_length.trim().parse::<u64>().ok());
+        let written = io::copy(&mut stream, writer)?;
+        if content_length.is_some_and(|content_length| content_length != written) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The download ended early.",
+            ));
+        }
+        Ok(written)
+    }
+
+    /// Gets the status and body of the resource at the given path, e.g. on the REST interface of
+    /// bitcoincore.
+    pub fn get(&self, path: &str) -> io::Result<(u16, Vec<u8>)> {
+        split_http_response(self.request("GET", path, &[])?)
+    }
+
+    fn send<R: serde::Serialize + ?Sized, T: serde::de::DeserializeOwned>(
+        &self,
+        request: &R,
+    ) -> Result<T, jsonrpc::Error> {
+        let body = serde_json::to_vec(request)?;
+        let response = self
+            .post(&body)
+            .map_err(|err| jsonrpc::Error::Transport(Box::new(err)))?;
+        Ok(serde_json::from_slice(&response)?)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send_request(&self, request: Request) -> Result<Response, jsonrpc::Error> {
+        self.send(&request)
+    }
+
+    fn send_batch(&self, requests: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+        self.send(requests)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(unix_socket) = &self.unix_socket {
+            return write!(f, "unix://{}", unix_socket.display());
+        }
+        write!(
+            f,
+            "{}://{}:{}{}",
+            if self.tls.is_some() { "https" } else { "http" },
+            self.host,
+            self.port,
+            self.path
+        )?;
+        if let Some(proxy) = &self.proxy {
+            write!(f, " via socks5 proxy {}", proxy.addr)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends requests to the current node of a list of nodes and moves on to the next one when a
+/// request could not be sent to it, i.e. the node is unreachable. Requests that time out or whose
+/// connection drops are not sent to another node, as the node may still be running them, and a
+/// second node would start the same dump or scan again. Rpc errors returned by a node are passed
+/// on as they are. The node that answered last stays the current one, so once the primary node
+/// fails it is not retried before the others.
+pub struct FailoverTransport {
+    transports: Vec<Box<dyn Transport>>,
+    current: AtomicUsize,
+}
+
+impl FailoverTransport {
+    pub fn new(transports: Vec<Box<dyn Transport>>) -> Self {
+        FailoverTransport {
+            transports,
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    fn send<T>(
+        &self,
+        send: impl Fn(&dyn Transport) -> Result<T, jsonrpc::Error>,
+    ) -> Result<T, jsonrpc::Error> {
+        let first = self.current.load(Ordering::SeqCst);
+        let mut attempt = 0;
+        loop {
+            let index = (first + attempt) % self.transports.len();
+            attempt += 1;
+            match send(self.transports[index].as_ref()) {
+                Err(err) if attempt < self.transports.len() && is_unsent_request(&err) => {
+                    let next = (index + 1) % self.transports.len();
+                    warn!(
+                        "Bitcoincore rpc node {} failed ({}), failing over to node {}.",
+                        index, err, next
+                    );
+                    let _ = self.current.compare_exchange(
+                        index,
+                        next,
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Transport for FailoverTransport {
+    fn send_request(&self, request: Request) -> Result<Response, jsonrpc::Error> {
+        self.send(|transport| transport.send_request(request.clone()))
+    }
+
+    fn send_batch(&self, requests: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+        self.send(|transport| transport.send_batch(requests))
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current = self.current.load(Ordering::SeqCst);
+        self.transports[current].fmt_target(f)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct RpcUrl {
+    https: bool,
+    host: String,
+    port: u16,
+    unix_socket: Option<PathBuf>,
+    path: String,
+}
+
+/// Splits an rpc url such as https://host:port/path into its parts. Urls such as
+/// unix:///path/to/socket point at a Unix socket.
+fn split_url(url: &str) -> Result<RpcUrl, RetrieverError> {
+    if let Some(unix_socket) = url.strip_prefix("unix://") {
+        if unix_socket.is_empty() {
+            return Err(RetrieverError::InvalidRpcUrl);
+        }
+        return Ok(RpcUrl {
+            https: false,
+            host: "localhost".to_string(),
+            port: 0,
+            unix_socket: Some(PathBuf::from(unix_socket)),
+            path: "/".to_string(),
+        });
+    }
+    let (https, after_scheme) = match url.split_once("://") {
+        Some(("http", after_scheme)) => (false, after_scheme),
+        Some(("https", after_scheme)) => (true, after_scheme),
+        Some(_) => return Err(RetrieverError::InvalidRpcUrl),
+        None => (false, url),
+    };
+    let (authority, path) = match after_scheme.find('/') {
+        Some(slash) => after_scheme.split_at(slash),
+        None => (after_scheme, "/"),
+    };
+    let (host, port) = authority
+        .rsplit_once(':')
+        .ok_or(RetrieverError::InvalidRpcUrl)?;
+    let port = port.parse().map_err(|_| RetrieverError::InvalidRpcUrl)?;
+    if host.is_empty() {
+        return Err(RetrieverError::InvalidRpcUrl);
+    }
+    Ok(RpcUrl {
+        https,
+        host: host.to_string(),
+        port,
+        unix_socket: None,
+        path: path.to_string(),
+    })
+}
+
+/// Returns the body of an http response. Bitcoincore answers rpc errors with an error status
+/// and a json body, so only responses without a body are treated as http errors.
+fn http_response_body(response: Vec<u8>) -> io::Result<Vec<u8>> {
+    let (status, body) = split_http_response(response)?;
+    if body.iter().all(u8::is_ascii_whitespace) && !(200..300).contains(&status) {
+        return Err(io::Error::other(format!("Http error status {}.", status)));
+    }
+    Ok(body)
+}
+
+/// Splits an http response into its status and body, decoding a chunked body, as TLS
+/// terminators in front of the node often send.
+fn split_http_response(mut response: Vec<u8>) -> io::Result<(u16, Vec<u8>)> {
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed http response."))?;
+    let head = String::from_utf8_lossy(&response[..header_end]).to_ascii_lowercase();
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed http status."))?;
+    let body = response.split_off(header_end + 4);
+    let chunked = head.lines().any(|line| {
+        line.strip_prefix("transfer-encoding:")
+            .is_some_and(|encoding| encoding.trim() == "chunked")
+    });
+    match chunked {
+        true => Ok((status, decode_chunked_body(&body)?)),
+        false => Ok((status, body)),
+    }
+}
+
+/// Joins the chunks of a chunked http body, each its size in hex on a line of its own, ignoring
+/// chunk extensions and the trailer after the last, empty chunk.
+fn decode_chunked_body(mut chunked: &[u8]) -> io::Result<Vec<u8>> {
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, "Malformed chunked http body.");
+    let mut body = vec![];
+    loop {
+        let line_end = chunked
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(malformed)?;
+        let size_line = String::from_utf8_lossy(&chunked[..line_end]);
+        let size =
+            usize::from_str_radix(size_line.split(';').next().unwrap_or_default().trim(), 16)
+                .map_err(|_| malformed())?;
+        chunked = &chunked[line_end + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        if chunked.len() < size + 2 || &chunked[size..size + 2] != b"\r\n" {
+            return Err(malformed());
+        }
+        body.extend_from_slice(&chunked[..size]);
+        chunked = &chunked[size + 2..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_url_works_01() {
+        assert_eq!(
+            split_url("http://127.0.0.1:8332").unwrap(),
+            RpcUrl {
+                https: false,
+                host: "127.0.0.1".to_string(),
+                port: 8332,
+                unix_socket: None,
+                path: "/".to_string()
+            }
+        );
+        assert_eq!(
+            split_url("abcdefghijklmnop.onion:8332/wallet/w1").unwrap(),
+            RpcUrl {
+                https: false,
+                host: "abcdefghijklmnop.onion".to_string(),
+                port: 8332,
+                unix_socket: None,
+                path: "/wallet/w1".to_string()
+            }
+        );
+        assert!(split_url("https://node.example.com:443").unwrap().https);
+        assert_eq!(
+            split_url("unix:///run/bitcoind/rpc.sock")
+                .unwrap()
+                .unix_socket,
+            Some(PathBuf::from("/run/bitcoind/rpc.sock"))
+        );
+        assert!(split_url("ftp://127.0.0.1:8332").is_err());
+        assert!(split_url("127.0.0.1").is_err());
+    }
+
+    enum DummyNode {
+        Up,
+        Unreachable,
+        TimingOut,
+    }
+
+    struct DummyTransport {
+        node: DummyNode,
+        name: &'static str,
+    }
+
+    impl Transport for DummyTransport {
+        fn send_request(&self, request: Request) -> Result<Response, jsonrpc::Error> {
+            let err = match self.node {
+                DummyNode::Up => None,
+                DummyNode::Unreachable => Some(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    UnsentRequestError(io::Error::from(io::ErrorKind::ConnectionRefused)),
+                )),
+                DummyNode::TimingOut => Some(io::Error::from(io::ErrorKind::TimedOut)),
+            };
+            if let Some(err) = err {
+                return Err(jsonrpc::Error::Transport(Box::new(err)));
+            }
+            Ok(Response {
+                result: Some(serde_json::value::to_raw_value(self.name).unwrap()),
+                error: None,
+                id: request.id,
+                jsonrpc: None,
+            })
+        }
+
+        fn send_batch(&self, _: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+            Ok(vec![])
+        }
+
+        fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.name)
+        }
+    }
+
+    #[test]
+    fn failover_transport_works_01() {
+        let transport = FailoverTransport::new(vec![
+            Box::new(DummyTransport {
+                node: DummyNode::Unreachable,
+                name: "primary",
+            }),
+            Box::new(DummyTransport {
+                node: DummyNode::Up,
+                name: "fallback",
+            }),
+        ]);
+        let request = Request {
+            method: "ping",
+            params: &[],
+            id: serde_json::Value::from(1),
+            jsonrpc: Some("2.0"),
+        };
+        for _ in 0..2 {
+            let response = transport.send_request(request.clone()).unwrap();
+            assert_eq!(response.result::<String>().unwrap(), "fallback");
+            assert_eq!(transport.current.load(Ordering::SeqCst), 1);
+        }
+        let transport = FailoverTransport::new(vec![Box::new(DummyTransport {
+            node: DummyNode::Unreachable,
+            name: "primary",
+        })]);
+        assert!(matches!(
+            transport.send_request(request),
+            Err(jsonrpc::Error::Transport(_))
+        ));
+    }
+
+    #[test]
+    fn failover_transport_works_02() {
+        // A timed out request may still run on the node, so it is not sent to the fallback.
+        let transport = FailoverTransport::new(vec![
+            Box::new(DummyTransport {
+                node: DummyNode::TimingOut,
+                name: "primary",
+            }),
+            Box::new(DummyTransport {
+                node: DummyNode::Up,
+                name: "fallback",
+            }),
+        ]);
+        let request = Request {
+            method: "dumptxoutset",
+            params: &[],
+            id: serde_json::Value::from(1),
+            jsonrpc: Some("2.0"),
+        };
+        let result = transport.send_request(request);
+        assert!(matches!(&result, Err(err) if !is_unsent_request(err)));
+        assert_eq!(transport.current.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn is_unsent_request_works_01() {
+        let transport =
+            HttpTransport::new("http://127.0.0.1:1", None, None, Duration::from_secs(1)).unwrap();
+        let request = Request {
+            method: "getblockcount",
+            params: &[],
+            id: serde_json::Value::from(1),
+            jsonrpc: Some("2.0"),
+        };
+        assert!(is_unsent_request(
+            &transport.send_request(request).unwrap_err()
+        ));
+        let err = jsonrpc::Error::Transport(Box::new(io::Error::from(io::ErrorKind::TimedOut)));
+        assert!(!is_unsent_request(&err));
+        let err = jsonrpc::Error::Transport(Box::new(simple_http::Error::SocketError(
+            io::Error::from(io::ErrorKind::ConnectionRefused),
+        )));
+        assert!(is_unsent_request(&err));
+    }
+
+    #[test]
+    fn http_response_body_works_01() {
+        let response =
+            b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 2\r\n\r\n{}".to_vec();
+        assert_eq!(http_response_body(response).unwrap(), b"{}".to_vec());
+        let response = b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n".to_vec();
+        assert!(http_response_body(response).is_err());
+    }
+
+    #[test]
+    fn http_response_body_works_02() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            8\r\n{\"result\r\n6;ext=1\r\n\":null\r\n1\r\n}\r\n0\r\n\r\n"
+            .to_vec();
+        assert_eq!(
+            http_response_body(response).unwrap(),
+            b"{\"result\":null}".to_vec()
+        );
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n9\r\n{}\r\n".to_vec();
+        assert_eq!(
+            http_response_body(response).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::io::{self, Read};
+
+use bitcoin::{
+    consensus::{encode, Decodable},
+    opcodes::all::{OP_CHECKSIG, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160, OP_RETURN},
+    opcodes::all::{OP_PUSHBYTES_20, OP_PUSHBYTES_33, OP_PUSHBYTES_65},
+    secp256k1::PublicKey,
+    OutPoint, ScriptBuf,
+};
+
+use crate::{chainstate::ChainstateCoin, error::RetrieverError};
+
+/// The script types the compressed script encoding of the chainstate stores in a byte and a key
+/// or hash, whose sizes are coded below this.
+const NUM_SPECIAL_SCRIPTS: u64 = 6;
+/// Scripts larger than this are unspendable and stored as an OP_RETURN.
+const MAX_SCRIPT_SIZE: u64 = 10_000;
+
+/// A coin as the chainstate stores it, all but its scriptpubkey, which is decoded apart into a
+/// buffer of the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoinHeader {
+    pub out_point: OutPoint,
+    pub height: u32,
+    pub is_coinbase: bool,
+    pub amount_sats: u64,
+}
+
+impl CoinHeader {
+    /// Decodes the height and coinbase flag, and the compressed amount, that precede the script
+    /// of a coin.
+    pub fn decode(out_point: OutPoint, reader: &mut impl Read) -> Result<Self, RetrieverError> {
+        let code = u64::from(txoutset::VarInt::consensus_decode(reader)?);
+        let amount = txoutset::Amount::consensus_decode(reader)?;
+        Ok(CoinHeader {
+            out_point,
+            height: (code >> 1) as u32,
+            is_coinbase: code & 1 == 1,
+            amount_sats: amount.into(),
+        })
+    }
+
+    pub fn with_script_pubkey(self, script_pubkey: Vec<u8>) -> ChainstateCoin {
+        ChainstateCoin {
+            out_point: self.out_point,
+            height: self.height,
+            is_coinbase: self.is_coinbase,
+            amount_sats: self.amount_sats,
+            script_pubkey: ScriptBuf::from_bytes(script_pubkey),
+        }
+    }
+}
+
+/// Coins read one after another, such as from a dump or the chainstate, whose scriptpubkeys are
+/// decoded into a buffer of the caller instead of one allocation each.
+pub trait CoinSource {
+    /// Decodes the next coin, appending its scriptpubkey to the buffer, or None once all coins
+    /// have been read. Nothing can be read after an error.
+    fn read_coin_into(
+        &mut self,
+        script_pubkey: &mut Vec<u8>,
+    ) -> Option<Result<CoinHeader, RetrieverError>>;
+
+    /// The next coin with its scriptpubkey in an allocation of its own.
+    fn read_coin(&mut self) -> Option<Result<ChainstateCoin, RetrieverError>> {
+        let mut script_pubkey = vec![];
+        self.read_coin_into(&mut script_pubkey)
+            .map(|coin| Ok(coin?.with_script_pubkey(script_pubkey)))
+    }
+}
+
+/// Decodes a script in the compressed encoding of the chainstate, appending it to the buffer.
+/// It is what `txoutset::Script` decodes, without allocating a script for every coin.
+pub fn decompress_script_into(
+    reader: &mut impl Read,
+    script_pubkey: &mut Vec<u8>,
+) -> Result<(), RetrieverError> {
+    let size = u64::from(txoutset::VarInt::consensus_decode(reader)?);
+    match size {
+        0x00 => {
+            let mut pubkey_hash = [0u8; 20];
+            reader.read_exact(&mut pubkey_hash)?;
+            script_pubkey.extend_from_slice(&[
+                OP_DUP.to_u8(),
+                OP_HASH160.to_u8(),
+                OP_PUSHBYTES_20.to_u8(),
+            ]);
+            script_pubkey.extend_from_slice(&pubkey_hash);
+            script_pubkey.extend_from_slice(&[OP_EQUALVERIFY.to_u8(), OP_CHECKSIG.to_u8()]);
+        }
+        0x01 => {
+            let mut script_hash = [0u8; 20];
+            reader.read_exact(&mut script_hash)?;
+            script_pubkey.extend_from_slice(&[OP_HASH160.to_u8(), OP_PUSHBYTES_20.to_u8()]);
+            script_pubkey.extend_from_slice(&script_hash);
+            script_pubkey.push(OP_EQUAL.to_u8());
+        }
+        0x02 | 0x03 => {
+            let mut x_coordinate = [0u8; 32];
+            reader.read_exact(&mut x_coordinate)?;
+            script_pubkey.extend_from_slice(&[OP_PUSHBYTES_33.to_u8(), size as u8]);
+            script_pubkey.extend_from_slice(&x_coordinate);
+            script_pubkey.push(OP_CHECKSIG.to_u8());
+        }
+        0x04 | 0x05 => {
+            // The key is stored compressed, its parity in the size.
+            let mut compressed_pubkey = [0u8; 33];
+            compressed_pubkey[0] = (size - 2) as u8;
+            reader.read_exact(&mut compressed_pubkey[1..])?;
+            let pubkey = PublicKey::from_slice(&compressed_pubkey)
+                .map_err(|_| encode::Error::ParseFailed("parse public key"))?;
+            script_pubkey.push(OP_PUSHBYTES_65.to_u8());
+            script_pubkey.extend_from_slice(&pubkey.serialize_uncompressed());
+            script_pubkey.push(OP_CHECKSIG.to_u8());
+        }
+        size => {
+            let size = size - NUM_SPECIAL_SCRIPTS;
+            if size > MAX_SCRIPT_SIZE {
+                io::copy(&mut reader.take(size), &mut io::sink())?;
+                script_pubkey.push(OP_RETURN.to_u8());
+            } else {
+                let start = script_pubkey.len();
+                script_pubkey.resize(start + size as usize, 0);
+                reader.read_exact(&mut script_pubkey[start..])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A batch of coins whose scriptpubkeys lie one after another in a single buffer. An arena is
+/// cleared and refilled batch after batch, so reading the utxo set allocates for the first few
+/// batches only, rather than for each of its coins.
+#[derive(Debug, Default)]
+pub struct CoinArena {
+    coins: Vec<CoinHeader>,
+    script_pubkeys: Vec<u8>,
+    /// Where the scriptpubkey of each coin ends in the buffer.
+    script_pubkey_ends: Vec<usize>,
+}
+
+impl CoinArena {
+    /// An arena with room for the number of coins, and for their scriptpubkeys if of an average
+    /// size.
+    pub fn with_capacity(coins: usize) -> Self {
+        CoinArena {
+            coins: Vec::with_capacity(coins),
+            script_pubkeys: Vec::with_capacity(coins * 32),
+            script_pubkey_ends: Vec::with_capacity(coins),
+        }
+    }
+
+    /// Replaces the coins of the arena with up to the number of coins read from the source, and
+    /// returns whether the source may have more.
+    pub fn refill(
+        &mut self,
+        source: &mut impl CoinSource,
+        coins: usize,
+    ) -> Result<bool, RetrieverError> {
+        self.coins.clear();
+        self.script_pubkeys.clear();
+        self.script_pubkey_ends.clear();
+        while self.coins.len() < coins {
+            match source.read_coin_into(&mut self.script_pubkeys) {
+                Some(coin) => {
+                    self.coins.push(coin?);
+                    self.script_pubkey_ends.push(self.script_pubkeys.len());
+                }
+                None => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// The coins with their scriptpubkeys.
+    pub fn iter(&self) -> impl Iterator<Item = (&CoinHeader, &[u8])> {
+        self.coins.iter().enumerate().map(|(index, coin)| {
+            let start = match index {
+                0 => 0,
+                index => self.script_pubkey_ends[index - 1],
+            };
+            (
+                coin,
+                &self.script_pubkeys[start..self.script_pubkey_ends[index]],
+            )
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.coins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coins.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{consensus::Encodable, hashes::Hash, key::Secp256k1, secp256k1::SecretKey, Txid};
+
+    use super::*;
+
+    /// Coins of the given compressed scripts, as a dump serializes them after their outpoints.
+    struct CompressedCoins(Vec<Vec<u8>>);
+
+    impl CoinSource for CompressedCoins {
+        fn read_coin_into(
+            &mut self,
+            script_pubkey: &mut Vec<u8>,
+        ) -> Option<Result<CoinHeader, RetrieverError>> {
+            if self.0.is_empty() {
+                return None;
+            }
+            let mut coin = vec![];
+            txoutset::VarInt::from(2u64)
+                .consensus_encode(&mut coin)
+                .unwrap();
+            txoutset::Amount::new(1_000)
+                .consensus_encode(&mut coin)
+                .unwrap();
+            coin.extend(self.0.remove(0));
+            let mut reader = coin.as_slice();
+            Some(
+                CoinHeader::decode(OutPoint::new(Txid::all_zeros(), 0), &mut reader).and_then(
+                    |header| {
+                        decompress_script_into(&mut reader, script_pubkey)?;
+                        Ok(header)
+                    },
+                ),
+            )
+        }
+    }
+
+    #[test]
+    fn coin_arena_works_01() {
+        let pubkey = SecretKey::from_slice(&[1u8; 32])
+            .unwrap()
+            .public_key(&Secp256k1::new());
+        let serialized = pubkey.serialize();
+        let mut compressed_scripts = vec![
+            [vec![0x00], vec![9u8; 20]].concat(),
+            [vec![0x01], vec![9u8; 20]].concat(),
+            [vec![serialized[0]], serialized[1..].to_vec()].concat(),
+            [vec![serialized[0] + 2], serialized[1..].to_vec()].concat(),
+            vec![6 + 2, 0x51, 0x52],
+        ];
+        let mut expected = vec![];
+        for script in compressed_scripts.iter() {
+            expected.push(
+                txoutset::Script::consensus_decode(&mut script.as_slice())
+                    .unwrap()
+                    .into_inner()
+                    .into_bytes(),
+            );
+        }
+        compressed_scripts.push([vec![0x04], vec![0xff; 32]].concat());
+        let mut coins = CompressedCoins(compressed_scripts);
+        let mut arena = CoinArena::with_capacity(2);
+        assert!(arena.refill(&mut coins, 2).unwrap());
+        assert_eq!(arena.len(), 2);
+        assert!(arena.refill(&mut coins, 3).unwrap());
+        assert_eq!(arena.len(), 3);
+        let decoded = arena
+            .iter()
+            .map(|(coin, script_pubkey)| {
+                assert_eq!(coin.height, 1);
+                assert!(!coin.is_coinbase);
+                assert_eq!(coin.amount_sats, 1_000);
+                script_pubkey.to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, expected[2..]);
+        // An x coordinate of no point on the curve.
+        assert!(arena.refill(&mut coins, 3).is_err());
+        assert!(!arena.refill(&mut coins, 3).unwrap());
+        assert!(arena.is_empty());
+    }
+}
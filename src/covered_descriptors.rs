@@ -1,6 +1,22 @@
+use std::sync::Arc;
+
+use bitcoin::{
+    bip32::{ChildNumber, DerivationPath},
+    hashes::hash160,
+    key::{Secp256k1, Verification},
+    opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_1},
+    script::Builder,
+    PubkeyHash, PublicKey, ScriptBuf, WPubkeyHash,
+};
+use miniscript::{
+    descriptor::{DescriptorType, ShInner},
+    Descriptor, Miniscript, MiniscriptKey, Terminal,
+};
 use serde::{Deserialize, Serialize};
 use strum::EnumIter;
 
+use crate::error::RetrieverError;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, EnumIter)]
 pub enum CoveredDescriptors {
     P2pk,
@@ -8,4 +24,153 @@ pub enum CoveredDescriptors {
     P2wpkh,
     P2shwpkh,
     P2tr,
-}
\ No newline at end of file
+    /// A 1-of-1 bare multisig, multi(1,K) in the scriptpubkey itself, as very old coins used.
+    BareMulti,
+    /// A plain pubkey script wrapped in p2sh, sh(pk(K)), as some early p2sh wallets made.
+    P2shpk,
+}
+
+impl CoveredDescriptors {
+    /// Returns the only descriptor type of paths under a BIP43 purpose with a single script type:
+    /// 44' (p2pkh), 49' (p2sh-p2wpkh), 84' (p2wpkh) and 86' (p2tr).
+    pub fn from_purpose(path: &DerivationPath) -> Option<Self> {
+        match path.as_ref().first() {
+            Some(ChildNumber::Hardened { index: 44 }) => Some(CoveredDescriptors::P2pkh),
+            Some(ChildNumber::Hardened { index: 49 }) => Some(CoveredDescriptors::P2shwpkh),
+            Some(ChildNumber::Hardened { index: 84 }) => Some(CoveredDescriptors::P2wpkh),
+            Some(ChildNumber::Hardened { index: 86 }) => Some(CoveredDescriptors::P2tr),
+            _ => None,
+        }
+    }
+
+    /// Returns the covered descriptor type of a single key descriptor, if any.
+    pub fn from_descriptor<Pk: MiniscriptKey>(descriptor: &Descriptor<Pk>) -> Option<Self> {
+        match descriptor.desc_type() {
+            DescriptorType::Bare => match descriptor {
+                Descriptor::Bare(bare) => match &bare.as_inner().node {
+                    Terminal::Multi(1, keys) if keys.len() == 1 => {
+                        Some(CoveredDescriptors::BareMulti)
+                    }
+                    Terminal::Multi(..) => None,
+                    _ => Some(CoveredDescriptors::P2pk),
+                },
+                _ => None,
+            },
+            DescriptorType::Pkh => Some(CoveredDescriptors::P2pkh),
+            DescriptorType::Wpkh => Some(CoveredDescriptors::P2wpkh),
+            DescriptorType::ShWpkh => Some(CoveredDescriptors::P2shwpkh),
+            DescriptorType::Tr => Some(CoveredDescriptors::P2tr),
+            DescriptorType::Sh => match descriptor {
+                Descriptor::Sh(sh) => match sh.as_inner() {
+                    ShInner::Ms(ms) => match &ms.node {
+                        Terminal::Check(inner) if matches!(inner.node, Terminal::PkK(_)) => {
+                            Some(CoveredDescriptors::P2shpk)
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the scriptpubkey of the descriptor of this type of the key, built from the key and
+    /// its hash160 without building the descriptor. The types that commit to the key by its hash
+    /// share the given one.
+    pub fn script_pubkey<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        key: &PublicKey,
+        key_hash: &hash160::Hash,
+    ) -> ScriptBuf {
+        match self {
+            CoveredDescriptors::P2pk => ScriptBuf::new_p2pk(key),
+            CoveredDescriptors::P2pkh => {
+                ScriptBuf::new_p2pkh(&PubkeyHash::from_raw_hash(*key_hash))
+            }
+            CoveredDescriptors::P2wpkh => {
+                ScriptBuf::new_p2wpkh(&WPubkeyHash::from_raw_hash(*key_hash))
+            }
+            CoveredDescriptors::P2shwpkh => ScriptBuf::new_p2sh(
+                &ScriptBuf::new_p2wpkh(&WPubkeyHash::from_raw_hash(*key_hash)).script_hash(),
+            ),
+            CoveredDescriptors::P2tr => {
+                ScriptBuf::new_p2tr(secp, key.inner.x_only_public_key().0, None)
+            }
+            CoveredDescriptors::BareMulti => Builder::new()
+                .push_opcode(OP_PUSHNUM_1)
+                .push_key(key)
+                .push_opcode(OP_PUSHNUM_1)
+                .push_opcode(OP_CHECKMULTISIG)
+                .into_script(),
+            CoveredDescriptors::P2shpk => {
+                ScriptBuf::new_p2sh(&ScriptBuf::new_p2pk(key).script_hash())
+            }
+        }
+    }
+
+    /// Wraps the given key in a descriptor of this type.
+    pub fn to_descriptor<Pk: MiniscriptKey>(
+        &self,
+        key: Pk,
+    ) -> Result<Descriptor<Pk>, RetrieverError> {
+        Ok(match self {
+            CoveredDescriptors::P2pk => Descriptor::new_pk(key),
+            CoveredDescriptors::P2pkh => Descriptor::new_pkh(key)?,
+            CoveredDescriptors::P2wpkh => Descriptor::new_wpkh(key)?,
+            CoveredDescriptors::P2shwpkh => Descriptor::new_sh_wpkh(key)?,
+            CoveredDescriptors::P2tr => Descriptor::new_tr(key, None)?,
+            CoveredDescriptors::BareMulti => {
+                Descriptor::new_bare(Miniscript::from_ast(Terminal::Multi(1, vec![key]))?)?
+            }
+            CoveredDescriptors::P2shpk => Descriptor::new_sh(Miniscript::from_ast(
+                Terminal::Check(Arc::new(Miniscript::from_ast(Terminal::PkK(key))?)),
+            )?)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::SecretKey;
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    #[test]
+    fn covered_descriptors_works_01() {
+        let key = PublicKey::new(
+            SecretKey::from_slice(&[1u8; 32])
+                .unwrap()
+                .public_key(&Secp256k1::new()),
+        );
+        for descriptor_type in CoveredDescriptors::iter() {
+            let descriptor = descriptor_type.to_descriptor(key).unwrap();
+            assert_eq!(
+                CoveredDescriptors::from_descriptor(&descriptor),
+                Some(descriptor_type)
+            );
+        }
+        let p2shpk = CoveredDescriptors::P2shpk.to_descriptor(key).unwrap();
+        assert_eq!(
+            p2shpk.to_string().split('#').next().unwrap(),
+            format!("sh(pk({}))", key)
+        );
+        assert!(p2shpk.script_pubkey().is_p2sh());
+    }
+
+    #[test]
+    fn covered_descriptors_works_02() {
+        let secp = Secp256k1::new();
+        let key = PublicKey::new(SecretKey::from_slice(&[2u8; 32]).unwrap().public_key(&secp));
+        let key_hash = key.pubkey_hash().to_raw_hash();
+        for descriptor_type in CoveredDescriptors::iter() {
+            assert_eq!(
+                descriptor_type.script_pubkey(&secp, &key, &key_hash),
+                descriptor_type.to_descriptor(key).unwrap().script_pubkey()
+            );
+        }
+    }
+}
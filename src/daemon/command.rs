@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use crate::report::{ReportedFind, SearchCoverage};
+
+/// A command sent to the daemon, as one json object per line on the control socket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum DaemonCommand {
+    /// Starts a search of the populated set. Unset fields fall back to the daemon's setting.
+    SubmitJob {
+        exploration_path: String,
+        base_derivation_paths: Option<Vec<String>>,
+        exploration_depth: Option<u32>,
+        sweep: Option<bool>,
+    },
+    Status,
+    Finds,
+    CancelJob,
+    Shutdown,
+}
+
+/// The response of the daemon to a command, as one json object per line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum DaemonResponse {
+    JobAccepted {
+        job_id: u64,
+    },
+    Status {
+        job: Option<JobStatus>,
+        /// Whether a block that does not extend the set was applied, so it needs populating again.
+        uspk_set_stale: bool,
+    },
+    Finds {
+        finds: Vec<ReportedFind>,
+    },
+    Ok,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub job_id: u64,
+    pub exploration_path: String,
+    pub state: JobState,
+    pub finds: usize,
+    pub coverage: Option<SearchCoverage>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Finished,
+    Cancelled,
+    Failed,
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::jsonrpc::serde_json;
+
+    use super::*;
+
+    #[test]
+    fn daemon_command_parsing_works_01() {
+        let command: DaemonCommand = serde_json::from_str(
+            r#"{"command":"submit_job","exploration_path":"0'/*","exploration_depth":10}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            command,
+            DaemonCommand::SubmitJob {
+                exploration_path: "0'/*".to_string(),
+                base_derivation_paths: None,
+                exploration_depth: Some(10),
+                sweep: None,
+            }
+        );
+        let command: DaemonCommand = serde_json::from_str(r#"{"command":"status"}"#).unwrap();
+        assert_eq!(command, DaemonCommand::Status);
+        assert_eq!(
+            serde_json::to_string(&DaemonResponse::JobAccepted { job_id: 3 }).unwrap(),
+            r#"{"response":"job_accepted","job_id":3}"#
+        );
+    }
+}
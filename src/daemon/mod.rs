@@ -0,0 +1,335 @@
+pub mod command;
+
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use bitcoin::{consensus::deserialize, Block};
+
+use bitcoincore_rpc::jsonrpc::serde_json;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{
+    data::defaults::ZMQ_RECONNECT_SECONDS, error::RetrieverError,
+    explorer::explorer_setting::ExplorerSetting, path_pairs::PathDescriptorPair,
+    report::ReportedFind, retriever::Retriever, setting::RetrieverSetting, zmq::ZmqSubscriber,
+};
+
+use self::command::{DaemonCommand, DaemonResponse, JobState, JobStatus};
+
+/// A resident retriever that keeps the unspent scriptpubkey set populated and runs exploration
+/// jobs submitted over a local Unix socket, so repeated searches skip the ingest of the dump.
+pub struct RetrieverDaemon {
+    socket_path: PathBuf,
+    zmq_rawblock: Option<String>,
+    state: Arc<DaemonState>,
+}
+
+struct DaemonState {
+    retriever: tokio::sync::Mutex<Retriever>,
+    explorer_setting: ExplorerSetting,
+    finds: Arc<Mutex<Vec<PathDescriptorPair>>>,
+    job: Mutex<Option<JobStatus>>,
+    job_cancellation_token: Mutex<CancellationToken>,
+    next_job_id: AtomicU64,
+    uspk_set_stale: AtomicBool,
+    shutdown_token: CancellationToken,
+}
+
+impl RetrieverDaemon {
+    /// Creates the retriever and populates its set, creating the dump file if needed. Cancelling
+    /// the shutdown token stops the creation with `RetrieverError::Cancelled`, and once the
+    /// daemon runs, stops the running job and the daemon like a shutdown command.
+    pub async fn new(
+        mut setting: RetrieverSetting,
+        socket_path: &str,
+        shutdown_token: CancellationToken,
+    ) -> Result<Self, RetrieverError> {
+        info!("Creation of retriever daemon started.");
+        // Jobs explore other paths than the set would be narrowed to, so it must keep every coin.
+        if setting.is_inverted_search_enabled() {
+            warn!("Inverted search is ignored by the daemon.");
+            setting.set_inverted_search(Some(false));
+        }
+        if setting.is_sorted_merge_search_enabled() {
+            warn!("Sorted merge search is ignored by the daemon.");
+            setting.set_sorted_merge_search(Some(false));
+        }
+        let explorer_setting = setting.get_explorer_setting();
+        let zmq_rawblock = setting.get_bitcoincore_zmq_rawblock().clone();
+        let mut retriever = Retriever::new(setting).await?;
+        retriever.set_cancellation_token(shutdown_token.child_token());
+        retriever
+            .check_for_dump_in_data_dir_or_create_dump_file()
+            .await?;
+        retriever.populate_uspk_set().await?;
+        let finds = retriever.finds().clone();
+        let job_cancellation_token = retriever.cancellation_token().clone();
+        info!("Creation of retriever daemon finished successfully.");
+        Ok(RetrieverDaemon {
+            socket_path: PathBuf::from_str(socket_path).unwrap(),
+            zmq_rawblock,
+            state: Arc::new(DaemonState {
+                retriever: tokio::sync::Mutex::new(retriever),
+                explorer_setting,
+                finds,
+                job: Mutex::new(None),
+                job_cancellation_token: Mutex::new(job_cancellation_token),
+                next_job_id: AtomicU64::new(1),
+                uspk_set_stale: AtomicBool::new(false),
+                shutdown_token,
+            }),
+        })
+    }
+
+    /// Serves commands on the control socket until a shutdown command is received or the
+    /// shutdown token is cancelled.
+    pub async fn run(&self) -> Result<(), RetrieverError> {
+        if self.socket_path.exists() {
+            warn!("Removing the stale control socket file.");
+            std::fs::remove_file(&self.socket_path)?;
+        }
+        let listener = UnixListener::bind(&self.socket_path)?;
+        info!(
+            "Retriever daemon listening on {}.",
+            self.socket_path.display()
+        );
+        if let Some(zmq_rawblock) = self.zmq_rawblock.clone() {
+            let state = self.state.clone();
+            tokio::spawn(async move { state.follow_blocks(&zmq_rawblock).await });
+        }
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, _) = accepted?;
+                    let state = self.state.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = state.serve_connection(stream).await {
+                            error!("Control socket connection failed: {:?}", err);
+                        }
+                    });
+                }
+                _ = self.state.shutdown_token.cancelled() => break,
+            }
+        }
+        self.state.job_cancellation_token.lock().unwrap().cancel();
+        std::fs::remove_file(&self.socket_path)?;
+        info!("Retriever daemon shut down.");
+        Ok(())
+    }
+}
+
+impl DaemonState {
+    async fn serve_connection(self: &Arc<Self>, stream: UnixStream) -> Result<(), RetrieverError> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<DaemonCommand>(&line) {
+                Ok(command) => self.handle_command(command).await,
+                Err(err) => DaemonResponse::Error {
+                    message: format!("Invalid command: {}", err),
+                },
+            };
+            let mut response_line = serde_json::to_string(&response)?;
+            response_line.push('\n');
+            writer.write_all(response_line.as_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_command(self: &Arc<Self>, command: DaemonCommand) -> DaemonResponse {
+        match command {
+            DaemonCommand::SubmitJob {
+                exploration_path,
+                base_derivation_paths,
+                exploration_depth,
+                sweep,
+            } => {
+                let setting = &self.explorer_setting;
+                let job_setting = ExplorerSetting::new(
+                    setting.get_mnemonic().to_owned(),
+                    setting.get_passphrase().to_owned(),
+                    base_derivation_paths
+                        .unwrap_or_else(|| setting.get_base_derivation_paths().to_owned()),
+                    exploration_path.clone(),
+                    exploration_depth.unwrap_or(*setting.get_exploration_depth()),
+                    *setting.get_network(),
+                    sweep.unwrap_or(*setting.get_sweep()),
+                )
+                .with_seed_type(*setting.get_seed_type())
+                .with_slip39_shares(setting.get_slip39_shares().to_owned())
+                .with_xpriv(setting.get_xpriv().to_owned())
+                .with_xpubs(setting.get_xpubs().to_owned())
+                .with_seed_hex(setting.get_seed_hex().to_owned())
+                .with_seed_qr(setting.get_seed_qr().to_owned())
+                .with_bip85_words(*setting.get_bip85_words())
+                .with_bip85_index(*setting.get_bip85_index())
+                .with_label(setting.get_label().clone())
+                .with_bip38_keys(
+                    setting.get_bip38_keys().to_owned(),
+                    setting.get_bip38_password().to_owned(),
+                )
+                .with_brainwallets(
+                    setting.get_brainwallet_file().to_owned(),
+                    setting.get_brainwallet_hashes().to_owned(),
+                    setting.get_warpwallet_salt().to_owned(),
+                )
+                .with_hwi(
+                    setting.get_hwi_command().to_owned(),
+                    setting.get_hwi_fingerprint().to_owned(),
+                )
+                .with_multisig(
+                    setting.get_multisig_cosigners().to_owned(),
+                    *setting.get_multisig_threshold(),
+                )
+                .with_miniscript_policy(setting.get_miniscript_policy().to_owned())
+                .with_multisig_preset(*setting.get_multisig_preset())
+                .with_green(
+                    setting.get_green_service_xpub().to_owned(),
+                    setting.get_green_subaccounts().to_owned(),
+                    setting.get_green_csv_blocks().to_owned(),
+                )
+                .with_timelocks(
+                    setting.get_timelock_templates().to_owned(),
+                    setting.get_timelock_values().to_owned(),
+                )
+                .with_lightning_node(*setting.get_lightning_node());
+                self.submit_job(job_setting, exploration_path)
+            }
+            DaemonCommand::Status => {
+                let mut job = self.job.lock().unwrap().clone();
+                if let Some(job) = job.as_mut() {
+                    job.finds = self.finds.lock().unwrap().len();
+                }
+                DaemonResponse::Status {
+                    job,
+                    uspk_set_stale: self.uspk_set_stale.load(Ordering::SeqCst),
+                }
+            }
+            DaemonCommand::Finds => {
+                let finds = self.finds.lock().unwrap().clone();
+                let finds = match self.retriever.try_lock() {
+                    Ok(retriever) => ReportedFind::from_finds(
+                        &finds,
+                        retriever.detailed_finds().as_deref(),
+                        retriever.mempool_activity().as_deref(),
+                    ),
+                    Err(_) => ReportedFind::from_finds(&finds, None, None),
+                };
+                DaemonResponse::Finds { finds }
+            }
+            DaemonCommand::CancelJob => {
+                self.job_cancellation_token.lock().unwrap().cancel();
+                DaemonResponse::Ok
+            }
+            DaemonCommand::Shutdown => {
+                info!("Shutdown of the retriever daemon requested.");
+                self.job_cancellation_token.lock().unwrap().cancel();
+                self.shutdown_token.cancel();
+                DaemonResponse::Ok
+            }
+        }
+    }
+
+    fn submit_job(
+        self: &Arc<Self>,
+        job_setting: ExplorerSetting,
+        exploration_path: String,
+    ) -> DaemonResponse {
+        let Ok(mut retriever) = self.retriever.try_lock() else {
+            return DaemonResponse::Error {
+                message: "A job is already running.".to_string(),
+            };
+        };
+        if let Err(err) = retriever.replace_explorer(job_setting) {
+            return DaemonResponse::Error {
+                message: format!("Invalid job: {:?}", err),
+            };
+        }
+        *self.job_cancellation_token.lock().unwrap() = retriever.cancellation_token().clone();
+        let job_id = self.next_job_id.fetch_add(1, Ordering::SeqCst);
+        *self.job.lock().unwrap() = Some(JobStatus {
+            job_id,
+            exploration_path,
+            state: JobState::Running,
+            finds: 0,
+            coverage: None,
+            error: None,
+        });
+        drop(retriever);
+        info!("Job {} accepted by the retriever daemon.", job_id);
+        let state = self.clone();
+        tokio::spawn(async move { state.run_job().await });
+        DaemonResponse::JobAccepted { job_id }
+    }
+
+    /// Applies the blocks bitcoind publishes over zmq to the set until shutdown, reconnecting
+    /// after errors. A block waits for a running job to end.
+    async fn follow_blocks(&self, zmq_rawblock: &str) {
+        while !self.shutdown_token.is_cancelled() {
+            let result = tokio::select! {
+                result = self.apply_published_blocks(zmq_rawblock) => result,
+                _ = self.shutdown_token.cancelled() => break,
+            };
+            if let Err(err) = result {
+                warn!(
+                    "Following blocks over zmq failed ({:?}), reconnecting in {} seconds.",
+                    err, ZMQ_RECONNECT_SECONDS
+                );
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(ZMQ_RECONNECT_SECONDS)) => {}
+                _ = self.shutdown_token.cancelled() => break,
+            }
+        }
+    }
+
+    async fn apply_published_blocks(&self, zmq_rawblock: &str) -> Result<(), RetrieverError> {
+        let mut subscriber = ZmqSubscriber::connect(zmq_rawblock, "rawblock").await?;
+        loop {
+            let frames = subscriber.recv().await?;
+            let Some(raw_block) = frames.get(1) else {
+                return Err(RetrieverError::ZmqProtocolError);
+            };
+            let block = deserialize::<Block>(raw_block)?;
+            let mut retriever = self.retriever.lock().await;
+            retriever.apply_block(&block)?;
+            self.uspk_set_stale
+                .store(retriever.uspk_set().is_stale(), Ordering::SeqCst);
+        }
+    }
+
+    async fn run_job(&self) {
+        let mut retriever = self.retriever.lock().await;
+        let mut result = retriever.search_the_uspk_set().await;
+        if result.is_ok() {
+            result = retriever.get_details_of_finds_from_bitcoincore().await;
+        }
+        let mut job = self.job.lock().unwrap();
+        if let Some(job) = job.as_mut() {
+            job.state = match &result {
+                Ok(_) => JobState::Finished,
+                Err(RetrieverError::Cancelled) => JobState::Cancelled,
+                Err(_) => JobState::Failed,
+            };
+            job.error = result.err().map(|err| format!("{:?}", err));
+            job.coverage = retriever.search_coverage().clone();
+            job.finds = self.finds.lock().unwrap().len();
+            info!("Job {} of the retriever daemon ended.", job.job_id);
+        }
+    }
+}
@@ -1,12 +1,80 @@
-use crate::covered_descriptors::CoveredDescriptors::{self, P2pk, P2pkh, P2shwpkh, P2tr, P2wpkh};
+use crate::{
+    client::scan_in_progress_policy::ScanInProgressPolicy,
+    covered_descriptors::CoveredDescriptors::{self, P2pk, P2pkh, P2shwpkh, P2tr, P2wpkh},
+    explorer::{brainwallet::BrainwalletHash, seed_type::SeedType},
+    uspk_store::UspkSetBackend,
+};
 
 pub const DEFAULT_EXPLORATION_DEPTH: u32 = 100;
 pub const DEFAULT_EXPLORATION_PATH: &str = "*";
+/// A step with exclusions is explored as the set of the indices it keeps, so it may span at most
+/// this many indices before the exclusions.
+pub const STEP_EXCLUSION_MAX_INDICES: u32 = 1_000_000;
 pub const DEFAULT_BITCOINCORE_RPC_URL: &str = "127.0.0.1";
 pub const DEFAULT_BITCOINCORE_RPC_PORT: &str = "8332";
 pub const DEFAULT_BITCOINCORE_RPC_TIMEOUT_SECONDS: u64 = 6800;
+pub const DEFAULT_BITCOINCORE_RPC_RETRIES: u32 = 3;
+pub const DEFAULT_BITCOINCORE_RPC_RETRY_BACKOFF_MILLIS: u64 = 1_000;
+pub const RPC_RETRY_MAX_BACKOFF_MILLIS: u64 = 60_000;
+pub const DEFAULT_BITCOINCORE_RPC_POOL_SIZE: usize = 4;
+pub const ZMQ_RECONNECT_SECONDS: u64 = 10;
+pub const DUMP_FETCH_TIMEOUT_SECONDS: u64 = 300;
 pub const DEFAULT_SWEEP: bool = false;
+pub const DEFAULT_CHECK_MEMPOOL: bool = false;
+pub const DEFAULT_EVENT_LOG: bool = true;
+pub const EVENT_LOG_PATHS_SCANNED_INTERVAL: usize = 100_000;
+pub const SEARCH_CHECKPOINT_INTERVAL_SECONDS: u64 = 60;
+pub const SCANTXOUTSET_BATCH_SIZE: usize = 1_000;
+/// 0 lets the pool use one thread per core.
+pub const DEFAULT_DERIVATION_THREADS: usize = 0;
+pub const DERIVATION_BATCH_SIZE: usize = 1_024;
+pub const DEFAULT_USPK_SET_BACKEND: UspkSetBackend = UspkSetBackend::Memory;
+pub const DISK_USPK_SET_FILE_NAME: &str = "uspk_set.redb";
+pub const SPILL_USPK_SET_FILE_NAME: &str = "uspk_set.spill.redb";
+pub const RUN_HISTORY_FILE_NAME: &str = "run_history.redb";
+pub const DEFAULT_INVERTED_SEARCH: bool = false;
+pub const DEFAULT_SORTED_MERGE_SEARCH: bool = false;
+/// The directory in data dir the sorted runs of a sorted merge search are written in.
+pub const SORTED_MERGE_DIR_NAME: &str = "sorted_merge";
+/// The records sorted in memory at once in a sorted merge search, which take about 130 MB for
+/// the utxo set and 250 MB for the derived scriptpubkeys.
+pub const SORTED_MERGE_RUN_ITEMS: usize = 4_000_000;
+pub const DEFAULT_USPK_SET_CACHE: bool = false;
+pub const DEFAULT_KEEP_COINS: bool = false;
+/// The most paths an inverted search derives the scriptpubkeys of before reading the utxo set.
+pub const INVERTED_SEARCH_MAX_PATHS: usize = 2_000_000;
+/// The number of scriptpubkeys added to the set at once, in one transaction when on disk.
+pub const USPK_SET_INSERT_BATCH_SIZE: usize = 100_000;
+/// The paths `Retriever::estimate` derives and checks to measure the rate of the search.
+pub const ESTIMATE_SAMPLE_PATHS: usize = 8 * DERIVATION_BATCH_SIZE;
+/// How often the node is asked for new blocks while waiting for a sweep to confirm.
+pub const SWEEP_CONFIRMATION_POLL_SECONDS: u64 = 30;
+/// The coins the decoding thread hands over at once while populating the set, and the number of
+/// such batches it may decode ahead of the insertions.
+pub const DECODED_COINS_BATCH_SIZE: usize = 10_000;
+pub const DECODED_COINS_BATCHES_AHEAD: usize = 16;
+/// The bloom filters of a set read from the chainstate are sized for about the mainnet utxo set,
+/// as the number of coins is not known beforehand.
+pub const USPK_SET_CHAINSTATE_EXPECTED_ITEMS: usize = 200_000_000;
+pub const SCANTXOUTSET_STATUS_POLL_SECONDS: u64 = 10;
+/// The times the finds whose scantxoutset batch failed after all rpc retries are scanned again,
+/// and the wait before each, long enough for a restarting node to come back.
+pub const DETAILS_SCAN_RETRY_ROUNDS: u32 = 3;
+pub const DETAILS_SCAN_RETRY_DELAY_SECONDS: u64 = 30;
+pub const DEFAULT_SCAN_IN_PROGRESS_POLICY: ScanInProgressPolicy = ScanInProgressPolicy::Wait;
+pub const DEFAULT_SEED_TYPE: SeedType = SeedType::Bip39;
+pub const DEFAULT_BIP85_WORDS: u32 = 12;
+pub const DEFAULT_CORRECT_TYPOS: bool = false;
+pub const DEFAULT_HWI_COMMAND: &str = "hwi";
+pub const DEFAULT_SILENT_PAYMENTS: bool = false;
+pub const DEFAULT_NARROW_DESCRIPTORS_BY_PURPOSE: bool = true;
+pub const DEFAULT_GREEN_SUBACCOUNTS: [u32; 1] = [0];
+pub const DEFAULT_SILENT_PAYMENTS_BIRTH_HEIGHT: u32 = 0;
+pub const DEFAULT_BRAINWALLET_HASH: BrainwalletHash = BrainwalletHash::Sha256;
 pub const DEFAULT_NETWORK: bitcoin::Network = bitcoin::Network::Bitcoin;
 pub const DEFAULT_SELECTED_DESCRIPTORS: [CoveredDescriptors; 5] =
     [P2pk, P2pkh, P2shwpkh, P2tr, P2wpkh];
 
+pub const SANITY_MAX_WILDCARD_DEPTH: u32 = 10_000;
+pub const SANITY_MAX_ESTIMATED_RUNTIME_SECONDS: u64 = 24 * 3600;
+pub const SANITY_ESTIMATED_PATHS_PER_SECOND: u64 = 5_000;
@@ -1,2 +1,4 @@
 pub mod defaults;
-pub mod wallets_info;
\ No newline at end of file
+pub mod electrum_v1_wordlist;
+pub mod slip39_wordlist;
+pub mod wallets_info;
@@ -0,0 +1,69 @@
+use bitcoin::ScriptBuf;
+use miniscript::descriptor::checksum::desc_checksum;
+
+use crate::error::RetrieverError;
+
+/// Returns the descriptor string with its BIP380 checksum appended. Any checksum already present
+/// is dropped and recomputed, so the result is always accepted by bitcoincore.
+pub fn with_checksum(descriptor: &str) -> Result<String, RetrieverError> {
+    let descriptor = strip_checksum(descriptor);
+    Ok(format!("{}#{}", descriptor, desc_checksum(descriptor)?))
+}
+
+/// Returns the raw() descriptor of the scriptpubkey with its checksum, for scriptpubkeys no other
+/// descriptor is known for.
+pub fn raw_descriptor_with_checksum(script_pubkey: &ScriptBuf) -> Result<String, RetrieverError> {
+    with_checksum(&format!("raw({})", script_pubkey.to_hex_string()))
+}
+
+/// Returns the descriptor string without its `#checksum` suffix, if any.
+pub fn strip_checksum(descriptor: &str) -> &str {
+    match descriptor.split_once('#') {
+        Some((descriptor, _)) => descriptor,
+        None => descriptor,
+    }
+}
+
+/// Checks whether the descriptor string carries a valid BIP380 checksum.
+pub fn has_valid_checksum(descriptor: &str) -> bool {
+    match descriptor.split_once('#') {
+        Some((body, checksum)) => desc_checksum(body).is_ok_and(|expected| expected == checksum),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_checksum_works_01() {
+        assert_eq!(
+            with_checksum("raw(deadbeef)").unwrap(),
+            "raw(deadbeef)#89f8spxm"
+        );
+        assert_eq!(
+            with_checksum("raw(deadbeef)#00000000").unwrap(),
+            "raw(deadbeef)#89f8spxm"
+        );
+        assert_eq!(
+            with_checksum("wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)").unwrap(),
+            "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)#wc3n3van"
+        );
+    }
+
+    #[test]
+    fn raw_descriptor_with_checksum_works_01() {
+        assert_eq!(
+            raw_descriptor_with_checksum(&ScriptBuf::from_hex("deadbeef").unwrap()).unwrap(),
+            "raw(deadbeef)#89f8spxm"
+        );
+    }
+
+    #[test]
+    fn has_valid_checksum_works_01() {
+        assert!(has_valid_checksum("raw(deadbeef)#89f8spxm"));
+        assert!(!has_valid_checksum("raw(deadbeef)#89f8spxn"));
+        assert!(!has_valid_checksum("raw(deadbeef)"));
+    }
+}
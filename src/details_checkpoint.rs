@@ -0,0 +1,160 @@
+use std::{fs, path::PathBuf};
+
+use bitcoincore_rpc::{json::ScanTxOutResult, jsonrpc::serde_json};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    error::RetrieverError,
+    path_pairs::{PathDescriptorPair, PathScanResultDescriptorTrio},
+};
+
+pub const DETAILS_CHECKPOINT_FILE_NAME: &str = "details_checkpoint.json";
+
+/// The scan results of finds whose details have been fetched so far. It is written into data dir
+/// after every scantxoutset batch, so a details phase that was interrupted resumes with the finds
+/// that are still missing instead of scanning all of them again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetailsCheckpoint {
+    results: Vec<CheckpointedScanResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CheckpointedScanResult {
+    path: String,
+    descriptor: String,
+    scan_result: ScanTxOutResult,
+}
+
+impl DetailsCheckpoint {
+    fn file_path(data_dir: &str) -> PathBuf {
+        PathBuf::from(data_dir).join(DETAILS_CHECKPOINT_FILE_NAME)
+    }
+
+    /// Loads the checkpoint in data dir, or an empty one if there is none.
+    pub fn load(data_dir: &str) -> Result<Self, RetrieverError> {
+        let file_path = Self::file_path(data_dir);
+        if !file_path.exists() {
+            return Ok(DetailsCheckpoint::default());
+        }
+        let checkpoint: DetailsCheckpoint = serde_json::from_slice(&fs::read(&file_path)?)?;
+        info!(
+            "Details checkpoint with {} scanned finds found in data dir.",
+            checkpoint.len()
+        );
+        Ok(checkpoint)
+    }
+
+    pub fn save(&self, data_dir: &str) -> Result<(), RetrieverError> {
+        fs::write(Self::file_path(data_dir), serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Removes the checkpoint from data dir once all details have been fetched.
+    pub fn remove(data_dir: &str) -> Result<(), RetrieverError> {
+        let file_path = Self::file_path(data_dir);
+        if file_path.exists() {
+            fs::remove_file(file_path)?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// The scan result of the given find, if it has been scanned already.
+    pub fn get(&self, find: &PathDescriptorPair) -> Option<&ScanTxOutResult> {
+        let path = find.0.to_string();
+        let descriptor = find.get_descriptor_string();
+        self.results
+            .iter()
+            .find(|result| result.path == path && result.descriptor == descriptor)
+            .map(|result| &result.scan_result)
+    }
+
+    pub fn extend(&mut self, results: &[PathScanResultDescriptorTrio]) {
+        self.results
+            .extend(results.iter().map(|result| CheckpointedScanResult {
+                path: result.0.to_string(),
+                descriptor: result.get_descriptor_string(),
+                scan_result: result.1.clone(),
+            }));
+    }
+
+    /// The details of the given finds that have been scanned, in the order of the finds.
+    pub fn details_of_finds(
+        &self,
+        finds: &[PathDescriptorPair],
+    ) -> Vec<PathScanResultDescriptorTrio> {
+        finds
+            .iter()
+            .filter_map(|find| {
+                self.get(find).map(|scan_result| {
+                    PathScanResultDescriptorTrio::new(
+                        find.0.clone(),
+                        scan_result.clone(),
+                        find.1.clone(),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{bip32::DerivationPath, key::Secp256k1, secp256k1::SecretKey, Amount};
+    use miniscript::Descriptor;
+
+    use super::*;
+
+    #[test]
+    fn details_checkpoint_works_01() {
+        let data_dir = std::env::temp_dir().join("retriever_details_checkpoint");
+        fs::create_dir_all(&data_dir).unwrap();
+        let data_dir = data_dir.to_string_lossy().to_string();
+        let finds = [1u8, 2].map(|byte| {
+            PathDescriptorPair::new(
+                DerivationPath::from_str(&format!("m/84'/0'/0'/0/{}", byte)).unwrap(),
+                Descriptor::new_wpkh(
+                    SecretKey::from_slice(&[byte; 32])
+                        .unwrap()
+                        .public_key(&Secp256k1::new())
+                        .into(),
+                )
+                .unwrap(),
+            )
+        });
+        let scan_result = ScanTxOutResult {
+            success: Some(true),
+            tx_outs: Some(1_000),
+            height: Some(840_000),
+            best_block_hash: None,
+            unspents: vec![],
+            total_amount: Amount::from_sat(0),
+        };
+        let mut checkpoint = DetailsCheckpoint::load(&data_dir).unwrap();
+        assert!(checkpoint.is_empty());
+        checkpoint.extend(&[PathScanResultDescriptorTrio::new(
+            finds[0].0.clone(),
+            scan_result.clone(),
+            finds[0].1.clone(),
+        )]);
+        checkpoint.save(&data_dir).unwrap();
+        let checkpoint = DetailsCheckpoint::load(&data_dir).unwrap();
+        assert_eq!(checkpoint.get(&finds[0]), Some(&scan_result));
+        assert_eq!(checkpoint.get(&finds[1]), None);
+        let details = checkpoint.details_of_finds(&finds);
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].0, finds[0].0);
+        DetailsCheckpoint::remove(&data_dir).unwrap();
+        assert!(DetailsCheckpoint::load(&data_dir).unwrap().is_empty());
+    }
+}
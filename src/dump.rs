@@ -0,0 +1,238 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use bitcoin::{consensus::Decodable, BlockHash, OutPoint, Txid};
+use memmap2::Mmap;
+use tracing::{error, info};
+
+use crate::{
+    chainstate::ChainstateCoin,
+    coin_arena::{decompress_script_into, CoinHeader, CoinSource},
+    error::RetrieverError,
+};
+
+/// Magic bytes at the start of dumps written by bitcoincore v28 and later.
+const DUMP_MAGIC_BYTES: [u8; 5] = *b"utxo\xff";
+/// Versions of the magic prefixed dump format that can be read.
+const SUPPORTED_DUMP_VERSIONS: [u16; 1] = [2];
+
+/// The layout of a utxo set dump written by `dumptxoutset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Written before bitcoincore v28: the base block hash and the number of coins, followed by
+    /// one outpoint and coin after another.
+    Legacy,
+    /// Written by bitcoincore v28 and later: magic bytes, the version and the network magic
+    /// precede the base block hash and the number of coins. Coins are grouped by txid.
+    Versioned {
+        version: u16,
+        network_magic: [u8; 4],
+    },
+}
+
+/// Iterates over the coins of a utxo set dump, detecting its format from the header. Coins are
+/// yielded as the chainstate stores them, which is what the dump serializes. The file is memory
+/// mapped, so its pages stay in the page cache of the OS from one run to the next and coins are
+/// decoded without copying the file through a buffer. As a `CoinSource`, the scriptpubkeys are
+/// decoded straight from the mapped file into the buffer of the caller.
+pub struct UtxoDump {
+    reader: Cursor<Mmap>,
+    pub format: DumpFormat,
+    pub block_hash: BlockHash,
+    pub utxo_set_size: u64,
+    coins_read: u64,
+    /// Txid of the current group and the number of its coins left, in the versioned format.
+    txid_group: Option<(Txid, u64)>,
+}
+
+impl UtxoDump {
+    pub fn open(dump_file_path: &str) -> Result<Self, RetrieverError> {
+        let file = File::open(Path::new(dump_file_path))?;
+        // SAFETY: the dump is complete before it is opened and is not written to while it is read.
+        // Truncating it meanwhile makes reading fault instead of returning an error.
+        let mmap = unsafe { Mmap::map(&file)? };
+        #[cfg(unix)]
+        mmap.advise(memmap2::Advice::Sequential)?;
+        let mut reader = Cursor::new(mmap);
+        let mut magic_bytes = [0u8; 5];
+        reader.read_exact(&mut magic_bytes)?;
+        let (format, block_hash) = if magic_bytes == DUMP_MAGIC_BYTES {
+            let version = u16::consensus_decode(&mut reader)?;
+            if !SUPPORTED_DUMP_VERSIONS.contains(&version) {
+                error!("Dump file version {} is not supported.", version);
+                return Err(RetrieverError::UnsupportedDumpVersion(version));
+            }
+            let mut network_magic = [0u8; 4];
+            reader.read_exact(&mut network_magic)?;
+            let format = DumpFormat::Versioned {
+                version,
+                network_magic,
+            };
+            (format, BlockHash::consensus_decode(&mut reader)?)
+        } else {
+            // The legacy format has no header, the bytes read belong to the block hash.
+            let mut block_hash_bytes = [0u8; 32];
+            block_hash_bytes[..5].copy_from_slice(&magic_bytes);
+            reader.read_exact(&mut block_hash_bytes[5..])?;
+            (
+                DumpFormat::Legacy,
+                BlockHash::consensus_decode(&mut block_hash_bytes.as_slice())?,
+            )
+        };
+        let utxo_set_size = u64::consensus_decode(&mut reader)?;
+        info!(
+            "Dump file of format {:?} at block {} with {} coins opened.",
+            format, block_hash, utxo_set_size
+        );
+        Ok(UtxoDump {
+            reader,
+            format,
+            block_hash,
+            utxo_set_size,
+            coins_read: 0,
+            txid_group: None,
+        })
+    }
+
+    fn read_coin_header_into(
+        &mut self,
+        script_pubkey: &mut Vec<u8>,
+    ) -> Result<CoinHeader, RetrieverError> {
+        let out_point = match self.format {
+            DumpFormat::Legacy => OutPoint::consensus_decode(&mut self.reader)?,
+            DumpFormat::Versioned { .. } => {
+                let (txid, coins_left) = match self.txid_group {
+                    Some(txid_group) if txid_group.1 > 0 => txid_group,
+                    _ => {
+                        let txid = Txid::consensus_decode(&mut self.reader)?;
+                        let coins = bitcoin::VarInt::consensus_decode(&mut self.reader)?.0;
+                        if coins == 0 {
+                            return Err(RetrieverError::InvalidDumpFile);
+                        }
+                        (txid, coins)
+                    }
+                };
+                self.txid_group = Some((txid, coins_left - 1));
+                let vout = bitcoin::VarInt::consensus_decode(&mut self.reader)?.0;
+                OutPoint::new(
+                    txid,
+                    u32::try_from(vout).map_err(|_| RetrieverError::InvalidDumpFile)?,
+                )
+            }
+        };
+        let coin = CoinHeader::decode(out_point, &mut self.reader)?;
+        decompress_script_into(&mut self.reader, script_pubkey)?;
+        Ok(coin)
+    }
+}
+
+impl CoinSource for UtxoDump {
+    fn read_coin_into(
+        &mut self,
+        script_pubkey: &mut Vec<u8>,
+    ) -> Option<Result<CoinHeader, RetrieverError>> {
+        if self.coins_read == self.utxo_set_size {
+            return None;
+        }
+        self.coins_read += 1;
+        let coin = self.read_coin_header_into(script_pubkey);
+        if coin.is_err() {
+            // The position in the file is lost, so nothing more can be read.
+            self.coins_read = self.utxo_set_size;
+        }
+        Some(coin)
+    }
+}
+
+impl Iterator for UtxoDump {
+    type Item = Result<ChainstateCoin, RetrieverError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_coin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use bitcoin::consensus::Encodable;
+
+    use super::*;
+
+    fn encode_coin(dump: &mut Vec<u8>, height: u64, amount: u64) {
+        txoutset::VarInt::from(height * 2)
+            .consensus_encode(dump)
+            .unwrap();
+        txoutset::Amount::new(amount)
+            .consensus_encode(dump)
+            .unwrap();
+        // Compressed p2pkh script.
+        dump.push(0);
+        dump.extend_from_slice(&[9u8; 20]);
+    }
+
+    fn write_dump(name: &str, dump: &[u8]) -> String {
+        let path = std::env::temp_dir().join(name);
+        File::create(&path).unwrap().write_all(dump).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn utxo_dump_works_01() {
+        let block_hash = [3u8; 32];
+        let txid = [7u8; 32];
+        let mut legacy = block_hash.to_vec();
+        legacy.extend_from_slice(&2u64.to_le_bytes());
+        for vout in [0u32, 5] {
+            legacy.extend_from_slice(&txid);
+            legacy.extend_from_slice(&vout.to_le_bytes());
+            encode_coin(&mut legacy, 840_000, 1_000);
+        }
+        let mut versioned = DUMP_MAGIC_BYTES.to_vec();
+        versioned.extend_from_slice(&2u16.to_le_bytes());
+        versioned.extend_from_slice(&[0xf9, 0xbe, 0xb4, 0xd9]);
+        versioned.extend_from_slice(&block_hash);
+        versioned.extend_from_slice(&2u64.to_le_bytes());
+        versioned.extend_from_slice(&txid);
+        versioned.push(2);
+        for vout in [0u8, 5] {
+            versioned.push(vout);
+            encode_coin(&mut versioned, 840_000, 1_000);
+        }
+        for (name, dump) in [
+            ("retriever_legacy_dump.dat", legacy),
+            ("retriever_versioned_dump.dat", versioned),
+        ] {
+            let path = write_dump(name, &dump);
+            let utxo_dump = UtxoDump::open(&path).unwrap();
+            assert_eq!(
+                utxo_dump.block_hash,
+                BlockHash::consensus_decode(&mut block_hash.as_slice()).unwrap()
+            );
+            let coins = utxo_dump.collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(coins.len(), 2);
+            assert_eq!(coins[1].out_point.vout, 5);
+            assert_eq!(coins[1].height, 840_000);
+            assert_eq!(coins[1].amount_sats, 1_000);
+            assert!(coins[1].script_pubkey.is_p2pkh());
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn utxo_dump_works_02() {
+        let mut dump = DUMP_MAGIC_BYTES.to_vec();
+        dump.extend_from_slice(&3u16.to_le_bytes());
+        dump.extend_from_slice(&[0u8; 44]);
+        let path = write_dump("retriever_unsupported_dump.dat", &dump);
+        assert!(matches!(
+            UtxoDump::open(&path),
+            Err(RetrieverError::UnsupportedDumpVersion(3))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use tracing::{error, info};
+
+use crate::{client::transport::HttpTransport, error::RetrieverError};
+
+/// Hashes everything written through it.
+struct HashingWriter<W> {
+    inner: W,
+    engine: sha256::HashEngine,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.engine.input(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Downloads a dump file made on another machine from an http(s) url into the given path. The
+/// file is written next to it with a .part extension and only moved in place once its sha256
+/// matches the expected hex checksum, so an interrupted or tampered download is never used.
+pub fn fetch_dump(
+    url: &str,
+    sha256_hex: &str,
+    dump_file_path: &Path,
+    timeout: Duration,
+) -> Result<(), RetrieverError> {
+    let expected = sha256::Hash::from_str(sha256_hex).map_err(|_| {
+        error!("The dump checksum is not a sha256 hex string.");
+        RetrieverError::InvalidDumpChecksum
+    })?;
+    let transport = HttpTransport::new(url, None, None, timeout)?;
+    let part_file_path = dump_file_path.with_extension("dat.part");
+    info!("Downloading the dump file from {}.", url);
+    let mut writer = HashingWriter {
+        inner: BufWriter::new(File::create(&part_file_path)?),
+        engine: sha256::Hash::engine(),
+    };
+    let download = transport
+        .download(&mut writer)
+        .and_then(|written| writer.flush().map(|_| written));
+    let written = match download {
+        Ok(written) => written,
+        Err(err) => {
+            error!("Downloading the dump file failed: {}", err);
+            fs::remove_file(&part_file_path)?;
+            return Err(err.into());
+        }
+    };
+    let checksum = sha256::Hash::from_engine(writer.engine);
+    if checksum != expected {
+        error!(
+            "The downloaded dump file has checksum {}, but {} is expected.",
+            checksum, expected
+        );
+        fs::remove_file(&part_file_path)?;
+        return Err(RetrieverError::DumpChecksumMismatch);
+    }
+    fs::rename(&part_file_path, dump_file_path)?;
+    info!("Dump file of {} bytes downloaded and verified.", written);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    fn serve_once(body: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/utxo_dump.dat", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn fetch_dump_works_01() {
+        let body = b"not really a dump";
+        let dump_file_path = std::env::temp_dir().join("retriever_fetched_dump.dat");
+        let checksum = sha256::Hash::hash(body).to_string();
+        fetch_dump(
+            &serve_once(body),
+            &checksum,
+            &dump_file_path,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert_eq!(fs::read(&dump_file_path).unwrap(), body);
+        fs::remove_file(&dump_file_path).unwrap();
+        let result = fetch_dump(
+            &serve_once(b"tampered dump"),
+            &checksum,
+            &dump_file_path,
+            Duration::from_secs(5),
+        );
+        assert!(matches!(result, Err(RetrieverError::DumpChecksumMismatch)));
+        assert!(!dump_file_path.exists());
+        assert!(!dump_file_path.with_extension("dat.part").exists());
+    }
+}
@@ -20,6 +20,75 @@ pub enum RetrieverError {
     TokioJoinError(tokio::task::JoinError),
     PopulatingUSPKSetInProgress,
     USPKSetAlreadyPopulated,
+    SerdeJsonError(bitcoincore_rpc::jsonrpc::serde_json::Error),
+    Cancelled,
+    InvalidChainstateDatabase,
+    NoRpcCredentials,
+    InvalidRpcUrl,
+    TlsError(native_tls::Error),
+    UnsupportedDumpVersion(u16),
+    InvalidDumpFile,
+    NodeNetworkMismatch,
+    NodeInInitialBlockDownload,
+    NodePrunedBelowRequiredHeight,
+    RpcRetriesExhausted,
+    InvalidZmqEndpoint,
+    ZmqProtocolError,
+    ZmqError(zeromq::ZmqError),
+    InvalidDumpChecksum,
+    DumpChecksumMismatch,
+    NodeTooOldForDump,
+    NodeTooOldForDumpHeight,
+    NodeTooOldForScan,
+    UnavailableOffline,
+    WalletImportFailed,
+    InvalidElectrumSeed,
+    UnsupportedElectrumSeedVersion,
+    InvalidSlip39Share,
+    InsufficientSlip39Shares,
+    InvalidSlip39Passphrase,
+    InvalidBip85Words,
+    InvalidBip85Indexes,
+    XprivNetworkMismatch,
+    XpubNetworkMismatch,
+    InvalidXpubOrigin,
+    PathNotBelowXpubs,
+    HardenedPathWatchOnly,
+    UnavailableWatchOnly,
+    InvalidSeedHex,
+    NoPassphraseCandidates,
+    InvalidUnknownWords,
+    NoTypoCorrections,
+    InvalidBip38Key,
+    WrongBip38Password,
+    HwiError,
+    InvalidSeedQr,
+    InvalidWordSwaps,
+    InvalidMultisig,
+    InvalidMiniscriptPolicy,
+    NodeTooOldForSilentPayments,
+    SilentPaymentsUnavailable,
+    InvalidGreenWallet,
+    InvalidTimelock,
+    InvalidWatchlist,
+    ThreadPoolBuildError(rayon::ThreadPoolBuildError),
+    RedbError(Box<redb::Error>),
+    ExplorationTooLargeForInvertedSearch,
+    InvalidUspkSetCache,
+    UspkSetNarrowedToExplorer,
+    InvalidSweep,
+    InvalidSweepAddress,
+    InvalidFeeRate,
+    SweepBelowDust,
+    SweepSigningFailed,
+    SweepNotFinalized,
+    NoSuchRun,
+    GpuUnavailable,
+    GpuSelfCheckFailed,
+    #[cfg(feature = "gpu")]
+    CudaDriverError(cudarc::driver::DriverError),
+    #[cfg(feature = "gpu")]
+    CudaCompileError(cudarc::nvrtc::CompileError),
 }
 
 impl From<bitcoincore_rpc::Error> for RetrieverError {
@@ -34,6 +103,26 @@ impl From<bitcoincore_rpc::jsonrpc::simple_http::Error> for RetrieverError {
     }
 }
 
+#[cfg(feature = "gpu")]
+impl From<cudarc::driver::DriverError> for RetrieverError {
+    fn from(value: cudarc::driver::DriverError) -> Self {
+        RetrieverError::CudaDriverError(value)
+    }
+}
+
+#[cfg(feature = "gpu")]
+impl From<cudarc::nvrtc::CompileError> for RetrieverError {
+    fn from(value: cudarc::nvrtc::CompileError) -> Self {
+        RetrieverError::CudaCompileError(value)
+    }
+}
+
+impl From<zeromq::ZmqError> for RetrieverError {
+    fn from(value: zeromq::ZmqError) -> Self {
+        RetrieverError::ZmqError(value)
+    }
+}
+
 impl From<std::io::Error> for RetrieverError {
     fn from(value: std::io::Error) -> Self {
         RetrieverError::IoError(value)
@@ -80,4 +169,58 @@ impl From<tokio::task::JoinError> for RetrieverError {
     fn from(value: tokio::task::JoinError) -> Self {
         RetrieverError::TokioJoinError(value)
     }
-}
\ No newline at end of file
+}
+
+impl From<bitcoincore_rpc::jsonrpc::serde_json::Error> for RetrieverError {
+    fn from(value: bitcoincore_rpc::jsonrpc::serde_json::Error) -> Self {
+        RetrieverError::SerdeJsonError(value)
+    }
+}
+
+impl From<native_tls::Error> for RetrieverError {
+    fn from(value: native_tls::Error) -> Self {
+        RetrieverError::TlsError(value)
+    }
+}
+
+impl From<rayon::ThreadPoolBuildError> for RetrieverError {
+    fn from(value: rayon::ThreadPoolBuildError) -> Self {
+        RetrieverError::ThreadPoolBuildError(value)
+    }
+}
+
+impl From<redb::Error> for RetrieverError {
+    fn from(value: redb::Error) -> Self {
+        RetrieverError::RedbError(Box::new(value))
+    }
+}
+
+impl From<redb::DatabaseError> for RetrieverError {
+    fn from(value: redb::DatabaseError) -> Self {
+        RetrieverError::RedbError(Box::new(redb::Error::from(value)))
+    }
+}
+
+impl From<redb::TransactionError> for RetrieverError {
+    fn from(value: redb::TransactionError) -> Self {
+        RetrieverError::RedbError(Box::new(redb::Error::from(value)))
+    }
+}
+
+impl From<redb::TableError> for RetrieverError {
+    fn from(value: redb::TableError) -> Self {
+        RetrieverError::RedbError(Box::new(redb::Error::from(value)))
+    }
+}
+
+impl From<redb::StorageError> for RetrieverError {
+    fn from(value: redb::StorageError) -> Self {
+        RetrieverError::RedbError(Box::new(redb::Error::from(value)))
+    }
+}
+
+impl From<redb::CommitError> for RetrieverError {
+    fn from(value: redb::CommitError) -> Self {
+        RetrieverError::RedbError(Box::new(redb::Error::from(value)))
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{LineWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bitcoincore_rpc::jsonrpc::serde_json;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::error;
+
+use crate::{error::RetrieverError, metrics::Phase};
+
+pub const EVENT_LOG_FILE_NAME: &str = "events.jsonl";
+/// The events a subscriber may fall behind by before it misses the oldest of them.
+pub const RETRIEVER_EVENTS_CAPACITY: usize = 1024;
+
+/// Events of a run, written one json object per line to the event log in data dir.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LoggedEvent {
+    RunStarted {
+        total_paths: usize,
+        selected_descriptors: usize,
+    },
+    DumpFound {
+        path: String,
+    },
+    DumpFetched {
+        url: String,
+    },
+    /// Bitcoincore is asked to dump the utxo set, as of the height if given.
+    DumpStarted {
+        height: Option<u64>,
+    },
+    RpcCall {
+        method: String,
+        success: bool,
+    },
+    PopulationStarted {
+        total_utxos: u64,
+    },
+    PopulationProgress {
+        done: u64,
+        total: u64,
+    },
+    PopulationFinished {
+        total_utxos: u64,
+        seconds: u64,
+    },
+    SearchStarted {
+        total_paths: usize,
+    },
+    PathsScanned {
+        scanned: usize,
+        total: usize,
+    },
+    FindDiscovered {
+        path: String,
+        descriptor: String,
+    },
+    SearchFinished {
+        scanned: usize,
+        finds: usize,
+    },
+    SearchCancelled {
+        scanned: usize,
+    },
+    DetailsFetched {
+        finds: usize,
+        total_sats: u64,
+    },
+    BlockApplied {
+        block_hash: String,
+        new_scripts: usize,
+    },
+    Error {
+        phase: String,
+        message: String,
+    },
+}
+
+/// The progress of a run, delivered to the subscribers of `Retriever::subscribe_events` as it
+/// happens, so applications embedding the retriever can track it without scraping its logs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RetrieverEvent {
+    DumpStarted {
+        height: Option<u64>,
+    },
+    /// The coins read into the set so far, of a total of 0 when reading the chainstate.
+    SetPopulationProgress {
+        done: u64,
+        total: u64,
+    },
+    SearchProgress {
+        searched: usize,
+        total: usize,
+    },
+    FindDiscovered {
+        path: String,
+        descriptor: String,
+    },
+    PhaseFinished {
+        phase: Phase,
+        duration: Duration,
+    },
+}
+
+impl RetrieverEvent {
+    /// The event subscribers are told of when the logged event happens, if any.
+    fn of_logged_event(event: &LoggedEvent) -> Option<Self> {
+        match event {
+            LoggedEvent::DumpStarted { height } => {
+                Some(RetrieverEvent::DumpStarted { height: *height })
+            }
+            LoggedEvent::PopulationProgress { done, total } => {
+                Some(RetrieverEvent::SetPopulationProgress {
+                    done: *done,
+                    total: *total,
+                })
+            }
+            LoggedEvent::PathsScanned { scanned, total } => Some(RetrieverEvent::SearchProgress {
+                searched: *scanned,
+                total: *total,
+            }),
+            LoggedEvent::FindDiscovered { path, descriptor } => {
+                Some(RetrieverEvent::FindDiscovered {
+                    path: path.clone(),
+                    descriptor: descriptor.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EventRecord<'a> {
+    timestamp_ms: u128,
+    #[serde(flatten)]
+    event: &'a LoggedEvent,
+}
+
+/// A persistent JSON Lines log of the events of runs, meant for external tooling rather than
+/// humans. Clones share the same file. The default event log is disabled and discards events.
+/// Whether enabled or not, the log also broadcasts the progress of the run to its subscribers,
+/// which clones share too.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    writer: Option<Arc<Mutex<LineWriter<File>>>>,
+    events: broadcast::Sender<RetrieverEvent>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        EventLog {
+            writer: None,
+            events: broadcast::channel(RETRIEVER_EVENTS_CAPACITY).0,
+        }
+    }
+}
+
+impl EventLog {
+    /// Opens (or creates) the event log file in data dir for appending.
+    pub fn open(data_dir: &str) -> Result<Self, RetrieverError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Path::new(data_dir).join(EVENT_LOG_FILE_NAME))?;
+        Ok(EventLog {
+            writer: Some(Arc::new(Mutex::new(LineWriter::new(file)))),
+            ..Default::default()
+        })
+    }
+
+    /// A receiver of the events broadcast from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<RetrieverEvent> {
+        self.events.subscribe()
+    }
+
+    /// Broadcasts the event to the subscribers, if there are any.
+    pub fn publish(&self, event: RetrieverEvent) {
+        let _ = self.events.send(event);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    pub fn log(&self, event: LoggedEvent) {
+        if let Some(retriever_event) = RetrieverEvent::of_logged_event(&event) {
+            self.publish(retriever_event);
+        }
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let record = EventRecord {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+            event: &event,
+        };
+        let written = serde_json::to_string(&record)
+            .map_err(RetrieverError::from)
+            .and_then(|line| Ok(writeln!(writer.lock().unwrap(), "{}", line)?));
+        if let Err(err) = written {
+            error!("Could not write to the event log: {:?}", err);
+        }
+    }
+
+    pub fn log_error(&self, phase: &str, err: &RetrieverError) {
+        self.log(LoggedEvent::Error {
+            phase: phase.to_string(),
+            message: format!("{:?}", err),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_record_serialization_works_01() {
+        let event = LoggedEvent::FindDiscovered {
+            path: "m/84'/0'/0'/0/0".to_string(),
+            descriptor: "raw(deadbeef)#89f8spxm".to_string(),
+        };
+        let line = serde_json::to_string(&EventRecord {
+            timestamp_ms: 1,
+            event: &event,
+        })
+        .unwrap();
+        assert_eq!(
+            line,
+            r#"{"timestamp_ms":1,"event":"find_discovered","path":"m/84'/0'/0'/0/0","descriptor":"raw(deadbeef)#89f8spxm"}"#
+        );
+        let parsed: LoggedEvent = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed, event);
+    }
+
+    #[test]
+    fn event_broadcast_works_01() {
+        let event_log = EventLog::default();
+        let mut events = event_log.clone().subscribe();
+        event_log.log(LoggedEvent::RpcCall {
+            method: "getblockcount".to_string(),
+            success: true,
+        });
+        event_log.log(LoggedEvent::PopulationProgress {
+            done: 100_000,
+            total: 0,
+        });
+        assert_eq!(
+            events.try_recv().unwrap(),
+            RetrieverEvent::SetPopulationProgress {
+                done: 100_000,
+                total: 0
+            }
+        );
+        assert!(events.try_recv().is_err());
+    }
+}
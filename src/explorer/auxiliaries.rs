@@ -1,11 +1,20 @@
+use std::{str::FromStr, sync::LazyLock};
+
 use bip39::Mnemonic;
 use bitcoin::{
     bip32::{DerivationPath, Xpriv},
     key::Secp256k1,
+    secp256k1::All,
 };
+use sha2::Sha256;
+use tracing::{error, warn};
+use zeroize::Zeroize;
 
 use crate::error::RetrieverError;
 
+/// The context shared by the derivations of every path, so none is allocated per path.
+pub static SECP: LazyLock<Secp256k1<All>> = LazyLock::new(Secp256k1::new);
+
 pub fn from_seed_to_master_xpriv(
     seed: [u8; 64],
     network: bitcoin::Network,
@@ -32,12 +41,73 @@ pub fn from_mnemonic_to_seed(mnemonic: Mnemonic, passphrase: &str) -> [u8; 64] {
     mnemonic.to_seed(passphrase)
 }
 
+/// Turns a hex backup into the bip32 seed: 64 bytes are the seed itself, while 16 to 32 bytes are
+/// the entropy of a BIP39 mnemonic, whose seed is then made with the passphrase.
+pub fn from_hex_to_seed(input: &str, passphrase: &str) -> Result<[u8; 64], RetrieverError> {
+    let mut bytes = hex::decode(input.trim()).map_err(|_| {
+        error!("The seed hex is not valid hex.");
+        RetrieverError::InvalidSeedHex
+    })?;
+    let seed = match bytes.len() {
+        64 => {
+            let mut seed = [0u8; 64];
+            seed.copy_from_slice(&bytes);
+            seed
+        }
+        16 | 20 | 24 | 28 | 32 => {
+            let mut mnemonic = Mnemonic::from_entropy(&bytes)?;
+            let seed = from_mnemonic_to_seed(mnemonic.clone(), passphrase);
+            mnemonic.zeroize();
+            seed
+        }
+        length => {
+            error!(
+                "The seed hex has {} bytes, but must be a 64 byte seed or 16 to 32 bytes of entropy.",
+                length
+            );
+            bytes.zeroize();
+            return Err(RetrieverError::InvalidSeedHex);
+        }
+    };
+    bytes.zeroize();
+    Ok(seed)
+}
+
+/// Parses an extended private key, which must be an xprv on mainnet and a tprv on the other
+/// networks. A key below the master is accepted, the base derivation paths then start from it.
+pub fn from_input_str_to_xpriv(
+    input: &str,
+    network: bitcoin::Network,
+) -> Result<Xpriv, RetrieverError> {
+    let mut xpriv = Xpriv::from_str(input.trim())?;
+    if (xpriv.network == bitcoin::Network::Bitcoin) != (network == bitcoin::Network::Bitcoin) {
+        error!(
+            "The extended private key is not for the {} network.",
+            network
+        );
+        return Err(RetrieverError::XprivNetworkMismatch);
+    }
+    if xpriv.depth > 0 {
+        warn!(
+            "The extended private key is at depth {}, base derivation paths are relative to it.",
+            xpriv.depth
+        );
+    }
+    xpriv.network = network;
+    Ok(xpriv)
+}
+
+/// PBKDF2 with HMAC-SHA256, as SLIP-39 and WarpWallet use it.
+pub fn pbkdf2_hmac_sha256(password: &[u8], salt: &[u8], rounds: u32, length: usize) -> Vec<u8> {
+    let mut output = vec![0u8; length];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, rounds, &mut output);
+    output
+}
+
 #[cfg(test)]
 mod tests {
     // Used https://learnmeabitcoin.com/technical/keys/hd-wallets/mnemonic-seed/ for test cases.
 
-    use std::str::FromStr;
-
     use super::*;
 
     #[test]
@@ -85,4 +155,41 @@ mod tests {
         let expected = hex::decode("87b50b8fbda1509700852f6ad3a0f9c8ee6ba076716a3bdf77044b5b8d48d49993384a10a2994713d63147517862fad9dc7989eea3ca9471fce0a13b823c7cd2").unwrap();
         assert_eq!(seed.to_vec(), expected);
     }
+
+    #[test]
+    fn xpriv_input_works() {
+        let input = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+        let xpriv = from_input_str_to_xpriv(input, bitcoin::Network::Bitcoin).unwrap();
+        assert_eq!(xpriv, Xpriv::from_str(input).unwrap());
+        assert!(matches!(
+            from_input_str_to_xpriv(input, bitcoin::Network::Signet),
+            Err(RetrieverError::XprivNetworkMismatch)
+        ));
+        assert!(matches!(
+            from_input_str_to_xpriv("xprv9s21", bitcoin::Network::Bitcoin),
+            Err(RetrieverError::Bip32Error(_))
+        ));
+    }
+
+    #[test]
+    fn seed_hex_works() {
+        // BIP39 test vector.
+        let seed = from_hex_to_seed("00000000000000000000000000000000", "TREZOR").unwrap();
+        let expected = hex::decode("c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04").unwrap();
+        assert_eq!(seed.to_vec(), expected);
+        assert_eq!(
+            from_hex_to_seed(&hex::encode(&expected), "ignored")
+                .unwrap()
+                .to_vec(),
+            expected
+        );
+        assert!(matches!(
+            from_hex_to_seed("00ff00", ""),
+            Err(RetrieverError::InvalidSeedHex)
+        ));
+        assert!(matches!(
+            from_hex_to_seed("zz", ""),
+            Err(RetrieverError::InvalidSeedHex)
+        ));
+    }
 }
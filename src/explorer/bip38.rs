@@ -0,0 +1,250 @@
+use aes::{
+    cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit},
+    Aes256,
+};
+use bitcoin::{
+    base58,
+    hashes::{sha256d, Hash},
+    key::Secp256k1,
+    secp256k1::{Scalar, SecretKey},
+    Address, PrivateKey, PublicKey,
+};
+use tracing::error;
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroize;
+
+use crate::error::RetrieverError;
+
+use super::scrypt::scrypt;
+
+const BIP38_KEY_BYTES: usize = 39;
+const PREFIX_NON_EC_MULTIPLY: [u8; 2] = [0x01, 0x42];
+const PREFIX_EC_MULTIPLY: [u8; 2] = [0x01, 0x43];
+/// The flag bits set on keys encrypted directly, without EC multiply.
+const FLAG_NON_EC_MULTIPLY: u8 = 0xc0;
+const FLAG_COMPRESSED: u8 = 0x20;
+const FLAG_LOT_SEQUENCE: u8 = 0x04;
+/// The scrypt parameters (log2 of N, r, p) BIP38 uses on the password.
+const SCRYPT_PASSWORD_PARAMS: (u8, u32, u32) = (14, 8, 8);
+/// The scrypt parameters BIP38 uses on the passpoint of EC-multiplied keys.
+const SCRYPT_PASSPOINT_PARAMS: (u8, u32, u32) = (10, 1, 1);
+
+/// Decrypts a BIP38 encrypted private key ("6P...") with its password, whether it was encrypted
+/// directly or created from an intermediate code (EC multiply). The password is checked against
+/// the address hash the key carries.
+pub fn from_bip38_to_private_key(
+    encrypted_key: &str,
+    password: &str,
+    network: bitcoin::Network,
+) -> Result<PrivateKey, RetrieverError> {
+    let data = base58::decode_check(encrypted_key.trim()).map_err(|_| {
+        error!("Invalid BIP38 key: {}", encrypted_key);
+        RetrieverError::InvalidBip38Key
+    })?;
+    if data.len() != BIP38_KEY_BYTES {
+        error!("Invalid BIP38 key: {}", encrypted_key);
+        return Err(RetrieverError::InvalidBip38Key);
+    }
+    let flag = data[2];
+    let address_hash = &data[3..7];
+    let mut password = password.nfc().collect::<String>();
+    let secret_key = match [data[0], data[1]] {
+        PREFIX_NON_EC_MULTIPLY => decrypt_non_ec_multiply(&data, password.as_bytes()),
+        PREFIX_EC_MULTIPLY => decrypt_ec_multiply(&data, password.as_bytes()),
+        _ => {
+            error!("Invalid BIP38 key: {}", encrypted_key);
+            Err(RetrieverError::InvalidBip38Key)
+        }
+    };
+    password.zeroize();
+    let private_key = PrivateKey {
+        compressed: flag & FLAG_COMPRESSED != 0,
+        network,
+        inner: secret_key?,
+    };
+    let public_key = PublicKey::from_private_key(&Secp256k1::new(), &private_key);
+    let address = Address::p2pkh(&public_key, bitcoin::Network::Bitcoin).to_string();
+    if sha256d::Hash::hash(address.as_bytes())[..4] != *address_hash {
+        error!("Wrong password for BIP38 key {}.", encrypted_key);
+        return Err(RetrieverError::WrongBip38Password);
+    }
+    Ok(private_key)
+}
+
+/// Encrypts a private key with a password as BIP38 does without EC multiply, e.g. for keys that
+/// are exported, the reverse of `from_bip38_to_private_key`.
+pub fn from_private_key_to_bip38(
+    private_key: &PrivateKey,
+    password: &str,
+) -> Result<String, RetrieverError> {
+    let public_key = PublicKey::from_private_key(&Secp256k1::new(), private_key);
+    let address = Address::p2pkh(&public_key, bitcoin::Network::Bitcoin).to_string();
+    let address_hash = &sha256d::Hash::hash(address.as_bytes())[..4];
+    let mut password = password.nfc().collect::<String>();
+    let (log_n, r, p) = SCRYPT_PASSWORD_PARAMS;
+    let mut derived = scrypt(password.as_bytes(), address_hash, log_n, r, p, 64);
+    password.zeroize();
+    let flag = match private_key.compressed {
+        true => FLAG_NON_EC_MULTIPLY | FLAG_COMPRESSED,
+        false => FLAG_NON_EC_MULTIPLY,
+    };
+    let mut data = [PREFIX_NON_EC_MULTIPLY.as_slice(), &[flag], address_hash].concat();
+    let mut secret = private_key.inner.secret_bytes();
+    for (half, secret_half) in secret.chunks(16).enumerate() {
+        let mut block: [u8; 16] =
+            core::array::from_fn(|index| secret_half[index] ^ derived[half * 16 + index]);
+        data.extend_from_slice(&aes256_encrypt_block(
+            &derived[32..].try_into().unwrap(),
+            &block,
+        ));
+        block.zeroize();
+    }
+    secret.zeroize();
+    derived.zeroize();
+    Ok(base58::encode_check(&data))
+}
+
+fn decrypt_non_ec_multiply(data: &[u8], password: &[u8]) -> Result<SecretKey, RetrieverError> {
+    let (log_n, r, p) = SCRYPT_PASSWORD_PARAMS;
+    let mut derived = scrypt(password, &data[3..7], log_n, r, p, 64);
+    let mut secret = [0u8; 32];
+    for (half, encrypted_half) in data[7..].chunks(16).enumerate() {
+        let decrypted_half =
+            aes256_decrypt_block(&derived[32..].try_into().unwrap(), encrypted_half);
+        for (index, byte) in decrypted_half.iter().enumerate() {
+            secret[half * 16 + index] = byte ^ derived[half * 16 + index];
+        }
+    }
+    derived.zeroize();
+    let secret_key = SecretKey::from_slice(&secret);
+    secret.zeroize();
+    Ok(secret_key?)
+}
+
+fn decrypt_ec_multiply(data: &[u8], password: &[u8]) -> Result<SecretKey, RetrieverError> {
+    let flag = data[2];
+    let owner_entropy = &data[7..15];
+    let owner_salt = if flag & FLAG_LOT_SEQUENCE != 0 {
+        &owner_entropy[..4]
+    } else {
+        owner_entropy
+    };
+    let (log_n, r, p) = SCRYPT_PASSWORD_PARAMS;
+    let mut pass_factor = scrypt(password, owner_salt, log_n, r, p, 32);
+    if flag & FLAG_LOT_SEQUENCE != 0 {
+        let hash = sha256d::Hash::hash(&[pass_factor.as_slice(), owner_entropy].concat());
+        pass_factor.zeroize();
+        pass_factor = hash.to_byte_array().to_vec();
+    }
+    let pass_factor_key = SecretKey::from_slice(&pass_factor);
+    pass_factor.zeroize();
+    let pass_factor_key = pass_factor_key?;
+    let pass_point = pass_factor_key.public_key(&Secp256k1::new()).serialize();
+    let (log_n, r, p) = SCRYPT_PASSPOINT_PARAMS;
+    let mut derived = scrypt(&pass_point, &data[3..15], log_n, r, p, 64);
+    let key = derived[32..].try_into().unwrap();
+    let mut decrypted_part2 = aes256_decrypt_block(&key, &data[23..39]);
+    decrypted_part2
+        .iter_mut()
+        .zip(&derived[16..32])
+        .for_each(|(byte, mask)| *byte ^= mask);
+    let encrypted_part1 = [&data[15..23], &decrypted_part2[..8]].concat();
+    let mut seed_b = aes256_decrypt_block(&key, &encrypted_part1).to_vec();
+    seed_b
+        .iter_mut()
+        .zip(&derived[..16])
+        .for_each(|(byte, mask)| *byte ^= mask);
+    seed_b.extend_from_slice(&decrypted_part2[8..]);
+    let factor_b = sha256d::Hash::hash(&seed_b).to_byte_array();
+    derived.zeroize();
+    decrypted_part2.zeroize();
+    seed_b.zeroize();
+    let factor_b = Scalar::from_be_bytes(factor_b).map_err(|_| RetrieverError::InvalidBip38Key)?;
+    Ok(pass_factor_key.mul_tweak(&factor_b)?)
+}
+
+/// Encrypts a single block with AES-256, as BIP38 uses it (ECB, no padding).
+fn aes256_encrypt_block(key: &[u8; 32], block: &[u8]) -> [u8; 16] {
+    let mut block = GenericArray::clone_from_slice(block);
+    Aes256::new(key.into()).encrypt_block(&mut block);
+    block.into()
+}
+
+/// Decrypts a single block with AES-256, as BIP38 uses it (ECB, no padding).
+fn aes256_decrypt_block(key: &[u8; 32], block: &[u8]) -> [u8; 16] {
+    let mut block = GenericArray::clone_from_slice(block);
+    Aes256::new(key.into()).decrypt_block(&mut block);
+    block.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bip38_works_01() {
+        // Test vector of FIPS-197.
+        let key: [u8; 32] = core::array::from_fn(|index| index as u8);
+        assert_eq!(
+            hex::encode(aes256_decrypt_block(
+                &key,
+                &hex::decode("8ea2b7ca516745bfeafc49904b496089").unwrap()
+            )),
+            "00112233445566778899aabbccddeeff"
+        );
+        assert_eq!(
+            hex::encode(aes256_encrypt_block(
+                &key,
+                &hex::decode("00112233445566778899aabbccddeeff").unwrap()
+            )),
+            "8ea2b7ca516745bfeafc49904b496089"
+        );
+    }
+
+    #[test]
+    fn bip38_works_02() {
+        // Test vectors of BIP38. Each decryption runs scrypt with N = 16384, so they are few.
+        assert_eq!(
+            from_bip38_to_private_key(
+                "6PYNKZ1EAgYgmQfmNVamxyXVWHzK5s6DGhwP4J5o44cvXdoY7sRzhtpUeo",
+                "TestingOneTwoThree",
+                bitcoin::Network::Bitcoin
+            )
+            .unwrap()
+            .to_wif(),
+            "L44B5gGEpqEDRS9vVPz7QT35jcBG2r3CZwSwQ4fCewXAhAhqGVpP"
+        );
+        assert_eq!(
+            from_private_key_to_bip38(
+                &PrivateKey::from_wif("L44B5gGEpqEDRS9vVPz7QT35jcBG2r3CZwSwQ4fCewXAhAhqGVpP")
+                    .unwrap(),
+                "TestingOneTwoThree"
+            )
+            .unwrap(),
+            "6PYNKZ1EAgYgmQfmNVamxyXVWHzK5s6DGhwP4J5o44cvXdoY7sRzhtpUeo"
+        );
+        assert!(matches!(
+            from_bip38_to_private_key(
+                "6PRVWUbkzzsbcVac2qwfssoUJAN1Xhrg6bNk8J7Nzm5H7kxEbn2Nh2ZoGg",
+                "TestingOneTwoFour",
+                bitcoin::Network::Bitcoin
+            ),
+            Err(RetrieverError::WrongBip38Password)
+        ));
+    }
+
+    #[test]
+    fn bip38_works_03() {
+        let private_key = from_bip38_to_private_key(
+            "6PfQu77ygVyJLZjfvMLyhLMQbYnu5uguoJJ4kMCLqWwPEdfpwANVS76gTX",
+            "TestingOneTwoThree",
+            bitcoin::Network::Bitcoin,
+        )
+        .unwrap();
+        assert!(!private_key.compressed);
+        assert_eq!(
+            private_key.to_wif(),
+            "5K4caxezwjGCGfnoPTZ8tMcJBLB7Jvyjv4xxeacadhq8nLisLR2"
+        );
+    }
+}
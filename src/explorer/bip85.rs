@@ -0,0 +1,106 @@
+use std::{ops::RangeInclusive, str::FromStr};
+
+use bip39::{Language, Mnemonic};
+use bitcoin::{
+    bip32::{DerivationPath, Xpriv},
+    hashes::{
+        hmac::{Hmac, HmacEngine},
+        sha512, Hash, HashEngine,
+    },
+    key::Secp256k1,
+};
+use tracing::error;
+use zeroize::Zeroize;
+
+use crate::error::RetrieverError;
+
+const BIP85_HMAC_KEY: &[u8] = b"bip-entropy-from-k";
+/// The application number of BIP39 mnemonics, with English as language.
+const BIP85_BIP39_PATH: &str = "m/83696968'/39'/0'";
+pub const BIP85_WORD_COUNTS: [u32; 3] = [12, 18, 24];
+
+/// Derives the BIP39 child mnemonic with the given number of words at the given BIP85 index of
+/// the master key, i.e. from the key at m/83696968'/39'/0'/{words}'/{index}'.
+pub fn from_master_xpriv_to_bip85_mnemonic(
+    master_xpriv: &Xpriv,
+    words: u32,
+    index: u32,
+) -> Result<Mnemonic, RetrieverError> {
+    if !BIP85_WORD_COUNTS.contains(&words) {
+        error!("BIP85 child mnemonics have 12, 18 or 24 words.");
+        return Err(RetrieverError::InvalidBip85Words);
+    }
+    let path = DerivationPath::from_str(&format!("{}/{}'/{}'", BIP85_BIP39_PATH, words, index))?;
+    let mut child_key = master_xpriv
+        .derive_priv(&Secp256k1::new(), &path)?
+        .private_key
+        .secret_bytes();
+    let mut engine = HmacEngine::<sha512::Hash>::new(BIP85_HMAC_KEY);
+    engine.input(&child_key);
+    let mut entropy = Hmac::from_engine(engine).to_byte_array();
+    let mnemonic = Mnemonic::from_entropy_in(Language::English, &entropy[..words as usize * 4 / 3]);
+    child_key.zeroize();
+    entropy.zeroize();
+    Ok(mnemonic?)
+}
+
+/// Parses the BIP85 indexes to explore, given as "A..B" (A to B, both inclusive), "..B" (0 to
+/// B) or a single index, like the steps of the exploration path.
+pub fn parse_bip85_indexes(indexes: &str) -> Result<RangeInclusive<u32>, RetrieverError> {
+    let parse_index = |index: &str| {
+        index.trim().parse::<u32>().map_err(|_| {
+            error!("Invalid BIP85 indexes: {}", indexes);
+            RetrieverError::InvalidBip85Indexes
+        })
+    };
+    let range = match indexes.split_once("..") {
+        Some((start, end)) if start.trim().is_empty() => 0..=parse_index(end)?,
+        Some((start, end)) => parse_index(start)?..=parse_index(end)?,
+        None => {
+            let index = parse_index(indexes)?;
+            index..=index
+        }
+    };
+    if range.is_empty() || *range.end() >= 1 << 31 {
+        error!("Invalid BIP85 indexes: {}", indexes);
+        return Err(RetrieverError::InvalidBip85Indexes);
+    }
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    // Test vectors of BIP85.
+
+    use super::*;
+
+    #[test]
+    fn bip85_works_01() {
+        let master_xpriv = Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+        assert_eq!(
+            from_master_xpriv_to_bip85_mnemonic(&master_xpriv, 12, 0)
+                .unwrap()
+                .to_string(),
+            "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose"
+        );
+        assert_eq!(
+            from_master_xpriv_to_bip85_mnemonic(&master_xpriv, 24, 0)
+                .unwrap()
+                .to_string(),
+            "puppy ocean match cereal symbol another shed magic wrap hammer bulb intact gadget divorce twin tonight reason outdoor destroy simple truth cigar social volcano"
+        );
+        assert!(matches!(
+            from_master_xpriv_to_bip85_mnemonic(&master_xpriv, 15, 0),
+            Err(RetrieverError::InvalidBip85Words)
+        ));
+    }
+
+    #[test]
+    fn bip85_works_02() {
+        assert_eq!(parse_bip85_indexes("3..7").unwrap(), 3..=7);
+        assert_eq!(parse_bip85_indexes("..4").unwrap(), 0..=4);
+        assert_eq!(parse_bip85_indexes("5").unwrap(), 5..=5);
+        assert!(parse_bip85_indexes("7..3").is_err());
+        assert!(parse_bip85_indexes("a..3").is_err());
+    }
+}
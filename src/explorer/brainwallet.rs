@@ -0,0 +1,131 @@
+use std::fs;
+
+use bitcoin::{
+    hashes::{sha256, sha256d, Hash},
+    secp256k1::SecretKey,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use zeroize::Zeroize;
+
+use crate::error::RetrieverError;
+
+use super::{auxiliaries::pbkdf2_hmac_sha256, scrypt::scrypt};
+
+/// How a brainwallet turns its passphrase into the private key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BrainwalletHash {
+    /// SHA256 of the passphrase, as most brainwallets did.
+    Sha256,
+    /// SHA256 applied twice.
+    DoubleSha256,
+    /// WarpWallet, which xors scrypt and PBKDF2 of the passphrase with a salt (often an email).
+    WarpWallet,
+}
+
+impl BrainwalletHash {
+    /// Names the hash in the labels of the keys.
+    pub fn label(&self) -> &'static str {
+        match self {
+            BrainwalletHash::Sha256 => "sha256",
+            BrainwalletHash::DoubleSha256 => "double_sha256",
+            BrainwalletHash::WarpWallet => "warpwallet",
+        }
+    }
+}
+
+/// Derives the private key of the brainwallet passphrase. The salt is only used by WarpWallet.
+pub fn from_passphrase_to_brainwallet_key(
+    passphrase: &str,
+    hash: BrainwalletHash,
+    warpwallet_salt: &str,
+) -> Result<SecretKey, RetrieverError> {
+    let mut secret = match hash {
+        BrainwalletHash::Sha256 => sha256::Hash::hash(passphrase.as_bytes()).to_byte_array(),
+        BrainwalletHash::DoubleSha256 => sha256d::Hash::hash(passphrase.as_bytes()).to_byte_array(),
+        BrainwalletHash::WarpWallet => {
+            let with_suffix = |input: &str, suffix: u8| [input.as_bytes(), &[suffix]].concat();
+            let mut scrypt_part = scrypt(
+                &with_suffix(passphrase, 1),
+                &with_suffix(warpwallet_salt, 1),
+                18,
+                8,
+                1,
+                32,
+            );
+            let mut pbkdf2_part = pbkdf2_hmac_sha256(
+                &with_suffix(passphrase, 2),
+                &with_suffix(warpwallet_salt, 2),
+                1 << 16,
+                32,
+            );
+            let mut secret = [0u8; 32];
+            for (index, byte) in secret.iter_mut().enumerate() {
+                *byte = scrypt_part[index] ^ pbkdf2_part[index];
+            }
+            scrypt_part.zeroize();
+            pbkdf2_part.zeroize();
+            secret
+        }
+    };
+    let secret_key = SecretKey::from_slice(&secret);
+    secret.zeroize();
+    Ok(secret_key?)
+}
+
+/// The candidate passphrases of the brainwallet file, one per line.
+pub fn read_brainwallet_candidates(file_path: &str) -> Result<Vec<String>, RetrieverError> {
+    let mut content = fs::read_to_string(file_path).inspect_err(|err| {
+        error!("Reading the brainwallet candidates file failed: {}", err);
+    })?;
+    let candidates = content
+        .lines()
+        .map(|line| line.trim_end_matches('\r').to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    content.zeroize();
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{key::Secp256k1, PrivateKey};
+
+    use super::*;
+
+    #[test]
+    fn brainwallet_works_01() {
+        let secret_key = from_passphrase_to_brainwallet_key(
+            "correct horse battery staple",
+            BrainwalletHash::Sha256,
+            "",
+        )
+        .unwrap();
+        let private_key = PrivateKey {
+            compressed: false,
+            network: bitcoin::Network::Bitcoin,
+            inner: secret_key,
+        };
+        assert_eq!(
+            private_key.to_wif(),
+            "5KJvsngHeMpm884wtkJNzQGaCErckhHJBGFsvd3VyK5qMZXj3hS"
+        );
+        assert_eq!(
+            bitcoin::Address::p2pkh(
+                &private_key.public_key(&Secp256k1::new()),
+                bitcoin::Network::Bitcoin
+            )
+            .to_string(),
+            "1JwSSubhmg6iPtRjtyqhUYYH7bZg3Lfy1T"
+        );
+        assert_ne!(
+            from_passphrase_to_brainwallet_key(
+                "correct horse battery staple",
+                BrainwalletHash::DoubleSha256,
+                "",
+            )
+            .unwrap(),
+            secret_key
+        );
+    }
+}
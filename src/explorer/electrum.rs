@@ -0,0 +1,151 @@
+use bitcoin::hashes::{
+    hmac::{Hmac, HmacEngine},
+    sha512, Hash, HashEngine,
+};
+use sha2::Sha512;
+use tracing::{error, info};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use zeroize::Zeroize;
+
+use crate::error::RetrieverError;
+
+const PBKDF2_ROUNDS: u32 = 2048;
+/// Hex prefixes of the seed version hash.
+const STANDARD_SEED_PREFIX: &str = "01";
+const SEGWIT_SEED_PREFIX: &str = "100";
+const TWO_FACTOR_SEED_PREFIXES: [&str; 2] = ["101", "102"];
+
+/// The version of an Electrum seed, telling which wallet Electrum built from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElectrumSeedVersion {
+    /// p2pkh addresses at m/0/i (receive) and m/1/i (change).
+    Standard,
+    /// p2wpkh addresses at m/0'/0/i (receive) and m/0'/1/i (change).
+    Segwit,
+}
+
+/// Normalizes a seed or passphrase the way Electrum does: NFKD, lower case, no accents, single
+/// spaces, and no spaces between CJK characters.
+fn normalize_text(text: &str) -> String {
+    let text = text
+        .nfkd()
+        .filter(|char| !is_combining_mark(*char))
+        .collect::<String>()
+        .to_lowercase();
+    let words = text.split_whitespace().collect::<Vec<_>>();
+    let mut normalized = String::new();
+    for (index, word) in words.iter().enumerate() {
+        if index > 0 {
+            let previous = words[index - 1].chars().last();
+            let next = word.chars().next();
+            if !(previous.is_some_and(is_cjk) && next.is_some_and(is_cjk)) {
+                normalized.push(' ');
+            }
+        }
+        normalized.push_str(word);
+    }
+    normalized
+}
+
+fn is_cjk(char: char) -> bool {
+    matches!(char as u32,
+        0x1100..=0x11FF
+        | 0x2E80..=0x2FDF
+        | 0x3000..=0x31FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA960..=0xA97F
+        | 0xAC00..=0xD7FF
+        | 0xF900..=0xFAFF
+        | 0xFF00..=0xFFEF
+        | 0x20000..=0x2FA1F)
+}
+
+/// Detects the version of an Electrum seed. Two-factor seeds are refused, as their wallets are
+/// multisig with a key held by TrustedCoin.
+pub fn electrum_seed_version(mnemonic: &str) -> Result<ElectrumSeedVersion, RetrieverError> {
+    let mut engine = HmacEngine::<sha512::Hash>::new(b"Seed version");
+    engine.input(normalize_text(mnemonic).as_bytes());
+    let version_hash = Hmac::from_engine(engine).to_string();
+    if version_hash.starts_with(STANDARD_SEED_PREFIX) {
+        Ok(ElectrumSeedVersion::Standard)
+    } else if version_hash.starts_with(SEGWIT_SEED_PREFIX) {
+        Ok(ElectrumSeedVersion::Segwit)
+    } else if TWO_FACTOR_SEED_PREFIXES
+        .iter()
+        .any(|prefix| version_hash.starts_with(prefix))
+    {
+        error!("Electrum two-factor seeds are not supported.");
+        Err(RetrieverError::UnsupportedElectrumSeedVersion)
+    } else {
+        error!("The mnemonic is not a valid Electrum seed.");
+        Err(RetrieverError::InvalidElectrumSeed)
+    }
+}
+
+/// Derives the bip32 seed of an Electrum seed with its passphrase (PBKDF2-HMAC-SHA512 with the
+/// salt "electrum" and the passphrase). The master key is then made from it as in BIP32.
+pub fn from_electrum_seed_to_seed(
+    mnemonic: &str,
+    passphrase: &str,
+) -> Result<[u8; 64], RetrieverError> {
+    let version = electrum_seed_version(mnemonic)?;
+    info!(
+        "Electrum {:?} seed detected, its addresses are derived under {}.",
+        version,
+        match version {
+            ElectrumSeedVersion::Standard => "m/0 and m/1",
+            ElectrumSeedVersion::Segwit => "m/0'/0 and m/0'/1",
+        }
+    );
+    let mut password = normalize_text(mnemonic);
+    let mut salt = format!("electrum{}", normalize_text(passphrase));
+    let mut seed = [0u8; 64];
+    pbkdf2::pbkdf2_hmac::<Sha512>(
+        password.as_bytes(),
+        salt.as_bytes(),
+        PBKDF2_ROUNDS,
+        &mut seed,
+    );
+    password.zeroize();
+    salt.zeroize();
+    Ok(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    // Test vectors of Electrum's test_mnemonic.py.
+
+    use super::*;
+
+    #[test]
+    fn electrum_seed_works_01() {
+        let mnemonic =
+            "wild father tree among universe such mobile favorite target dynamic credit identify";
+        assert_eq!(
+            electrum_seed_version(mnemonic).unwrap(),
+            ElectrumSeedVersion::Segwit
+        );
+        let seed = from_electrum_seed_to_seed(mnemonic, "").unwrap();
+        assert_eq!(seed.to_vec(), hex::decode("aac2a6302e48577ab4b46f23dbae0774e2e62c796f797d0a1b5faeb528301e3064342dafb79069e7c4c6b8c38ae11d7a973bec0d4f70626f8cc5184a8d0b0756").unwrap());
+        let seed = from_electrum_seed_to_seed(
+            "Wild  Father tree among universe such mobile favorite target dynamic credit identify",
+            "Did you ever hear the tragedy of Darth Plagueis the Wise?",
+        )
+        .unwrap();
+        assert_eq!(seed.to_vec(), hex::decode("4aa29f2aeb0127efb55138ab9e7be83b36750358751906f86c662b21a1ea1370f949e6d1a12fa56d3d93cadda93038c76ac8118597364e46f5156fde6183c82f").unwrap());
+    }
+
+    #[test]
+    fn electrum_seed_works_02() {
+        // A BIP39 mnemonic is no Electrum seed.
+        assert!(matches!(
+            electrum_seed_version(
+                "camera phrase loan curtain island hammer soft fault hockey enter power busy"
+            ),
+            Err(RetrieverError::InvalidElectrumSeed)
+        ));
+        assert_eq!(normalize_text(" Café  au\tlait "), "cafe au lait");
+        assert_eq!(normalize_text("あい うえ abc"), "あいうえ abc");
+    }
+}
@@ -0,0 +1,124 @@
+use bitcoin::{
+    hashes::{sha256, sha256d, Hash, HashEngine},
+    key::Secp256k1,
+    secp256k1::{Scalar, SecretKey},
+    PublicKey,
+};
+use tracing::error;
+use zeroize::Zeroize;
+
+use crate::{data::electrum_v1_wordlist::ELECTRUM_V1_WORDLIST, error::RetrieverError};
+
+/// The rounds of SHA256 with which Electrum v1 stretches the seed into the master private key.
+const STRETCH_ROUNDS: u32 = 100_000;
+
+/// Turns an Electrum v1 seed into its hex form, which is what the key is stretched from. The seed
+/// is either 12 or 24 words of the old wordlist, each 3 of them encoding 4 bytes, or the hex
+/// itself (16 or 32 bytes), as the earliest versions showed it.
+fn from_electrum_v1_seed_to_hex(input: &str) -> Result<String, RetrieverError> {
+    let input = input.trim().to_lowercase();
+    if hex::decode(&input).is_ok_and(|bytes| bytes.len() == 16 || bytes.len() == 32) {
+        return Ok(input);
+    }
+    let mut indexes = vec![];
+    for word in input.split_whitespace() {
+        let Some(index) = ELECTRUM_V1_WORDLIST
+            .iter()
+            .position(|list_word| *list_word == word)
+        else {
+            error!("The Electrum v1 seed contains a word not in the old wordlist.");
+            return Err(RetrieverError::InvalidElectrumSeed);
+        };
+        indexes.push(index);
+    }
+    if indexes.len() != 12 && indexes.len() != 24 {
+        error!(
+            "The Electrum v1 seed has {} words, but must have 12 or 24.",
+            indexes.len()
+        );
+        return Err(RetrieverError::InvalidElectrumSeed);
+    }
+    let list_len = ELECTRUM_V1_WORDLIST.len();
+    let mut seed_hex = String::new();
+    for triple in indexes.chunks(3) {
+        let value = triple[0]
+            + list_len * ((list_len + triple[1] - triple[0]) % list_len)
+            + list_len * list_len * ((list_len + triple[2] - triple[1]) % list_len);
+        seed_hex.push_str(&format!("{:08x}", value));
+    }
+    indexes.zeroize();
+    Ok(seed_hex)
+}
+
+/// Derives the master private key of an Electrum v1 seed: its hex form hashed again and again
+/// with itself appended. Electrum v1 has no passphrase.
+pub fn from_electrum_v1_seed_to_master_key(input: &str) -> Result<SecretKey, RetrieverError> {
+    let mut seed_hex = from_electrum_v1_seed_to_hex(input)?;
+    let mut stretched = seed_hex.as_bytes().to_vec();
+    for _ in 0..STRETCH_ROUNDS {
+        let mut engine = sha256::Hash::engine();
+        engine.input(&stretched);
+        engine.input(seed_hex.as_bytes());
+        stretched.zeroize();
+        stretched = sha256::Hash::from_engine(engine).to_byte_array().to_vec();
+    }
+    seed_hex.zeroize();
+    let master_key = SecretKey::from_slice(&stretched);
+    stretched.zeroize();
+    Ok(master_key?)
+}
+
+/// Derives the uncompressed public key of an Electrum v1 wallet at the index of the chain (0 for
+/// receiving, 1 for change). Its tweak is the double SHA256 of "{index}:{chain}:" followed by the
+/// master public key, so no private key is needed.
+pub fn derive_electrum_v1_public_key(
+    master_public_key: &bitcoin::secp256k1::PublicKey,
+    chain: u32,
+    index: u32,
+) -> Result<PublicKey, RetrieverError> {
+    let mut engine = sha256d::Hash::engine();
+    engine.input(format!("{}:{}:", index, chain).as_bytes());
+    engine.input(&master_public_key.serialize_uncompressed()[1..]);
+    let tweak = Scalar::from_be_bytes(sha256d::Hash::from_engine(engine).to_byte_array())
+        .map_err(|_| RetrieverError::InvalidElectrumSeed)?;
+    let public_key = master_public_key.add_exp_tweak(&Secp256k1::new(), &tweak)?;
+    Ok(PublicKey::new_uncompressed(public_key))
+}
+
+#[cfg(test)]
+mod tests {
+    // Test vector of Electrum's test_wallet_vertical.py.
+
+    use super::*;
+
+    #[test]
+    fn electrum_v1_works_01() {
+        let seed =
+            "powerful random nobody notice nothing important anyway look away hidden message over";
+        assert_eq!(
+            from_electrum_v1_seed_to_hex(seed).unwrap(),
+            "acb740e454c3134901d7c8f16497cc1c"
+        );
+        let master_public_key = from_electrum_v1_seed_to_master_key(seed)
+            .unwrap()
+            .public_key(&Secp256k1::new());
+        assert_eq!(
+            hex::encode(&master_public_key.serialize_uncompressed()[1..]),
+            "e9d4b7866dd1e91c862aebf62a49548c7dbf7bcc6e4b7b8c9da820c7737968df9c09d5a3e271dc814a29981f81b3faaf2737b551ef5dcc6189cf0f8252c442b3"
+        );
+        for (chain, address) in [
+            (0, "1FJEEB8ihPMbzs2SkLmr37dHyRFzakqUmo"),
+            (1, "1KRW8pH6HFHZh889VDq6fEKvmrsmApwNfe"),
+        ] {
+            let public_key = derive_electrum_v1_public_key(&master_public_key, chain, 0).unwrap();
+            assert_eq!(
+                bitcoin::Address::p2pkh(&public_key, bitcoin::Network::Bitcoin).to_string(),
+                address
+            );
+        }
+        assert!(matches!(
+            from_electrum_v1_seed_to_hex("powerful random nobody"),
+            Err(RetrieverError::InvalidElectrumSeed)
+        ));
+    }
+}
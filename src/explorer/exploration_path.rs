@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use tracing::{error, info};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use crate::error::RetrieverError;
+use crate::{data::defaults::STEP_EXCLUSION_MAX_INDICES, error::RetrieverError};
 
 use super::exploration_step::{ExplorationStep, ExplorationStepHardness};
 
@@ -81,6 +81,13 @@ impl ExplorationPath {
         }
     }
 
+    /// The number of explored paths below each of the base paths, sweep aside.
+    pub fn num_of_paths_per_base(&self) -> usize {
+        self.explore
+            .iter()
+            .fold(1usize, |acc, step| acc * step.num_children() as usize)
+    }
+
     pub fn num_of_paths_sweep(&self) -> usize {
         info!("Calculating the number of sweep paths in exploration path.");
         let mut num_paths = 1;
@@ -127,7 +134,7 @@ impl Zeroize for ExplorationPath {
 impl ZeroizeOnDrop for ExplorationPath {}
 
 pub fn check_input_chars(input: &str) -> bool {
-    let regex = Regex::new(r"^[\d./'ha*]+$").unwrap();
+    let regex = Regex::new(r"^[\d./'ha*{},:!]+$").unwrap();
     regex.is_match(input)
 }
 
@@ -141,17 +148,50 @@ pub fn split_path_steps(input: &str) -> Vec<String> {
 }
 
 pub fn step_is_range(step: &str) -> bool {
-    let range_regex = Regex::new(r"^\d*(\.\.)?\d+[h'a]?$").unwrap();
+    let range_regex = Regex::new(r"^(\d*\.\.\d+:[1-9]\d*|\d*(\.\.)?\d+)[h'a]?$").unwrap();
     range_regex.is_match(step)
 }
 
+/// Whether the step is a wildcard, such as * or *h, or a wildcard with its own bound in place of
+/// the exploration depth, such as *{500}h.
 pub fn step_is_wildcard(step: &str) -> bool {
-    let wildcard_regex = Regex::new(r"^\*[h'a]?$").unwrap();
+    let wildcard_regex = Regex::new(r"^\*(\{\d+\})?[h'a]?$").unwrap();
     wildcard_regex.is_match(step)
 }
 
+/// Whether the step is a set of indices, such as {0,1,5,21} or {0,44}h.
+pub fn step_is_set(step: &str) -> bool {
+    let set_regex = Regex::new(r"^\{\d+(,\d+)*\}[h'a]?$").unwrap();
+    set_regex.is_match(step)
+}
+
+/// Whether the step is a range, wildcard or set followed by the indices and ranges it excludes,
+/// such as 0..100!{13,37..40} or *!{0}h, the hardness suffix coming last.
+pub fn step_is_exclusion(step: &str) -> bool {
+    let exclusion_regex =
+        Regex::new(r"^[\d.*:{},]+!\{\d+(\.\.\d+)?(,\d+(\.\.\d+)?)*\}[h'a]?$").unwrap();
+    exclusion_regex.is_match(step) && {
+        let (base, _) = split_exclusion_step(step);
+        step_is_wildcard(&base) || step_is_range(&base) || step_is_set(&base)
+    }
+}
+
+/// Splits an exclusion step into its base step, with the hardness suffix of the step, and the
+/// inside of its exclusion braces.
+fn split_exclusion_step(step: &str) -> (String, &str) {
+    let (base, exclusion) = step.split_once('!').unwrap();
+    let suffix = exclusion.trim_start_matches(|char| char != '}');
+    (
+        format!("{}{}", base, &suffix[1..]),
+        &exclusion[1..exclusion.len() - suffix.len()],
+    )
+}
+
 pub fn check_step_sanity(step: String) -> bool {
-    step_is_wildcard(&step) || step_is_range(&step)
+    step_is_wildcard(&step)
+        || step_is_range(&step)
+        || step_is_set(&step)
+        || step_is_exclusion(&step)
 }
 
 pub fn extract_step_hardness(step: &str) -> ExplorationStepHardness {
@@ -165,11 +205,23 @@ pub fn extract_step_hardness(step: &str) -> ExplorationStepHardness {
 pub fn translate_wildcard_step_string_to_exploration_step(
     step_string: String,
     exploration_depth: u32,
-) -> ExplorationStep {
+) -> Result<ExplorationStep, RetrieverError> {
     let hardness = extract_step_hardness(&step_string);
+    let bound_regex = Regex::new(r"\{\d+\}").unwrap();
     let start_inclusive = 0;
-    let end_inclusive = exploration_depth;
-    ExplorationStep::new(start_inclusive, end_inclusive, hardness)
+    let end_inclusive = match bound_regex.find(&step_string) {
+        Some(bound) => bound
+            .as_str()
+            .trim_matches(|char| char == '{' || char == '}')
+            .parse::<u32>()
+            .map_err(|_| RetrieverError::InvalidStepRange)?,
+        None => exploration_depth,
+    };
+    Ok(ExplorationStep::new(
+        start_inclusive,
+        end_inclusive,
+        hardness,
+    ))
 }
 
 pub fn translate_range_step_string_to_exploration_step(
@@ -180,6 +232,7 @@ pub fn translate_range_step_string_to_exploration_step(
     let point_regex = Regex::new(r"^\d+[h'a]?$").unwrap();
     let start_regex = Regex::new(r"^\d+\.\.").unwrap();
     let end_regex = Regex::new(r"\.\.\d+").unwrap();
+    let stride_regex = Regex::new(r":\d+").unwrap();
 
     let start_inclusive = match point_regex.find(&step_string) {
         Some(start) => start
@@ -233,13 +286,79 @@ pub fn translate_range_step_string_to_exploration_step(
         return Err(RetrieverError::InvalidStepRange);
     }
 
-    Ok(ExplorationStep::new(
+    let stride = match stride_regex.find(&step_string) {
+        Some(stride) => stride.as_str()[1..]
+            .parse::<u32>()
+            .map_err(|_| RetrieverError::InvalidStepRange)?,
+        None => 1,
+    };
+    if stride == 0 {
+        return Err(RetrieverError::InvalidStepRange);
+    }
+
+    Ok(ExplorationStep::new_strided(
         start_inclusive,
         end_inclusive,
+        stride,
         hardness,
     ))
 }
 
+pub fn translate_set_step_string_to_exploration_step(
+    step_string: String,
+) -> Result<ExplorationStep, RetrieverError> {
+    let hardness = extract_step_hardness(&step_string);
+    let indices = step_string
+        .trim_end_matches(['h', '\'', 'a'])
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split(',')
+        .map(|index| index.parse::<u32>())
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|_| RetrieverError::InvalidExplorationPath)?;
+    Ok(ExplorationStep::new_set(indices, hardness))
+}
+
+pub fn translate_exclusion_step_string_to_exploration_step(
+    step_string: String,
+    exploration_depth: u32,
+) -> Result<ExplorationStep, RetrieverError> {
+    let (base, exclusion) = split_exclusion_step(&step_string);
+    let base = translate_step_string_to_exploration_step(base, exploration_depth)?;
+    if base.num_indices() > STEP_EXCLUSION_MAX_INDICES {
+        error!(
+            "A step with exclusions may span at most {} indices.",
+            STEP_EXCLUSION_MAX_INDICES
+        );
+        return Err(RetrieverError::InvalidStepRange);
+    }
+    let mut excluded = vec![];
+    for excluded_range in exclusion.split(',') {
+        let (start, end) = excluded_range
+            .split_once("..")
+            .unwrap_or((excluded_range, excluded_range));
+        let start = start
+            .parse::<u32>()
+            .map_err(|_| RetrieverError::InvalidStepRange)?;
+        let end = end
+            .parse::<u32>()
+            .map_err(|_| RetrieverError::InvalidStepRange)?;
+        if end < start {
+            return Err(RetrieverError::InvalidStepRange);
+        }
+        excluded.push(start..=end);
+    }
+    let kept = base
+        .child_indices()
+        .filter(|index| !excluded.iter().any(|range| range.contains(index)))
+        .collect::<Vec<u32>>();
+    if kept.is_empty() {
+        error!("A step excludes all of its indices.");
+        return Err(RetrieverError::InvalidStepRange);
+    }
+    Ok(ExplorationStep::new_set(kept, base.get_hardness().clone()))
+}
+
 pub fn translate_step_string_to_exploration_step(
     step_string: String,
     exploration_depth: u32,
@@ -249,10 +368,11 @@ pub fn translate_step_string_to_exploration_step(
             step_string,
         )?)
     } else if step_is_wildcard(&step_string) {
-        Ok(translate_wildcard_step_string_to_exploration_step(
-            step_string,
-            exploration_depth,
-        ))
+        translate_wildcard_step_string_to_exploration_step(step_string, exploration_depth)
+    } else if step_is_set(&step_string) {
+        translate_set_step_string_to_exploration_step(step_string)
+    } else if step_is_exclusion(&step_string) {
+        translate_exclusion_step_string_to_exploration_step(step_string, exploration_depth)
     } else {
         Err(RetrieverError::InvalidExplorationPath)
     }
@@ -272,6 +392,7 @@ mod tests {
         assert!(!check_input_chars("+/*h/90/5"));
         assert!(!check_input_chars("+/+"));
         assert!(!check_input_chars("+/7"));
+        assert!(check_input_chars("{0,1,5}h/*"));
     }
 
     #[test]
@@ -302,13 +423,23 @@ mod tests {
 
     #[test]
     fn step_is_range_works_01() {
-        let range_steps = vec!["..90", "8..78", "..4h", "8..9'", "9..9a"];
+        let range_steps = [
+            "..90",
+            "8..78",
+            "..4h",
+            "8..9'",
+            "9..9a",
+            "0..100:10",
+            "..90:7h",
+            "5..5:1a",
+        ];
         range_steps
             .iter()
             .for_each(|step| assert!(step_is_range(step)));
 
-        let not_range_steps = vec![
-            "*", "*'", "*h", "*a", "p", "..*", "h..*", "*'ha", "..*h", "*ha", "89'h",
+        let not_range_steps = [
+            "*", "*'", "*h", "*a", "p", "..*", "h..*", "*'ha", "..*h", "*ha", "89'h", "8:2",
+            "0..9:", "0..9:0", "0..9h:2",
         ];
         not_range_steps
             .iter()
@@ -317,19 +448,114 @@ mod tests {
 
     #[test]
     fn step_is_wildcard_works_01() {
-        let not_wildcard_steps = vec![
-            "..90", "8..78", "..4h", "8..9'", "9..9a", "**", "..*h", "*ha", "89'h",
+        let not_wildcard_steps = [
+            "..90", "8..78", "..4h", "8..9'", "9..9a", "**", "..*h", "*ha", "89'h", "*{}",
+            "*{5,6}", "*h{5}", "*{..5}",
         ];
         not_wildcard_steps
             .iter()
             .for_each(|step| assert!(!step_is_wildcard(step)));
 
-        let wildcard_steps = vec!["*", "*'", "*h", "*a"];
+        let wildcard_steps = ["*", "*'", "*h", "*a", "*{500}", "*{0}h", "*{20}a"];
         wildcard_steps
             .iter()
             .for_each(|step| assert!(step_is_wildcard(step)));
     }
 
+    #[test]
+    fn step_is_set_works_01() {
+        let set_steps = ["{0}", "{0,1,5,21}", "{0,44}h", "{3,1}'", "{7,9}a"];
+        set_steps.iter().for_each(|step| assert!(step_is_set(step)));
+
+        let not_set_steps = [
+            "{}", "{0,}", "{,1}", "0,1", "{0..5}", "{*}", "{0}ha", "{0}{1}",
+        ];
+        not_set_steps
+            .iter()
+            .for_each(|step| assert!(!step_is_set(step)));
+    }
+
+    #[test]
+    fn translate_set_step_string_to_exploration_step_works_01() {
+        let mut result =
+            translate_set_step_string_to_exploration_step("{21,0,5,1,5}h".to_string()).unwrap();
+        assert_eq!(result.num_children(), 4);
+        assert_eq!(
+            result.by_ref().collect::<Vec<_>>(),
+            vec!["0'", "1'", "5'", "21'"]
+        );
+
+        let result = translate_set_step_string_to_exploration_step("{2,7}a".to_string()).unwrap();
+        assert_eq!(result.collect::<Vec<_>>(), vec!["2'", "7'", "2", "7"]);
+
+        let result = translate_set_step_string_to_exploration_step("{4294967296}".to_string());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn step_is_exclusion_works_01() {
+        let exclusion_steps = [
+            "0..100!{13,37..40}",
+            "*!{0}h",
+            "..9!{2}'",
+            "0..20:5!{10}a",
+            "{1,2,3}!{2}",
+            "*{50}!{7}",
+        ];
+        exclusion_steps
+            .iter()
+            .for_each(|step| assert!(step_is_exclusion(step)));
+
+        let not_exclusion_steps = [
+            "0..100!{}",
+            "0..100!{13,}",
+            "0..100h!{13}",
+            "0..100!13",
+            "!{13}",
+            "0..100!{13}!{14}",
+            "0..100!{..13}",
+            "*{5}h!{2}",
+        ];
+        not_exclusion_steps
+            .iter()
+            .for_each(|step| assert!(!step_is_exclusion(step)));
+    }
+
+    #[test]
+    fn translate_exclusion_step_string_to_exploration_step_works_01() {
+        let result =
+            translate_exclusion_step_string_to_exploration_step("0..10!{3,5..8}h".to_string(), 4)
+                .unwrap();
+        assert_eq!(
+            result.collect::<Vec<_>>(),
+            ["0'", "1'", "2'", "4'", "9'", "10'"]
+        );
+
+        let result =
+            translate_exclusion_step_string_to_exploration_step("*!{0..2}a".to_string(), 4)
+                .unwrap();
+        assert_eq!(result.collect::<Vec<_>>(), ["3'", "4'", "3", "4"]);
+
+        let result =
+            translate_exclusion_step_string_to_exploration_step("0..20:5!{10}".to_string(), 4)
+                .unwrap();
+        assert_eq!(result.collect::<Vec<_>>(), ["0", "5", "15", "20"]);
+
+        assert!(
+            translate_exclusion_step_string_to_exploration_step("0..10!{8..3}".to_string(), 4)
+                .is_err()
+        );
+        assert!(
+            translate_exclusion_step_string_to_exploration_step("0..3!{0..3}".to_string(), 4)
+                .is_err()
+        );
+        assert!(translate_exclusion_step_string_to_exploration_step(
+            "0..2000000!{0}".to_string(),
+            4
+        )
+        .is_err());
+    }
+
     #[test]
     fn extract_step_hardness_works_01() {
         assert_eq!(
@@ -349,21 +575,39 @@ mod tests {
 
     #[test]
     fn translate_wildcard_step_string_to_exploration_step_works_01() {
-        let result = translate_wildcard_step_string_to_exploration_step("*h".to_string(), 10);
+        let result =
+            translate_wildcard_step_string_to_exploration_step("*h".to_string(), 10).unwrap();
         let expected = ExplorationStep::new(0, 10, ExplorationStepHardness::Hardened);
         assert_eq!(result, expected);
 
-        let result = translate_wildcard_step_string_to_exploration_step("*'".to_string(), 10);
+        let result =
+            translate_wildcard_step_string_to_exploration_step("*'".to_string(), 10).unwrap();
         let expected = ExplorationStep::new(0, 10, ExplorationStepHardness::Hardened);
         assert_eq!(result, expected);
 
-        let result = translate_wildcard_step_string_to_exploration_step("*a".to_string(), 10);
+        let result =
+            translate_wildcard_step_string_to_exploration_step("*a".to_string(), 10).unwrap();
         let expected = ExplorationStep::new(0, 10, ExplorationStepHardness::HardenedAndNormal);
         assert_eq!(result, expected);
 
-        let result = translate_wildcard_step_string_to_exploration_step("*".to_string(), 10);
+        let result =
+            translate_wildcard_step_string_to_exploration_step("*".to_string(), 10).unwrap();
         let expected = ExplorationStep::new(0, 10, ExplorationStepHardness::Normal);
         assert_eq!(result, expected);
+
+        let result =
+            translate_wildcard_step_string_to_exploration_step("*{500}h".to_string(), 10).unwrap();
+        let expected = ExplorationStep::new(0, 500, ExplorationStepHardness::Hardened);
+        assert_eq!(result, expected);
+
+        let result =
+            translate_wildcard_step_string_to_exploration_step("*{0}".to_string(), 10).unwrap();
+        let expected = ExplorationStep::new(0, 0, ExplorationStepHardness::Normal);
+        assert_eq!(result, expected);
+
+        let result =
+            translate_wildcard_step_string_to_exploration_step("*{4294967296}".to_string(), 10);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -387,6 +631,21 @@ mod tests {
 
         let result = translate_range_step_string_to_exploration_step("9..7".to_string());
         assert!(result.is_err());
+
+        let result =
+            translate_range_step_string_to_exploration_step("0..100000:1000h".to_string()).unwrap();
+        let expected =
+            ExplorationStep::new_strided(0, 100000, 1000, ExplorationStepHardness::Hardened);
+        assert_eq!(result, expected);
+        assert_eq!(result.num_children(), 101);
+        assert_eq!(
+            result.skip(99).collect::<Vec<_>>(),
+            vec!["99000'", "100000'"]
+        );
+
+        let result =
+            translate_range_step_string_to_exploration_step("3..10:4a".to_string()).unwrap();
+        assert_eq!(result.collect::<Vec<_>>(), vec!["3'", "7'", "3", "7"]);
     }
 
     #[test]
@@ -469,6 +728,15 @@ mod tests {
 
         let exploration_path = ExplorationPath::new(None, "/8/*a/..90'/0", 5, false).unwrap();
         assert_eq!(exploration_path.num_of_paths(), 1092);
+
+        let exploration_path = ExplorationPath::new(None, "{0,1,5,21}h/*a", 5, false).unwrap();
+        assert_eq!(exploration_path.num_of_paths(), 48);
+
+        let exploration_path = ExplorationPath::new(None, "0..100000:1000/..9", 5, false).unwrap();
+        assert_eq!(exploration_path.num_of_paths(), 1010);
+
+        let exploration_path = ExplorationPath::new(None, "*{500}h/*/*{1}", 5, false).unwrap();
+        assert_eq!(exploration_path.num_of_paths(), 6012);
     }
 
     #[test]
@@ -18,6 +18,22 @@ pub struct ExplorationStep {
     end_inclusive: u32,
     hardness: ExplorationStepHardness,
     iterator_position: u32,
+    /// The indices of a set step such as {0,1,5}, in ascending order, which are explored
+    /// instead of the whole range between the first and the last of them. Empty for ranges.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    indices: Vec<u32>,
+    /// The distance between the explored indices of a range such as 0..100000:1000, which
+    /// explores 0, 1000, ..., 100000. 1 explores every index of the range.
+    #[serde(default = "unit_stride", skip_serializing_if = "is_unit_stride")]
+    stride: u32,
+}
+
+fn unit_stride() -> u32 {
+    1
+}
+
+fn is_unit_stride(stride: &u32) -> bool {
+    *stride == 1
 }
 
 impl ExplorationStep {
@@ -31,13 +47,71 @@ impl ExplorationStep {
             end_inclusive,
             hardness,
             iterator_position: 0,
+            indices: vec![],
+            stride: 1,
         }
     }
+
+    /// A step exploring every stride-th index from the start up to the end. The stride must not
+    /// be 0.
+    pub fn new_strided(
+        start_inclusive: u32,
+        end_inclusive: u32,
+        stride: u32,
+        hardness: ExplorationStepHardness,
+    ) -> Self {
+        let mut step = ExplorationStep::new(start_inclusive, end_inclusive, hardness);
+        step.stride = stride;
+        step
+    }
+
+    /// A step exploring the given indices only. They are sorted and deduplicated, and there
+    /// must be at least one.
+    pub fn new_set(mut indices: Vec<u32>, hardness: ExplorationStepHardness) -> Self {
+        indices.sort_unstable();
+        indices.dedup();
+        ExplorationStep {
+            start_inclusive: indices[0],
+            end_inclusive: indices[indices.len() - 1],
+            hardness,
+            iterator_position: 0,
+            indices,
+            stride: 1,
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        !self.indices.is_empty()
+    }
+
+    /// The number of distinct indices explored, hardened or not.
+    pub(crate) fn num_indices(&self) -> u32 {
+        if self.is_set() {
+            self.indices.len() as u32
+        } else {
+            (self.end_inclusive - self.start_inclusive) / self.stride + 1
+        }
+    }
+
+    /// The indices explored, each once whatever the hardness.
+    pub fn child_indices(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.num_indices()).map(|position| self.index_at(position))
+    }
+
+    /// The index at the given position among the indices explored.
+    fn index_at(&self, position: u32) -> u32 {
+        if self.is_set() {
+            self.indices[position as usize]
+        } else {
+            self.start_inclusive + position * self.stride
+        }
+    }
+
     pub fn num_children(&self) -> u32 {
         if self.hardness == ExplorationStepHardness::HardenedAndNormal {
-            2 * (self.end_inclusive - self.start_inclusive + 1)
+            2 * self.num_indices()
         } else {
-            self.end_inclusive - self.start_inclusive + 1
+            self.num_indices()
         }
     }
 
@@ -57,22 +131,22 @@ impl Iterator for ExplorationStep {
             match self.hardness {
                 ExplorationStepHardness::Hardened => Some(format!(
                     "{}'",
-                    self.start_inclusive + self.iterator_position
+                    self.index_at(self.iterator_position)
                 )),
                 ExplorationStepHardness::Normal => Some(format!(
                     "{}",
-                    self.start_inclusive + self.iterator_position
+                    self.index_at(self.iterator_position)
                 )),
                 ExplorationStepHardness::HardenedAndNormal => {
                     if self.iterator_position < size / 2 {
                         Some(format!(
                             "{}'",
-                            self.start_inclusive + self.iterator_position
+                            self.index_at(self.iterator_position)
                         ))
                     } else {
                         Some(format!(
                             "{}",
-                            self.start_inclusive + self.iterator_position - (size / 2)
+                            self.index_at(self.iterator_position - (size / 2))
                         ))
                     }
                 }
@@ -0,0 +1,427 @@
+use std::str::FromStr;
+
+use bitcoin::{
+    base58,
+    bip32::{ChainCode, ChildNumber, DerivationPath, Fingerprint, Xpriv, Xpub},
+    key::Secp256k1,
+    secp256k1::{All, PublicKey},
+};
+use tracing::error;
+
+use crate::error::RetrieverError;
+
+/// The versions of xpubs and tpubs.
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPUB_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+/// The versions SLIP-132 gives the extended public keys of segwit wallets (ypub, zpub, Ypub and
+/// Zpub, then their testnet forms), which Electrum shows. Nothing else tells them from xpubs.
+const SLIP132_MAINNET_VERSIONS: [[u8; 4]; 4] = [
+    [0x04, 0x9d, 0x7c, 0xb2],
+    [0x04, 0xb2, 0x47, 0x46],
+    [0x02, 0x95, 0xb4, 0x3f],
+    [0x02, 0xaa, 0x7e, 0xd3],
+];
+const SLIP132_TESTNET_VERSIONS: [[u8; 4]; 4] = [
+    [0x04, 0x4a, 0x52, 0x62],
+    [0x04, 0x5f, 0x1c, 0xf6],
+    [0x02, 0x42, 0x89, 0xef],
+    [0x02, 0x57, 0x54, 0x83],
+];
+
+/// Overwrites the private key and the chain code of the xpriv, as `Xpriv` cannot be zeroized.
+pub(crate) fn erase_xpriv(xpriv: &mut Xpriv) {
+    xpriv.private_key.non_secure_erase();
+    xpriv.chain_code = ChainCode::from([0u8; 32]);
+    std::hint::black_box(xpriv);
+}
+
+/// The key from which the explorer derives the public keys of the explored paths. The master
+/// key is erased once the last explorer sharing it is dropped.
+#[derive(Debug, Clone)]
+pub enum ExplorerKey {
+    /// A master key, from which any path can be derived.
+    Xpriv(Xpriv),
+    /// Extended public keys together with the paths they were derived at. Only the non-hardened
+    /// paths below them can be derived, and no private key is ever held.
+    Xpubs(Vec<(DerivationPath, Xpub)>),
+}
+
+impl Drop for ExplorerKey {
+    fn drop(&mut self) {
+        if let ExplorerKey::Xpriv(xpriv) = self {
+            erase_xpriv(xpriv);
+        }
+    }
+}
+
+impl ExplorerKey {
+    /// The master key, unless only xpubs are held.
+    pub fn get_master_xpriv(&self) -> Option<&Xpriv> {
+        match self {
+            ExplorerKey::Xpriv(xpriv) => Some(xpriv),
+            ExplorerKey::Xpubs(_) => None,
+        }
+    }
+
+    /// The fingerprint of the master key, or of the first xpub when only xpubs are held.
+    pub fn get_fingerprint(&self, secp: &Secp256k1<All>) -> Fingerprint {
+        match self {
+            ExplorerKey::Xpriv(xpriv) => xpriv.fingerprint(secp),
+            ExplorerKey::Xpubs(xpubs) => xpubs
+                .first()
+                .map(|(_, xpub)| xpub.fingerprint())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn is_watch_only(&self) -> bool {
+        matches!(self, ExplorerKey::Xpubs(_))
+    }
+
+    /// Derives the public key at the path. With xpubs, it is derived from the xpub with the
+    /// longest origin path the path starts with.
+    pub fn derive_public_key(
+        &self,
+        secp: &Secp256k1<All>,
+        path: &DerivationPath,
+    ) -> Result<PublicKey, RetrieverError> {
+        match self {
+            ExplorerKey::Xpriv(xpriv) => {
+                Ok(xpriv.derive_priv(secp, path)?.private_key.public_key(secp))
+            }
+            ExplorerKey::Xpubs(xpubs) => {
+                let Some((origin, xpub)) = xpubs
+                    .iter()
+                    .filter(|(origin, _)| path.as_ref().starts_with(origin.as_ref()))
+                    .max_by_key(|(origin, _)| origin.len())
+                else {
+                    error!("Path {} is not below any of the xpubs.", path);
+                    return Err(RetrieverError::PathNotBelowXpubs);
+                };
+                let relative_path = DerivationPath::from(&path.as_ref()[origin.len()..]);
+                Ok(xpub.derive_pub(secp, &relative_path)?.public_key)
+            }
+        }
+    }
+
+    /// Derives the public key at the path from the extended key of its parent when it is given,
+    /// which leaves only the last child to derive, and from the key itself otherwise.
+    pub fn derive_public_key_from_parent(
+        &self,
+        secp: &Secp256k1<All>,
+        path: &DerivationPath,
+        parent_key: Option<&ParentKey>,
+    ) -> Result<PublicKey, RetrieverError> {
+        match (parent_key, path.as_ref().last()) {
+            (Some(ParentKey::Xpriv(parent_xpriv)), Some(child)) => Ok(parent_xpriv
+                .derive_priv(secp, &[*child])?
+                .private_key
+                .public_key(secp)),
+            (Some(ParentKey::Xpub(parent_xpub)), Some(child)) => {
+                Ok(parent_xpub.ckd_pub(secp, *child)?.public_key)
+            }
+            _ => self.derive_public_key(secp, path),
+        }
+    }
+}
+
+/// The extended key of the parent of an explored path. The child of a normal step is a tweak of
+/// the parent public key, one point addition, so only a hardened step needs the xpriv.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParentKey {
+    Xpriv(Xpriv),
+    Xpub(Xpub),
+}
+
+/// The xprivs along the last derived path, so that the next path only derives the children
+/// after the prefix both share. Explored paths are generated in order, so consecutive paths
+/// mostly differ in their last step only.
+#[derive(Debug, Clone)]
+pub struct XprivCache {
+    path: Vec<ChildNumber>,
+    /// The master key followed by the xpriv at each child of the path.
+    xprivs: Vec<Xpriv>,
+    /// The xpub at the path, once asked for.
+    xpub: Option<Xpub>,
+}
+
+impl XprivCache {
+    pub fn new(master_xpriv: Xpriv) -> Self {
+        XprivCache {
+            path: vec![],
+            xprivs: vec![master_xpriv],
+            xpub: None,
+        }
+    }
+
+    /// The key the children of the path are derived from: its xpub if the child is normal, and
+    /// its xpriv if hardened.
+    pub fn derive_parent_key(
+        &mut self,
+        secp: &Secp256k1<All>,
+        path: &DerivationPath,
+        child: ChildNumber,
+    ) -> Result<ParentKey, RetrieverError> {
+        let xpriv = self.derive_xpriv(secp, path)?;
+        if child.is_hardened() {
+            return Ok(ParentKey::Xpriv(xpriv));
+        }
+        Ok(ParentKey::Xpub(
+            *self
+                .xpub
+                .get_or_insert_with(|| Xpub::from_priv(secp, &xpriv)),
+        ))
+    }
+
+    pub fn derive_xpriv(
+        &mut self,
+        secp: &Secp256k1<All>,
+        path: &DerivationPath,
+    ) -> Result<Xpriv, RetrieverError> {
+        let shared = self
+            .path
+            .iter()
+            .zip(path.as_ref())
+            .take_while(|(cached, child)| cached == child)
+            .count();
+        if shared < self.path.len() || shared < path.len() {
+            self.xpub = None;
+        }
+        self.path.truncate(shared);
+        self.xprivs
+            .iter_mut()
+            .skip(shared + 1)
+            .for_each(erase_xpriv);
+        self.xprivs.truncate(shared + 1);
+        for child in &path.as_ref()[shared..] {
+            let xpriv = self.xprivs.last().unwrap().derive_priv(secp, &[*child])?;
+            self.path.push(*child);
+            self.xprivs.push(xpriv);
+        }
+        Ok(*self.xprivs.last().unwrap())
+    }
+}
+
+impl Drop for XprivCache {
+    fn drop(&mut self) {
+        self.xprivs.iter_mut().for_each(erase_xpriv);
+    }
+}
+
+/// Parses extended public keys, given either alone or with their origin as in descriptors, e.g.
+/// "[d34db33f/84'/0'/0']xpub...". An xpub without origin sits at "m". They must be xpubs on
+/// mainnet and tpubs on the other networks, and no two may have the same origin path.
+pub fn from_input_strs_to_xpubs(
+    inputs: &[String],
+    network: bitcoin::Network,
+) -> Result<Vec<(DerivationPath, Xpub)>, RetrieverError> {
+    if inputs.is_empty() {
+        error!("No xpubs were given.");
+        return Err(RetrieverError::InvalidXpubOrigin);
+    }
+    let mut xpubs: Vec<(DerivationPath, Xpub)> = vec![];
+    for input in inputs {
+        let (origin, xpub) = from_input_str_to_xpub(input, network)?;
+        if xpubs
+            .iter()
+            .any(|(other_origin, _)| *other_origin == origin)
+        {
+            error!(
+                "Two xpubs have the origin path {}, give their origins to tell them apart.",
+                origin
+            );
+            return Err(RetrieverError::InvalidXpubOrigin);
+        }
+        xpubs.push((origin, xpub));
+    }
+    Ok(xpubs)
+}
+
+/// Parses a single extended public key, alone or with its origin, into its origin path and key.
+pub fn from_input_str_to_xpub(
+    input: &str,
+    network: bitcoin::Network,
+) -> Result<(DerivationPath, Xpub), RetrieverError> {
+    let input = input.trim();
+    let (origin, key) = match input.strip_prefix('[') {
+        Some(rest) => {
+            let Some((origin, key)) = rest.split_once(']') else {
+                error!("Invalid xpub origin: {}", input);
+                return Err(RetrieverError::InvalidXpubOrigin);
+            };
+            let (fingerprint, path) = origin.split_once('/').unwrap_or((origin, ""));
+            if fingerprint.len() != 8 || !fingerprint.chars().all(|char| char.is_ascii_hexdigit()) {
+                error!("Invalid fingerprint in xpub origin: {}", input);
+                return Err(RetrieverError::InvalidXpubOrigin);
+            }
+            let origin = DerivationPath::from_str(format!("m/{}", path).trim_end_matches('/'))
+                .map_err(|_| {
+                    error!("Invalid path in xpub origin: {}", input);
+                    RetrieverError::InvalidXpubOrigin
+                })?;
+            (origin, key)
+        }
+        None => (DerivationPath::master(), input),
+    };
+    let mut xpub = Xpub::from_str(&from_slip132_to_xpub_str(key))?;
+    if (xpub.network == bitcoin::Network::Bitcoin) != (network == bitcoin::Network::Bitcoin) {
+        error!(
+            "The extended public key is not for the {} network.",
+            network
+        );
+        return Err(RetrieverError::XpubNetworkMismatch);
+    }
+    xpub.network = network;
+    Ok((origin, xpub))
+}
+
+/// Gives a SLIP-132 key the version of an xpub (or tpub), and leaves other keys as they are.
+fn from_slip132_to_xpub_str(key: &str) -> String {
+    let Ok(mut data) = base58::decode_check(key) else {
+        return key.to_string();
+    };
+    let version = match data.get(..4) {
+        Some(version)
+            if SLIP132_MAINNET_VERSIONS
+                .iter()
+                .any(|slip132| slip132 == version) =>
+        {
+            XPUB_VERSION
+        }
+        Some(version)
+            if SLIP132_TESTNET_VERSIONS
+                .iter()
+                .any(|slip132| slip132 == version) =>
+        {
+            TPUB_VERSION
+        }
+        _ => return key.to_string(),
+    };
+    data[..4].copy_from_slice(&version);
+    base58::encode_check(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explorer_key_works_01() {
+        let secp = Secp256k1::new();
+        let master_xpriv = Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+        let account_path = DerivationPath::from_str("m/84'/0'/0'").unwrap();
+        let account_xpub = Xpub::from_priv(
+            &secp,
+            &master_xpriv.derive_priv(&secp, &account_path).unwrap(),
+        );
+        let xpubs = from_input_strs_to_xpubs(
+            &[
+                format!(
+                    "[{}/84h/0h/0h]{}",
+                    master_xpriv.fingerprint(&secp),
+                    account_xpub
+                ),
+                Xpub::from_priv(&secp, &master_xpriv).to_string(),
+            ],
+            bitcoin::Network::Bitcoin,
+        )
+        .unwrap();
+        assert_eq!(xpubs[0].0, account_path);
+        assert_eq!(xpubs[1].0, DerivationPath::master());
+        let watch_only = ExplorerKey::Xpubs(xpubs);
+        let full = ExplorerKey::Xpriv(master_xpriv);
+        for path in ["m/84'/0'/0'/1/7", "m/0/3"] {
+            let path = DerivationPath::from_str(path).unwrap();
+            assert_eq!(
+                watch_only.derive_public_key(&secp, &path).unwrap(),
+                full.derive_public_key(&secp, &path).unwrap()
+            );
+        }
+        assert!(watch_only
+            .derive_public_key(&secp, &DerivationPath::from_str("m/44'/0'/0'/0/0").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn xpriv_cache_works_01() {
+        let secp = Secp256k1::new();
+        let master_xpriv = Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+        let full = ExplorerKey::Xpriv(master_xpriv);
+        let mut cache = XprivCache::new(master_xpriv);
+        for path in [
+            "m/84'/0'/0'/0",
+            "m/84'/0'/0'/1",
+            "m/84'/0'/1'/0",
+            "m/44'/0'/0'",
+            "m",
+            "m/44'/0'/0'/0",
+        ] {
+            let path = DerivationPath::from_str(path).unwrap();
+            assert_eq!(
+                cache.derive_xpriv(&secp, &path).unwrap(),
+                master_xpriv.derive_priv(&secp, &path).unwrap()
+            );
+            for child in [
+                ChildNumber::from_normal_idx(5).unwrap(),
+                ChildNumber::from_hardened_idx(5).unwrap(),
+            ] {
+                let parent_key = cache.derive_parent_key(&secp, &path, child).unwrap();
+                assert_eq!(matches!(parent_key, ParentKey::Xpub(_)), child.is_normal());
+                assert_eq!(
+                    full.derive_public_key_from_parent(
+                        &secp,
+                        &path.child(child),
+                        Some(&parent_key)
+                    )
+                    .unwrap(),
+                    full.derive_public_key(&secp, &path.child(child)).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn explorer_key_works_02() {
+        let master_xpriv = Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+        let xpub = Xpub::from_priv(&Secp256k1::new(), &master_xpriv).to_string();
+        assert!(matches!(
+            from_input_strs_to_xpubs(&[xpub.clone(), xpub.clone()], bitcoin::Network::Bitcoin),
+            Err(RetrieverError::InvalidXpubOrigin)
+        ));
+        assert!(matches!(
+            from_input_strs_to_xpubs(std::slice::from_ref(&xpub), bitcoin::Network::Signet),
+            Err(RetrieverError::XpubNetworkMismatch)
+        ));
+        assert_eq!(
+            from_input_strs_to_xpubs(
+                &[format!("[d34db33f/84h]{}", xpub)],
+                bitcoin::Network::Bitcoin
+            )
+            .unwrap()[0]
+                .0,
+            DerivationPath::from_str("m/84'").unwrap()
+        );
+    }
+
+    #[test]
+    fn explorer_key_works_03() {
+        // The account key of BIP84's test vector, as zpub and as xpub.
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let xpub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        assert_eq!(
+            from_input_str_to_xpub(zpub, bitcoin::Network::Bitcoin).unwrap(),
+            from_input_str_to_xpub(xpub, bitcoin::Network::Bitcoin).unwrap()
+        );
+        assert_eq!(from_slip132_to_xpub_str(xpub), xpub);
+    }
+
+    #[test]
+    fn erase_xpriv_works_01() {
+        let master_xpriv = Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+        let mut erased = master_xpriv;
+        erase_xpriv(&mut erased);
+        assert_ne!(erased.private_key, master_xpriv.private_key);
+        assert_eq!(erased.chain_code, ChainCode::from([0u8; 32]));
+        assert_eq!(erased.depth, master_xpriv.depth);
+    }
+}
@@ -2,6 +2,13 @@ use getset::Getters;
 use serde::{Deserialize, Serialize};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use crate::data::defaults::{DEFAULT_BIP85_WORDS, DEFAULT_HWI_COMMAND};
+
+use super::{
+    brainwallet::BrainwalletHash, lightning::LightningNode, multisig::MultisigPreset,
+    seed_type::SeedType, timelock::TimelockTemplate,
+};
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
 #[get = "pub with_prefix"]
 pub struct ExplorerSetting {
@@ -12,6 +19,41 @@ pub struct ExplorerSetting {
     exploration_depth: u32,
     network: bitcoin::Network,
     sweep: bool,
+    seed_type: SeedType,
+    slip39_shares: Vec<String>,
+    xpriv: String,
+    xpubs: Vec<String>,
+    seed_hex: String,
+    seed_qr: String,
+    bip85_words: u32,
+    /// If set, the BIP85 child mnemonic at this index is explored instead of the seed itself.
+    bip85_index: Option<u32>,
+    /// Tells the seed apart from the others of a batch.
+    label: Option<String>,
+    /// BIP38 encrypted private keys searched alongside the paths.
+    bip38_keys: Vec<String>,
+    bip38_password: String,
+    /// A file of brainwallet passphrases searched alongside the paths, none if empty.
+    brainwallet_file: String,
+    brainwallet_hashes: Vec<BrainwalletHash>,
+    warpwallet_salt: String,
+    hwi_command: String,
+    /// The hardware wallet to fetch the xpubs from, the only connected one if empty.
+    hwi_fingerprint: String,
+    /// The xpubs of the cosigners of a multisig the explored keys belong to, none if empty.
+    multisig_cosigners: Vec<String>,
+    multisig_threshold: usize,
+    /// The miniscript policy the explored keys are searched in, none if empty.
+    miniscript_policy: String,
+    /// The wallet software the multisig follows the conventions of, if known.
+    multisig_preset: Option<MultisigPreset>,
+    /// The Green service xpub of a Green 2-of-2 wallet to recover, none if empty.
+    green_service_xpub: String,
+    green_subaccounts: Vec<u32>,
+    green_csv_blocks: Vec<u32>,
+    timelock_templates: Vec<TimelockTemplate>,
+    timelock_values: Vec<String>,
+    lightning_node: Option<LightningNode>,
 }
 
 impl Default for ExplorerSetting {
@@ -24,6 +66,32 @@ impl Default for ExplorerSetting {
             exploration_depth: Default::default(),
             network: bitcoin::Network::Bitcoin,
             sweep: Default::default(),
+            seed_type: Default::default(),
+            slip39_shares: Default::default(),
+            xpriv: Default::default(),
+            xpubs: Default::default(),
+            seed_hex: Default::default(),
+            seed_qr: Default::default(),
+            bip85_words: DEFAULT_BIP85_WORDS,
+            bip85_index: None,
+            label: None,
+            bip38_keys: Default::default(),
+            bip38_password: Default::default(),
+            brainwallet_file: Default::default(),
+            brainwallet_hashes: Default::default(),
+            warpwallet_salt: Default::default(),
+            hwi_command: DEFAULT_HWI_COMMAND.to_string(),
+            hwi_fingerprint: Default::default(),
+            multisig_cosigners: Default::default(),
+            multisig_threshold: Default::default(),
+            miniscript_policy: Default::default(),
+            multisig_preset: None,
+            green_service_xpub: Default::default(),
+            green_subaccounts: Default::default(),
+            green_csv_blocks: Default::default(),
+            timelock_templates: Default::default(),
+            timelock_values: Default::default(),
+            lightning_node: None,
         }
     }
 }
@@ -46,6 +114,184 @@ impl ExplorerSetting {
             exploration_depth,
             network,
             sweep,
+            seed_type: SeedType::default(),
+            slip39_shares: vec![],
+            xpriv: String::new(),
+            xpubs: vec![],
+            seed_hex: String::new(),
+            seed_qr: String::new(),
+            bip85_words: DEFAULT_BIP85_WORDS,
+            bip85_index: None,
+            label: None,
+            bip38_keys: Default::default(),
+            bip38_password: Default::default(),
+            brainwallet_file: Default::default(),
+            brainwallet_hashes: Default::default(),
+            warpwallet_salt: Default::default(),
+            hwi_command: DEFAULT_HWI_COMMAND.to_string(),
+            hwi_fingerprint: Default::default(),
+            multisig_cosigners: Default::default(),
+            multisig_threshold: Default::default(),
+            miniscript_policy: Default::default(),
+            multisig_preset: None,
+            green_service_xpub: Default::default(),
+            green_subaccounts: Default::default(),
+            green_csv_blocks: Default::default(),
+            timelock_templates: Default::default(),
+            timelock_values: Default::default(),
+            lightning_node: None,
+        }
+    }
+
+    pub fn with_seed_type(mut self, seed_type: SeedType) -> Self {
+        self.seed_type = seed_type;
+        self
+    }
+
+    pub fn with_slip39_shares(mut self, slip39_shares: Vec<String>) -> Self {
+        self.slip39_shares = slip39_shares;
+        self
+    }
+
+    pub fn with_xpriv(mut self, xpriv: String) -> Self {
+        self.xpriv = xpriv;
+        self
+    }
+
+    pub fn with_xpubs(mut self, xpubs: Vec<String>) -> Self {
+        self.xpubs = xpubs;
+        self
+    }
+
+    pub fn with_seed_hex(mut self, seed_hex: String) -> Self {
+        self.seed_hex = seed_hex;
+        self
+    }
+
+    pub fn with_seed_qr(mut self, seed_qr: String) -> Self {
+        self.seed_qr = seed_qr;
+        self
+    }
+
+    pub fn with_bip85_words(mut self, bip85_words: u32) -> Self {
+        self.bip85_words = bip85_words;
+        self
+    }
+
+    pub fn with_bip85_index(mut self, bip85_index: Option<u32>) -> Self {
+        self.bip85_index = bip85_index;
+        self
+    }
+
+    pub fn with_seed(mut self, mnemonic: String, passphrase: String) -> Self {
+        self.mnemonic.zeroize();
+        self.passphrase.zeroize();
+        self.mnemonic = mnemonic;
+        self.passphrase = passphrase;
+        self
+    }
+
+    pub fn with_passphrase(mut self, passphrase: String) -> Self {
+        self.passphrase.zeroize();
+        self.passphrase = passphrase;
+        self
+    }
+
+    pub fn with_label(mut self, label: Option<String>) -> Self {
+        self.label = label;
+        self
+    }
+
+    pub fn with_bip38_keys(mut self, bip38_keys: Vec<String>, bip38_password: String) -> Self {
+        self.bip38_keys.zeroize();
+        self.bip38_password.zeroize();
+        self.bip38_keys = bip38_keys;
+        self.bip38_password = bip38_password;
+        self
+    }
+
+    pub fn with_brainwallets(
+        mut self,
+        brainwallet_file: String,
+        brainwallet_hashes: Vec<BrainwalletHash>,
+        warpwallet_salt: String,
+    ) -> Self {
+        self.brainwallet_file = brainwallet_file;
+        self.brainwallet_hashes = brainwallet_hashes;
+        self.warpwallet_salt.zeroize();
+        self.warpwallet_salt = warpwallet_salt;
+        self
+    }
+
+    pub fn with_hwi(mut self, hwi_command: String, hwi_fingerprint: String) -> Self {
+        self.hwi_command = hwi_command;
+        self.hwi_fingerprint = hwi_fingerprint;
+        self
+    }
+
+    pub fn with_multisig(
+        mut self,
+        multisig_cosigners: Vec<String>,
+        multisig_threshold: usize,
+    ) -> Self {
+        self.multisig_cosigners = multisig_cosigners;
+        self.multisig_threshold = multisig_threshold;
+        self
+    }
+
+    pub fn with_miniscript_policy(mut self, miniscript_policy: String) -> Self {
+        self.miniscript_policy = miniscript_policy;
+        self
+    }
+
+    pub fn with_multisig_preset(mut self, multisig_preset: Option<MultisigPreset>) -> Self {
+        self.multisig_preset = multisig_preset;
+        self
+    }
+
+    pub fn with_green(
+        mut self,
+        green_service_xpub: String,
+        green_subaccounts: Vec<u32>,
+        green_csv_blocks: Vec<u32>,
+    ) -> Self {
+        self.green_service_xpub = green_service_xpub;
+        self.green_subaccounts = green_subaccounts;
+        self.green_csv_blocks = green_csv_blocks;
+        self
+    }
+
+    pub fn with_timelocks(
+        mut self,
+        timelock_templates: Vec<TimelockTemplate>,
+        timelock_values: Vec<String>,
+    ) -> Self {
+        self.timelock_templates = timelock_templates;
+        self.timelock_values = timelock_values;
+        self
+    }
+
+    pub fn with_lightning_node(mut self, lightning_node: Option<LightningNode>) -> Self {
+        self.lightning_node = lightning_node;
+        self
+    }
+
+    /// Drops the BIP38 keys and brainwallets, e.g. for the runs of a batch after the first one.
+    pub fn without_standalone_keys(self) -> Self {
+        let brainwallet_hashes = self.brainwallet_hashes.clone();
+        self.with_bip38_keys(vec![], String::new())
+            .with_brainwallets(String::new(), brainwallet_hashes, String::new())
+    }
+
+    /// The label of what is explored, joining the label of the seed and the BIP85 index, e.g.
+    /// seed_2_bip85_3. None when the only seed is explored itself.
+    pub fn get_run_label(&self) -> Option<String> {
+        let bip85_label = self
+            .bip85_index
+            .map(|bip85_index| format!("bip85_{}", bip85_index));
+        match (self.label.clone(), bip85_label) {
+            (Some(label), Some(bip85_label)) => Some(format!("{}_{}", label, bip85_label)),
+            (label, bip85_label) => label.or(bip85_label),
         }
     }
 }
@@ -59,6 +305,32 @@ impl Zeroize for ExplorerSetting {
         self.exploration_depth.zeroize();
         self.network = bitcoin::Network::Regtest;
         self.sweep.zeroize();
+        self.seed_type = SeedType::default();
+        self.slip39_shares.zeroize();
+        self.xpriv.zeroize();
+        self.xpubs.zeroize();
+        self.seed_hex.zeroize();
+        self.seed_qr.zeroize();
+        self.bip85_words = DEFAULT_BIP85_WORDS;
+        self.bip85_index = None;
+        self.label.zeroize();
+        self.bip38_keys.zeroize();
+        self.bip38_password.zeroize();
+        self.brainwallet_file.zeroize();
+        self.brainwallet_hashes.clear();
+        self.warpwallet_salt.zeroize();
+        self.hwi_command = DEFAULT_HWI_COMMAND.to_string();
+        self.hwi_fingerprint.zeroize();
+        self.multisig_cosigners.zeroize();
+        self.multisig_threshold.zeroize();
+        self.miniscript_policy.zeroize();
+        self.multisig_preset = None;
+        self.green_service_xpub.zeroize();
+        self.green_subaccounts.zeroize();
+        self.green_csv_blocks.zeroize();
+        self.timelock_templates.clear();
+        self.timelock_values.zeroize();
+        self.lightning_node = None;
     }
 }
 
@@ -0,0 +1,208 @@
+use std::str::FromStr;
+
+use bitcoin::{
+    bip32::{ChildNumber, DerivationPath, Xpriv, Xpub},
+    hashes::{hmac, sha512, Hash, HashEngine},
+    key::Secp256k1,
+    PublicKey,
+};
+use miniscript::{Descriptor, Miniscript, Segwitv0};
+use tracing::error;
+
+use crate::error::RetrieverError;
+
+use super::{auxiliaries::SECP, explorer_key::from_input_str_to_xpub};
+
+/// The key of the HMAC giving the path of a wallet below the Green service key.
+const GAIT_PATH_HMAC_KEY: &[u8] = b"GreenAddress.it HD wallet path";
+/// The hardened child of the master key whose xpub the path of a wallet is computed from.
+const GAIT_PATH_CHILD: u32 = 0x4741;
+/// The CSV timeouts Green lets its wallets choose from, in blocks, on mainnet and elsewhere.
+pub const GREEN_MAINNET_CSV_BLOCKS: [u32; 3] = [25_920, 51_840, 65_535];
+pub const GREEN_TESTNET_CSV_BLOCKS: [u32; 3] = [144, 4_320, 51_840];
+/// The addresses of a subaccount are the children of its base path.
+pub const GREEN_EXPLORATION_PATH: &str = "*";
+
+/// The path of a Green wallet below the service key, 32 children of 16 bits each from the
+/// HMAC-SHA512 of the chain code and key of the wallet's xpub at m/18241'.
+fn get_gait_path(master_xpriv: &Xpriv) -> Result<Vec<ChildNumber>, RetrieverError> {
+    let secp = Secp256k1::new();
+    let xpub = Xpub::from_priv(
+        &secp,
+        &master_xpriv.derive_priv(&secp, &[ChildNumber::from_hardened_idx(GAIT_PATH_CHILD)?])?,
+    );
+    let mut engine = hmac::HmacEngine::<sha512::Hash>::new(GAIT_PATH_HMAC_KEY);
+    engine.input(xpub.chain_code.as_bytes());
+    engine.input(&xpub.public_key.serialize());
+    hmac::Hmac::<sha512::Hash>::from_engine(engine)
+        .to_byte_array()
+        .chunks(2)
+        .map(|pair| {
+            Ok(ChildNumber::from_normal_idx(
+                u16::from_be_bytes([pair[0], pair[1]]) as u32,
+            )?)
+        })
+        .collect()
+}
+
+/// A Green 2-of-2 wallet, each of whose addresses needs the key of the user and a key of the
+/// Green service. The user keys of subaccount 0 sit at m/1/i and those of subaccount n at
+/// m/3'/n'/1/i, while the service keys are derived from the service xpub along the path of the
+/// wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenWallet {
+    /// The base path of the user keys of each subaccount, with the service xpub of the
+    /// subaccount.
+    subaccounts: Vec<(DerivationPath, Xpub)>,
+    csv_blocks: Vec<u32>,
+}
+
+impl GreenWallet {
+    pub fn new(
+        service_xpub: &str,
+        master_xpriv: &Xpriv,
+        subaccounts: &[u32],
+        csv_blocks: &[u32],
+        network: bitcoin::Network,
+    ) -> Result<Self, RetrieverError> {
+        let (_, service_xpub) = from_input_str_to_xpub(service_xpub, network)?;
+        if subaccounts.is_empty()
+            || csv_blocks
+                .iter()
+                .any(|blocks| *blocks == 0 || *blocks > u16::MAX as u32)
+        {
+            error!("Green recovery needs at least one subaccount and CSV timeouts of at most 65535 blocks.");
+            return Err(RetrieverError::InvalidGreenWallet);
+        }
+        let secp = Secp256k1::new();
+        let gait_path = get_gait_path(master_xpriv)?;
+        let mut green_subaccounts = vec![];
+        for subaccount in subaccounts {
+            let (user_path, service_path) = match subaccount {
+                0 => (
+                    DerivationPath::from_str("m/1")?,
+                    DerivationPath::from(vec![ChildNumber::from_normal_idx(1)?]).extend(&gait_path),
+                ),
+                _ => (
+                    DerivationPath::from_str(&format!("m/3'/{}'/1", subaccount))?,
+                    DerivationPath::from(vec![ChildNumber::from_normal_idx(3)?])
+                        .extend(&gait_path)
+                        .extend([ChildNumber::from_normal_idx(*subaccount)?]),
+                ),
+            };
+            green_subaccounts.push((user_path, service_xpub.derive_pub(&secp, &service_path)?));
+        }
+        Ok(GreenWallet {
+            subaccounts: green_subaccounts,
+            csv_blocks: csv_blocks.to_vec(),
+        })
+    }
+
+    /// The base paths of the user keys of the subaccounts.
+    pub fn get_base_paths(subaccounts: &[u32]) -> Vec<String> {
+        subaccounts
+            .iter()
+            .map(|subaccount| match subaccount {
+                0 => "m/1".to_string(),
+                _ => format!("m/3'/{}'/1", subaccount),
+            })
+            .collect()
+    }
+
+    /// The scripts of the user key at the path: the 2-of-2 multisig of the service key and the
+    /// user key, in p2sh and p2sh-p2wsh, and for each CSV timeout the p2sh-p2wsh script letting
+    /// the user spend alone once it passed. Paths in none of the subaccounts have none.
+    pub fn get_descriptors(
+        &self,
+        key: PublicKey,
+        path: &DerivationPath,
+    ) -> Result<Vec<Descriptor<PublicKey>>, RetrieverError> {
+        let Some((user_path, service_xpub)) = self.subaccounts.iter().find(|(user_path, _)| {
+            path.len() == user_path.len() + 1 && path.as_ref().starts_with(user_path.as_ref())
+        }) else {
+            return Ok(vec![]);
+        };
+        let pointer = DerivationPath::from(&path.as_ref()[user_path.len()..]);
+        let service_key = PublicKey::new(service_xpub.derive_pub(&SECP, &pointer)?.public_key);
+        let mut descriptors = vec![Descriptor::new_sh(Miniscript::from_ast(
+            miniscript::Terminal::Multi(2, vec![service_key, key]),
+        )?)?];
+        if !key.compressed {
+            return Ok(descriptors);
+        }
+        descriptors.push(Descriptor::new_sh_wsh(Miniscript::from_ast(
+            miniscript::Terminal::Multi(2, vec![service_key, key]),
+        )?)?);
+        for blocks in self.csv_blocks.iter() {
+            let csv = Miniscript::<PublicKey, Segwitv0>::from_str(&format!(
+                "and_v(v:pk({}),or_d(pk({}),older({})))",
+                key, service_key, blocks
+            ))?;
+            descriptors.push(Descriptor::new_sh_wsh(csv)?);
+        }
+        Ok(descriptors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn green_works_01() {
+        let secp = Secp256k1::new();
+        let master_xpriv = Xpriv::new_master(bitcoin::Network::Bitcoin, &[1u8; 64]).unwrap();
+        let service_xpriv = Xpriv::new_master(bitcoin::Network::Bitcoin, &[2u8; 64]).unwrap();
+        let service_xpub = Xpub::from_priv(&secp, &service_xpriv);
+        let gait_path = get_gait_path(&master_xpriv).unwrap();
+        assert_eq!(gait_path.len(), 32);
+        let green = GreenWallet::new(
+            &service_xpub.to_string(),
+            &master_xpriv,
+            &[0, 2],
+            &[51_840],
+            bitcoin::Network::Bitcoin,
+        )
+        .unwrap();
+        assert_eq!(
+            GreenWallet::get_base_paths(&[0, 2]),
+            vec!["m/1", "m/3'/2'/1"]
+        );
+        let path = DerivationPath::from_str("m/3'/2'/1/9").unwrap();
+        let key = PublicKey::new(
+            master_xpriv
+                .derive_priv(&secp, &path)
+                .unwrap()
+                .to_keypair(&secp)
+                .public_key(),
+        );
+        let service_path = DerivationPath::from(vec![ChildNumber::from_normal_idx(3).unwrap()])
+            .extend(&gait_path)
+            .extend([
+                ChildNumber::from_normal_idx(2).unwrap(),
+                ChildNumber::from_normal_idx(9).unwrap(),
+            ]);
+        let service_key = service_xpriv
+            .derive_priv(&secp, &service_path)
+            .unwrap()
+            .to_keypair(&secp)
+            .public_key();
+        let descriptors = green.get_descriptors(key, &path).unwrap();
+        assert_eq!(descriptors.len(), 3);
+        assert_eq!(
+            descriptors[1].to_string().split('#').next().unwrap(),
+            format!("sh(wsh(multi(2,{},{})))", service_key, key)
+        );
+        assert_eq!(
+            descriptors[2].explicit_script().unwrap().to_asm_string(),
+            format!(
+                "OP_PUSHBYTES_33 {} OP_CHECKSIGVERIFY OP_PUSHBYTES_33 {} OP_CHECKSIG OP_IFDUP OP_NOTIF OP_PUSHBYTES_3 80ca00 OP_CSV OP_ENDIF",
+                key, service_key
+            )
+        );
+        assert!(green
+            .get_descriptors(key, &DerivationPath::from_str("m/3'/1'/1/9").unwrap())
+            .unwrap()
+            .is_empty());
+    }
+}
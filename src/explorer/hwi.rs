@@ -0,0 +1,176 @@
+use std::{process::Command, str::FromStr};
+
+use bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
+use bitcoincore_rpc::jsonrpc::serde_json::{self, Value};
+use tracing::{error, info};
+
+use crate::error::RetrieverError;
+
+use super::key_source::KeySource;
+
+/// A hardware wallet reached through the HWI command line tool
+/// (https://github.com/bitcoin-core/HWI), which must be installed and the device unlocked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HwiKeySource {
+    command: String,
+    fingerprint: Fingerprint,
+    network: bitcoin::Network,
+}
+
+impl HwiKeySource {
+    /// Connects to the device with the fingerprint, or to the only connected device if none is
+    /// given.
+    pub fn new(
+        command: &str,
+        fingerprint: Option<&str>,
+        network: bitcoin::Network,
+    ) -> Result<Self, RetrieverError> {
+        let fingerprint = match fingerprint {
+            Some(fingerprint) => Fingerprint::from_str(fingerprint).map_err(|_| {
+                error!("Invalid hardware wallet fingerprint: {}", fingerprint);
+                RetrieverError::HwiError
+            })?,
+            None => {
+                let output = run_hwi(command, &["enumerate"])?;
+                from_enumerate_output_to_fingerprint(&output)?
+            }
+        };
+        info!(
+            "Using the hardware wallet with fingerprint {}.",
+            fingerprint
+        );
+        Ok(HwiKeySource {
+            command: command.to_string(),
+            fingerprint,
+            network,
+        })
+    }
+
+    fn chain(&self) -> &'static str {
+        match self.network {
+            bitcoin::Network::Bitcoin => "main",
+            bitcoin::Network::Signet => "signet",
+            bitcoin::Network::Regtest => "regtest",
+            _ => "test",
+        }
+    }
+}
+
+impl KeySource for HwiKeySource {
+    fn get_fingerprint(&self) -> Result<Fingerprint, RetrieverError> {
+        Ok(self.fingerprint)
+    }
+
+    fn get_xpub(&self, path: &DerivationPath) -> Result<Xpub, RetrieverError> {
+        let fingerprint = self.fingerprint.to_string();
+        let path = path.to_string();
+        let output = run_hwi(
+            &self.command,
+            &[
+                "--chain",
+                self.chain(),
+                "-f",
+                &fingerprint,
+                "getxpub",
+                &path,
+            ],
+        )?;
+        from_getxpub_output_to_xpub(&output)
+    }
+}
+
+/// Runs HWI with the arguments and returns what it printed.
+fn run_hwi(command: &str, args: &[&str]) -> Result<String, RetrieverError> {
+    let output = Command::new(command)
+        .args(args)
+        .output()
+        .inspect_err(|err| error!("Running {} failed: {}", command, err))?;
+    if !output.status.success() && output.stdout.is_empty() {
+        error!(
+            "HWI failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return Err(RetrieverError::HwiError);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Fails with the error HWI reported in its json output, if any.
+fn check_hwi_error(value: &Value) -> Result<(), RetrieverError> {
+    if let Some(hwi_error) = value.get("error") {
+        error!("HWI returned an error: {}", hwi_error);
+        return Err(RetrieverError::HwiError);
+    }
+    Ok(())
+}
+
+fn from_enumerate_output_to_fingerprint(output: &str) -> Result<Fingerprint, RetrieverError> {
+    let devices: Value = serde_json::from_str(output)?;
+    check_hwi_error(&devices)?;
+    let devices = devices
+        .as_array()
+        .map(|devices| {
+            devices
+                .iter()
+                .filter(|device| check_hwi_error(device).is_ok())
+                .filter_map(|device| device["fingerprint"].as_str())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    match devices.as_slice() {
+        [fingerprint] => Fingerprint::from_str(fingerprint).map_err(|_| {
+            error!("HWI returned an invalid fingerprint: {}", fingerprint);
+            RetrieverError::HwiError
+        }),
+        [] => {
+            error!("No unlocked hardware wallet is connected.");
+            Err(RetrieverError::HwiError)
+        }
+        _ => {
+            error!(
+                "Several hardware wallets are connected ({}), choose one with hwi_fingerprint.",
+                devices.join(", ")
+            );
+            Err(RetrieverError::HwiError)
+        }
+    }
+}
+
+fn from_getxpub_output_to_xpub(output: &str) -> Result<Xpub, RetrieverError> {
+    let value: Value = serde_json::from_str(output)?;
+    check_hwi_error(&value)?;
+    let Some(xpub) = value["xpub"].as_str() else {
+        error!("HWI returned no xpub: {}", output.trim());
+        return Err(RetrieverError::HwiError);
+    };
+    Ok(Xpub::from_str(xpub)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hwi_works_01() {
+        assert_eq!(
+            from_enumerate_output_to_fingerprint(
+                r#"[{"type": "trezor", "path": "webusb:001:4", "needs_pin_sent": false, "needs_passphrase_sent": false, "fingerprint": "73c5da0a"}]"#
+            )
+            .unwrap(),
+            Fingerprint::from_str("73c5da0a").unwrap()
+        );
+        assert!(from_enumerate_output_to_fingerprint("[]").is_err());
+        assert!(from_enumerate_output_to_fingerprint(
+            r#"[{"fingerprint": "73c5da0a"}, {"fingerprint": "d34db33f"}]"#
+        )
+        .is_err());
+        assert!(from_getxpub_output_to_xpub(
+            r#"{"error": "Could not open client or get fingerprint information", "code": -7}"#
+        )
+        .is_err());
+        assert!(from_getxpub_output_to_xpub(
+            r#"{"xpub": "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V"}"#
+        )
+        .is_ok());
+    }
+}
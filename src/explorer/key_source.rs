@@ -0,0 +1,86 @@
+use std::str::FromStr;
+
+use bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
+use tracing::{error, info};
+
+use crate::error::RetrieverError;
+
+/// Hands out extended public keys without ever revealing a private key, e.g. a hardware wallet.
+pub trait KeySource {
+    /// The fingerprint of the master key, which the origins of the xpubs carry.
+    fn get_fingerprint(&self) -> Result<Fingerprint, RetrieverError>;
+    /// The extended public key at the path.
+    fn get_xpub(&self, path: &DerivationPath) -> Result<Xpub, RetrieverError>;
+}
+
+/// Fetches the xpub of each base path from the key source and writes it with its origin, e.g.
+/// "[73c5da0a/84'/0'/0']xpub...", as the xpubs of watch-only explorers are given.
+pub fn from_key_source_to_xpubs(
+    key_source: &impl KeySource,
+    base_paths: &[String],
+) -> Result<Vec<String>, RetrieverError> {
+    let fingerprint = key_source.get_fingerprint()?;
+    let mut xpubs = vec![];
+    for base_path in base_paths {
+        let path = DerivationPath::from_str(base_path).inspect_err(|_| {
+            error!("Invalid base derivation path: {}", base_path);
+        })?;
+        let xpub = key_source.get_xpub(&path)?;
+        let origin = path.to_string();
+        let origin = origin.trim_start_matches('m').trim_start_matches('/');
+        xpubs.push(if origin.is_empty() {
+            format!("[{}]{}", fingerprint, xpub)
+        } else {
+            format!("[{}/{}]{}", fingerprint, origin, xpub)
+        });
+    }
+    info!(
+        "{} xpubs fetched from the key source of {}.",
+        xpubs.len(),
+        fingerprint
+    );
+    Ok(xpubs)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{bip32::Xpriv, key::Secp256k1};
+
+    use crate::explorer::explorer_key::from_input_strs_to_xpubs;
+
+    use super::*;
+
+    struct XprivKeySource(Xpriv);
+
+    impl KeySource for XprivKeySource {
+        fn get_fingerprint(&self) -> Result<Fingerprint, RetrieverError> {
+            Ok(self.0.fingerprint(&Secp256k1::new()))
+        }
+
+        fn get_xpub(&self, path: &DerivationPath) -> Result<Xpub, RetrieverError> {
+            let secp = Secp256k1::new();
+            Ok(Xpub::from_priv(&secp, &self.0.derive_priv(&secp, path)?))
+        }
+    }
+
+    #[test]
+    fn key_source_works_01() {
+        let key_source = XprivKeySource(Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap());
+        let xpubs =
+            from_key_source_to_xpubs(&key_source, &["m/84'/0'/0'".to_string(), "m".to_string()])
+                .unwrap();
+        assert!(xpubs[0].starts_with(&format!(
+            "[{}/84'/0'/0']xpub",
+            key_source.get_fingerprint().unwrap()
+        )));
+        let xpubs = from_input_strs_to_xpubs(&xpubs, bitcoin::Network::Bitcoin).unwrap();
+        assert_eq!(xpubs[0].0, DerivationPath::from_str("m/84'/0'/0'").unwrap());
+        assert_eq!(xpubs[1].0, DerivationPath::master());
+        assert_eq!(
+            xpubs[0].1,
+            key_source
+                .get_xpub(&DerivationPath::from_str("m/84'/0'/0'").unwrap())
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use bitcoin::{
+    bip32::{ChildNumber, DerivationPath, Xpriv},
+    hashes::{hmac, sha256, Hash, HashEngine},
+    PublicKey,
+};
+use miniscript::{Descriptor, Miniscript, Segwitv0};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use zeroize::Zeroize;
+
+use crate::error::RetrieverError;
+
+use super::seed_type::SeedType;
+
+/// The purpose under which LND derives the keys of its channels, in key families.
+const LND_KEY_PURPOSE: u32 = 1017;
+/// The LND key family of the 2-of-2 funding keys of channels, which the anchor outputs pay to.
+const LND_MULTISIG_KEY_FAMILY: u32 = 0;
+/// The LND key family of the payment base points, which to_remote outputs pay to.
+const LND_PAYMENT_BASE_KEY_FAMILY: u32 = 3;
+/// The info of the HKDF turning the hsm_secret of a Core Lightning node into its bip32 seed.
+const CLN_BIP32_SEED_INFO: &[u8] = b"bip32 seed";
+/// The keys of a Lightning node's channels and wallet are the children of its base paths.
+pub const LIGHTNING_EXPLORATION_PATH: &str = "*";
+
+/// The Lightning node implementation whose seed is explored.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum LightningNode {
+    /// LND, whose root key is derived from the aezeed, e.g. by `chantools showrootkey`.
+    Lnd,
+    /// Core Lightning, whose wallet keys are derived from the hsm_secret.
+    Cln,
+}
+
+impl LightningNode {
+    /// The paths the keys the node closes channels to sit under: the funding keys and payment
+    /// base points for LND, and the on-chain wallet for Core Lightning, which sweeps the outputs
+    /// of closed channels to it. The coin type of LND is 1 off mainnet.
+    pub fn get_base_paths(&self, network: bitcoin::Network) -> Vec<String> {
+        let coin_type = match network {
+            bitcoin::Network::Bitcoin => 0,
+            _ => 1,
+        };
+        match self {
+            LightningNode::Lnd => [LND_MULTISIG_KEY_FAMILY, LND_PAYMENT_BASE_KEY_FAMILY]
+                .iter()
+                .map(|family| format!("m/{}'/{}'/{}'/0", LND_KEY_PURPOSE, coin_type, family))
+                .collect(),
+            LightningNode::Cln => vec!["m/0/0".to_string()],
+        }
+    }
+
+    /// The seed type the node's backup is given as.
+    pub fn get_seed_type(&self) -> SeedType {
+        match self {
+            LightningNode::Lnd => SeedType::Xpriv,
+            LightningNode::Cln => SeedType::ClnHsmSecret,
+        }
+    }
+
+    /// The channel scripts of the key at the path, for LND keys of its key families: the anchor
+    /// output of a funding key, <key> OP_CHECKSIG OP_IFDUP OP_NOTIF 16 OP_CSV OP_ENDIF, and the
+    /// to_remote output of a payment base point in anchor channels, <key> OP_CHECKSIGVERIFY 1
+    /// OP_CSV. The p2wpkh to_remote output of static remote key channels is found as any wpkh
+    /// descriptor. Core Lightning keys have none.
+    pub fn get_descriptors(
+        &self,
+        key: PublicKey,
+        path: &DerivationPath,
+    ) -> Result<Vec<Descriptor<PublicKey>>, RetrieverError> {
+        if *self != LightningNode::Lnd || !key.compressed || path.len() != 5 {
+            return Ok(vec![]);
+        }
+        let family = match (path[0], path[2], path[3]) {
+            (
+                ChildNumber::Hardened {
+                    index: LND_KEY_PURPOSE,
+                },
+                ChildNumber::Hardened { index: family },
+                ChildNumber::Normal { index: 0 },
+            ) => family,
+            _ => return Ok(vec![]),
+        };
+        let miniscript = match family {
+            LND_MULTISIG_KEY_FAMILY => format!("or_d(pk({}),older(16))", key),
+            LND_PAYMENT_BASE_KEY_FAMILY => format!("and_v(v:pk({}),older(1))", key),
+            _ => return Ok(vec![]),
+        };
+        // Anyone can spend an anchor output after 16 blocks, which miniscript deems insane.
+        Ok(vec![Descriptor::new_wsh(
+            Miniscript::<PublicKey, Segwitv0>::from_str_insane(&miniscript)?,
+        )?])
+    }
+}
+
+/// The first 32 bytes of the HKDF-SHA256 (RFC 5869) of the input key material.
+fn hkdf_sha256(salt: &[u8], ikm: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut extract = hmac::HmacEngine::<sha256::Hash>::new(salt);
+    extract.input(ikm);
+    let mut prk = hmac::Hmac::<sha256::Hash>::from_engine(extract).to_byte_array();
+    let mut expand = hmac::HmacEngine::<sha256::Hash>::new(&prk);
+    expand.input(info);
+    expand.input(&[1]);
+    prk.zeroize();
+    hmac::Hmac::<sha256::Hash>::from_engine(expand).to_byte_array()
+}
+
+/// Turns the 32 byte hsm_secret of a Core Lightning node, in hex, into its master key, whose
+/// bip32 seed is the HKDF of the secret with a salt counting the seeds tried from 0.
+pub fn from_cln_hsm_secret_to_master_xpriv(
+    input: &str,
+    network: bitcoin::Network,
+) -> Result<Xpriv, RetrieverError> {
+    let mut hsm_secret = hex::decode(input.trim()).map_err(|_| {
+        error!("The hsm_secret is not valid hex.");
+        RetrieverError::InvalidSeedHex
+    })?;
+    if hsm_secret.len() != 32 {
+        error!(
+            "The hsm_secret has {} bytes, but must have 32. Encrypted hsm_secrets must be decrypted first.",
+            hsm_secret.len()
+        );
+        hsm_secret.zeroize();
+        return Err(RetrieverError::InvalidSeedHex);
+    }
+    let mut salt = 0u32;
+    let master_xpriv = loop {
+        let mut seed = hkdf_sha256(&salt.to_le_bytes(), &hsm_secret, CLN_BIP32_SEED_INFO);
+        let master_xpriv = Xpriv::new_master(network, &seed);
+        seed.zeroize();
+        match master_xpriv {
+            Ok(master_xpriv) => break master_xpriv,
+            Err(_) => salt += 1,
+        }
+    };
+    hsm_secret.zeroize();
+    Ok(master_xpriv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::key::Secp256k1;
+
+    use super::*;
+
+    #[test]
+    fn lightning_works_01() {
+        // RFC 5869, test case 1.
+        assert_eq!(
+            hex::encode(hkdf_sha256(
+                &hex::decode("000102030405060708090a0b0c").unwrap(),
+                &[0x0b; 22],
+                &hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap()
+            )),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf"
+        );
+        assert!(
+            from_cln_hsm_secret_to_master_xpriv(&"11".repeat(32), bitcoin::Network::Bitcoin)
+                .is_ok()
+        );
+        assert!(matches!(
+            from_cln_hsm_secret_to_master_xpriv(&"11".repeat(31), bitcoin::Network::Bitcoin),
+            Err(RetrieverError::InvalidSeedHex)
+        ));
+    }
+
+    #[test]
+    fn lightning_works_02() {
+        let secp = Secp256k1::new();
+        let key = PublicKey::new(
+            Xpriv::new_master(bitcoin::Network::Bitcoin, &[1u8; 64])
+                .unwrap()
+                .to_keypair(&secp)
+                .public_key(),
+        );
+        assert_eq!(
+            LightningNode::Lnd.get_base_paths(bitcoin::Network::Bitcoin),
+            vec!["m/1017'/0'/0'/0", "m/1017'/0'/3'/0"]
+        );
+        let anchor = LightningNode::Lnd
+            .get_descriptors(key, &DerivationPath::from_str("m/1017'/0'/0'/0/4").unwrap())
+            .unwrap();
+        assert_eq!(
+            anchor[0].explicit_script().unwrap().to_asm_string(),
+            format!(
+                "OP_PUSHBYTES_33 {} OP_CHECKSIG OP_IFDUP OP_NOTIF OP_PUSHNUM_16 OP_CSV OP_ENDIF",
+                key
+            )
+        );
+        let to_remote = LightningNode::Lnd
+            .get_descriptors(key, &DerivationPath::from_str("m/1017'/0'/3'/0/4").unwrap())
+            .unwrap();
+        assert_eq!(
+            to_remote[0].explicit_script().unwrap().to_asm_string(),
+            format!(
+                "OP_PUSHBYTES_33 {} OP_CHECKSIGVERIFY OP_PUSHNUM_1 OP_CSV",
+                key
+            )
+        );
+        assert!(LightningNode::Lnd
+            .get_descriptors(key, &DerivationPath::from_str("m/1017'/0'/1'/0/4").unwrap())
+            .unwrap()
+            .is_empty());
+        assert!(LightningNode::Cln
+            .get_descriptors(key, &DerivationPath::from_str("m/0/0/4").unwrap())
+            .unwrap()
+            .is_empty());
+    }
+}
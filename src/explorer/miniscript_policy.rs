@@ -0,0 +1,203 @@
+use std::str::FromStr;
+
+use bitcoin::{
+    bip32::{DerivationPath, Xpub},
+    hashes::{hash160, ripemd160, sha256},
+    PublicKey,
+};
+use miniscript::{hash256, policy::Concrete, Descriptor, Segwitv0, TranslatePk, Translator};
+use tracing::error;
+
+use crate::error::RetrieverError;
+
+use super::{auxiliaries::SECP, explorer_key::from_input_str_to_xpub};
+
+/// Stands for the explored key in a policy.
+pub const EXPLORED_KEY: &str = "key";
+
+/// A key of a policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PolicyKey {
+    /// The explored key.
+    Explored,
+    /// A public key, the same at every path.
+    Fixed(PublicKey),
+    /// An xpub, whose key is derived at the explored path below its base path.
+    Xpub(Xpub),
+}
+
+/// A miniscript spending policy the explored keys are searched in. It is compiled once with the
+/// keys as written, and its descriptors get the keys of each explored path substituted, which
+/// yields the same scripts as compiling it for each key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiniscriptPolicy {
+    descriptors: Vec<Descriptor<String>>,
+    keys: Vec<(String, PolicyKey)>,
+}
+
+impl MiniscriptPolicy {
+    /// Compiles the policy, e.g. "or(pk(key),and(pk(xpub...),older(52560)))", into wsh and
+    /// sh(wsh) descriptors. The explored key is written as `key`, the other keys as hex public
+    /// keys or as xpubs, alone or with their origin.
+    pub fn new(policy: &str, network: bitcoin::Network) -> Result<Self, RetrieverError> {
+        let policy = Concrete::<String>::from_str(policy.trim()).map_err(|err| {
+            error!("Invalid miniscript policy: {}", err);
+            RetrieverError::InvalidMiniscriptPolicy
+        })?;
+        let mut keys = vec![];
+        for key in policy.keys() {
+            let policy_key = if key == EXPLORED_KEY {
+                PolicyKey::Explored
+            } else if let Ok(public_key) = PublicKey::from_str(key) {
+                PolicyKey::Fixed(public_key)
+            } else {
+                PolicyKey::Xpub(from_input_str_to_xpub(key, network)?.1)
+            };
+            keys.push((key.to_owned(), policy_key));
+        }
+        if !keys.iter().any(|(_, key)| *key == PolicyKey::Explored) {
+            error!(
+                "The miniscript policy must hold the explored key, written as {}.",
+                EXPLORED_KEY
+            );
+            return Err(RetrieverError::InvalidMiniscriptPolicy);
+        }
+        let miniscript = policy.compile::<Segwitv0>().map_err(|err| {
+            error!("Compiling the miniscript policy failed: {}", err);
+            RetrieverError::InvalidMiniscriptPolicy
+        })?;
+        Ok(MiniscriptPolicy {
+            descriptors: vec![
+                Descriptor::new_wsh(miniscript.clone())?,
+                Descriptor::new_sh_wsh(miniscript)?,
+            ],
+            keys,
+        })
+    }
+
+    /// Whether the policy holds xpubs, whose keys can only be derived at non-hardened paths.
+    pub fn has_xpubs(&self) -> bool {
+        self.keys
+            .iter()
+            .any(|(_, key)| matches!(key, PolicyKey::Xpub(_)))
+    }
+
+    /// The descriptors of the policy with the key and the keys of the xpubs at the relative path,
+    /// which is the explored path below its base path. Uncompressed keys have none, as the policy
+    /// is compiled for segwit.
+    pub fn get_descriptors(
+        &self,
+        key: PublicKey,
+        relative_path: &DerivationPath,
+    ) -> Result<Vec<Descriptor<PublicKey>>, RetrieverError> {
+        if !key.compressed {
+            return Ok(vec![]);
+        }
+        let mut translator = PolicyKeyTranslator { keys: vec![] };
+        for (name, policy_key) in self.keys.iter() {
+            let public_key = match policy_key {
+                PolicyKey::Explored => key,
+                PolicyKey::Fixed(public_key) => *public_key,
+                PolicyKey::Xpub(xpub) => {
+                    PublicKey::new(xpub.derive_pub(&SECP, relative_path)?.public_key)
+                }
+            };
+            translator.keys.push((name.as_str(), public_key));
+        }
+        self.descriptors
+            .iter()
+            .map(|descriptor| {
+                descriptor.translate_pk(&mut translator).map_err(|_| {
+                    error!("Substituting the keys of the miniscript policy failed.");
+                    RetrieverError::InvalidMiniscriptPolicy
+                })
+            })
+            .collect()
+    }
+}
+
+/// Puts the keys of a path in place of the keys of the policy as written.
+struct PolicyKeyTranslator<'a> {
+    keys: Vec<(&'a str, PublicKey)>,
+}
+
+impl Translator<String, PublicKey, RetrieverError> for PolicyKeyTranslator<'_> {
+    fn pk(&mut self, pk: &String) -> Result<PublicKey, RetrieverError> {
+        self.keys
+            .iter()
+            .find(|(name, _)| name == pk)
+            .map(|(_, public_key)| *public_key)
+            .ok_or(RetrieverError::InvalidMiniscriptPolicy)
+    }
+
+    fn sha256(&mut self, sha256: &String) -> Result<sha256::Hash, RetrieverError> {
+        sha256::Hash::from_str(sha256).map_err(|_| RetrieverError::InvalidMiniscriptPolicy)
+    }
+
+    fn hash256(&mut self, hash256: &String) -> Result<hash256::Hash, RetrieverError> {
+        hash256::Hash::from_str(hash256).map_err(|_| RetrieverError::InvalidMiniscriptPolicy)
+    }
+
+    fn ripemd160(&mut self, ripemd160: &String) -> Result<ripemd160::Hash, RetrieverError> {
+        ripemd160::Hash::from_str(ripemd160).map_err(|_| RetrieverError::InvalidMiniscriptPolicy)
+    }
+
+    fn hash160(&mut self, hash160: &String) -> Result<hash160::Hash, RetrieverError> {
+        hash160::Hash::from_str(hash160).map_err(|_| RetrieverError::InvalidMiniscriptPolicy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{bip32::Xpriv, key::Secp256k1};
+
+    use super::*;
+
+    #[test]
+    fn miniscript_policy_works_01() {
+        let secp = Secp256k1::new();
+        let recovery = Xpub::from_priv(
+            &secp,
+            &Xpriv::new_master(bitcoin::Network::Bitcoin, &[2u8; 64]).unwrap(),
+        );
+        let policy = MiniscriptPolicy::new(
+            &format!("or(99@pk(key),1@and(pk({}),older(52560)))", recovery),
+            bitcoin::Network::Bitcoin,
+        )
+        .unwrap();
+        assert!(policy.has_xpubs());
+        let key = PublicKey::new(
+            Xpriv::new_master(bitcoin::Network::Bitcoin, &[1u8; 64])
+                .unwrap()
+                .to_keypair(&secp)
+                .public_key(),
+        );
+        let relative_path = DerivationPath::from_str("m/0/7").unwrap();
+        let descriptors = policy.get_descriptors(key, &relative_path).unwrap();
+        let recovery_key = recovery
+            .derive_pub(&secp, &relative_path)
+            .unwrap()
+            .public_key;
+        assert_eq!(
+            descriptors[0].to_string().split('#').next().unwrap(),
+            format!(
+                "wsh(or_d(pk({}),and_v(v:pkh({}),older(52560))))",
+                key, recovery_key
+            )
+        );
+        assert!(descriptors[1].to_string().starts_with("sh(wsh(or_d(pk("));
+        assert!(policy
+            .get_descriptors(PublicKey::new_uncompressed(key.inner), &relative_path)
+            .unwrap()
+            .is_empty());
+        assert!(MiniscriptPolicy::new("pk(02abc)", bitcoin::Network::Bitcoin).is_err());
+        assert!(matches!(
+            MiniscriptPolicy::new(&format!("pk({})", recovery), bitcoin::Network::Bitcoin),
+            Err(RetrieverError::InvalidMiniscriptPolicy)
+        ));
+        assert!(matches!(
+            MiniscriptPolicy::new("and(pk(key),", bitcoin::Network::Bitcoin),
+            Err(RetrieverError::InvalidMiniscriptPolicy)
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+use bip39::{Language, Mnemonic};
+use itertools::Itertools;
+use tracing::error;
+use zeroize::Zeroize;
+
+use crate::error::RetrieverError;
+
+/// Stands for a word of the mnemonic that is not known.
+pub const UNKNOWN_WORD: &str = "?";
+/// Each unknown word multiplies the candidates by 2048, two already make over four million.
+pub const MAX_UNKNOWN_WORDS: usize = 2;
+const MNEMONIC_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+pub fn has_unknown_words(mnemonic: &str) -> bool {
+    mnemonic.split_whitespace().any(|word| word == UNKNOWN_WORD)
+}
+
+/// Fills the unknown words of the BIP39 mnemonic with every word of the English list and yields
+/// the candidates whose checksum holds, together with the words filled in. The candidates are
+/// made as they are asked for, so the whole set is never held in memory.
+pub fn from_mnemonic_with_unknown_words_to_candidates(
+    mnemonic: &str,
+) -> Result<impl Iterator<Item = (Vec<&'static str>, String)>, RetrieverError> {
+    let mut words = mnemonic
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    let unknown_positions = words
+        .iter()
+        .positions(|word| word == UNKNOWN_WORD)
+        .collect::<Vec<_>>();
+    let word_list = Language::English.word_list();
+    let invalid_word = words
+        .iter()
+        .position(|word| word != UNKNOWN_WORD && Language::English.find_word(word).is_none());
+    if unknown_positions.is_empty()
+        || unknown_positions.len() > MAX_UNKNOWN_WORDS
+        || !MNEMONIC_WORD_COUNTS.contains(&words.len())
+        || invalid_word.is_some()
+    {
+        words.zeroize();
+        error!(
+            "A mnemonic with unknown words needs 12, 15, 18, 21 or 24 words of the English BIP39 list, of which 1 to {} are marked with {}.",
+            MAX_UNKNOWN_WORDS, UNKNOWN_WORD
+        );
+        return Err(RetrieverError::InvalidUnknownWords);
+    }
+    Ok(unknown_positions
+        .iter()
+        .map(|_| word_list.iter().copied())
+        .multi_cartesian_product()
+        .filter_map(move |filling| {
+            for (position, word) in unknown_positions.iter().zip(&filling) {
+                words[*position].zeroize();
+                words[*position] = word.to_string();
+            }
+            let mut candidate = words.join(" ");
+            if Mnemonic::parse_in_normalized(Language::English, &candidate).is_ok() {
+                Some((filling, candidate))
+            } else {
+                candidate.zeroize();
+                None
+            }
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_words_works_01() {
+        let candidates = from_mnemonic_with_unknown_words_to_candidates(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon ?",
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+        // 7 bits of the last word are entropy, its 4 others are the checksum.
+        assert_eq!(candidates.len(), 128);
+        assert!(candidates.contains(&(
+            vec!["about"],
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+                .to_string()
+        )));
+        let candidates = from_mnemonic_with_unknown_words_to_candidates(
+            "? ? abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        )
+        .unwrap();
+        assert!(candidates
+            .map(|(filling, _)| filling)
+            .any(|filling| filling == vec!["abandon", "abandon"]));
+    }
+
+    #[test]
+    fn missing_words_works_02() {
+        for mnemonic in [
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "? ? ? abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon ?",
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon notaword ?",
+        ] {
+            assert!(matches!(
+                from_mnemonic_with_unknown_words_to_candidates(mnemonic),
+                Err(RetrieverError::InvalidUnknownWords)
+            ));
+        }
+        assert!(has_unknown_words("abandon ? about"));
+        assert!(!has_unknown_words("abandon about?"));
+    }
+}
@@ -1,40 +1,104 @@
 pub mod auxiliaries;
+pub mod bip38;
+pub mod bip85;
+pub mod brainwallet;
+pub mod electrum;
+pub mod electrum_v1;
 pub mod exploration_path;
 pub mod exploration_step;
+pub mod explorer_key;
 pub mod explorer_setting;
+pub mod green;
+pub mod hwi;
+pub mod key_source;
+pub mod lightning;
+pub mod miniscript_policy;
+pub mod missing_words;
+pub mod multisig;
+pub mod sanity;
+pub mod scrypt;
+pub mod seed_qr;
+pub mod seed_type;
+pub mod slip39;
+pub mod timelock;
+pub mod typo_correction;
+pub mod word_order;
 
 use std::sync::Arc;
 
-use bitcoin::bip32::Xpriv;
+use bitcoin::{bip32::Xpriv, key::Secp256k1, PublicKey};
 use getset::Getters;
 
-use tracing::info;
+use tracing::{error, info};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::{
     error::RetrieverError,
     explorer::auxiliaries::{
-        from_input_str_to_mnemonic, from_mnemonic_to_seed, from_seed_to_master_xpriv,
+        from_hex_to_seed, from_input_str_to_mnemonic, from_input_str_to_xpriv,
+        from_mnemonic_to_seed, from_seed_to_master_xpriv,
     },
 };
 
-use self::{exploration_path::ExplorationPath, explorer_setting::ExplorerSetting};
+use self::{
+    bip38::from_bip38_to_private_key,
+    bip85::from_master_xpriv_to_bip85_mnemonic,
+    brainwallet::{from_passphrase_to_brainwallet_key, read_brainwallet_candidates},
+    electrum::from_electrum_seed_to_seed,
+    electrum_v1::{derive_electrum_v1_public_key, from_electrum_v1_seed_to_master_key},
+    exploration_path::ExplorationPath,
+    exploration_step::ExplorationStepHardness,
+    explorer_key::{from_input_strs_to_xpubs, ExplorerKey},
+    explorer_setting::ExplorerSetting,
+    green::GreenWallet,
+    hwi::HwiKeySource,
+    key_source::from_key_source_to_xpubs,
+    lightning::{from_cln_hsm_secret_to_master_xpriv, LightningNode},
+    miniscript_policy::MiniscriptPolicy,
+    multisig::Multisig,
+    seed_qr::from_seed_qr_to_mnemonic,
+    seed_type::SeedType,
+    slip39::from_slip39_shares_to_master_secret,
+    timelock::Timelocks,
+};
 
 /// a data structure to capture the set of self-sufficient data for scanning certain paths.
 #[derive(Debug, Clone, Getters)]
 #[get = "pub with_prefix"]
 pub struct Explorer {
-    master_xpriv: Arc<Xpriv>,
+    key: Arc<ExplorerKey>,
     exploration_path: Arc<ExplorationPath>,
+    /// Prefixes the files written for the finds, see `ExplorerSetting::get_run_label`.
+    label: Option<String>,
+    /// Keys that belong to no path, e.g. decrypted BIP38 keys or brainwallets, labelled after
+    /// where they come from. Their finds are at path m.
+    standalone_keys: Arc<Vec<(String, PublicKey)>>,
+    /// The multisig the explored keys are searched in besides their single key descriptors.
+    multisig: Option<Arc<Multisig>>,
+    /// The miniscript policy the explored keys are searched in, compiled with each of them.
+    miniscript_policy: Option<Arc<MiniscriptPolicy>>,
+    /// The Green 2-of-2 wallet the explored keys are searched in.
+    green: Option<Arc<GreenWallet>>,
+    /// The timelocked scripts the explored keys are searched in.
+    timelocks: Option<Arc<Timelocks>>,
+    /// The Lightning node whose channel scripts the explored keys are searched in.
+    lightning_node: Option<LightningNode>,
 }
 
 impl Default for Explorer {
     fn default() -> Self {
         Self {
-            master_xpriv: Arc::new(
+            key: Arc::new(ExplorerKey::Xpriv(
                 Xpriv::new_master(bitcoin::Network::Bitcoin, &[0u8; 64]).unwrap(),
-            ),
+            )),
             exploration_path: Default::default(),
+            label: None,
+            standalone_keys: Default::default(),
+            multisig: None,
+            miniscript_policy: None,
+            green: None,
+            timelocks: None,
+            lightning_node: None,
         }
     }
 }
@@ -42,35 +106,437 @@ impl Default for Explorer {
 impl Explorer {
     pub fn new(setting: ExplorerSetting) -> Result<Self, RetrieverError> {
         info!("Creation of explorer started.");
+        if matches!(setting.get_seed_type(), SeedType::Xpubs | SeedType::Hwi) {
+            return Self::new_watch_only(setting);
+        }
+        if *setting.get_seed_type() == SeedType::ElectrumV1 {
+            return Self::new_electrum_v1(setting);
+        }
         let exploration_path = ExplorationPath::new(
             Some(setting.get_base_derivation_paths().to_owned()),
             setting.get_exploration_path(),
             *setting.get_exploration_depth(),
             setting.get_sweep().to_owned(),
         )?;
-        let mut mnemonic = from_input_str_to_mnemonic(setting.get_mnemonic())?;
-        let mut seed = from_mnemonic_to_seed(mnemonic.clone(), setting.get_passphrase());
-        mnemonic.zeroize();
-        let master_xpriv = from_seed_to_master_xpriv(seed, *setting.get_network())?;
-        seed.zeroize();
+        let mut master_xpriv = match setting.get_seed_type() {
+            SeedType::Bip39 => {
+                let mut mnemonic = from_input_str_to_mnemonic(setting.get_mnemonic())?;
+                let mut seed = from_mnemonic_to_seed(mnemonic.clone(), setting.get_passphrase());
+                mnemonic.zeroize();
+                let master_xpriv = from_seed_to_master_xpriv(seed, *setting.get_network());
+                seed.zeroize();
+                master_xpriv?
+            }
+            SeedType::Electrum => {
+                let mut seed =
+                    from_electrum_seed_to_seed(setting.get_mnemonic(), setting.get_passphrase())?;
+                let master_xpriv = from_seed_to_master_xpriv(seed, *setting.get_network());
+                seed.zeroize();
+                master_xpriv?
+            }
+            SeedType::Slip39 => {
+                let mut master_secret = from_slip39_shares_to_master_secret(
+                    setting.get_slip39_shares(),
+                    setting.get_passphrase(),
+                )?;
+                let master_xpriv = Xpriv::new_master(*setting.get_network(), &master_secret);
+                master_secret.zeroize();
+                master_xpriv?
+            }
+            SeedType::Xpriv => {
+                from_input_str_to_xpriv(setting.get_xpriv(), *setting.get_network())?
+            }
+            SeedType::Hex => {
+                let mut seed = from_hex_to_seed(setting.get_seed_hex(), setting.get_passphrase())?;
+                let master_xpriv = from_seed_to_master_xpriv(seed, *setting.get_network());
+                seed.zeroize();
+                master_xpriv?
+            }
+            SeedType::SeedQr => {
+                let mut mnemonic = from_seed_qr_to_mnemonic(setting.get_seed_qr())?;
+                let mut seed = from_mnemonic_to_seed(mnemonic.clone(), setting.get_passphrase());
+                mnemonic.zeroize();
+                let master_xpriv = from_seed_to_master_xpriv(seed, *setting.get_network());
+                seed.zeroize();
+                master_xpriv?
+            }
+            SeedType::ClnHsmSecret => {
+                from_cln_hsm_secret_to_master_xpriv(setting.get_seed_hex(), *setting.get_network())?
+            }
+            SeedType::Xpubs | SeedType::Hwi | SeedType::ElectrumV1 => {
+                unreachable!("explorers without a master xpriv are created separately")
+            }
+        };
+        if let Some(bip85_index) = setting.get_bip85_index() {
+            info!(
+                "Exploring the {} word BIP85 child mnemonic at index {}.",
+                setting.get_bip85_words(),
+                bip85_index
+            );
+            let mut mnemonic = from_master_xpriv_to_bip85_mnemonic(
+                &master_xpriv,
+                *setting.get_bip85_words(),
+                *bip85_index,
+            )?;
+            let mut seed = from_mnemonic_to_seed(mnemonic.clone(), "");
+            mnemonic.zeroize();
+            master_xpriv = from_seed_to_master_xpriv(seed, *setting.get_network())?;
+            seed.zeroize();
+        }
+        let multisig = get_multisig(&setting, &exploration_path)?;
+        let miniscript_policy = get_miniscript_policy(&setting, &exploration_path)?;
+        let green = get_green(&setting, &master_xpriv)?;
         info!("Creation of explorer finished successfully.");
         Ok(Explorer {
-            master_xpriv: Arc::new(master_xpriv),
+            key: Arc::new(ExplorerKey::Xpriv(master_xpriv)),
+            exploration_path: Arc::new(exploration_path),
+            label: setting.get_run_label(),
+            standalone_keys: Arc::new(get_standalone_keys(&setting)?),
+            multisig,
+            miniscript_policy,
+            green,
+            timelocks: get_timelocks(&setting)?,
+            lightning_node: *setting.get_lightning_node(),
+        })
+    }
+
+    /// The explorer of the same keys and scripts exploring another path, without deriving the key
+    /// again. The path must be non-hardened below xpubs, multisig cosigners or xpubs in the
+    /// miniscript policy, as for a new explorer, and below xpubs its base paths stay their origins.
+    pub fn with_exploration_path(
+        &self,
+        exploration_path: ExplorationPath,
+    ) -> Result<Self, RetrieverError> {
+        let hardened = exploration_path
+            .get_explore()
+            .iter()
+            .any(|step| *step.get_hardness() != ExplorationStepHardness::Normal);
+        if hardened && matches!(*self.key, ExplorerKey::Xpubs(_)) {
+            error!("Only non-hardened exploration paths can be explored below xpubs.");
+            return Err(RetrieverError::HardenedPathWatchOnly);
+        }
+        if matches!(*self.key, ExplorerKey::Xpubs(_))
+            && exploration_path.get_base_paths() != self.exploration_path.get_base_paths()
+        {
+            error!("The base paths below xpubs are their origins and cannot be replaced.");
+            return Err(RetrieverError::UnavailableWatchOnly);
+        }
+        if hardened && self.multisig.is_some() {
+            error!("Only non-hardened exploration paths can be explored with multisig cosigners.");
+            return Err(RetrieverError::InvalidMultisig);
+        }
+        if hardened
+            && self
+                .miniscript_policy
+                .as_ref()
+                .is_some_and(|miniscript_policy| miniscript_policy.has_xpubs())
+        {
+            error!("Only non-hardened exploration paths can be explored with xpubs in the miniscript policy.");
+            return Err(RetrieverError::InvalidMiniscriptPolicy);
+        }
+        Ok(Explorer {
+            exploration_path: Arc::new(exploration_path),
+            ..self.clone()
+        })
+    }
+
+    /// Creates an explorer holding only xpubs, whose origin paths replace the base derivation
+    /// paths. The exploration path must be non-hardened, as nothing else can be derived. With
+    /// HWI, the xpubs of the base derivation paths are fetched from the hardware wallet.
+    fn new_watch_only(setting: ExplorerSetting) -> Result<Self, RetrieverError> {
+        if setting.get_bip85_index().is_some() {
+            error!("BIP85 child mnemonics cannot be derived from xpubs.");
+            return Err(RetrieverError::UnavailableWatchOnly);
+        }
+        if !setting.get_green_service_xpub().is_empty() {
+            error!(
+                "The path of a Green wallet below the service key cannot be derived from xpubs."
+            );
+            return Err(RetrieverError::InvalidGreenWallet);
+        }
+        let xpubs = match setting.get_seed_type() {
+            SeedType::Hwi => {
+                let hwi = HwiKeySource::new(
+                    setting.get_hwi_command(),
+                    Some(setting.get_hwi_fingerprint().as_str())
+                        .filter(|fingerprint| !fingerprint.is_empty()),
+                    *setting.get_network(),
+                )?;
+                from_key_source_to_xpubs(&hwi, setting.get_base_derivation_paths())?
+            }
+            _ => setting.get_xpubs().to_owned(),
+        };
+        let xpubs = from_input_strs_to_xpubs(&xpubs, *setting.get_network())?;
+        let exploration_path = ExplorationPath::new(
+            Some(xpubs.iter().map(|(origin, _)| origin.to_string()).collect()),
+            setting.get_exploration_path(),
+            *setting.get_exploration_depth(),
+            setting.get_sweep().to_owned(),
+        )?;
+        if exploration_path
+            .get_explore()
+            .iter()
+            .any(|step| *step.get_hardness() != ExplorationStepHardness::Normal)
+        {
+            error!("Only non-hardened exploration paths can be explored below xpubs.");
+            return Err(RetrieverError::HardenedPathWatchOnly);
+        }
+        let multisig = get_multisig(&setting, &exploration_path)?;
+        let miniscript_policy = get_miniscript_policy(&setting, &exploration_path)?;
+        info!(
+            "Creation of watch-only explorer for {} xpubs finished successfully.",
+            xpubs.len()
+        );
+        Ok(Explorer {
+            key: Arc::new(ExplorerKey::Xpubs(xpubs)),
             exploration_path: Arc::new(exploration_path),
+            label: setting.get_run_label(),
+            standalone_keys: Arc::new(get_standalone_keys(&setting)?),
+            multisig,
+            miniscript_policy,
+            green: None,
+            timelocks: get_timelocks(&setting)?,
+            lightning_node: *setting.get_lightning_node(),
+        })
+    }
+
+    /// Creates an explorer for an Electrum v1 seed, whose keys are no bip32 keys. Its receiving
+    /// and change keys up to the exploration depth are searched as standalone keys, and no path
+    /// is explored.
+    fn new_electrum_v1(setting: ExplorerSetting) -> Result<Self, RetrieverError> {
+        if setting.get_bip85_index().is_some() {
+            error!("BIP85 child mnemonics cannot be derived from Electrum v1 seeds.");
+            return Err(RetrieverError::UnavailableWatchOnly);
+        }
+        if !setting.get_green_service_xpub().is_empty() {
+            error!("Green wallets cannot be recovered from Electrum v1 seeds.");
+            return Err(RetrieverError::InvalidGreenWallet);
+        }
+        let secp = Secp256k1::new();
+        let master_key = from_electrum_v1_seed_to_master_key(setting.get_mnemonic())?;
+        let master_public_key = master_key.public_key(&secp);
+        let mut standalone_keys = vec![];
+        for chain in 0..2 {
+            for index in 0..=*setting.get_exploration_depth() {
+                standalone_keys.push((
+                    format!("electrum_v1_{}_{}", chain, index),
+                    derive_electrum_v1_public_key(&master_public_key, chain, index)?,
+                ));
+            }
+        }
+        standalone_keys.extend(get_standalone_keys(&setting)?);
+        info!(
+            "Creation of explorer for the Electrum v1 seed finished successfully, {} receiving and change keys are searched.",
+            2 * (*setting.get_exploration_depth() + 1)
+        );
+        Ok(Explorer {
+            key: Arc::new(ExplorerKey::Xpubs(vec![])),
+            exploration_path: Default::default(),
+            label: setting.get_run_label(),
+            standalone_keys: Arc::new(standalone_keys),
+            multisig: None,
+            miniscript_policy: None,
+            green: None,
+            timelocks: None,
+            lightning_node: None,
         })
     }
 }
 
+/// The multisig of the cosigners of the setting, if any. Their keys are derived at the explored
+/// paths below the base paths, which must thus be non-hardened.
+fn get_multisig(
+    setting: &ExplorerSetting,
+    exploration_path: &ExplorationPath,
+) -> Result<Option<Arc<Multisig>>, RetrieverError> {
+    if setting.get_multisig_cosigners().is_empty() {
+        if setting.get_multisig_preset().is_some() {
+            error!("A multisig preset needs the xpubs of the cosigners.");
+            return Err(RetrieverError::InvalidMultisig);
+        }
+        return Ok(None);
+    }
+    if exploration_path
+        .get_explore()
+        .iter()
+        .any(|step| *step.get_hardness() != ExplorationStepHardness::Normal)
+    {
+        error!("Only non-hardened exploration paths can be explored with multisig cosigners.");
+        return Err(RetrieverError::InvalidMultisig);
+    }
+    let multisig = Multisig::new(
+        *setting.get_multisig_threshold(),
+        setting.get_multisig_cosigners(),
+        *setting.get_network(),
+    )?
+    .with_script(
+        setting
+            .get_multisig_preset()
+            .map(|multisig_preset| multisig_preset.get_script()),
+    );
+    info!(
+        "The explored keys are also searched in {}-of-{} multisigs.",
+        setting.get_multisig_threshold(),
+        setting.get_multisig_cosigners().len() + 1
+    );
+    Ok(Some(Arc::new(multisig)))
+}
+
+/// The compiled miniscript policy of the setting, if any. Like the keys of multisig cosigners,
+/// the keys of its xpubs need non-hardened explored paths.
+fn get_miniscript_policy(
+    setting: &ExplorerSetting,
+    exploration_path: &ExplorationPath,
+) -> Result<Option<Arc<MiniscriptPolicy>>, RetrieverError> {
+    if setting.get_miniscript_policy().is_empty() {
+        return Ok(None);
+    }
+    let miniscript_policy =
+        MiniscriptPolicy::new(setting.get_miniscript_policy(), *setting.get_network())?;
+    if miniscript_policy.has_xpubs()
+        && exploration_path
+            .get_explore()
+            .iter()
+            .any(|step| *step.get_hardness() != ExplorationStepHardness::Normal)
+    {
+        error!("Only non-hardened exploration paths can be explored with xpubs in the miniscript policy.");
+        return Err(RetrieverError::InvalidMiniscriptPolicy);
+    }
+    info!("The explored keys are also searched in the miniscript policy.");
+    Ok(Some(Arc::new(miniscript_policy)))
+}
+
+/// The Green 2-of-2 wallet of the service xpub of the setting, if any, whose service keys sit
+/// along a path computed from the master key.
+fn get_green(
+    setting: &ExplorerSetting,
+    master_xpriv: &Xpriv,
+) -> Result<Option<Arc<GreenWallet>>, RetrieverError> {
+    if setting.get_green_service_xpub().is_empty() {
+        return Ok(None);
+    }
+    let green = GreenWallet::new(
+        setting.get_green_service_xpub(),
+        master_xpriv,
+        setting.get_green_subaccounts(),
+        setting.get_green_csv_blocks(),
+        *setting.get_network(),
+    )?;
+    info!(
+        "The explored keys are also searched in the scripts of {} Green subaccounts.",
+        setting.get_green_subaccounts().len()
+    );
+    Ok(Some(Arc::new(green)))
+}
+
+/// The timelocked scripts of the templates of the setting, if any. They hold only the explored
+/// key, so any path can be explored.
+fn get_timelocks(setting: &ExplorerSetting) -> Result<Option<Arc<Timelocks>>, RetrieverError> {
+    if setting.get_timelock_templates().is_empty() {
+        return Ok(None);
+    }
+    let timelocks = Timelocks::new(
+        setting.get_timelock_templates(),
+        setting.get_timelock_values(),
+    )?;
+    info!(
+        "The explored keys are also searched in {} timelocked scripts.",
+        timelocks.len()
+    );
+    Ok(Some(Arc::new(timelocks)))
+}
+
+/// The public keys of the BIP38 keys and brainwallet candidates of the setting. Brainwallets
+/// are tried both compressed and uncompressed with each of the hashes.
+fn get_standalone_keys(
+    setting: &ExplorerSetting,
+) -> Result<Vec<(String, PublicKey)>, RetrieverError> {
+    let secp = Secp256k1::new();
+    let mut standalone_keys = vec![];
+    for (index, bip38_key) in setting.get_bip38_keys().iter().enumerate() {
+        let private_key = from_bip38_to_private_key(
+            bip38_key,
+            setting.get_bip38_password(),
+            *setting.get_network(),
+        )?;
+        standalone_keys.push((
+            format!("bip38_{}", index + 1),
+            private_key.public_key(&secp),
+        ));
+    }
+    if !setting.get_brainwallet_file().is_empty() {
+        let mut candidates = read_brainwallet_candidates(setting.get_brainwallet_file())?;
+        for (line, candidate) in candidates.iter().enumerate() {
+            for hash in setting.get_brainwallet_hashes() {
+                let secret_key = from_passphrase_to_brainwallet_key(
+                    candidate,
+                    *hash,
+                    setting.get_warpwallet_salt(),
+                )?;
+                let label = format!("brainwallet_{}_{}", line + 1, hash.label());
+                let public_key = secret_key.public_key(&secp);
+                standalone_keys.push((label.clone(), PublicKey::new(public_key)));
+                standalone_keys.push((label, PublicKey::new_uncompressed(public_key)));
+            }
+        }
+        candidates.zeroize();
+    }
+    if !standalone_keys.is_empty() {
+        info!("{} standalone keys to search.", standalone_keys.len());
+    }
+    Ok(standalone_keys)
+}
+
 impl Zeroize for Explorer {
     fn zeroize(&mut self) {
         info!("Zeroizing explorer initialized.");
-        self.master_xpriv =
-            Arc::new(Xpriv::new_master(bitcoin::Network::Bitcoin, &[0u8; 64]).unwrap());
+        self.key = Arc::new(ExplorerKey::Xpriv(
+            Xpriv::new_master(bitcoin::Network::Bitcoin, &[0u8; 64]).unwrap(),
+        ));
         self.exploration_path = Arc::new(ExplorationPath::new(None, "*a/*a", 10, false).unwrap());
+        self.label = None;
+        self.standalone_keys = Default::default();
+        self.multisig = None;
+        self.miniscript_policy = None;
+        self.green = None;
+        self.timelocks = None;
+        self.lightning_node = None;
     }
 }
 
 impl ZeroizeOnDrop for Explorer {}
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_exploration_path_works_01() {
+        let explorer = Explorer::new(ExplorerSetting::new(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".to_string(),
+            String::new(),
+            vec!["m/84h/0h/0h".to_string()],
+            "*a/*a".to_string(),
+            10,
+            bitcoin::Network::Bitcoin,
+            false,
+        ))
+        .unwrap();
+        let narrowed = explorer
+            .with_exploration_path(ExplorationPath::new(None, "0/*", 3, false).unwrap())
+            .unwrap();
+        assert_eq!(
+            narrowed.get_key().get_fingerprint(&auxiliaries::SECP),
+            explorer.get_key().get_fingerprint(&auxiliaries::SECP)
+        );
+        assert_eq!(narrowed.get_exploration_path().size(), 4);
+        let watch_only = Explorer {
+            key: Arc::new(ExplorerKey::Xpubs(vec![])),
+            ..explorer
+        };
+        assert!(matches!(
+            watch_only.with_exploration_path(ExplorationPath::new(None, "0h/*", 3, false).unwrap()),
+            Err(RetrieverError::HardenedPathWatchOnly)
+        ));
+    }
+}
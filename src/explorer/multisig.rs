@@ -0,0 +1,289 @@
+use std::str::FromStr;
+
+use bitcoin::{
+    bip32::{DerivationPath, Xpub},
+    PublicKey,
+};
+use miniscript::{Descriptor, Miniscript, Terminal};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::error::RetrieverError;
+
+use super::{auxiliaries::SECP, explorer_key::from_input_str_to_xpub, seed_type::SeedType};
+
+/// The most keys a multisig may have, as legacy p2sh scripts cannot hold more.
+const MAX_MULTISIG_KEYS: usize = 15;
+/// The most keys of the bare multisig outputs bitcoincore relays.
+const MAX_BARE_MULTISIG_KEYS: usize = 3;
+/// Electrum puts the receiving and change addresses of every wallet at 0/i and 1/i.
+pub const ELECTRUM_EXPLORATION_PATH: &str = "..1/*";
+
+/// The script a multisig wallet locks its coins in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MultisigScript {
+    /// Legacy p2sh.
+    P2sh,
+    /// P2wsh nested in p2sh.
+    P2shP2wsh,
+    /// Native segwit p2wsh.
+    P2wsh,
+}
+
+/// The conventions of the multisig wallets of a known wallet software: where each cosigner's
+/// key sits, and the script it is used in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MultisigPreset {
+    /// Electrum multisig from a standard Electrum seed, its key at m, in p2sh.
+    ElectrumStandard,
+    /// Electrum multisig from a segwit Electrum seed, its key at m/1', in p2wsh.
+    ElectrumSegwit,
+    /// Electrum multisig from a BIP39 seed in p2sh, its key at m/45'/0.
+    ElectrumBip39P2sh,
+    /// Electrum multisig from a BIP39 seed in p2sh-p2wsh, its key at m/48'/0'/0'/1'.
+    ElectrumBip39P2shP2wsh,
+    /// Electrum multisig from a BIP39 seed in p2wsh, its key at m/48'/0'/0'/2'.
+    ElectrumBip39P2wsh,
+}
+
+impl MultisigPreset {
+    /// The path of the key of each cosigner, the coin type being 1 off mainnet.
+    pub fn get_base_path(&self, network: bitcoin::Network) -> DerivationPath {
+        let coin_type = match network {
+            bitcoin::Network::Bitcoin => 0,
+            _ => 1,
+        };
+        let path = match self {
+            MultisigPreset::ElectrumStandard => "m".to_string(),
+            MultisigPreset::ElectrumSegwit => "m/1'".to_string(),
+            MultisigPreset::ElectrumBip39P2sh => "m/45'/0".to_string(),
+            MultisigPreset::ElectrumBip39P2shP2wsh => format!("m/48'/{}'/0'/1'", coin_type),
+            MultisigPreset::ElectrumBip39P2wsh => format!("m/48'/{}'/0'/2'", coin_type),
+        };
+        DerivationPath::from_str(&path).unwrap()
+    }
+
+    /// Electrum always sorts the keys of its multisig scripts.
+    pub fn get_script(&self) -> MultisigScript {
+        match self {
+            MultisigPreset::ElectrumStandard | MultisigPreset::ElectrumBip39P2sh => {
+                MultisigScript::P2sh
+            }
+            MultisigPreset::ElectrumBip39P2shP2wsh => MultisigScript::P2shP2wsh,
+            MultisigPreset::ElectrumSegwit | MultisigPreset::ElectrumBip39P2wsh => {
+                MultisigScript::P2wsh
+            }
+        }
+    }
+
+    pub fn get_seed_type(&self) -> SeedType {
+        match self {
+            MultisigPreset::ElectrumStandard | MultisigPreset::ElectrumSegwit => SeedType::Electrum,
+            _ => SeedType::Bip39,
+        }
+    }
+
+    pub fn get_exploration_path(&self) -> &'static str {
+        ELECTRUM_EXPLORATION_PATH
+    }
+}
+
+/// A multisig wallet in which the explored key is one of the keys and the others are derived from
+/// the xpubs of its cosigners.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Multisig {
+    threshold: usize,
+    cosigners: Vec<Xpub>,
+    /// The only script searched, with sorted keys, if the wallet software is known.
+    script: Option<MultisigScript>,
+}
+
+impl Multisig {
+    /// Parses the cosigner xpubs, given alone or with their origin. The threshold counts all the
+    /// keys, the explored one included.
+    pub fn new(
+        threshold: usize,
+        cosigners: &[String],
+        network: bitcoin::Network,
+    ) -> Result<Self, RetrieverError> {
+        let cosigners = cosigners
+            .iter()
+            .map(|cosigner| Ok(from_input_str_to_xpub(cosigner, network)?.1))
+            .collect::<Result<Vec<_>, RetrieverError>>()?;
+        let keys = cosigners.len() + 1;
+        if cosigners.is_empty() || keys > MAX_MULTISIG_KEYS || threshold == 0 || threshold > keys {
+            error!(
+                "A multisig needs 1 to {} cosigners and a threshold of 1 to the number of keys, the explored one included.",
+                MAX_MULTISIG_KEYS - 1
+            );
+            return Err(RetrieverError::InvalidMultisig);
+        }
+        Ok(Multisig {
+            threshold,
+            cosigners,
+            script: None,
+        })
+    }
+
+    pub fn with_script(mut self, script: Option<MultisigScript>) -> Self {
+        self.script = script;
+        self
+    }
+
+    /// The multisig descriptors of the key with the keys of the cosigners at the relative path,
+    /// which is the explored path below its base path. As the keys of multi() keep their order,
+    /// the key is put at each position among the cosigners, which keep the order they were given
+    /// in, and bare multi() is tried too for up to 3 keys. Segwit descriptors are left out for
+    /// uncompressed keys. With a known script, only its sortedmulti descriptor is returned.
+    pub fn get_descriptors(
+        &self,
+        key: PublicKey,
+        relative_path: &DerivationPath,
+    ) -> Result<Vec<Descriptor<PublicKey>>, RetrieverError> {
+        let cosigner_keys = self
+            .cosigners
+            .iter()
+            .map(|cosigner| {
+                Ok(PublicKey::new(
+                    cosigner.derive_pub(&SECP, relative_path)?.public_key,
+                ))
+            })
+            .collect::<Result<Vec<_>, RetrieverError>>()?;
+        if let Some(script) = self.script {
+            let mut keys = cosigner_keys;
+            keys.push(key);
+            if !key.compressed && script != MultisigScript::P2sh {
+                return Ok(vec![]);
+            }
+            return Ok(vec![match script {
+                MultisigScript::P2sh => Descriptor::new_sh_sortedmulti(self.threshold, keys)?,
+                MultisigScript::P2shP2wsh => {
+                    Descriptor::new_sh_wsh_sortedmulti(self.threshold, keys)?
+                }
+                MultisigScript::P2wsh => Descriptor::new_wsh_sortedmulti(self.threshold, keys)?,
+            }]);
+        }
+        let mut descriptors = vec![];
+        for position in 0..=cosigner_keys.len() {
+            let mut keys = cosigner_keys.clone();
+            keys.insert(position, key);
+            if keys.len() <= MAX_BARE_MULTISIG_KEYS {
+                descriptors.push(Descriptor::new_bare(Miniscript::from_ast(
+                    Terminal::Multi(self.threshold, keys.clone()),
+                )?)?);
+            }
+            descriptors.push(Descriptor::new_sh(Miniscript::from_ast(Terminal::Multi(
+                self.threshold,
+                keys.clone(),
+            ))?)?);
+            if key.compressed {
+                descriptors.push(Descriptor::new_wsh(Miniscript::from_ast(
+                    Terminal::Multi(self.threshold, keys),
+                )?)?);
+            }
+        }
+        let mut keys = cosigner_keys;
+        keys.push(key);
+        descriptors.push(Descriptor::new_sh_sortedmulti(
+            self.threshold,
+            keys.clone(),
+        )?);
+        if key.compressed {
+            descriptors.push(Descriptor::new_wsh_sortedmulti(
+                self.threshold,
+                keys.clone(),
+            )?);
+            descriptors.push(Descriptor::new_sh_wsh_sortedmulti(self.threshold, keys)?);
+        }
+        Ok(descriptors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{bip32::Xpriv, key::Secp256k1};
+
+    use super::*;
+
+    #[test]
+    fn multisig_works_01() {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(bitcoin::Network::Bitcoin, &[1u8; 64]).unwrap();
+        let account_path = DerivationPath::from_str("m/48'/0'/0'/2'").unwrap();
+        let cosigners = [[2u8; 64], [3u8; 64]]
+            .iter()
+            .map(|seed| {
+                let cosigner = Xpriv::new_master(bitcoin::Network::Bitcoin, seed).unwrap();
+                Xpub::from_priv(&secp, &cosigner.derive_priv(&secp, &account_path).unwrap())
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        let multisig = Multisig::new(2, &cosigners, bitcoin::Network::Bitcoin).unwrap();
+        let relative_path = DerivationPath::from_str("m/0/5").unwrap();
+        let key = PublicKey::new(
+            master
+                .derive_priv(&secp, &account_path.extend(&relative_path))
+                .unwrap()
+                .to_keypair(&secp)
+                .public_key(),
+        );
+        let descriptors = multisig.get_descriptors(key, &relative_path).unwrap();
+        // bare, sh and wsh multi at each of the 3 positions, and the 3 sortedmulti.
+        assert_eq!(descriptors.len(), 12);
+        let wsh_sortedmulti = descriptors
+            .iter()
+            .find(|descriptor| descriptor.to_string().starts_with("wsh(sortedmulti(2,"))
+            .unwrap();
+        assert!(wsh_sortedmulti.to_string().contains(&key.to_string()));
+        let uncompressed = PublicKey::new_uncompressed(key.inner);
+        assert_eq!(
+            multisig
+                .get_descriptors(uncompressed, &relative_path)
+                .unwrap()
+                .len(),
+            7
+        );
+        assert!(matches!(
+            Multisig::new(4, &cosigners, bitcoin::Network::Bitcoin),
+            Err(RetrieverError::InvalidMultisig)
+        ));
+    }
+
+    #[test]
+    fn multisig_works_02() {
+        let secp = Secp256k1::new();
+        let preset = MultisigPreset::ElectrumBip39P2wsh;
+        let account_path = preset.get_base_path(bitcoin::Network::Bitcoin);
+        assert_eq!(account_path.to_string(), "m/48'/0'/0'/2'");
+        let cosigners = [[2u8; 64], [3u8; 64]]
+            .iter()
+            .map(|seed| {
+                let cosigner = Xpriv::new_master(bitcoin::Network::Bitcoin, seed).unwrap();
+                Xpub::from_priv(&secp, &cosigner.derive_priv(&secp, &account_path).unwrap())
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        let multisig = Multisig::new(2, &cosigners, bitcoin::Network::Bitcoin)
+            .unwrap()
+            .with_script(Some(preset.get_script()));
+        let key = PublicKey::new(
+            Xpriv::new_master(bitcoin::Network::Bitcoin, &[1u8; 64])
+                .unwrap()
+                .to_keypair(&secp)
+                .public_key(),
+        );
+        let descriptors = multisig
+            .get_descriptors(key, &DerivationPath::from_str("m/1/3").unwrap())
+            .unwrap();
+        assert_eq!(descriptors.len(), 1);
+        assert!(descriptors[0].to_string().starts_with("wsh(sortedmulti(2,"));
+        assert_eq!(
+            MultisigPreset::ElectrumSegwit
+                .get_base_path(bitcoin::Network::Testnet)
+                .to_string(),
+            "m/1'"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+
+use bitcoin::bip32::{ChildNumber, DerivationPath};
+use num_format::{Locale, ToFormattedString};
+
+use crate::data::defaults::{
+    SANITY_ESTIMATED_PATHS_PER_SECOND, SANITY_MAX_ESTIMATED_RUNTIME_SECONDS,
+    SANITY_MAX_WILDCARD_DEPTH,
+};
+
+use super::{exploration_path::ExplorationPath, exploration_step::ExplorationStepHardness};
+
+/// BIP43 purposes whose account level paths only have normal children (change/index) below them.
+const SINGLE_SIG_PURPOSES: [u32; 4] = [44, 49, 84, 86];
+
+/// Things in an exploration configuration that are most likely a mistake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExplorationWarning {
+    /// A hardened only step is explored under an account level base path of BIP44/49/84/86,
+    /// where wallets only use normal children.
+    HardenedStepUnderAccountPath {
+        base_path: DerivationPath,
+        step_index: usize,
+    },
+    /// Wildcards are explored to a depth that multiplies the search space on every such step.
+    LargeWildcardDepth { depth: u32, wildcard_steps: usize },
+    /// The estimated runtime of the search is unusually long.
+    LongEstimatedRuntime {
+        total_paths: usize,
+        estimated_seconds: u64,
+    },
+}
+
+impl fmt::Display for ExplorationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplorationWarning::HardenedStepUnderAccountPath {
+                base_path,
+                step_index,
+            } => write!(
+                f,
+                "Step {} of the exploration path is hardened only, but wallets using base path {} only derive normal children.",
+                step_index + 1,
+                base_path
+            ),
+            ExplorationWarning::LargeWildcardDepth {
+                depth,
+                wildcard_steps,
+            } => write!(
+                f,
+                "Exploration depth of {} is applied to each of the {} wildcard steps.",
+                depth.to_formatted_string(&Locale::en),
+                wildcard_steps
+            ),
+            ExplorationWarning::LongEstimatedRuntime {
+                total_paths,
+                estimated_seconds,
+            } => write!(
+                f,
+                "Exploring {} paths is estimated to take ~{} hours.",
+                total_paths.to_formatted_string(&Locale::en),
+                (1 + estimated_seconds / 3600).to_formatted_string(&Locale::en)
+            ),
+        }
+    }
+}
+
+/// Estimates the seconds it takes to derive and check all the paths of the exploration path.
+pub fn estimate_runtime_seconds(exploration_path: &ExplorationPath, num_descriptors: usize) -> u64 {
+    let scripts = exploration_path.size() as u64 * num_descriptors.max(1) as u64;
+    scripts / SANITY_ESTIMATED_PATHS_PER_SECOND
+}
+
+/// Looks for unlikely combinations of base paths, exploration steps and depth.
+pub fn check_exploration_sanity(
+    exploration_path: &ExplorationPath,
+    num_descriptors: usize,
+) -> Vec<ExplorationWarning> {
+    let mut warnings = vec![];
+    for base_path in exploration_path.get_base_paths() {
+        if !is_single_sig_account_path(base_path) {
+            continue;
+        }
+        for (step_index, step) in exploration_path.get_explore().iter().enumerate() {
+            if *step.get_hardness() == ExplorationStepHardness::Hardened {
+                warnings.push(ExplorationWarning::HardenedStepUnderAccountPath {
+                    base_path: base_path.clone(),
+                    step_index,
+                });
+            }
+        }
+    }
+
+    let depth = *exploration_path.get_depth();
+    let wildcard_steps = exploration_path
+        .get_explore()
+        .iter()
+        .filter(|step| {
+            !step.is_set()
+                && *step.get_stride() == 1
+                && *step.get_start_inclusive() == 0
+                && *step.get_end_inclusive() == depth
+        })
+        .count();
+    if depth > SANITY_MAX_WILDCARD_DEPTH && wildcard_steps > 0 {
+        warnings.push(ExplorationWarning::LargeWildcardDepth {
+            depth,
+            wildcard_steps,
+        });
+    }
+
+    let estimated_seconds = estimate_runtime_seconds(exploration_path, num_descriptors);
+    if estimated_seconds > SANITY_MAX_ESTIMATED_RUNTIME_SECONDS {
+        warnings.push(ExplorationWarning::LongEstimatedRuntime {
+            total_paths: exploration_path.size(),
+            estimated_seconds,
+        });
+    }
+    warnings
+}
+
+fn is_single_sig_account_path(path: &DerivationPath) -> bool {
+    match path.as_ref() {
+        [ChildNumber::Hardened { index: purpose }, ChildNumber::Hardened { .. }, ChildNumber::Hardened { .. }] => {
+            SINGLE_SIG_PURPOSES.contains(purpose)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_exploration_sanity_works_01() {
+        let exploration_path = ExplorationPath::new(
+            Some(vec!["m/84'/0'/0'".to_string(), "m/0'".to_string()]),
+            "*h/..5",
+            10,
+            false,
+        )
+        .unwrap();
+        let warnings = check_exploration_sanity(&exploration_path, 5);
+        assert_eq!(
+            warnings,
+            vec![ExplorationWarning::HardenedStepUnderAccountPath {
+                base_path: "m/84'/0'/0'".parse().unwrap(),
+                step_index: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn check_exploration_sanity_works_02() {
+        let exploration_path = ExplorationPath::new(None, "*/*", 20_000, false).unwrap();
+        let warnings = check_exploration_sanity(&exploration_path, 5);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[0],
+            ExplorationWarning::LargeWildcardDepth {
+                depth: 20_000,
+                wildcard_steps: 2
+            }
+        );
+        assert!(matches!(
+            warnings[1],
+            ExplorationWarning::LongEstimatedRuntime { .. }
+        ));
+    }
+}
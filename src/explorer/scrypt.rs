@@ -0,0 +1,30 @@
+use ::scrypt::Params;
+
+/// The scrypt key derivation function (RFC 7914) with N = 2^log_n.
+pub fn scrypt(password: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32, length: usize) -> Vec<u8> {
+    let params = Params::new(log_n, r, p, Params::RECOMMENDED_LEN)
+        .expect("the scrypt parameters of BIP38 and WarpWallet are valid");
+    let mut derived = vec![0u8; length];
+    ::scrypt::scrypt(password, salt, &params, &mut derived)
+        .expect("the scrypt output length is valid");
+    derived
+}
+
+#[cfg(test)]
+mod tests {
+    // Test vectors of RFC 7914.
+
+    use super::*;
+
+    #[test]
+    fn scrypt_works_01() {
+        assert_eq!(
+            hex::encode(scrypt(b"", b"", 4, 1, 1, 64)),
+            "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906"
+        );
+        assert_eq!(
+            hex::encode(scrypt(b"password", b"NaCl", 10, 8, 16, 64)),
+            "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b3731622eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use bip39::{Language, Mnemonic};
+use tracing::error;
+use zeroize::Zeroize;
+
+use crate::error::RetrieverError;
+
+/// Each word of a standard SeedQR is its index in the BIP39 list, written with four digits.
+const SEED_QR_DIGITS_PER_WORD: usize = 4;
+
+/// Turns a SeedQR, as made by SeedSigner and Krux, into its BIP39 mnemonic. A standard SeedQR
+/// is a string of digits (48 for 12 words, 96 for 24 words), and a CompactSeedQR holds the raw
+/// entropy (16 or 32 bytes), which is given here in hex. The lengths never overlap.
+pub fn from_seed_qr_to_mnemonic(input: &str) -> Result<Mnemonic, RetrieverError> {
+    let input = input.trim();
+    if input.chars().all(|char| char.is_ascii_digit()) && (input.len() == 48 || input.len() == 96) {
+        from_standard_seed_qr_to_mnemonic(input)
+    } else {
+        from_compact_seed_qr_to_mnemonic(input)
+    }
+}
+
+fn from_standard_seed_qr_to_mnemonic(input: &str) -> Result<Mnemonic, RetrieverError> {
+    let word_list = Language::English.word_list();
+    let mut words = vec![];
+    for chunk in input.as_bytes().chunks(SEED_QR_DIGITS_PER_WORD) {
+        let index = std::str::from_utf8(chunk)
+            .ok()
+            .and_then(|digits| digits.parse::<usize>().ok())
+            .filter(|index| *index < word_list.len())
+            .ok_or_else(|| {
+                error!("The standard SeedQR holds a word index out of the BIP39 list.");
+                RetrieverError::InvalidSeedQr
+            })?;
+        words.push(word_list[index]);
+    }
+    let mut mnemonic = words.join(" ");
+    let parsed = Mnemonic::parse_in_normalized(Language::English, &mnemonic);
+    mnemonic.zeroize();
+    Ok(parsed?)
+}
+
+fn from_compact_seed_qr_to_mnemonic(input: &str) -> Result<Mnemonic, RetrieverError> {
+    let mut entropy = hex::decode(input).map_err(|_| {
+        error!("The SeedQR is neither 48 or 96 digits nor the hex of a CompactSeedQR.");
+        RetrieverError::InvalidSeedQr
+    })?;
+    if entropy.len() != 16 && entropy.len() != 32 {
+        error!(
+            "The CompactSeedQR has {} bytes, but must have 16 or 32.",
+            entropy.len()
+        );
+        entropy.zeroize();
+        return Err(RetrieverError::InvalidSeedQr);
+    }
+    let mnemonic = Mnemonic::from_entropy(&entropy);
+    entropy.zeroize();
+    Ok(mnemonic?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_qr_works_01() {
+        // Test vector of the SeedQR specification of SeedSigner.
+        let mnemonic = "attack pizza motion avocado network gather crop fresh patrol unusual wild holiday candy pony ranch winter theme error hybrid van cereal salon goddess expire";
+        assert_eq!(
+            from_seed_qr_to_mnemonic("011513251154012711900771041507421289190620080870026613431420201617920614089619290300152408010643")
+                .unwrap()
+                .to_string(),
+            mnemonic
+        );
+        assert_eq!(
+            from_seed_qr_to_mnemonic(&hex::encode(
+                Mnemonic::parse(mnemonic).unwrap().to_entropy()
+            ))
+            .unwrap()
+            .to_string(),
+            mnemonic
+        );
+        assert_eq!(
+            from_seed_qr_to_mnemonic("00000000000000000000000000000000")
+                .unwrap()
+                .to_string(),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        assert!(matches!(
+            from_seed_qr_to_mnemonic(&format!("9999{}", "0000".repeat(11))),
+            Err(RetrieverError::InvalidSeedQr)
+        ));
+        assert!(matches!(
+            from_seed_qr_to_mnemonic("0115132511540127119007710415074212891906200808702"),
+            Err(RetrieverError::InvalidSeedQr)
+        ));
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// The scheme the mnemonic follows, which decides how the master key is derived from it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeedType {
+    /// A BIP39 mnemonic, as used by most wallets.
+    #[default]
+    Bip39,
+    /// An Electrum seed (standard or segwit) created by Electrum 2.0 and later. Its words come
+    /// from the BIP39 list but carry a version instead of a checksum.
+    Electrum,
+    /// An Electrum v1 seed, made by Electrum before 2.0: 12 or 24 words of its own wordlist, or
+    /// the hex they encode. Its keys are derived from a single stretched key instead of bip32,
+    /// and are uncompressed.
+    ElectrumV1,
+    /// A SLIP-39 (Shamir) backup, given as shares rather than as the mnemonic. The master secret
+    /// reconstructed from them is the bip32 seed.
+    Slip39,
+    /// An extended private key (xprv, or tprv off mainnet), used as the master key directly.
+    Xpriv,
+    /// Extended public keys, which allow scanning the non-hardened paths below them without any
+    /// private key.
+    Xpubs,
+    /// A bip32 seed (64 bytes) or the entropy of a BIP39 mnemonic (16 to 32 bytes) in hex.
+    Hex,
+    /// Extended public keys of the base derivation paths, fetched from a hardware wallet through
+    /// HWI and scanned watch-only.
+    Hwi,
+    /// A SeedQR (SeedSigner, Krux), either standard (the word indexes as digits) or compact (the
+    /// entropy in hex), which is turned into its BIP39 mnemonic.
+    SeedQr,
+    /// The hsm_secret of a Core Lightning node (32 bytes) in hex, whose bip32 seed is derived
+    /// from it by HKDF.
+    ClnHsmSecret,
+}
@@ -0,0 +1,410 @@
+use std::collections::BTreeMap;
+
+use bitcoin::hashes::{
+    hmac::{Hmac, HmacEngine},
+    sha256, Hash, HashEngine,
+};
+use tracing::{error, info};
+use zeroize::Zeroize;
+
+use crate::{data::slip39_wordlist::SLIP39_WORDLIST, error::RetrieverError};
+
+use super::auxiliaries::pbkdf2_hmac_sha256;
+
+const RADIX_BITS: usize = 10;
+const CHECKSUM_WORDS: usize = 3;
+/// Identifier, extendable flag, iteration exponent, group and member fields.
+const HEADER_WORDS: usize = 4;
+const MIN_SHARE_WORDS: usize = 20;
+const MIN_SECRET_BYTES: usize = 16;
+const CUSTOMIZATION_STRING: &[u8] = b"shamir";
+const CUSTOMIZATION_STRING_EXTENDABLE: &[u8] = b"shamir_extendable";
+/// The x coordinates at which the shared secret and its digest are stored.
+const SECRET_INDEX: u8 = 255;
+const DIGEST_INDEX: u8 = 254;
+const DIGEST_BYTES: usize = 4;
+const BASE_ITERATION_COUNT: u32 = 10_000;
+const ROUND_COUNT: u8 = 4;
+const RS1024_GENERATOR: [u32; 10] = [
+    0xE0E040, 0x1C1C080, 0x3838100, 0x7070200, 0xE0E0009, 0x1C0C2412, 0x38086C24, 0x3090FC48,
+    0x21B1F890, 0x3F3F120,
+];
+
+/// A single share of a SLIP-39 (Shamir) backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Slip39Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Vec<u8>,
+}
+
+impl Zeroize for Slip39Share {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl Slip39Share {
+    fn from_mnemonic(mnemonic: &str) -> Result<Self, RetrieverError> {
+        let mut indices = mnemonic
+            .split_whitespace()
+            .map(|word| {
+                SLIP39_WORDLIST
+                    .binary_search(&word.to_lowercase().as_str())
+                    .map(|index| index as u32)
+                    .map_err(|_| {
+                        error!("A SLIP-39 share contains a word not in the wordlist.");
+                        RetrieverError::InvalidSlip39Share
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if indices.len() < MIN_SHARE_WORDS {
+            error!(
+                "A SLIP-39 share must have at least {} words.",
+                MIN_SHARE_WORDS
+            );
+            return Err(RetrieverError::InvalidSlip39Share);
+        }
+        let id_and_exponent = (indices[0] << RADIX_BITS) | indices[1];
+        let extendable = (id_and_exponent >> 4) & 1 == 1;
+        let customization_string = if extendable {
+            CUSTOMIZATION_STRING_EXTENDABLE
+        } else {
+            CUSTOMIZATION_STRING
+        };
+        let values = customization_string
+            .iter()
+            .map(|byte| *byte as u32)
+            .chain(indices.iter().copied());
+        if rs1024_polymod(values) != 1 {
+            error!("The checksum of a SLIP-39 share is invalid.");
+            return Err(RetrieverError::InvalidSlip39Share);
+        }
+        let group_and_member = (indices[2] << RADIX_BITS) | indices[3];
+        let value_indices = &indices[HEADER_WORDS..indices.len() - CHECKSUM_WORDS];
+        let padding_bits = (RADIX_BITS * value_indices.len()) % 16;
+        if padding_bits > 8 {
+            error!("A SLIP-39 share has an invalid length.");
+            return Err(RetrieverError::InvalidSlip39Share);
+        }
+        let value = from_indices_to_bytes(value_indices, padding_bits)?;
+        indices.zeroize();
+        let share = Slip39Share {
+            identifier: (id_and_exponent >> 5) as u16,
+            extendable,
+            iteration_exponent: (id_and_exponent & 0xF) as u8,
+            group_index: (group_and_member >> 16) as u8,
+            group_threshold: ((group_and_member >> 12) & 0xF) as u8 + 1,
+            group_count: ((group_and_member >> 8) & 0xF) as u8 + 1,
+            member_index: ((group_and_member >> 4) & 0xF) as u8,
+            member_threshold: (group_and_member & 0xF) as u8 + 1,
+            value,
+        };
+        if share.group_threshold > share.group_count {
+            error!("A SLIP-39 share has a group threshold above its group count.");
+            return Err(RetrieverError::InvalidSlip39Share);
+        }
+        Ok(share)
+    }
+}
+
+/// Packs the 10 bit word indices into bytes, dropping the leading padding bits, which must be
+/// zero.
+fn from_indices_to_bytes(indices: &[u32], padding_bits: usize) -> Result<Vec<u8>, RetrieverError> {
+    let mut bits = indices
+        .iter()
+        .flat_map(|index| {
+            (0..RADIX_BITS)
+                .rev()
+                .map(move |bit| (index >> bit) & 1 == 1)
+        })
+        .collect::<Vec<_>>();
+    if bits[..padding_bits].iter().any(|bit| *bit) {
+        error!("The padding of a SLIP-39 share is not zero.");
+        return Err(RetrieverError::InvalidSlip39Share);
+    }
+    let bytes = bits[padding_bits..]
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |acc, bit| (acc << 1) | *bit as u8))
+        .collect::<Vec<_>>();
+    bits.zeroize();
+    if bytes.len() < MIN_SECRET_BYTES {
+        error!("A SLIP-39 share holds less than 128 bits.");
+        return Err(RetrieverError::InvalidSlip39Share);
+    }
+    Ok(bytes)
+}
+
+fn rs1024_polymod(values: impl Iterator<Item = u32>) -> u32 {
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 20;
+        checksum = ((checksum & 0xFFFFF) << RADIX_BITS) ^ value;
+        for (bit, generator) in RS1024_GENERATOR.iter().enumerate() {
+            if (top >> bit) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// Exponent and logarithm tables of GF(256) with the Rijndael polynomial and generator 3.
+fn gf256_tables() -> ([u8; 255], [u8; 256]) {
+    let mut exp = [0u8; 255];
+    let mut log = [0u8; 256];
+    let mut power = 1u16;
+    for (index, exp) in exp.iter_mut().enumerate() {
+        *exp = power as u8;
+        log[power as usize] = index as u8;
+        power ^= power << 1;
+        if power & 0x100 != 0 {
+            power ^= 0x11B;
+        }
+    }
+    (exp, log)
+}
+
+/// Evaluates at x the polynomial passing through the points, by Lagrange interpolation in GF(256).
+fn interpolate(points: &[(u8, &[u8])], x: u8) -> Vec<u8> {
+    if let Some((_, value)) = points.iter().find(|(point_x, _)| *point_x == x) {
+        return value.to_vec();
+    }
+    let (exp, log) = gf256_tables();
+    let log_product = points
+        .iter()
+        .map(|(point_x, _)| log[(point_x ^ x) as usize] as usize)
+        .sum::<usize>();
+    let mut result = vec![0u8; points[0].1.len()];
+    for (point_x, value) in points {
+        let log_denominator = log[(point_x ^ x) as usize] as usize
+            + points
+                .iter()
+                .map(|(other_x, _)| log[(point_x ^ other_x) as usize] as usize)
+                .sum::<usize>();
+        let log_basis = (log_product % 255 + 255 - log_denominator % 255) % 255;
+        for (result, byte) in result.iter_mut().zip(value.iter()) {
+            if *byte != 0 {
+                *result ^= exp[(log[*byte as usize] as usize + log_basis) % 255];
+            }
+        }
+    }
+    result
+}
+
+/// Recovers the secret shared among the points, checking its digest when the threshold is above
+/// one.
+fn recover_secret(threshold: u8, points: &[(u8, &[u8])]) -> Result<Vec<u8>, RetrieverError> {
+    if threshold == 1 {
+        return Ok(points[0].1.to_vec());
+    }
+    let secret = interpolate(points, SECRET_INDEX);
+    let mut digest_share = interpolate(points, DIGEST_INDEX);
+    let mut engine = HmacEngine::<sha256::Hash>::new(&digest_share[DIGEST_BYTES..]);
+    engine.input(&secret);
+    let digest = Hmac::from_engine(engine).to_byte_array();
+    let is_valid = digest[..DIGEST_BYTES] == digest_share[..DIGEST_BYTES];
+    digest_share.zeroize();
+    if !is_valid {
+        error!("The digest of the secret recovered from the SLIP-39 shares is invalid.");
+        return Err(RetrieverError::InvalidSlip39Share);
+    }
+    Ok(secret)
+}
+
+/// Decrypts the master secret with the passphrase by running the four round Feistel network
+/// backwards.
+fn decrypt(
+    encrypted_master_secret: &[u8],
+    passphrase: &[u8],
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+) -> Vec<u8> {
+    let half = encrypted_master_secret.len() / 2;
+    let mut left = encrypted_master_secret[..half].to_vec();
+    let mut right = encrypted_master_secret[half..].to_vec();
+    let mut salt = vec![];
+    if !extendable {
+        salt.extend_from_slice(CUSTOMIZATION_STRING);
+        salt.extend_from_slice(&identifier.to_be_bytes());
+    }
+    let rounds = (BASE_ITERATION_COUNT << iteration_exponent) / ROUND_COUNT as u32;
+    for round in (0..ROUND_COUNT).rev() {
+        let mut password = vec![round];
+        password.extend_from_slice(passphrase);
+        let mut round_salt = salt.clone();
+        round_salt.extend_from_slice(&right);
+        let mut round_key = pbkdf2_hmac_sha256(&password, &round_salt, rounds, right.len());
+        let next_right = left
+            .iter()
+            .zip(round_key.iter())
+            .map(|(left, key)| left ^ key)
+            .collect::<Vec<_>>();
+        left.zeroize();
+        left = std::mem::replace(&mut right, next_right);
+        password.zeroize();
+        round_salt.zeroize();
+        round_key.zeroize();
+    }
+    right.extend_from_slice(&left);
+    left.zeroize();
+    right
+}
+
+/// Reconstructs the master secret of a SLIP-39 backup from its mnemonic shares and passphrase.
+/// Shares may come from several groups, as long as enough groups have enough shares. The master
+/// secret serves as the bip32 seed.
+pub fn from_slip39_shares_to_master_secret(
+    mnemonics: &[String],
+    passphrase: &str,
+) -> Result<Vec<u8>, RetrieverError> {
+    if !passphrase.bytes().all(|byte| (32..=126).contains(&byte)) {
+        error!("A SLIP-39 passphrase may only contain printable ASCII characters.");
+        return Err(RetrieverError::InvalidSlip39Passphrase);
+    }
+    let mut shares = mnemonics
+        .iter()
+        .map(|mnemonic| Slip39Share::from_mnemonic(mnemonic))
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(first) = shares.first().cloned() else {
+        error!("No SLIP-39 shares were given.");
+        return Err(RetrieverError::InsufficientSlip39Shares);
+    };
+    if shares.iter().any(|share| {
+        share.identifier != first.identifier
+            || share.extendable != first.extendable
+            || share.iteration_exponent != first.iteration_exponent
+            || share.group_threshold != first.group_threshold
+            || share.group_count != first.group_count
+            || share.value.len() != first.value.len()
+    }) {
+        error!("The SLIP-39 shares do not belong to the same backup.");
+        return Err(RetrieverError::InvalidSlip39Share);
+    }
+    let mut groups = BTreeMap::<u8, BTreeMap<u8, &Slip39Share>>::new();
+    for share in shares.iter() {
+        let members = groups.entry(share.group_index).or_default();
+        if let Some(member) = members.insert(share.member_index, share) {
+            if member != share {
+                error!("Two different SLIP-39 shares have the same index.");
+                return Err(RetrieverError::InvalidSlip39Share);
+            }
+        }
+    }
+    let mut group_secrets = vec![];
+    for (group_index, members) in groups.iter() {
+        let member_threshold = members.values().next().unwrap().member_threshold;
+        if members
+            .values()
+            .any(|member| member.member_threshold != member_threshold)
+        {
+            error!(
+                "The SLIP-39 shares of group {} disagree on the threshold.",
+                group_index
+            );
+            return Err(RetrieverError::InvalidSlip39Share);
+        }
+        if members.len() < member_threshold as usize {
+            continue;
+        }
+        let points = members
+            .values()
+            .take(member_threshold as usize)
+            .map(|member| (member.member_index, member.value.as_slice()))
+            .collect::<Vec<_>>();
+        group_secrets.push((*group_index, recover_secret(member_threshold, &points)?));
+    }
+    if group_secrets.len() < first.group_threshold as usize {
+        error!(
+            "The SLIP-39 shares complete {} of the {} groups needed.",
+            group_secrets.len(),
+            first.group_threshold
+        );
+        group_secrets
+            .iter_mut()
+            .for_each(|(_, secret)| secret.zeroize());
+        shares.iter_mut().for_each(|share| share.zeroize());
+        return Err(RetrieverError::InsufficientSlip39Shares);
+    }
+    let points = group_secrets
+        .iter()
+        .take(first.group_threshold as usize)
+        .map(|(group_index, secret)| (*group_index, secret.as_slice()))
+        .collect::<Vec<_>>();
+    let mut encrypted_master_secret = recover_secret(first.group_threshold, &points)?;
+    let master_secret = decrypt(
+        &encrypted_master_secret,
+        passphrase.as_bytes(),
+        first.iteration_exponent,
+        first.identifier,
+        first.extendable,
+    );
+    info!(
+        "Master secret of {} bits reconstructed from {} SLIP-39 shares.",
+        master_secret.len() * 8,
+        mnemonics.len()
+    );
+    encrypted_master_secret.zeroize();
+    group_secrets
+        .iter_mut()
+        .for_each(|(_, secret)| secret.zeroize());
+    shares.iter_mut().for_each(|share| share.zeroize());
+    Ok(master_secret)
+}
+
+#[cfg(test)]
+mod tests {
+    // Test vectors of SLIP-0039, all with the passphrase "TREZOR".
+
+    use super::*;
+
+    fn shares(mnemonics: &[&str]) -> Vec<String> {
+        mnemonics
+            .iter()
+            .map(|mnemonic| mnemonic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn slip39_works_01() {
+        let master_secret = from_slip39_shares_to_master_secret(
+            &shares(&["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard"]),
+            "TREZOR",
+        )
+        .unwrap();
+        assert_eq!(
+            master_secret,
+            hex::decode("bb54aac4b89dc868ba37d9cc21b2cece").unwrap()
+        );
+        assert!(matches!(
+            from_slip39_shares_to_master_secret(
+                &shares(&["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney"]),
+                "TREZOR",
+            ),
+            Err(RetrieverError::InvalidSlip39Share)
+        ));
+    }
+
+    #[test]
+    fn slip39_works_02() {
+        let first = "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed";
+        let second = "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking";
+        let master_secret =
+            from_slip39_shares_to_master_secret(&shares(&[first, second]), "TREZOR").unwrap();
+        assert_eq!(
+            master_secret,
+            hex::decode("b43ceb7e57a0ea8766221624d01b0864").unwrap()
+        );
+        assert!(matches!(
+            from_slip39_shares_to_master_secret(&shares(&[first]), "TREZOR"),
+            Err(RetrieverError::InsufficientSlip39Shares)
+        ));
+    }
+}
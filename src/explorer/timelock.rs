@@ -0,0 +1,165 @@
+use std::str::FromStr;
+
+use bitcoin::PublicKey;
+use miniscript::{Descriptor, Miniscript, Segwitv0};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::error::RetrieverError;
+
+/// The most lock values searched, as every explored key is tried with each of them.
+const MAX_TIMELOCK_VALUES: usize = 10_000;
+/// The lock values of after() and older() are below 2^31, above which they would be disabled.
+const MAX_TIMELOCK_VALUE: u32 = 0x7fff_ffff;
+
+/// A script locking the coins of the explored key until a time.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TimelockTemplate {
+    /// The key can spend once the absolute lock (CLTV) passed: and_v(v:pk(K),after(N)), the lock
+    /// being a block height below 500000000 or a unix time from it on.
+    Cltv,
+    /// The key can spend once the coins are as old as the relative lock (CSV):
+    /// and_v(v:pk(K),older(N)), as BIP68 encodes it.
+    Csv,
+}
+
+impl TimelockTemplate {
+    fn get_miniscript(&self, key: PublicKey, value: u32) -> String {
+        match self {
+            TimelockTemplate::Cltv => format!("and_v(v:pk({}),after({}))", key, value),
+            TimelockTemplate::Csv => format!("and_v(v:pk({}),older({}))", key, value),
+        }
+    }
+}
+
+/// The timelocked scripts the explored keys are searched in, each template with each lock
+/// value, in wsh and sh(wsh).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timelocks {
+    templates: Vec<TimelockTemplate>,
+    values: Vec<u32>,
+}
+
+impl Timelocks {
+    /// Parses the lock values, each a single value, e.g. "840000", or an inclusive range, e.g.
+    /// "840000..841000".
+    pub fn new(templates: &[TimelockTemplate], values: &[String]) -> Result<Self, RetrieverError> {
+        let mut parsed_values = vec![];
+        for value in values {
+            let (start, end) = match value.trim().split_once("..") {
+                Some((start, end)) => (start.trim().parse::<u32>(), end.trim().parse::<u32>()),
+                None => (value.trim().parse::<u32>(), value.trim().parse::<u32>()),
+            };
+            let (Ok(start), Ok(end)) = (start, end) else {
+                error!(
+                    "Invalid timelock value {}, expected a number or a range like 840000..841000.",
+                    value
+                );
+                return Err(RetrieverError::InvalidTimelock);
+            };
+            if start == 0 || start > end || end > MAX_TIMELOCK_VALUE {
+                error!(
+                    "Invalid timelock value {}, lock values are from 1 to {}.",
+                    value, MAX_TIMELOCK_VALUE
+                );
+                return Err(RetrieverError::InvalidTimelock);
+            }
+            if parsed_values.len() + (end - start) as usize >= MAX_TIMELOCK_VALUES {
+                error!(
+                    "At most {} timelock values can be searched.",
+                    MAX_TIMELOCK_VALUES
+                );
+                return Err(RetrieverError::InvalidTimelock);
+            }
+            parsed_values.extend(start..=end);
+        }
+        parsed_values.sort_unstable();
+        parsed_values.dedup();
+        if templates.is_empty() || parsed_values.is_empty() {
+            error!("Timelocked scripts need at least one template and one lock value.");
+            return Err(RetrieverError::InvalidTimelock);
+        }
+        Ok(Timelocks {
+            templates: templates.to_vec(),
+            values: parsed_values,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len() * self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The wsh and sh(wsh) descriptors of each template with each lock value for the key.
+    /// Uncompressed keys have none, as the scripts are segwit.
+    pub fn get_descriptors(
+        &self,
+        key: PublicKey,
+    ) -> Result<Vec<Descriptor<PublicKey>>, RetrieverError> {
+        if !key.compressed {
+            return Ok(vec![]);
+        }
+        let mut descriptors = vec![];
+        for template in self.templates.iter() {
+            for value in self.values.iter() {
+                let miniscript = Miniscript::<PublicKey, Segwitv0>::from_str(
+                    &template.get_miniscript(key, *value),
+                )?;
+                descriptors.push(Descriptor::new_wsh(miniscript.clone())?);
+                descriptors.push(Descriptor::new_sh_wsh(miniscript)?);
+            }
+        }
+        Ok(descriptors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{bip32::Xpriv, key::Secp256k1};
+
+    use super::*;
+
+    #[test]
+    fn timelock_works_01() {
+        let secp = Secp256k1::new();
+        let key = PublicKey::new(
+            Xpriv::new_master(bitcoin::Network::Bitcoin, &[1u8; 64])
+                .unwrap()
+                .to_keypair(&secp)
+                .public_key(),
+        );
+        let timelocks = Timelocks::new(
+            &[TimelockTemplate::Cltv, TimelockTemplate::Csv],
+            &["840000..840002".to_string(), "840001".to_string()],
+        )
+        .unwrap();
+        assert_eq!(timelocks.len(), 6);
+        let descriptors = timelocks.get_descriptors(key).unwrap();
+        assert_eq!(descriptors.len(), 12);
+        assert_eq!(
+            descriptors[0].to_string().split('#').next().unwrap(),
+            format!("wsh(and_v(v:pk({}),after(840000)))", key)
+        );
+        assert_eq!(
+            descriptors[11].to_string().split('#').next().unwrap(),
+            format!("sh(wsh(and_v(v:pk({}),older(840002))))", key)
+        );
+        assert!(timelocks
+            .get_descriptors(PublicKey::new_uncompressed(key.inner))
+            .unwrap()
+            .is_empty());
+        for values in [&["0"][..], &["2..1"], &["1..20000"], &["a"]] {
+            let values = values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            assert!(matches!(
+                Timelocks::new(&[TimelockTemplate::Cltv], &values),
+                Err(RetrieverError::InvalidTimelock)
+            ));
+        }
+    }
+}
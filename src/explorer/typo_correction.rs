@@ -0,0 +1,143 @@
+use bip39::{Language, Mnemonic};
+use itertools::Itertools;
+use tracing::error;
+use zeroize::Zeroize;
+
+use crate::error::RetrieverError;
+
+/// The positions (from 1) of the corrected words of a mnemonic, with the words put there.
+pub type Corrections = Vec<(usize, &'static str)>;
+
+/// Whether the words are one substitution, insertion or deletion of a letter apart.
+fn is_one_edit_apart(first: &str, second: &str) -> bool {
+    let (shorter, longer) = if first.len() <= second.len() {
+        (first.as_bytes(), second.as_bytes())
+    } else {
+        (second.as_bytes(), first.as_bytes())
+    };
+    let prefix = shorter
+        .iter()
+        .zip(longer)
+        .take_while(|(a, b)| a == b)
+        .count();
+    match longer.len() - shorter.len() {
+        0 => prefix < shorter.len() && shorter[prefix + 1..] == longer[prefix + 1..],
+        1 => shorter[prefix..] == longer[prefix + 1..],
+        _ => false,
+    }
+}
+
+/// The words of the English BIP39 list one edit away from the word.
+pub fn nearest_words(word: &str) -> Vec<&'static str> {
+    Language::English
+        .word_list()
+        .iter()
+        .copied()
+        .filter(|list_word| is_one_edit_apart(word, list_word))
+        .collect()
+}
+
+/// Corrects the typos of a BIP39 mnemonic that does not parse and returns the candidates whose
+/// checksum holds, each with its corrections. Words that are not in the list are replaced by
+/// their nearest words. If all are in the list, a typo may have turned a word into another one,
+/// so each word in turn is replaced by its nearest words.
+pub fn from_mnemonic_with_typos_to_candidates(
+    mnemonic: &str,
+) -> Result<Vec<(Corrections, String)>, RetrieverError> {
+    let mut words = mnemonic
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    let typo_positions = words
+        .iter()
+        .positions(|word| Language::English.find_word(word).is_none())
+        .collect::<Vec<_>>();
+    let corrections: Vec<Vec<(usize, &'static str)>> = if typo_positions.is_empty() {
+        words
+            .iter()
+            .enumerate()
+            .flat_map(|(position, word)| {
+                nearest_words(word)
+                    .into_iter()
+                    .map(move |nearest_word| vec![(position, nearest_word)])
+            })
+            .collect()
+    } else {
+        typo_positions
+            .iter()
+            .map(|position| {
+                nearest_words(&words[*position])
+                    .into_iter()
+                    .map(|nearest_word| (*position, nearest_word))
+                    .collect::<Vec<_>>()
+            })
+            .multi_cartesian_product()
+            .collect()
+    };
+    let mut candidates = vec![];
+    for correction in corrections {
+        let mut corrected_words = words.clone();
+        for (position, word) in correction.iter() {
+            corrected_words[*position] = word.to_string();
+        }
+        let mut candidate = corrected_words.join(" ");
+        corrected_words.zeroize();
+        if Mnemonic::parse_in_normalized(Language::English, &candidate).is_ok() {
+            let correction = correction
+                .into_iter()
+                .map(|(position, word)| (position + 1, word))
+                .collect();
+            candidates.push((correction, candidate));
+        } else {
+            candidate.zeroize();
+        }
+    }
+    words.zeroize();
+    if candidates.is_empty() {
+        error!("No correction of the typos in the mnemonic passes its checksum.");
+        return Err(RetrieverError::NoTypoCorrections);
+    }
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typo_correction_works_01() {
+        assert!(is_one_edit_apart("abandn", "abandon"));
+        assert!(is_one_edit_apart("abandom", "abandon"));
+        assert!(is_one_edit_apart("abanndon", "abandon"));
+        assert!(!is_one_edit_apart("abandon", "abandon"));
+        assert!(!is_one_edit_apart("abadnon", "abandon"));
+        assert!(nearest_words("abot").contains(&"about"));
+    }
+
+    #[test]
+    fn typo_correction_works_02() {
+        let candidates = from_mnemonic_with_typos_to_candidates(
+            "abandon abandon abandon abandon abandon abandon abandon abandn abandon abandon abandon abot",
+        )
+        .unwrap();
+        assert!(candidates.contains(&(
+            vec![(8, "abandon"), (12, "about")],
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+                .to_string()
+        )));
+        // "yellow" mistyped as "fellow", another word of the list.
+        let candidates = from_mnemonic_with_typos_to_candidates(
+            "legal winner thank year wave sausage worth useful legal winner thank fellow",
+        )
+        .unwrap();
+        assert!(candidates
+            .iter()
+            .any(|(correction, _)| *correction == vec![(12, "yellow")]));
+        assert!(matches!(
+            from_mnemonic_with_typos_to_candidates(
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon xqzvw",
+            ),
+            Err(RetrieverError::NoTypoCorrections)
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+use bip39::{Language, Mnemonic};
+use itertools::Itertools;
+use tracing::error;
+use zeroize::Zeroize;
+
+use crate::error::RetrieverError;
+
+const MNEMONIC_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// The moves of a reordered mnemonic: each moved word, from its written position to the position
+/// it is put in (both from 1).
+pub type WordMoves = Vec<(usize, usize)>;
+
+/// Yields every ordering of `len` positions that is at most `max_swaps` swaps of two positions
+/// away from the written order, each once, as the written position of the word put at each
+/// position. Each ordering is built position by position: the next one either stays in place or
+/// is slipped into the cycle of a previous one, which costs a swap. Only the choices are
+/// enumerated, so nothing is held in memory.
+fn orderings_within_swaps(len: usize, max_swaps: usize) -> impl Iterator<Item = Vec<usize>> {
+    let build = move |slips: Vec<(usize, usize)>| {
+        let mut ordering = (0..len).collect::<Vec<_>>();
+        for (position, after) in slips {
+            ordering[position] = ordering[after];
+            ordering[after] = position;
+        }
+        ordering
+    };
+    let swapped = (1..=max_swaps.min(len.saturating_sub(1))).flat_map(move |swaps| {
+        (1..len).combinations(swaps).flat_map(move |positions| {
+            let afters = positions.iter().map(|position| 0..*position);
+            afters
+                .multi_cartesian_product()
+                .map(move |afters| positions.iter().copied().zip(afters).collect::<Vec<_>>())
+        })
+    });
+    std::iter::once(vec![]).chain(swapped).map(build)
+}
+
+/// Reorders the words of a BIP39 mnemonic by at most `max_swaps` swaps of two words, and yields
+/// the candidates whose checksum holds, together with their moves. The written order is the first
+/// candidate if it is valid. With one swap less than the words, every order is tried.
+pub fn from_mnemonic_with_swaps_to_candidates(
+    mnemonic: &str,
+    max_swaps: usize,
+) -> Result<impl Iterator<Item = (WordMoves, String)>, RetrieverError> {
+    let mut words = mnemonic
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    if max_swaps == 0
+        || !MNEMONIC_WORD_COUNTS.contains(&words.len())
+        || words
+            .iter()
+            .any(|word| Language::English.find_word(word).is_none())
+    {
+        words.zeroize();
+        error!("Reordering a mnemonic needs at least one swap and 12, 15, 18, 21 or 24 words of the English BIP39 list.");
+        return Err(RetrieverError::InvalidWordSwaps);
+    }
+    Ok(
+        orderings_within_swaps(words.len(), max_swaps).filter_map(move |ordering| {
+            let mut candidate = ordering
+                .iter()
+                .map(|written_position| words[*written_position].as_str())
+                .join(" ");
+            if Mnemonic::parse_in_normalized(Language::English, &candidate).is_err() {
+                candidate.zeroize();
+                return None;
+            }
+            let moves = ordering
+                .iter()
+                .enumerate()
+                .filter(|(position, written_position)| position != *written_position)
+                .map(|(position, written_position)| (written_position + 1, position + 1))
+                .collect();
+            Some((moves, candidate))
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn word_order_works_01() {
+        // 5! orderings, 1 + 10 within a single swap, and 35 more (3-cycles and two swaps) within two.
+        let orderings = orderings_within_swaps(5, 4).collect::<HashSet<_>>();
+        assert_eq!(orderings.len(), 120);
+        assert!(orderings
+            .iter()
+            .all(|ordering| ordering.iter().sorted().copied().eq(0..5)));
+        assert_eq!(orderings_within_swaps(5, 1).count(), 11);
+        assert_eq!(orderings_within_swaps(5, 2).count(), 1 + 10 + 35);
+    }
+
+    #[test]
+    fn word_order_works_02() {
+        // "thank" and "wave" of the valid mnemonic written in each other's place.
+        let candidates = from_mnemonic_with_swaps_to_candidates(
+            "legal winner wave year thank sausage worth useful legal winner thank yellow",
+            1,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+        assert!(candidates.contains(&(
+            vec![(5, 3), (3, 5)],
+            "legal winner thank year wave sausage worth useful legal winner thank yellow"
+                .to_string()
+        )));
+        assert!(matches!(
+            from_mnemonic_with_swaps_to_candidates(
+                "legal winner wave year thank sausage worth useful legal winner thank",
+                1
+            ),
+            Err(RetrieverError::InvalidWordSwaps)
+        ));
+    }
+}
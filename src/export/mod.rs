@@ -0,0 +1,192 @@
+pub mod private_descriptor;
+pub mod sparrow;
+pub mod wif;
+
+use bitcoin::{
+    bip32::{ChildNumber, DerivationPath, Xpriv, Xpub},
+    key::Secp256k1,
+};
+use itertools::Itertools;
+use miniscript::{
+    descriptor::{DescriptorSecretKey, DescriptorXKey, KeyMap, Wildcard},
+    Descriptor, DescriptorPublicKey, ForEachKey,
+};
+
+use crate::{
+    covered_descriptors::CoveredDescriptors, descriptor_checksum::with_checksum,
+    error::RetrieverError, path_pairs::PathDescriptorPair,
+};
+
+/// Splits a found path into its account path and the normal children that follow it.
+/// The account path ends with the last hardened child of the path, so everything after it
+/// is derivable from the account xpub.
+pub fn split_account_path(path: &DerivationPath) -> (DerivationPath, DerivationPath) {
+    let children = path.as_ref();
+    let account_len = children
+        .iter()
+        .rposition(|child| child.is_hardened())
+        .map_or(0, |position| position + 1);
+    (
+        DerivationPath::from(children[..account_len].to_vec()),
+        DerivationPath::from(children[account_len..].to_vec()),
+    )
+}
+
+/// Creates the account level public descriptor covering a found path. The last normal child of
+/// the path is replaced by a wildcard, so the whole branch of the find gets covered.
+pub fn account_descriptor(
+    master_xpriv: &Xpriv,
+    path: &DerivationPath,
+    descriptor_type: &CoveredDescriptors,
+) -> Result<Descriptor<DescriptorPublicKey>, RetrieverError> {
+    let secp = Secp256k1::new();
+    let (account_path, normal_children) = split_account_path(path);
+    let account_xpub = Xpub::from_priv(&secp, &master_xpriv.derive_priv(&secp, &account_path)?);
+    let (derivation_path, wildcard) = match normal_children.as_ref().split_last() {
+        Some((_, branch)) => (DerivationPath::from(branch.to_vec()), Wildcard::Unhardened),
+        None => (DerivationPath::master(), Wildcard::None),
+    };
+    let key = DescriptorPublicKey::XPub(DescriptorXKey {
+        origin: Some((master_xpriv.fingerprint(&secp), account_path)),
+        xkey: account_xpub,
+        derivation_path,
+        wildcard,
+    });
+    descriptor_type.to_descriptor(key)
+}
+
+/// The account level descriptor covering a found path like `account_descriptor`, with the
+/// account xpriv in place of its xpub and with its checksum, so a descriptor wallet importing it
+/// can spend from the whole branch of the find.
+pub fn account_private_descriptor(
+    master_xpriv: &Xpriv,
+    path: &DerivationPath,
+    descriptor_type: &CoveredDescriptors,
+) -> Result<String, RetrieverError> {
+    let secp = Secp256k1::new();
+    let descriptor = account_descriptor(master_xpriv, path, descriptor_type)?;
+    let (account_path, _) = split_account_path(path);
+    let account_xpriv = master_xpriv.derive_priv(&secp, &account_path)?;
+    let mut key_map = KeyMap::new();
+    descriptor.for_each_key(|key| {
+        if let DescriptorPublicKey::XPub(xkey) = key {
+            key_map.insert(
+                key.clone(),
+                DescriptorSecretKey::XPrv(DescriptorXKey {
+                    origin: xkey.origin.clone(),
+                    xkey: account_xpriv,
+                    derivation_path: xkey.derivation_path.clone(),
+                    wildcard: xkey.wildcard,
+                }),
+            );
+        }
+        true
+    });
+    with_checksum(&descriptor.to_string_with_secret(&key_map))
+}
+
+/// The path and descriptor type of one find of each branch of an account, whose account
+/// descriptor covers the other finds of the branch. Finds of standalone keys, at path m, belong
+/// to no account.
+pub fn found_accounts(finds: &[PathDescriptorPair]) -> Vec<(DerivationPath, CoveredDescriptors)> {
+    finds
+        .iter()
+        .filter(|find| !find.0.is_master())
+        .filter_map(|find| {
+            CoveredDescriptors::from_descriptor(&find.1)
+                .map(|descriptor_type| (find.0.clone(), descriptor_type))
+        })
+        .unique_by(|(path, descriptor_type)| {
+            let (account_path, normal_children) = split_account_path(path);
+            let branch_len = normal_children.len().saturating_sub(1);
+            (
+                account_path,
+                normal_children.as_ref()[..branch_len].to_vec(),
+                descriptor_type.clone(),
+            )
+        })
+        .collect()
+}
+
+/// Turns a derivation path into a string usable in file names, e.g. m/84'/0'/0' into m_84h_0h_0h.
+pub fn path_to_file_name_part(path: &DerivationPath) -> String {
+    let mut part = "m".to_string();
+    for child in path.as_ref() {
+        match child {
+            ChildNumber::Hardened { index } => part.push_str(&format!("_{}h", index)),
+            ChildNumber::Normal { index } => part.push_str(&format!("_{}", index)),
+        }
+    }
+    part
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn split_account_path_works_01() {
+        let path = DerivationPath::from_str("m/84'/0'/0'/0/5").unwrap();
+        let (account, rest) = split_account_path(&path);
+        assert_eq!(account, DerivationPath::from_str("m/84'/0'/0'").unwrap());
+        assert_eq!(rest, DerivationPath::from_str("m/0/5").unwrap());
+
+        let path = DerivationPath::from_str("m/0/0'/5/8'").unwrap();
+        let (account, rest) = split_account_path(&path);
+        assert_eq!(account, path);
+        assert!(rest.is_master());
+
+        let path = DerivationPath::from_str("m/0/1").unwrap();
+        let (account, rest) = split_account_path(&path);
+        assert!(account.is_master());
+        assert_eq!(rest, path);
+    }
+
+    #[test]
+    fn account_descriptor_works_01() {
+        // BIP84 test vector.
+        let mnemonic = bip39::Mnemonic::from_str("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap();
+        let master = Xpriv::new_master(bitcoin::Network::Bitcoin, &mnemonic.to_seed("")).unwrap();
+        let path = DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap();
+        let descriptor = account_descriptor(&master, &path, &CoveredDescriptors::P2wpkh).unwrap();
+        assert_eq!(
+            descriptor.to_string(),
+            "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)#wc3n3van"
+        );
+        assert_eq!(
+            descriptor
+                .at_derivation_index(0)
+                .unwrap()
+                .address(bitcoin::Network::Bitcoin)
+                .unwrap()
+                .to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+    }
+
+    #[test]
+    fn account_private_descriptor_works_01() {
+        let mnemonic = bip39::Mnemonic::from_str("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap();
+        let master = Xpriv::new_master(bitcoin::Network::Bitcoin, &mnemonic.to_seed("")).unwrap();
+        let path = DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap();
+        let private_descriptor =
+            account_private_descriptor(&master, &path, &CoveredDescriptors::P2wpkh).unwrap();
+        assert!(private_descriptor.starts_with("wpkh([73c5da0a/84'/0'/0']xprv"));
+        assert!(private_descriptor.contains("/0/*)#"));
+        let (descriptor, key_map) =
+            Descriptor::parse_descriptor(&Secp256k1::new(), &private_descriptor).unwrap();
+        assert_eq!(key_map.len(), 1);
+        assert_eq!(
+            descriptor,
+            account_descriptor(&master, &path, &CoveredDescriptors::P2wpkh).unwrap()
+        );
+    }
+
+    #[test]
+    fn path_to_file_name_part_works_01() {
+        let path = DerivationPath::from_str("m/84'/0'/0'/1").unwrap();
+        assert_eq!(path_to_file_name_part(&path), "m_84h_0h_0h_1");
+    }
+}
@@ -0,0 +1,40 @@
+use bitcoin::bip32::Xpriv;
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::{error::RetrieverError, path_pairs::PathDescriptorPair};
+
+use super::{account_private_descriptor, found_accounts, split_account_path};
+
+/// The private ranged descriptor of an account with finds, covering the branch of its finds,
+/// e.g. `wpkh([fingerprint/84'/0'/0']xprv.../0/*)`, to restore the whole account in a descriptor
+/// wallet in one import. It is zeroized when dropped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct FoundAccountDescriptor {
+    account_path: String,
+    descriptor: String,
+}
+
+impl Drop for FoundAccountDescriptor {
+    fn drop(&mut self) {
+        self.descriptor.zeroize();
+    }
+}
+
+/// The private descriptor of each account and branch among the finds, once for all its finds.
+pub fn private_descriptors_of_finds(
+    master_xpriv: &Xpriv,
+    finds: &[PathDescriptorPair],
+) -> Result<Vec<FoundAccountDescriptor>, RetrieverError> {
+    found_accounts(finds)
+        .into_iter()
+        .map(|(path, descriptor_type)| {
+            Ok(FoundAccountDescriptor {
+                account_path: split_account_path(&path).0.to_string(),
+                descriptor: account_private_descriptor(master_xpriv, &path, &descriptor_type)?,
+            })
+        })
+        .collect()
+}
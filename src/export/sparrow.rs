@@ -0,0 +1,249 @@
+use bitcoin::{
+    bip32::{ChildNumber, DerivationPath, Xpriv, Xpub},
+    key::Secp256k1,
+};
+use getset::Getters;
+use miniscript::{
+    descriptor::{DerivPaths, DescriptorMultiXKey, Wildcard},
+    Descriptor, DescriptorPublicKey, ForEachKey,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    covered_descriptors::CoveredDescriptors,
+    descriptor_checksum::with_checksum,
+    error::RetrieverError,
+    path_pairs::{PathDescriptorPair, PathScanResultDescriptorTrio},
+};
+
+use super::{account_descriptor, found_accounts, path_to_file_name_part, split_account_path};
+
+/// A wallet export in the json format of Specter Desktop wallet files, which Sparrow imports
+/// (File > Import Wallet > Specter Desktop). Each export covers one account in which funds were
+/// found, with its receive and change branches in a single multipath descriptor.
+#[derive(Debug, Serialize, Deserialize, Getters, Clone, PartialEq, Eq)]
+#[get = "pub with_prefix"]
+pub struct SparrowWalletExport {
+    label: String,
+    /// The height from which Sparrow should look for the wallet's transactions.
+    blockheight: u64,
+    descriptor: String,
+}
+
+impl SparrowWalletExport {
+    /// Creates one export per account and descriptor type among the finds, covering both its
+    /// receive (0) and change (1) branches. Accounts with finds on other branches get one export
+    /// per branch instead, as a Sparrow wallet only derives those two. If details of finds are
+    /// given, the block height of the oldest utxo of each export is used as its birthday.
+    pub fn from_finds(
+        master_xpriv: &Xpriv,
+        finds: &[PathDescriptorPair],
+        detailed_finds: Option<&[PathScanResultDescriptorTrio]>,
+    ) -> Result<Vec<Self>, RetrieverError> {
+        let mut accounts: Vec<(DerivationPath, CoveredDescriptors, Vec<DerivationPath>)> = vec![];
+        for (path, descriptor_type) in found_accounts(finds) {
+            let (account_path, _) = split_account_path(&path);
+            match accounts.iter_mut().find(|(other_path, other_type, _)| {
+                *other_path == account_path && *other_type == descriptor_type
+            }) {
+                Some((_, _, paths)) => paths.push(path),
+                None => accounts.push((account_path, descriptor_type, vec![path])),
+            }
+        }
+        let mut wallets = vec![];
+        for (account_path, descriptor_type, paths) in accounts {
+            if paths.iter().all(is_on_receive_or_change_branch) {
+                let descriptor =
+                    account_multipath_descriptor(master_xpriv, &account_path, &descriptor_type)?;
+                wallets.push((account_path, descriptor_type, descriptor));
+            } else {
+                for path in paths {
+                    let descriptor = account_descriptor(master_xpriv, &path, &descriptor_type)?;
+                    let branch_path = match path.as_ref().split_last() {
+                        Some((_, branch_path)) if branch_path.len() > account_path.len() => {
+                            DerivationPath::from(branch_path.to_vec())
+                        }
+                        _ => account_path.clone(),
+                    };
+                    wallets.push((branch_path, descriptor_type.clone(), descriptor));
+                }
+            }
+        }
+        let mut exports = vec![];
+        for (label_path, descriptor_type, descriptor) in wallets {
+            let blockheight = match detailed_finds {
+                Some(detailed_finds) => {
+                    account_birth_height(&descriptor, &descriptor_type, detailed_finds)
+                }
+                None => 0,
+            };
+            exports.push(SparrowWalletExport {
+                label: format!(
+                    "Retrieved {:?} {}",
+                    descriptor_type,
+                    path_to_file_name_part(&label_path)
+                ),
+                blockheight,
+                descriptor: with_checksum(&descriptor.to_string())?,
+            });
+        }
+        Ok(exports)
+    }
+
+    /// Marks the export as coming from a search that did not cover the whole exploration path.
+    pub fn mark_partial(&mut self) {
+        self.label = format!("Partially {}", self.label.to_lowercase());
+    }
+
+    /// A file name derived from the label, e.g. sparrow_p2wpkh_m_84h_0h_0h.json, or
+    /// sparrow_p2wpkh_m_84h_0h_0h_5.json for the export of branch 5 of that account, prefixed by
+    /// partial_ for partial exports.
+    pub fn file_name(&self) -> String {
+        let label_words = self.label.split(' ').collect::<Vec<&str>>();
+        format!(
+            "sparrow_{}{}.json",
+            if self.label.starts_with("Partially") {
+                "partial_"
+            } else {
+                ""
+            },
+            label_words[label_words.len() - 2..]
+                .join("_")
+                .to_lowercase()
+        )
+    }
+}
+
+/// Whether the path is a key of the receive or change branch of its account, e.g.
+/// m/84'/0'/0'/1/5.
+fn is_on_receive_or_change_branch(path: &DerivationPath) -> bool {
+    let (_, normal_children) = split_account_path(path);
+    matches!(
+        normal_children.as_ref(),
+        [ChildNumber::Normal { index: 0 | 1 }, _]
+    )
+}
+
+/// The account descriptor covering both the receive and change branches of the account, e.g.
+/// wpkh([73c5da0a/84'/0'/0']xpub.../<0;1>/*).
+fn account_multipath_descriptor(
+    master_xpriv: &Xpriv,
+    account_path: &DerivationPath,
+    descriptor_type: &CoveredDescriptors,
+) -> Result<Descriptor<DescriptorPublicKey>, RetrieverError> {
+    let secp = Secp256k1::new();
+    let account_xpub = Xpub::from_priv(&secp, &master_xpriv.derive_priv(&secp, account_path)?);
+    let branches = [0, 1]
+        .map(|index| DerivationPath::from(vec![ChildNumber::Normal { index }]))
+        .to_vec();
+    let key = DescriptorPublicKey::MultiXPub(DescriptorMultiXKey {
+        origin: Some((master_xpriv.fingerprint(&secp), account_path.clone())),
+        xkey: account_xpub,
+        derivation_paths: DerivPaths::new(branches).unwrap(),
+        wildcard: Wildcard::Unhardened,
+    });
+    descriptor_type.to_descriptor(key)
+}
+
+fn account_birth_height(
+    descriptor: &Descriptor<DescriptorPublicKey>,
+    descriptor_type: &CoveredDescriptors,
+    detailed_finds: &[PathScanResultDescriptorTrio],
+) -> u64 {
+    detailed_finds
+        .iter()
+        .filter(|detail| {
+            CoveredDescriptors::from_descriptor(&detail.2).as_ref() == Some(descriptor_type)
+                && is_covered_by(descriptor, &detail.0)
+        })
+        .flat_map(|detail| detail.1.unspents.iter().map(|utxo| utxo.height))
+        .min()
+        .unwrap_or_default()
+}
+
+fn is_covered_by(descriptor: &Descriptor<DescriptorPublicKey>, path: &DerivationPath) -> bool {
+    let mut covered = false;
+    descriptor.for_each_key(|key| {
+        let (origin, derivation_paths, wildcard) = match key {
+            DescriptorPublicKey::XPub(xkey) => (
+                &xkey.origin,
+                vec![xkey.derivation_path.clone()],
+                xkey.wildcard,
+            ),
+            DescriptorPublicKey::MultiXPub(xkey) => (
+                &xkey.origin,
+                xkey.derivation_paths.paths().clone(),
+                xkey.wildcard,
+            ),
+            DescriptorPublicKey::Single(_) => return true,
+        };
+        covered = derivation_paths.iter().any(|derivation_path| {
+            let origin_path = match origin {
+                Some((_, origin_path)) => origin_path.extend(derivation_path),
+                None => derivation_path.clone(),
+            };
+            match wildcard {
+                Wildcard::None => *path == origin_path,
+                _ => {
+                    path.len() == origin_path.len() + 1
+                        && path.as_ref().starts_with(origin_path.as_ref())
+                }
+            }
+        });
+        true
+    });
+    covered
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::PublicKey;
+
+    use super::*;
+
+    #[test]
+    fn from_finds_works_01() {
+        let mnemonic = bip39::Mnemonic::from_str("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap();
+        let master = Xpriv::new_master(bitcoin::Network::Bitcoin, &mnemonic.to_seed("")).unwrap();
+        let secp = Secp256k1::new();
+        let find = |path: &str| {
+            let path = DerivationPath::from_str(path).unwrap();
+            let key = PublicKey::new(
+                master
+                    .derive_priv(&secp, &path)
+                    .unwrap()
+                    .private_key
+                    .public_key(&secp),
+            );
+            PathDescriptorPair::new(path, CoveredDescriptors::P2wpkh.to_descriptor(key).unwrap())
+        };
+
+        let finds = [
+            find("m/84'/0'/0'/0/3"),
+            find("m/84'/0'/0'/1/7"),
+            find("m/84'/0'/0'/0/9"),
+        ];
+        let exports = SparrowWalletExport::from_finds(&master, &finds, None).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].file_name(), "sparrow_p2wpkh_m_84h_0h_0h.json");
+        assert!(exports[0].get_descriptor().starts_with(
+            "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/<0;1>/*)#"
+        ));
+
+        let finds = [find("m/84'/0'/0'/0/3"), find("m/84'/0'/0'/5/1")];
+        let exports = SparrowWalletExport::from_finds(&master, &finds, None).unwrap();
+        assert_eq!(
+            exports
+                .iter()
+                .map(|export| export.file_name())
+                .collect::<Vec<_>>(),
+            [
+                "sparrow_p2wpkh_m_84h_0h_0h_0.json",
+                "sparrow_p2wpkh_m_84h_0h_0h_5.json"
+            ]
+        );
+        assert!(exports[1].get_descriptor().contains("/5/*)#"));
+    }
+}
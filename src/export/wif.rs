@@ -0,0 +1,117 @@
+use bitcoin::bip32::{DerivationPath, Xpriv};
+use getset::Getters;
+use hashbrown::HashMap;
+use miniscript::ForEachKey;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use zeroize::Zeroize;
+
+use crate::{
+    error::RetrieverError,
+    explorer::{auxiliaries::SECP, bip38::from_private_key_to_bip38},
+    path_pairs::PathDescriptorPair,
+};
+
+/// The private key of a find, to sweep its funds with other wallet software. The key is in WIF,
+/// or BIP38 encrypted ("6P...") when exported with a password, and is zeroized when dropped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct FoundWif {
+    path: String,
+    descriptor: String,
+    wif: String,
+    encrypted: bool,
+}
+
+impl Drop for FoundWif {
+    fn drop(&mut self) {
+        self.wif.zeroize();
+    }
+}
+
+/// Derives the private key of each find from the master key at its path. Finds whose descriptor
+/// does not hold the key of their path, such as those of standalone keys at m, are skipped. As
+/// BIP38 encryption is slow, each path is encrypted once for all its descriptors.
+pub fn wifs_of_finds(
+    master_xpriv: &Xpriv,
+    finds: &[PathDescriptorPair],
+    password: Option<&str>,
+) -> Result<Vec<FoundWif>, RetrieverError> {
+    let mut wifs_of_paths = HashMap::<DerivationPath, String>::new();
+    let mut wifs = vec![];
+    for find in finds {
+        let private_key = master_xpriv.derive_priv(&SECP, &find.0)?.to_priv();
+        let public_key = private_key.public_key(&SECP);
+        if !find.1.for_any_key(|key| *key == public_key) {
+            warn!(
+                "The key of the find at {} is not derived from the master key, so it is not exported.",
+                find.0
+            );
+            continue;
+        }
+        let wif = match wifs_of_paths.get(&find.0) {
+            Some(wif) => wif.clone(),
+            None => {
+                let wif = match password {
+                    Some(password) => from_private_key_to_bip38(&private_key, password)?,
+                    None => private_key.to_wif(),
+                };
+                wifs_of_paths.insert(find.0.clone(), wif.clone());
+                wif
+            }
+        };
+        wifs.push(FoundWif {
+            path: find.0.to_string(),
+            descriptor: find.get_descriptor_string(),
+            wif,
+            encrypted: password.is_some(),
+        });
+    }
+    wifs_of_paths.values_mut().for_each(String::zeroize);
+    Ok(wifs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::PublicKey;
+    use miniscript::Descriptor;
+
+    use super::*;
+
+    #[test]
+    fn wifs_of_finds_works_01() {
+        let master_xpriv = Xpriv::new_master(bitcoin::Network::Bitcoin, &[7u8; 32]).unwrap();
+        let path = DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap();
+        let public_key = PublicKey::new(
+            master_xpriv
+                .derive_priv(&SECP, &path)
+                .unwrap()
+                .private_key
+                .public_key(&SECP),
+        );
+        let standalone_key = PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let finds = [
+            PathDescriptorPair::new(path.clone(), Descriptor::new_wpkh(public_key).unwrap()),
+            PathDescriptorPair::new(path.clone(), Descriptor::new_pkh(public_key).unwrap()),
+            PathDescriptorPair::new(
+                DerivationPath::master(),
+                Descriptor::new_pkh(standalone_key).unwrap(),
+            ),
+        ];
+        let wifs = wifs_of_finds(&master_xpriv, &finds, None).unwrap();
+        assert_eq!(wifs.len(), 2);
+        assert_eq!(wifs[0].get_wif(), wifs[1].get_wif());
+        assert_eq!(
+            bitcoin::PrivateKey::from_wif(wifs[0].get_wif())
+                .unwrap()
+                .public_key(&SECP),
+            public_key
+        );
+        assert!(!wifs[0].get_encrypted());
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::path_pairs::PathDescriptorPair;
+
+/// The streams finds are sent to as they are discovered, each fed through a channel of its own.
+#[derive(Debug, Clone, Default)]
+pub struct FindSubscribers {
+    senders: Arc<Mutex<Vec<mpsc::UnboundedSender<PathDescriptorPair>>>>,
+}
+
+impl FindSubscribers {
+    /// A stream of the finds sent from now on, until the subscribers are closed.
+    pub fn subscribe(&self) -> FindsStream {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.senders.lock().unwrap().push(sender);
+        FindsStream { receiver }
+    }
+
+    /// Sends the find to every stream, forgetting those that were dropped.
+    pub fn publish(&self, find: &PathDescriptorPair) {
+        self.senders
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.send(find.clone()).is_ok());
+    }
+
+    /// Ends the streams once they have yielded the finds sent so far.
+    pub fn close(&self) {
+        self.senders.lock().unwrap().clear();
+    }
+}
+
+/// The finds of a search as they are discovered, see `Retriever::finds_stream`.
+#[derive(Debug)]
+pub struct FindsStream {
+    receiver: mpsc::UnboundedReceiver<PathDescriptorPair>,
+}
+
+impl Stream for FindsStream {
+    type Item = PathDescriptorPair;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::poll_fn, str::FromStr};
+
+    use bitcoin::{bip32::DerivationPath, key::Secp256k1, secp256k1::SecretKey};
+    use miniscript::Descriptor;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn finds_stream_works_01() {
+        let find = PathDescriptorPair::new(
+            DerivationPath::from_str("m/84'/0'/0'/0/1").unwrap(),
+            Descriptor::new_wpkh(
+                SecretKey::from_slice(&[1u8; 32])
+                    .unwrap()
+                    .public_key(&Secp256k1::new())
+                    .into(),
+            )
+            .unwrap(),
+        );
+        let subscribers = FindSubscribers::default();
+        subscribers.publish(&find);
+        let mut stream = subscribers.subscribe();
+        let dropped = subscribers.subscribe();
+        drop(dropped);
+        subscribers.publish(&find);
+        assert_eq!(subscribers.senders.lock().unwrap().len(), 1);
+        subscribers.close();
+        assert_eq!(
+            poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await,
+            Some(find)
+        );
+        assert_eq!(
+            poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await,
+            None
+        );
+    }
+}
@@ -0,0 +1,566 @@
+// Derives the normal child of an extended public key and the hash160 of its compressed public
+// key, one child per thread. Field elements and scalars are eight 32 bit limbs, least
+// significant first.
+
+typedef unsigned char u8;
+typedef unsigned int u32;
+typedef unsigned long long u64;
+
+// The bytes of a parent: the x and y coordinates of its public key, its chain code and the index
+// of the child, all big endian.
+#define PARENT_LEN 100
+// The bytes of a result: whether the child is valid, the x and y coordinates of its public key,
+// big endian, and the hash160 of its compressed public key.
+#define RESULT_LEN 88
+
+typedef struct {
+    u32 v[8];
+} fe;
+
+typedef struct {
+    fe x, y, z;
+    int infinity;
+} jacobian;
+
+__device__ const u32 FIELD_P[8] = {0xFFFFFC2F, 0xFFFFFFFE, 0xFFFFFFFF, 0xFFFFFFFF,
+                                   0xFFFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF};
+__device__ const u32 FIELD_P_MINUS_2[8] = {0xFFFFFC2D, 0xFFFFFFFE, 0xFFFFFFFF, 0xFFFFFFFF,
+                                           0xFFFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF};
+__device__ const u32 CURVE_N[8] = {0xD0364141, 0xBFD25E8C, 0xAF48A03B, 0xBAAEDCE6,
+                                   0xFFFFFFFE, 0xFFFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF};
+
+__device__ int limbs_less_than(const u32 *a, const u32 *b) {
+    for (int i = 7; i >= 0; i--) {
+        if (a[i] != b[i]) {
+            return a[i] < b[i];
+        }
+    }
+    return 0;
+}
+
+__device__ int fe_is_zero(const fe *a) {
+    u32 bits = 0;
+    for (int i = 0; i < 8; i++) {
+        bits |= a->v[i];
+    }
+    return bits == 0;
+}
+
+__device__ void fe_from_be_bytes(fe *r, const u8 *bytes) {
+    for (int i = 0; i < 8; i++) {
+        const u8 *limb = bytes + 28 - 4 * i;
+        r->v[i] = ((u32)limb[0] << 24) | ((u32)limb[1] << 16) | ((u32)limb[2] << 8) | limb[3];
+    }
+}
+
+__device__ void fe_to_be_bytes(u8 *bytes, const fe *a) {
+    for (int i = 0; i < 8; i++) {
+        u8 *limb = bytes + 28 - 4 * i;
+        limb[0] = (u8)(a->v[i] >> 24);
+        limb[1] = (u8)(a->v[i] >> 16);
+        limb[2] = (u8)(a->v[i] >> 8);
+        limb[3] = (u8)a->v[i];
+    }
+}
+
+// Subtracts p once if the limbs, below 2p, are not below p.
+__device__ void fe_normalize(fe *r) {
+    if (limbs_less_than(r->v, FIELD_P)) {
+        return;
+    }
+    u64 borrow = 0;
+    for (int i = 0; i < 8; i++) {
+        u64 t = (u64)r->v[i] - FIELD_P[i] - borrow;
+        r->v[i] = (u32)t;
+        borrow = (t >> 63) & 1;
+    }
+}
+
+// Adds 2^256 mod p, 2^32 + 977, to limbs that wrapped past 2^256 and are now small.
+__device__ void fe_add_wrapped(fe *r) {
+    u64 c = (u64)r->v[0] + 977;
+    r->v[0] = (u32)c;
+    c = (c >> 32) + r->v[1] + 1;
+    r->v[1] = (u32)c;
+    c >>= 32;
+    for (int i = 2; i < 8 && c; i++) {
+        c += r->v[i];
+        r->v[i] = (u32)c;
+        c >>= 32;
+    }
+}
+
+__device__ void fe_add(fe *r, const fe *a, const fe *b) {
+    u64 c = 0;
+    for (int i = 0; i < 8; i++) {
+        c += (u64)a->v[i] + b->v[i];
+        r->v[i] = (u32)c;
+        c >>= 32;
+    }
+    if (c) {
+        fe_add_wrapped(r);
+    }
+    fe_normalize(r);
+}
+
+__device__ void fe_sub(fe *r, const fe *a, const fe *b) {
+    u64 borrow = 0;
+    for (int i = 0; i < 8; i++) {
+        u64 t = (u64)a->v[i] - b->v[i] - borrow;
+        r->v[i] = (u32)t;
+        borrow = (t >> 63) & 1;
+    }
+    if (borrow) {
+        u64 c = 0;
+        for (int i = 0; i < 8; i++) {
+            c += (u64)r->v[i] + FIELD_P[i];
+            r->v[i] = (u32)c;
+            c >>= 32;
+        }
+    }
+}
+
+// Multiplies and reduces with 2^256 = 2^32 + 977 mod p.
+__device__ void fe_mul(fe *r, const fe *a, const fe *b) {
+    u32 w[16];
+    for (int i = 0; i < 16; i++) {
+        w[i] = 0;
+    }
+    for (int i = 0; i < 8; i++) {
+        u64 c = 0;
+        for (int j = 0; j < 8; j++) {
+            c += (u64)a->v[i] * b->v[j] + w[i + j];
+            w[i + j] = (u32)c;
+            c >>= 32;
+        }
+        w[i + 8] = (u32)c;
+    }
+    u64 c = 0;
+    for (int i = 0; i < 8; i++) {
+        c += (u64)w[i] + (u64)w[i + 8] * 977 + (i > 0 ? w[i + 7] : 0);
+        r->v[i] = (u32)c;
+        c >>= 32;
+    }
+    u64 k = c + w[15];
+    u64 m = k * 977;
+    c = (u64)r->v[0] + (m & 0xFFFFFFFF);
+    r->v[0] = (u32)c;
+    c = (c >> 32) + r->v[1] + (m >> 32) + (k & 0xFFFFFFFF);
+    r->v[1] = (u32)c;
+    c = (c >> 32) + r->v[2] + (k >> 32);
+    r->v[2] = (u32)c;
+    c >>= 32;
+    for (int i = 3; i < 8; i++) {
+        c += r->v[i];
+        r->v[i] = (u32)c;
+        c >>= 32;
+    }
+    if (c) {
+        fe_add_wrapped(r);
+    }
+    fe_normalize(r);
+}
+
+__device__ void fe_inv(fe *r, const fe *a) {
+    fe result = {{1, 0, 0, 0, 0, 0, 0, 0}};
+    for (int i = 255; i >= 0; i--) {
+        fe_mul(&result, &result, &result);
+        if ((FIELD_P_MINUS_2[i / 32] >> (i % 32)) & 1) {
+            fe_mul(&result, &result, a);
+        }
+    }
+    *r = result;
+}
+
+__device__ void point_double(jacobian *p) {
+    if (p->infinity || fe_is_zero(&p->y)) {
+        p->infinity = 1;
+        return;
+    }
+    fe a, b, c, d, e, f, t;
+    fe_mul(&a, &p->x, &p->x);
+    fe_mul(&b, &p->y, &p->y);
+    fe_mul(&c, &b, &b);
+    fe_add(&t, &p->x, &b);
+    fe_mul(&d, &t, &t);
+    fe_sub(&d, &d, &a);
+    fe_sub(&d, &d, &c);
+    fe_add(&d, &d, &d);
+    fe_add(&e, &a, &a);
+    fe_add(&e, &e, &a);
+    fe_mul(&f, &e, &e);
+    fe_mul(&p->z, &p->y, &p->z);
+    fe_add(&p->z, &p->z, &p->z);
+    fe_sub(&p->x, &f, &d);
+    fe_sub(&p->x, &p->x, &d);
+    fe_sub(&t, &d, &p->x);
+    fe_mul(&p->y, &e, &t);
+    fe_add(&c, &c, &c);
+    fe_add(&c, &c, &c);
+    fe_add(&c, &c, &c);
+    fe_sub(&p->y, &p->y, &c);
+}
+
+// Adds the affine point (x, y) to p.
+__device__ void point_add_affine(jacobian *p, const fe *x, const fe *y) {
+    if (p->infinity) {
+        p->x = *x;
+        p->y = *y;
+        for (int i = 0; i < 8; i++) {
+            p->z.v[i] = i == 0;
+        }
+        p->infinity = 0;
+        return;
+    }
+    fe z1z1, u2, s2, h, r, hh, hhh, v, t;
+    fe_mul(&z1z1, &p->z, &p->z);
+    fe_mul(&u2, x, &z1z1);
+    fe_mul(&s2, y, &p->z);
+    fe_mul(&s2, &s2, &z1z1);
+    fe_sub(&h, &u2, &p->x);
+    fe_sub(&r, &s2, &p->y);
+    if (fe_is_zero(&h)) {
+        if (fe_is_zero(&r)) {
+            point_double(p);
+        } else {
+            p->infinity = 1;
+        }
+        return;
+    }
+    fe_mul(&hh, &h, &h);
+    fe_mul(&hhh, &h, &hh);
+    fe_mul(&v, &p->x, &hh);
+    fe_mul(&t, &r, &r);
+    fe_sub(&t, &t, &hhh);
+    fe_sub(&t, &t, &v);
+    fe_sub(&t, &t, &v);
+    fe_mul(&p->z, &p->z, &h);
+    fe_sub(&v, &v, &t);
+    fe_mul(&v, &r, &v);
+    fe_mul(&hhh, &p->y, &hhh);
+    fe_sub(&p->y, &v, &hhh);
+    p->x = t;
+}
+
+__device__ const u64 SHA512_K[80] = {
+    0x428a2f98d728ae22ULL, 0x7137449123ef65cdULL, 0xb5c0fbcfec4d3b2fULL, 0xe9b5dba58189dbbcULL,
+    0x3956c25bf348b538ULL, 0x59f111f1b605d019ULL, 0x923f82a4af194f9bULL, 0xab1c5ed5da6d8118ULL,
+    0xd807aa98a3030242ULL, 0x12835b0145706fbeULL, 0x243185be4ee4b28cULL, 0x550c7dc3d5ffb4e2ULL,
+    0x72be5d74f27b896fULL, 0x80deb1fe3b1696b1ULL, 0x9bdc06a725c71235ULL, 0xc19bf174cf692694ULL,
+    0xe49b69c19ef14ad2ULL, 0xefbe4786384f25e3ULL, 0x0fc19dc68b8cd5b5ULL, 0x240ca1cc77ac9c65ULL,
+    0x2de92c6f592b0275ULL, 0x4a7484aa6ea6e483ULL, 0x5cb0a9dcbd41fbd4ULL, 0x76f988da831153b5ULL,
+    0x983e5152ee66dfabULL, 0xa831c66d2db43210ULL, 0xb00327c898fb213fULL, 0xbf597fc7beef0ee4ULL,
+    0xc6e00bf33da88fc2ULL, 0xd5a79147930aa725ULL, 0x06ca6351e003826fULL, 0x142929670a0e6e70ULL,
+    0x27b70a8546d22ffcULL, 0x2e1b21385c26c926ULL, 0x4d2c6dfc5ac42aedULL, 0x53380d139d95b3dfULL,
+    0x650a73548baf63deULL, 0x766a0abb3c77b2a8ULL, 0x81c2c92e47edaee6ULL, 0x92722c851482353bULL,
+    0xa2bfe8a14cf10364ULL, 0xa81a664bbc423001ULL, 0xc24b8b70d0f89791ULL, 0xc76c51a30654be30ULL,
+    0xd192e819d6ef5218ULL, 0xd69906245565a910ULL, 0xf40e35855771202aULL, 0x106aa07032bbd1b8ULL,
+    0x19a4c116b8d2d0c8ULL, 0x1e376c085141ab53ULL, 0x2748774cdf8eeb99ULL, 0x34b0bcb5e19b48a8ULL,
+    0x391c0cb3c5c95a63ULL, 0x4ed8aa4ae3418acbULL, 0x5b9cca4f7763e373ULL, 0x682e6ff3d6b2b8a3ULL,
+    0x748f82ee5defb2fcULL, 0x78a5636f43172f60ULL, 0x84c87814a1f0ab72ULL, 0x8cc702081a6439ecULL,
+    0x90befffa23631e28ULL, 0xa4506cebde82bde9ULL, 0xbef9a3f7b2c67915ULL, 0xc67178f2e372532bULL,
+    0xca273eceea26619cULL, 0xd186b8c721c0c207ULL, 0xeada7dd6cde0eb1eULL, 0xf57d4f7fee6ed178ULL,
+    0x06f067aa72176fbaULL, 0x0a637dc5a2c898a6ULL, 0x113f9804bef90daeULL, 0x1b710b35131c471bULL,
+    0x28db77f523047d84ULL, 0x32caab7b40c72493ULL, 0x3c9ebe0a15c9bebcULL, 0x431d67c49c100d4cULL,
+    0x4cc5d4becb3e42b6ULL, 0x597f299cfc657e2aULL, 0x5fcb6fab3ad6faecULL, 0x6c44198c4a475817ULL};
+
+__device__ u64 rotr64(u64 x, int n) { return (x >> n) | (x << (64 - n)); }
+
+__device__ void sha512_init(u64 *state) {
+    state[0] = 0x6a09e667f3bcc908ULL;
+    state[1] = 0xbb67ae8584caa73bULL;
+    state[2] = 0x3c6ef372fe94f82bULL;
+    state[3] = 0xa54ff53a5f1d36f1ULL;
+    state[4] = 0x510e527fade682d1ULL;
+    state[5] = 0x9b05688c2b3e6c1fULL;
+    state[6] = 0x1f83d9abfb41bd6bULL;
+    state[7] = 0x5be0cd19137e2179ULL;
+}
+
+__device__ void sha512_compress(u64 *state, const u8 *block) {
+    u64 w[80];
+    for (int i = 0; i < 16; i++) {
+        w[i] = 0;
+        for (int j = 0; j < 8; j++) {
+            w[i] = (w[i] << 8) | block[8 * i + j];
+        }
+    }
+    for (int i = 16; i < 80; i++) {
+        u64 s0 = rotr64(w[i - 15], 1) ^ rotr64(w[i - 15], 8) ^ (w[i - 15] >> 7);
+        u64 s1 = rotr64(w[i - 2], 19) ^ rotr64(w[i - 2], 61) ^ (w[i - 2] >> 6);
+        w[i] = w[i - 16] + s0 + w[i - 7] + s1;
+    }
+    u64 a = state[0], b = state[1], c = state[2], d = state[3];
+    u64 e = state[4], f = state[5], g = state[6], h = state[7];
+    for (int i = 0; i < 80; i++) {
+        u64 t1 = h + (rotr64(e, 14) ^ rotr64(e, 18) ^ rotr64(e, 41)) + ((e & f) ^ (~e & g)) +
+                 SHA512_K[i] + w[i];
+        u64 t2 = (rotr64(a, 28) ^ rotr64(a, 34) ^ rotr64(a, 39)) + ((a & b) ^ (a & c) ^ (b & c));
+        h = g;
+        g = f;
+        f = e;
+        e = d + t1;
+        d = c;
+        c = b;
+        b = a;
+        a = t1 + t2;
+    }
+    state[0] += a;
+    state[1] += b;
+    state[2] += c;
+    state[3] += d;
+    state[4] += e;
+    state[5] += f;
+    state[6] += g;
+    state[7] += h;
+}
+
+// Compresses one more block holding the last bytes of a message that follows whole blocks, with
+// its padding and total length in bits.
+__device__ void sha512_final_block(u64 *state, const u8 *tail, int tail_len, u64 total_len) {
+    u8 block[128];
+    for (int i = 0; i < 128; i++) {
+        block[i] = i < tail_len ? tail[i] : 0;
+    }
+    block[tail_len] = 0x80;
+    u64 bits = total_len * 8;
+    for (int i = 0; i < 8; i++) {
+        block[127 - i] = (u8)(bits >> (8 * i));
+    }
+    sha512_compress(state, block);
+}
+
+// HMAC-SHA512 keyed with a chain code of 32 bytes over a message of at most 64 bytes.
+__device__ void hmac_sha512(u8 *out, const u8 *key, const u8 *message, int message_len) {
+    u8 pad[128];
+    u64 inner[8], outer[8];
+    for (int i = 0; i < 128; i++) {
+        pad[i] = (i < 32 ? key[i] : 0) ^ 0x36;
+    }
+    sha512_init(inner);
+    sha512_compress(inner, pad);
+    sha512_final_block(inner, message, message_len, 128 + message_len);
+    u8 inner_hash[64];
+    for (int i = 0; i < 64; i++) {
+        inner_hash[i] = (u8)(inner[i / 8] >> (56 - 8 * (i % 8)));
+    }
+    for (int i = 0; i < 128; i++) {
+        pad[i] = (i < 32 ? key[i] : 0) ^ 0x5c;
+    }
+    sha512_init(outer);
+    sha512_compress(outer, pad);
+    sha512_final_block(outer, inner_hash, 64, 128 + 64);
+    for (int i = 0; i < 64; i++) {
+        out[i] = (u8)(outer[i / 8] >> (56 - 8 * (i % 8)));
+    }
+}
+
+__device__ const u32 SHA256_K[64] = {
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2};
+
+__device__ u32 rotr32(u32 x, int n) { return (x >> n) | (x << (32 - n)); }
+
+// SHA256 of a compressed public key, which fits one block with its padding.
+__device__ void sha256_of_compressed_key(u8 *out, const u8 *key) {
+    u32 w[64];
+    u8 block[64];
+    for (int i = 0; i < 64; i++) {
+        block[i] = i < 33 ? key[i] : 0;
+    }
+    block[33] = 0x80;
+    block[62] = (33 * 8) >> 8;
+    block[63] = (u8)(33 * 8);
+    for (int i = 0; i < 16; i++) {
+        w[i] = ((u32)block[4 * i] << 24) | ((u32)block[4 * i + 1] << 16) |
+               ((u32)block[4 * i + 2] << 8) | block[4 * i + 3];
+    }
+    for (int i = 16; i < 64; i++) {
+        u32 s0 = rotr32(w[i - 15], 7) ^ rotr32(w[i - 15], 18) ^ (w[i - 15] >> 3);
+        u32 s1 = rotr32(w[i - 2], 17) ^ rotr32(w[i - 2], 19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16] + s0 + w[i - 7] + s1;
+    }
+    u32 state[8] = {0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+                    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19};
+    u32 a = state[0], b = state[1], c = state[2], d = state[3];
+    u32 e = state[4], f = state[5], g = state[6], h = state[7];
+    for (int i = 0; i < 64; i++) {
+        u32 t1 = h + (rotr32(e, 6) ^ rotr32(e, 11) ^ rotr32(e, 25)) + ((e & f) ^ (~e & g)) +
+                 SHA256_K[i] + w[i];
+        u32 t2 = (rotr32(a, 2) ^ rotr32(a, 13) ^ rotr32(a, 22)) + ((a & b) ^ (a & c) ^ (b & c));
+        h = g;
+        g = f;
+        f = e;
+        e = d + t1;
+        d = c;
+        c = b;
+        b = a;
+        a = t1 + t2;
+    }
+    state[0] += a;
+    state[1] += b;
+    state[2] += c;
+    state[3] += d;
+    state[4] += e;
+    state[5] += f;
+    state[6] += g;
+    state[7] += h;
+    for (int i = 0; i < 32; i++) {
+        out[i] = (u8)(state[i / 4] >> (24 - 8 * (i % 4)));
+    }
+}
+
+__device__ const int RIPEMD160_R[80] = {
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 7, 4, 13, 1, 10, 6, 15, 3, 12, 0, 9, 5,
+    2, 14, 11, 8, 3, 10, 14, 4, 9, 15, 8, 1, 2, 7, 0, 6, 13, 11, 5, 12, 1, 9, 11, 10, 0, 8, 12, 4,
+    13, 3, 7, 15, 14, 5, 6, 2, 4, 0, 5, 9, 7, 12, 2, 10, 14, 1, 3, 8, 11, 6, 15, 13};
+__device__ const int RIPEMD160_R_PRIME[80] = {
+    5, 14, 7, 0, 9, 2, 11, 4, 13, 6, 15, 8, 1, 10, 3, 12, 6, 11, 3, 7, 0, 13, 5, 10, 14, 15, 8, 12,
+    4, 9, 1, 2, 15, 5, 1, 3, 7, 14, 6, 9, 11, 8, 12, 2, 10, 0, 4, 13, 8, 6, 4, 1, 3, 11, 15, 0, 5,
+    12, 2, 13, 9, 7, 10, 14, 12, 15, 10, 4, 1, 5, 8, 7, 6, 2, 13, 14, 0, 3, 9, 11};
+__device__ const int RIPEMD160_S[80] = {
+    11, 14, 15, 12, 5, 8, 7, 9, 11, 13, 14, 15, 6, 7, 9, 8, 7, 6, 8, 13, 11, 9, 7, 15, 7, 12, 15,
+    9, 11, 7, 13, 12, 11, 13, 6, 7, 14, 9, 13, 15, 14, 8, 13, 6, 5, 12, 7, 5, 11, 12, 14, 15, 14,
+    15, 9, 8, 9, 14, 5, 6, 8, 6, 5, 12, 9, 15, 5, 11, 6, 8, 13, 12, 5, 12, 13, 14, 11, 8, 5, 6};
+__device__ const int RIPEMD160_S_PRIME[80] = {
+    8, 9, 9, 11, 13, 15, 15, 5, 7, 7, 8, 11, 14, 14, 12, 6, 9, 13, 15, 7, 12, 8, 9, 11, 7, 7, 12,
+    7, 6, 15, 13, 11, 9, 7, 15, 11, 8, 6, 6, 14, 12, 13, 5, 14, 13, 13, 7, 5, 15, 5, 8, 11, 14,
+    14, 6, 14, 6, 9, 12, 9, 12, 5, 15, 8, 8, 5, 12, 9, 12, 5, 14, 6, 8, 13, 6, 5, 15, 13, 11, 11};
+__device__ const u32 RIPEMD160_K[5] = {0x00000000, 0x5a827999, 0x6ed9eba1, 0x8f1bbcdc, 0xa953fd4e};
+__device__ const u32 RIPEMD160_K_PRIME[5] = {0x50a28be6, 0x5c4dd124, 0x6d703ef3, 0x7a6d76e9,
+                                             0x00000000};
+
+__device__ u32 rotl32(u32 x, int n) { return (x << n) | (x >> (32 - n)); }
+
+__device__ u32 ripemd160_f(int round, u32 x, u32 y, u32 z) {
+    switch (round) {
+    case 0:
+        return x ^ y ^ z;
+    case 1:
+        return (x & y) | (~x & z);
+    case 2:
+        return (x | ~y) ^ z;
+    case 3:
+        return (x & z) | (y & ~z);
+    default:
+        return x ^ (y | ~z);
+    }
+}
+
+// RIPEMD160 of a SHA256 digest, which fits one block with its padding.
+__device__ void ripemd160_of_digest(u8 *out, const u8 *digest) {
+    u32 x[16];
+    for (int i = 0; i < 16; i++) {
+        x[i] = 0;
+    }
+    for (int i = 0; i < 8; i++) {
+        x[i] = (u32)digest[4 * i] | ((u32)digest[4 * i + 1] << 8) | ((u32)digest[4 * i + 2] << 16) |
+               ((u32)digest[4 * i + 3] << 24);
+    }
+    x[8] = 0x80;
+    x[14] = 32 * 8;
+    u32 h[5] = {0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0};
+    u32 a = h[0], b = h[1], c = h[2], d = h[3], e = h[4];
+    u32 ap = h[0], bp = h[1], cp = h[2], dp = h[3], ep = h[4];
+    for (int j = 0; j < 80; j++) {
+        int round = j / 16;
+        u32 t = rotl32(a + ripemd160_f(round, b, c, d) + x[RIPEMD160_R[j]] + RIPEMD160_K[round],
+                       RIPEMD160_S[j]) +
+                e;
+        a = e;
+        e = d;
+        d = rotl32(c, 10);
+        c = b;
+        b = t;
+        t = rotl32(ap + ripemd160_f(4 - round, bp, cp, dp) + x[RIPEMD160_R_PRIME[j]] +
+                       RIPEMD160_K_PRIME[round],
+                   RIPEMD160_S_PRIME[j]) +
+            ep;
+        ap = ep;
+        ep = dp;
+        dp = rotl32(cp, 10);
+        cp = bp;
+        bp = t;
+    }
+    u32 t = h[1] + c + dp;
+    h[1] = h[2] + d + ep;
+    h[2] = h[3] + e + ap;
+    h[3] = h[4] + a + bp;
+    h[4] = h[0] + b + cp;
+    h[0] = t;
+    for (int i = 0; i < 20; i++) {
+        out[i] = (u8)(h[i / 4] >> (8 * (i % 4)));
+    }
+}
+
+// The generator table holds 2^i G for i from 0 to 255, each as x then y.
+__device__ void derive_one(const u8 *parent, const u32 *generator_table, u8 *result) {
+    for (int i = 0; i < RESULT_LEN; i++) {
+        result[i] = 0;
+    }
+    fe parent_x, parent_y;
+    fe_from_be_bytes(&parent_x, parent);
+    fe_from_be_bytes(&parent_y, parent + 32);
+    u8 message[37];
+    message[0] = 0x02 | (parent_y.v[0] & 1);
+    for (int i = 0; i < 32; i++) {
+        message[1 + i] = parent[i];
+    }
+    for (int i = 0; i < 4; i++) {
+        message[33 + i] = parent[96 + i];
+    }
+    u8 hmac[64];
+    hmac_sha512(hmac, parent + 64, message, 37);
+    fe tweak;
+    fe_from_be_bytes(&tweak, hmac);
+    if (!limbs_less_than(tweak.v, CURVE_N)) {
+        return;
+    }
+    jacobian child;
+    child.infinity = 1;
+    point_add_affine(&child, &parent_x, &parent_y);
+    for (int i = 0; i < 256; i++) {
+        if ((tweak.v[i / 32] >> (i % 32)) & 1) {
+            fe x, y;
+            for (int j = 0; j < 8; j++) {
+                x.v[j] = generator_table[16 * i + j];
+                y.v[j] = generator_table[16 * i + 8 + j];
+            }
+            point_add_affine(&child, &x, &y);
+        }
+    }
+    if (child.infinity) {
+        return;
+    }
+    fe z_inv, z_inv2, x, y;
+    fe_inv(&z_inv, &child.z);
+    fe_mul(&z_inv2, &z_inv, &z_inv);
+    fe_mul(&x, &child.x, &z_inv2);
+    fe_mul(&z_inv2, &z_inv2, &z_inv);
+    fe_mul(&y, &child.y, &z_inv2);
+    result[0] = 1;
+    fe_to_be_bytes(result + 1, &x);
+    fe_to_be_bytes(result + 33, &y);
+    u8 compressed[33];
+    compressed[0] = 0x02 | (y.v[0] & 1);
+    for (int i = 0; i < 32; i++) {
+        compressed[1 + i] = result[1 + i];
+    }
+    u8 sha256[32];
+    sha256_of_compressed_key(sha256, compressed);
+    ripemd160_of_digest(result + 65, sha256);
+}
+
+extern "C" __global__ void derive_and_hash(const u8 *parents, const u32 *generator_table,
+                                           u8 *results, u32 count) {
+    u32 i = blockIdx.x * blockDim.x + threadIdx.x;
+    if (i < count) {
+        derive_one(parents + (u64)i * PARENT_LEN, generator_table, results + (u64)i * RESULT_LEN);
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use bitcoin::{
+    bip32::{ChildNumber, Xpub},
+    hashes::{hash160, Hash},
+    secp256k1::{self, SecretKey},
+    PublicKey,
+};
+use cudarc::{
+    driver::{CudaContext, CudaFunction, CudaSlice, CudaStream, LaunchConfig, PushKernelArg},
+    nvrtc,
+};
+use tracing::{info, warn};
+
+use crate::{
+    error::RetrieverError,
+    explorer::{auxiliaries::SECP, explorer_key::ParentKey},
+    path_pairs::PathParentKeyPair,
+};
+
+/// The kernel deriving the normal children of xpubs and the hash160s of their keys.
+const KERNEL_SOURCE: &str = include_str!("derive_and_hash.cu");
+const KERNEL_NAME: &str = "derive_and_hash";
+/// The bytes of a child handed to the kernel: the coordinates of the parent key, its chain code
+/// and the index of the child.
+const PARENT_LEN: usize = 100;
+/// The bytes of a child the kernel hands back: whether it is valid, the coordinates of its key
+/// and its hash160, padded.
+const RESULT_LEN: usize = 88;
+const THREADS_PER_BLOCK: u32 = 128;
+/// The children derived on both the GPU and the CPU before the GPU is used, which must agree.
+const SELF_CHECK_CHILDREN: u32 = 64;
+
+/// A CUDA device deriving normal children of xpubs and hashing their keys, the bulk of the work
+/// of a search whose explored paths end in normal steps. The kernel is compiled with NVRTC when
+/// the deriver is set up, so neither the CUDA toolkit nor a GPU is needed to build the retriever.
+pub struct GpuDeriver {
+    stream: Arc<CudaStream>,
+    function: CudaFunction,
+    /// 2^i G for i from 0 to 255, which the tweak of each child is summed from.
+    generator_table: CudaSlice<u32>,
+    /// Launches are serialized, the search hands the device one batch at a time anyway.
+    launch: Mutex<()>,
+}
+
+impl GpuDeriver {
+    /// Sets up the first CUDA device and checks its derivations against the CPU. Fails when the
+    /// driver or NVRTC libraries are missing, there is no device, or the checks disagree.
+    pub fn new() -> Result<Self, RetrieverError> {
+        // Loading a missing library panics in cudarc, so they are looked for first.
+        let libraries_present = unsafe {
+            cudarc::driver::sys::is_culib_present() && cudarc::nvrtc::sys::is_culib_present()
+        };
+        if !libraries_present {
+            return Err(RetrieverError::GpuUnavailable);
+        }
+        let context = CudaContext::new(0)?;
+        let module = context.load_module(nvrtc::compile_ptx(KERNEL_SOURCE)?)?;
+        let function = module.load_function(KERNEL_NAME)?;
+        let stream = context.default_stream();
+        let generator_table = stream.clone_htod(&generator_table())?;
+        let deriver = GpuDeriver {
+            stream,
+            function,
+            generator_table,
+            launch: Mutex::new(()),
+        };
+        deriver.self_check()?;
+        info!(
+            "Deriving normal children on the GPU {}.",
+            context.name().unwrap_or_default()
+        );
+        Ok(deriver)
+    }
+
+    /// Derives the children of the xpubs on the device and returns their keys with the hash160s
+    /// of the compressed keys, or none for the invalid children the CPU refuses too.
+    pub fn derive_and_hash(
+        &self,
+        children: &[(&Xpub, u32)],
+    ) -> Result<Vec<Option<(PublicKey, hash160::Hash)>>, RetrieverError> {
+        if children.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut parents = Vec::with_capacity(children.len() * PARENT_LEN);
+        for (xpub, index) in children {
+            parents.extend_from_slice(&xpub.public_key.serialize_uncompressed()[1..]);
+            parents.extend_from_slice(xpub.chain_code.as_bytes());
+            parents.extend_from_slice(&index.to_be_bytes());
+        }
+        let count = children.len() as u32;
+        let results = {
+            let _launch = self.launch.lock().unwrap();
+            let parents = self.stream.clone_htod(&parents)?;
+            let mut results = self.stream.alloc_zeros::<u8>(children.len() * RESULT_LEN)?;
+            let config = LaunchConfig {
+                grid_dim: (count.div_ceil(THREADS_PER_BLOCK), 1, 1),
+                block_dim: (THREADS_PER_BLOCK, 1, 1),
+                shared_mem_bytes: 0,
+            };
+            let mut launch = self.stream.launch_builder(&self.function);
+            launch
+                .arg(&parents)
+                .arg(&self.generator_table)
+                .arg(&mut results)
+                .arg(&count);
+            unsafe { launch.launch(config) }?;
+            self.stream.clone_dtoh(&results)?
+        };
+        Ok(results
+            .chunks_exact(RESULT_LEN)
+            .map(|result| {
+                if result[0] != 1 {
+                    return None;
+                }
+                let mut uncompressed = [4u8; 65];
+                uncompressed[1..].copy_from_slice(&result[1..65]);
+                let pubkey = PublicKey::new(secp256k1::PublicKey::from_slice(&uncompressed).ok()?);
+                Some((pubkey, hash160::Hash::from_slice(&result[65..85]).ok()?))
+            })
+            .collect())
+    }
+
+    /// Derives children of a fixed xpub on both the device and the CPU, and fails unless they
+    /// agree, so a faulty driver or device never costs finds.
+    fn self_check(&self) -> Result<(), RetrieverError> {
+        let xpub = Xpub::from_priv(
+            &SECP,
+            &bitcoin::bip32::Xpriv::new_master(bitcoin::Network::Bitcoin, &[7u8; 32])?,
+        );
+        let indexes = (0..SELF_CHECK_CHILDREN)
+            .map(|i| i.wrapping_mul(0x9e37_79b9) & 0x7fff_ffff)
+            .collect::<Vec<_>>();
+        let derived = self.derive_and_hash(
+            &indexes
+                .iter()
+                .map(|index| (&xpub, *index))
+                .collect::<Vec<_>>(),
+        )?;
+        for (index, derived) in indexes.iter().zip(derived) {
+            let child = PublicKey::new(
+                xpub.ckd_pub(&SECP, ChildNumber::from_normal_idx(*index)?)?
+                    .public_key,
+            );
+            if derived != Some((child, child.pubkey_hash().to_raw_hash())) {
+                return Err(RetrieverError::GpuSelfCheckFailed);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The GPU deriver of the process, set up on first use, or none without a usable device, in
+/// which case keys are derived on the CPU as without the gpu feature.
+pub fn gpu_deriver() -> Option<&'static GpuDeriver> {
+    static GPU_DERIVER: OnceLock<Option<GpuDeriver>> = OnceLock::new();
+    GPU_DERIVER
+        .get_or_init(
+            || match panic::catch_unwind(AssertUnwindSafe(GpuDeriver::new)) {
+                Ok(Ok(deriver)) => Some(deriver),
+                Ok(Err(err)) => {
+                    warn!("No usable GPU ({:?}), deriving on the CPU.", err);
+                    None
+                }
+                Err(_) => {
+                    warn!("Setting up the GPU failed, deriving on the CPU.");
+                    None
+                }
+            },
+        )
+        .as_ref()
+}
+
+/// The keys and hash160s of the paths of the batch whose last step is a normal child of an
+/// xpub, derived on the GPU, in the order of the batch. The other paths, and all of them
+/// without a usable GPU or when the launch fails, are left to the CPU.
+pub fn derive_batch(batch: &[PathParentKeyPair]) -> Vec<Option<(PublicKey, hash160::Hash)>> {
+    let Some(deriver) = gpu_deriver() else {
+        return vec![];
+    };
+    let (positions, children): (Vec<_>, Vec<_>) = batch
+        .iter()
+        .enumerate()
+        .filter_map(|(position, PathParentKeyPair(path, parent_key))| {
+            match (parent_key, path.as_ref().last()) {
+                (Some(ParentKey::Xpub(xpub)), Some(ChildNumber::Normal { index })) => {
+                    Some((position, (xpub, *index)))
+                }
+                _ => None,
+            }
+        })
+        .unzip();
+    let derived = match deriver.derive_and_hash(&children) {
+        Ok(derived) => derived,
+        Err(err) => {
+            warn!(
+                "Deriving on the GPU failed ({:?}), deriving on the CPU.",
+                err
+            );
+            return vec![];
+        }
+    };
+    let mut keys = vec![None; batch.len()];
+    for (position, derived) in positions.into_iter().zip(derived) {
+        keys[position] = derived;
+    }
+    keys
+}
+
+/// 2^i G for i from 0 to 255, each as the little endian 32 bit limbs of x and then y.
+fn generator_table() -> Vec<u32> {
+    let mut table = Vec::with_capacity(256 * 16);
+    for i in 0..256 {
+        let mut secret = [0u8; 32];
+        secret[31 - i / 8] = 1 << (i % 8);
+        let point = SecretKey::from_slice(&secret)
+            .unwrap()
+            .public_key(&SECP)
+            .serialize_uncompressed();
+        for coordinate in [&point[1..33], &point[33..65]] {
+            table.extend(
+                coordinate
+                    .rchunks_exact(4)
+                    .map(|limb| u32::from_be_bytes(limb.try_into().unwrap())),
+            );
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generator_table_works_01() {
+        let table = generator_table();
+        let generator = SecretKey::from_slice(&[[0u8; 31].as_slice(), &[1]].concat())
+            .unwrap()
+            .public_key(&SECP)
+            .serialize_uncompressed();
+        assert_eq!(table.len(), 256 * 16);
+        assert_eq!(
+            table[7],
+            u32::from_be_bytes(generator[1..5].try_into().unwrap())
+        );
+        assert_eq!(
+            table[0],
+            u32::from_be_bytes(generator[29..33].try_into().unwrap())
+        );
+        assert_eq!(
+            table[15],
+            u32::from_be_bytes(generator[33..37].try_into().unwrap())
+        );
+    }
+
+    #[test]
+    fn gpu_deriver_works_01() {
+        // Without a CUDA device the deriver is not set up and the batch is left to the CPU.
+        let Some(deriver) = gpu_deriver() else {
+            return;
+        };
+        let xpub = Xpub::from_priv(
+            &SECP,
+            &bitcoin::bip32::Xpriv::new_master(bitcoin::Network::Bitcoin, &[1u8; 32]).unwrap(),
+        );
+        let derived = deriver.derive_and_hash(&[(&xpub, 0), (&xpub, 5)]).unwrap();
+        let child = PublicKey::new(
+            xpub.ckd_pub(&SECP, ChildNumber::from_normal_idx(5).unwrap())
+                .unwrap()
+                .public_key,
+        );
+        assert_eq!(derived[1], Some((child, child.pubkey_hash().to_raw_hash())));
+    }
+}
@@ -4,10 +4,38 @@
 
 pub mod client;
 pub mod uspk_set;
+pub mod uspk_store;
+pub mod unspent_coins;
+pub mod uspk_cache;
 pub mod retriever;
+pub mod retriever_builder;
+pub mod retriever_phases;
 pub mod setting;
 pub mod error;
 pub mod data;
 pub mod path_pairs;
 pub mod explorer;
-pub mod covered_descriptors;
\ No newline at end of file
+pub mod covered_descriptors;
+pub mod descriptor_checksum;
+pub mod event_log;
+pub mod finds_stream;
+pub mod export;
+pub mod sweep;
+pub mod report;
+pub mod daemon;
+pub mod chainstate;
+pub mod coin_arena;
+pub mod dump;
+pub mod details_checkpoint;
+pub mod search_checkpoint;
+pub mod zmq;
+pub mod mempool;
+pub mod metrics;
+pub mod offline;
+pub mod dump_fetch;
+pub mod silent_payments;
+pub mod sorted_merge;
+pub mod watchlist;
+pub mod run_history;
+#[cfg(feature = "gpu")]
+pub mod gpu;
@@ -0,0 +1,44 @@
+use bitcoin::Txid;
+use itertools::Itertools;
+use num_format::{Locale, ToFormattedString};
+use serde::{Deserialize, Serialize};
+
+/// What the mempool of the node holds for a find: unconfirmed transactions spending its outputs
+/// and unconfirmed outputs paying to its scriptpubkey.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MempoolActivity {
+    pub spending_txids: Vec<Txid>,
+    /// The amount of the found outputs that unconfirmed transactions spend.
+    pub spending_sats: u64,
+    pub incoming_txids: Vec<Txid>,
+    /// The amount of unconfirmed outputs paying to the find.
+    pub incoming_sats: u64,
+}
+
+impl MempoolActivity {
+    pub fn is_empty(&self) -> bool {
+        self.spending_txids.is_empty() && self.incoming_txids.is_empty()
+    }
+}
+
+impl std::fmt::Display for MempoolActivity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.spending_txids.is_empty() {
+            write!(
+                f,
+                "\nBeing spent in mempool(satoshis): {} by {}",
+                self.spending_sats.to_formatted_string(&Locale::en),
+                self.spending_txids.iter().join(", ")
+            )?;
+        }
+        if !self.incoming_txids.is_empty() {
+            write!(
+                f,
+                "\nIncoming in mempool(satoshis): {} from {}",
+                self.incoming_sats.to_formatted_string(&Locale::en),
+                self.incoming_txids.iter().join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
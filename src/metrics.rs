@@ -0,0 +1,308 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+
+use crate::event_log::{EventLog, RetrieverEvent};
+
+/// The phases of a run of the retriever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    Idle,
+    /// Finding, fetching or creating the dump file.
+    Dump,
+    /// Reading the dump or the chainstate into the unspent scriptpubkey set.
+    Population,
+    /// Deriving the explored paths and looking their scriptpubkeys up in the set.
+    Search,
+    /// Fetching the details of the finds.
+    Details,
+}
+
+impl Phase {
+    const ALL: [Phase; 5] = [
+        Phase::Idle,
+        Phase::Dump,
+        Phase::Population,
+        Phase::Search,
+        Phase::Details,
+    ];
+
+    fn from_u8(value: u8) -> Self {
+        Self::ALL[value as usize]
+    }
+}
+
+/// Counters of a run, updated as it goes, so an application embedding the retriever can show its
+/// progress from another thread. Read them with `snapshot`.
+#[derive(Debug)]
+pub struct Metrics {
+    start: Instant,
+    phase: AtomicU8,
+    /// When the current phase started, in microseconds since the start.
+    phase_start_micros: AtomicU64,
+    /// How long each finished phase took, in the order they ran.
+    phase_timings: Mutex<Vec<(Phase, Duration)>>,
+    coins_inserted: AtomicU64,
+    /// The number of coins to insert, 0 if unknown as with the chainstate.
+    total_coins: AtomicU64,
+    paths_searched: AtomicU64,
+    /// The paths a resumed search had searched before, left out of its rate.
+    paths_resumed: AtomicU64,
+    total_paths: AtomicU64,
+    /// The scriptpubkeys of the searched paths looked up in the set.
+    scripts_checked: AtomicU64,
+    finds: AtomicU64,
+    /// Where the end of each phase is published.
+    event_log: EventLog,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            start: Instant::now(),
+            phase: AtomicU8::new(Phase::Idle as u8),
+            phase_start_micros: AtomicU64::new(0),
+            phase_timings: Mutex::new(vec![]),
+            coins_inserted: AtomicU64::new(0),
+            total_coins: AtomicU64::new(0),
+            paths_searched: AtomicU64::new(0),
+            paths_resumed: AtomicU64::new(0),
+            total_paths: AtomicU64::new(0),
+            scripts_checked: AtomicU64::new(0),
+            finds: AtomicU64::new(0),
+            event_log: EventLog::default(),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the metrics publish the end of each phase to the subscribers of the event log.
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = event_log;
+        self
+    }
+
+    /// Enters the phase until the returned guard is dropped, when the time it took is recorded
+    /// and the retriever is idle again, whether the phase finished or failed.
+    pub fn enter_phase(self: &Arc<Self>, phase: Phase) -> PhaseGuard {
+        self.phase.store(phase as u8, Ordering::Relaxed);
+        self.phase_start_micros
+            .store(self.start.elapsed().as_micros() as u64, Ordering::Relaxed);
+        PhaseGuard {
+            metrics: self.clone(),
+            phase,
+        }
+    }
+
+    pub fn start_population(&self, total_coins: u64) {
+        self.coins_inserted.store(0, Ordering::Relaxed);
+        self.total_coins.store(total_coins, Ordering::Relaxed);
+    }
+
+    pub fn add_coins_inserted(&self, coins: u64) {
+        self.coins_inserted.fetch_add(coins, Ordering::Relaxed);
+    }
+
+    /// Starts counting the paths of a search, of which some may have been searched already.
+    pub fn start_search(&self, total_paths: u64, paths_searched: u64) {
+        self.total_paths.store(total_paths, Ordering::Relaxed);
+        self.paths_searched.store(paths_searched, Ordering::Relaxed);
+        self.paths_resumed.store(paths_searched, Ordering::Relaxed);
+        self.scripts_checked.store(0, Ordering::Relaxed);
+    }
+
+    pub fn set_paths_searched(&self, paths_searched: u64) {
+        self.paths_searched.store(paths_searched, Ordering::Relaxed);
+    }
+
+    pub fn set_finds(&self, finds: u64) {
+        self.finds.store(finds, Ordering::Relaxed);
+    }
+
+    pub fn add_script_checked(&self) {
+        self.scripts_checked.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_find(&self) {
+        self.finds.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The counters as of now, with the rates of the current phase and its estimated time left.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let phase = Phase::from_u8(self.phase.load(Ordering::Relaxed));
+        let elapsed = self.start.elapsed();
+        let phase_elapsed = match phase {
+            Phase::Idle => Duration::ZERO,
+            _ => elapsed.saturating_sub(Duration::from_micros(
+                self.phase_start_micros.load(Ordering::Relaxed),
+            )),
+        };
+        let coins_inserted = self.coins_inserted.load(Ordering::Relaxed);
+        let total_coins = self.total_coins.load(Ordering::Relaxed);
+        let paths_searched = self.paths_searched.load(Ordering::Relaxed);
+        let total_paths = self.total_paths.load(Ordering::Relaxed);
+        let rate = |done: u64| match phase_elapsed.is_zero() {
+            true => 0.0,
+            false => done as f64 / phase_elapsed.as_secs_f64(),
+        };
+        let inserts_per_second = match phase {
+            Phase::Population => rate(coins_inserted),
+            _ => 0.0,
+        };
+        let paths_per_second = match phase {
+            Phase::Search => {
+                rate(paths_searched.saturating_sub(self.paths_resumed.load(Ordering::Relaxed)))
+            }
+            _ => 0.0,
+        };
+        let time_left = |done: u64, total: u64, per_second: f64| {
+            (total > 0 && per_second > 0.0)
+                .then(|| Duration::from_secs_f64(total.saturating_sub(done) as f64 / per_second))
+        };
+        let eta = match phase {
+            Phase::Population => time_left(coins_inserted, total_coins, inserts_per_second),
+            Phase::Search => time_left(paths_searched, total_paths, paths_per_second),
+            _ => None,
+        };
+        MetricsSnapshot {
+            phase,
+            elapsed,
+            phase_elapsed,
+            phase_timings: self.phase_timings.lock().unwrap().clone(),
+            coins_inserted,
+            total_coins,
+            inserts_per_second,
+            paths_searched,
+            total_paths,
+            paths_per_second,
+            eta,
+            scripts_checked: self.scripts_checked.load(Ordering::Relaxed),
+            finds: self.finds.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Keeps the retriever in a phase while alive, see `Metrics::enter_phase`. When dropped, it
+/// records the duration of the phase in milliseconds and its counters on the current span, as
+/// `duration_ms` and, by phase, `coins_inserted`, `paths_searched`, `scripts_checked` and
+/// `finds`, for the fields the span declares.
+pub struct PhaseGuard {
+    metrics: Arc<Metrics>,
+    phase: Phase,
+}
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        let phase_start =
+            Duration::from_micros(self.metrics.phase_start_micros.load(Ordering::Relaxed));
+        let duration = self.metrics.start.elapsed().saturating_sub(phase_start);
+        let span = Span::current();
+        span.record("duration_ms", duration.as_millis() as u64);
+        let counter = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        match self.phase {
+            Phase::Population => {
+                span.record("coins_inserted", counter(&self.metrics.coins_inserted));
+            }
+            Phase::Search => {
+                span.record("paths_searched", counter(&self.metrics.paths_searched));
+                span.record("scripts_checked", counter(&self.metrics.scripts_checked));
+                span.record("finds", counter(&self.metrics.finds));
+            }
+            Phase::Details => {
+                span.record("finds", counter(&self.metrics.finds));
+            }
+            Phase::Idle | Phase::Dump => {}
+        }
+        self.metrics
+            .phase_timings
+            .lock()
+            .unwrap()
+            .push((self.phase, duration));
+        self.metrics
+            .phase
+            .store(Phase::Idle as u8, Ordering::Relaxed);
+        self.metrics
+            .event_log
+            .publish(RetrieverEvent::PhaseFinished {
+                phase: self.phase,
+                duration,
+            });
+    }
+}
+
+/// The metrics of a run at a point in time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricsSnapshot {
+    pub phase: Phase,
+    /// Since the retriever was created.
+    pub elapsed: Duration,
+    pub phase_elapsed: Duration,
+    pub phase_timings: Vec<(Phase, Duration)>,
+    pub coins_inserted: u64,
+    /// 0 when the number of coins is not known beforehand, as with the chainstate.
+    pub total_coins: u64,
+    /// Coins inserted into the set per second, while populating.
+    pub inserts_per_second: f64,
+    pub paths_searched: u64,
+    pub total_paths: u64,
+    /// Paths derived and searched per second, while searching.
+    pub paths_per_second: f64,
+    /// The estimated time left of the population or the search.
+    pub eta: Option<Duration>,
+    /// Of the paths searched in this search, not those of a search it resumed.
+    pub scripts_checked: u64,
+    pub finds: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_works_01() {
+        let event_log = EventLog::default();
+        let mut events = event_log.subscribe();
+        let metrics = Arc::new(Metrics::new().with_event_log(event_log));
+        assert_eq!(metrics.snapshot().phase, Phase::Idle);
+        {
+            let _phase = metrics.enter_phase(Phase::Search);
+            metrics.start_search(1_000, 100);
+            std::thread::sleep(Duration::from_millis(20));
+            metrics.set_paths_searched(500);
+            metrics.add_script_checked();
+            metrics.add_find();
+            let snapshot = metrics.snapshot();
+            assert_eq!(snapshot.phase, Phase::Search);
+            assert_eq!(snapshot.paths_searched, 500);
+            assert_eq!(snapshot.scripts_checked, 1);
+            assert_eq!(snapshot.finds, 1);
+            assert!(snapshot.paths_per_second > 0.0);
+            assert!(snapshot.eta.is_some());
+            assert_eq!(snapshot.inserts_per_second, 0.0);
+        }
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.phase, Phase::Idle);
+        assert_eq!(snapshot.eta, None);
+        assert_eq!(snapshot.phase_timings.len(), 1);
+        assert_eq!(snapshot.phase_timings[0].0, Phase::Search);
+        assert!(snapshot.phase_timings[0].1 >= Duration::from_millis(20));
+        assert_eq!(
+            events.try_recv().unwrap(),
+            RetrieverEvent::PhaseFinished {
+                phase: Phase::Search,
+                duration: snapshot.phase_timings[0].1
+            }
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use bitcoin::{Amount, BlockHash};
+use bitcoincore_rpc::json::{ScanTxOutResult, Utxo};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    chainstate::ChainstateCoin,
+    error::RetrieverError,
+    path_pairs::{PathDescriptorPair, PathScanResultDescriptorTrio},
+};
+
+/// How many coins are read between checks for cancellation.
+const CANCELLATION_CHECK_INTERVAL: u64 = 100_000;
+
+/// Builds the details of finds from the coins of a local utxo set (a dump file or the chainstate),
+/// shaped like the results of scantxoutset, so runs without any rpc connection get amounts too.
+pub fn scan_coins_for_finds(
+    coins: impl Iterator<Item = Result<ChainstateCoin, RetrieverError>>,
+    best_block_hash: Option<BlockHash>,
+    finds: &[PathDescriptorPair],
+    cancellation_token: &CancellationToken,
+) -> Result<Vec<PathScanResultDescriptorTrio>, RetrieverError> {
+    let mut finds_by_script_pubkey = hashbrown::HashMap::<_, Vec<usize>>::new();
+    for (index, find) in finds.iter().enumerate() {
+        finds_by_script_pubkey
+            .entry(find.1.script_pubkey())
+            .or_default()
+            .push(index);
+    }
+    let mut unspents = vec![vec![]; finds.len()];
+    let mut coins_read = 0u64;
+    for coin in coins {
+        let coin = coin?;
+        coins_read += 1;
+        if coins_read.is_multiple_of(CANCELLATION_CHECK_INTERVAL)
+            && cancellation_token.is_cancelled()
+        {
+            return Err(RetrieverError::Cancelled);
+        }
+        let Some(indices) = finds_by_script_pubkey.get(&coin.script_pubkey) else {
+            continue;
+        };
+        for index in indices {
+            unspents[*index].push(Utxo {
+                txid: coin.out_point.txid,
+                vout: coin.out_point.vout,
+                script_pub_key: coin.script_pubkey.clone(),
+                descriptor: finds[*index].get_descriptor_string(),
+                amount: Amount::from_sat(coin.amount_sats),
+                height: coin.height as u64,
+            });
+        }
+    }
+    Ok(finds
+        .iter()
+        .zip(unspents)
+        .map(|(find, unspents)| {
+            PathScanResultDescriptorTrio::new(
+                find.0.clone(),
+                scan_result_of_unspents(unspents, coins_read, best_block_hash),
+                find.1.clone(),
+            )
+        })
+        .collect())
+}
+
+/// A successful scantxoutset result of the unspents, out of the number of coins read.
+pub fn scan_result_of_unspents(
+    unspents: Vec<Utxo>,
+    coins_read: u64,
+    best_block_hash: Option<BlockHash>,
+) -> ScanTxOutResult {
+    ScanTxOutResult {
+        success: Some(true),
+        tx_outs: Some(coins_read),
+        height: None,
+        best_block_hash,
+        total_amount: unspents.iter().map(|utxo| utxo.amount).sum(),
+        unspents,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{bip32::DerivationPath, key::Secp256k1, secp256k1::SecretKey, OutPoint, Txid};
+    use miniscript::Descriptor;
+
+    use super::*;
+
+    #[test]
+    fn scan_coins_for_finds_works_01() {
+        let finds = [1u8, 2].map(|byte| {
+            PathDescriptorPair::new(
+                DerivationPath::from_str(&format!("m/84'/0'/0'/0/{}", byte)).unwrap(),
+                Descriptor::new_wpkh(
+                    SecretKey::from_slice(&[byte; 32])
+                        .unwrap()
+                        .public_key(&Secp256k1::new())
+                        .into(),
+                )
+                .unwrap(),
+            )
+        });
+        let coin = |vout, amount_sats, find: &PathDescriptorPair| {
+            Ok(ChainstateCoin {
+                out_point: OutPoint::new(Txid::from_str(&"ab".repeat(32)).unwrap(), vout),
+                height: 840_000,
+                is_coinbase: false,
+                amount_sats,
+                script_pubkey: find.1.script_pubkey(),
+            })
+        };
+        let other = PathDescriptorPair::new(
+            DerivationPath::from_str("m/84'/0'/0'/0/3").unwrap(),
+            Descriptor::new_wpkh(
+                SecretKey::from_slice(&[3u8; 32])
+                    .unwrap()
+                    .public_key(&Secp256k1::new())
+                    .into(),
+            )
+            .unwrap(),
+        );
+        let coins = vec![
+            coin(0, 1_000, &finds[0]),
+            coin(1, 5_000, &other),
+            coin(2, 2_000, &finds[0]),
+        ];
+        let details =
+            scan_coins_for_finds(coins.into_iter(), None, &finds, &CancellationToken::new())
+                .unwrap();
+        assert_eq!(details.len(), 2);
+        assert_eq!(details[0].1.unspents.len(), 2);
+        assert_eq!(details[0].1.total_amount, Amount::from_sat(3_000));
+        assert_eq!(details[0].1.tx_outs, Some(3));
+        assert!(details[1].1.unspents.is_empty());
+    }
+}
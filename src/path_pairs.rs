@@ -1,16 +1,31 @@
 use std::str::FromStr;
 
 use bitcoin::{
-    bip32::DerivationPath, key::Secp256k1, secp256k1::SecretKey, Amount, BlockHash, ScriptBuf, Txid,
+    bip32::{ChildNumber, DerivationPath, Fingerprint},
+    key::Secp256k1,
+    secp256k1::SecretKey,
+    Amount, BlockHash, ScriptBuf, Txid,
 };
 use bitcoincore_rpc::json::{ScanTxOutRequest, ScanTxOutResult, Utxo};
-use miniscript::{bitcoin::secp256k1::PublicKey, Descriptor};
+use miniscript::{bitcoin::PublicKey, descriptor::DescriptorType, Descriptor};
 use tracing::info;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use crate::{descriptor_checksum::with_checksum, explorer::explorer_key::ParentKey};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PathScriptPubKeyBytesPair(DerivationPath, Vec<u8>);
 
+/// An explored path with the extended key of its parent, if the explorer holds the master key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathParentKeyPair(pub(crate) DerivationPath, pub(crate) Option<ParentKey>);
+
+impl PathParentKeyPair {
+    pub fn new(path: DerivationPath, parent_key: Option<ParentKey>) -> Self {
+        PathParentKeyPair(path, parent_key)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PathDescriptorPair(pub(crate) DerivationPath, pub(crate) Descriptor<PublicKey>);
 
@@ -19,8 +34,13 @@ impl PathDescriptorPair {
         PathDescriptorPair(path, descriptor)
     }
 
+    /// The descriptor of the find, with its checksum.
+    pub fn get_descriptor_string(&self) -> String {
+        with_checksum(&self.1.to_string()).unwrap()
+    }
+
     pub fn to_path_scan_request_descriptor_trio(&self) -> PathScanRequestDescriptorTrio {
-        let scan_request = ScanTxOutRequest::Single(self.1.to_string());
+        let scan_request = ScanTxOutRequest::Single(self.get_descriptor_string());
         PathScanRequestDescriptorTrio(self.0.clone(), scan_request, self.1.clone())
     }
 }
@@ -32,7 +52,8 @@ impl Zeroize for PathDescriptorPair {
         self.1 = Descriptor::new_pkh(
             SecretKey::from_slice(&[0u8; 32])
                 .unwrap()
-                .public_key(&Secp256k1::new()),
+                .public_key(&Secp256k1::new())
+                .into(),
         )
         .unwrap();
     }
@@ -40,6 +61,16 @@ impl Zeroize for PathDescriptorPair {
 
 impl ZeroizeOnDrop for PathDescriptorPair {}
 
+/// A find of `Retriever::search_with_explorer`, tagged with the explorer that produced it: its
+/// index among the explorers searched with, the fingerprint of its key and its run label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerFind {
+    pub explorer_index: usize,
+    pub fingerprint: Fingerprint,
+    pub label: Option<String>,
+    pub find: PathDescriptorPair,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PathScanRequestDescriptorTrio(
     pub(crate) DerivationPath,
@@ -57,7 +88,7 @@ impl PathScanRequestDescriptorTrio {
     }
 
     pub fn from_path_descriptor_pair(path_descriptor_pair: PathDescriptorPair) -> Self {
-        let scan_request = ScanTxOutRequest::Single(path_descriptor_pair.1.to_string());
+        let scan_request = ScanTxOutRequest::Single(path_descriptor_pair.get_descriptor_string());
         PathScanRequestDescriptorTrio(path_descriptor_pair.0, scan_request, path_descriptor_pair.1)
     }
 }
@@ -99,6 +130,11 @@ impl PathScanResultDescriptorTrio {
     pub fn get_descriptor(&self) -> Descriptor<PublicKey> {
         self.2.clone()
     }
+
+    /// The descriptor of the find, with its checksum.
+    pub fn get_descriptor_string(&self) -> String {
+        with_checksum(&self.2.to_string()).unwrap()
+    }
 }
 
 impl Zeroize for PathScanResultDescriptorTrio {
@@ -137,3 +173,171 @@ impl Zeroize for PathScanResultDescriptorTrio {
 }
 
 impl ZeroizeOnDrop for PathScanResultDescriptorTrio {}
+
+/// Splits the result of a scantxoutset call made with the descriptors of all the given requests
+/// back into one result per request, matching the unspents by their scriptpubkey.
+pub fn split_batch_scan_result(
+    requests: Vec<PathScanRequestDescriptorTrio>,
+    batch_result: &ScanTxOutResult,
+) -> Vec<PathScanResultDescriptorTrio> {
+    requests
+        .into_iter()
+        .map(|PathScanRequestDescriptorTrio(path, _, descriptor)| {
+            let script_pubkey = descriptor.script_pubkey();
+            let unspents = batch_result
+                .unspents
+                .iter()
+                .filter(|utxo| utxo.script_pub_key == script_pubkey)
+                .cloned()
+                .collect::<Vec<Utxo>>();
+            let scan_result = ScanTxOutResult {
+                success: batch_result.success,
+                tx_outs: batch_result.tx_outs,
+                height: batch_result.height,
+                best_block_hash: batch_result.best_block_hash,
+                total_amount: unspents.iter().map(|utxo| utxo.amount).sum(),
+                unspents,
+            };
+            PathScanResultDescriptorTrio::new(path, scan_result, descriptor)
+        })
+        .collect()
+}
+
+/// The key finds are ordered by: the base path they were explored under (standalone keys, which
+/// are under none, first), the rest of their path, their descriptor type and their descriptor.
+fn find_order_key<'a>(
+    base_paths: &[DerivationPath],
+    path: &'a DerivationPath,
+    descriptor: &'a Descriptor<PublicKey>,
+) -> (
+    Option<usize>,
+    &'a [ChildNumber],
+    DescriptorType,
+    &'a Descriptor<PublicKey>,
+) {
+    let base_path = base_paths
+        .iter()
+        .enumerate()
+        .filter(|(_, base_path)| path.as_ref().starts_with(base_path.as_ref()))
+        .max_by_key(|(_, base_path)| base_path.len());
+    let below_base = match base_path {
+        Some((_, base_path)) => &path.as_ref()[base_path.len()..],
+        None => path.as_ref(),
+    };
+    (
+        base_path.map(|(index, _)| index),
+        below_base,
+        descriptor.desc_type(),
+        descriptor,
+    )
+}
+
+/// Sorts finds by base path, derivation index and descriptor type, so the order does not depend
+/// on which worker found them first.
+pub fn sort_finds(finds: &mut [PathDescriptorPair], base_paths: &[DerivationPath]) {
+    finds.sort_by(|a, b| {
+        find_order_key(base_paths, &a.0, &a.1).cmp(&find_order_key(base_paths, &b.0, &b.1))
+    });
+}
+
+/// Sorts the details of finds in the same order as [`sort_finds`].
+pub fn sort_detailed_finds(
+    detailed_finds: &mut [PathScanResultDescriptorTrio],
+    base_paths: &[DerivationPath],
+) {
+    detailed_finds.sort_by(|a, b| {
+        find_order_key(base_paths, &a.0, &a.2).cmp(&find_order_key(base_paths, &b.0, &b.2))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_batch_scan_result_works_01() {
+        let secp = Secp256k1::new();
+        let descriptors = [1u8, 2]
+            .map(|byte| {
+                Descriptor::new_wpkh(
+                    SecretKey::from_slice(&[byte; 32])
+                        .unwrap()
+                        .public_key(&secp)
+                        .into(),
+                )
+                .unwrap()
+            })
+            .to_vec();
+        let requests = descriptors
+            .iter()
+            .enumerate()
+            .map(|(index, descriptor)| {
+                PathDescriptorPair::new(
+                    DerivationPath::from_str(&format!("m/84'/0'/0'/0/{}", index)).unwrap(),
+                    descriptor.clone(),
+                )
+                .to_path_scan_request_descriptor_trio()
+            })
+            .collect::<Vec<_>>();
+        let utxo = |vout: u32, sats: u64| Utxo {
+            txid: Txid::from_str(
+                "f3aa99937337582a105c90e0595847177d8ab99d50201e318634a5d2db4f9d85",
+            )
+            .unwrap(),
+            vout,
+            script_pub_key: descriptors[1].script_pubkey(),
+            descriptor: "none".to_string(),
+            amount: Amount::from_sat(sats),
+            height: 42,
+        };
+        let batch_result = ScanTxOutResult {
+            success: Some(true),
+            tx_outs: Some(1_000),
+            height: Some(840_000),
+            best_block_hash: None,
+            unspents: vec![utxo(0, 1_000), utxo(1, 2_000)],
+            total_amount: Amount::from_sat(3_000),
+        };
+        let results = split_batch_scan_result(requests, &batch_result);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].1.unspents.is_empty());
+        assert_eq!(results[0].1.total_amount, Amount::ZERO);
+        assert_eq!(results[1].1.unspents.len(), 2);
+        assert_eq!(results[1].1.total_amount, Amount::from_sat(3_000));
+        assert_eq!(results[1].1.height, Some(840_000));
+    }
+
+    #[test]
+    fn sort_finds_works_01() {
+        let secp = Secp256k1::new();
+        let pubkey: PublicKey = SecretKey::from_slice(&[1u8; 32])
+            .unwrap()
+            .public_key(&secp)
+            .into();
+        let wpkh = Descriptor::new_wpkh(pubkey).unwrap();
+        let pkh = Descriptor::new_pkh(pubkey).unwrap();
+        let find = |path: &str, descriptor: &Descriptor<PublicKey>| {
+            PathDescriptorPair::new(DerivationPath::from_str(path).unwrap(), descriptor.clone())
+        };
+        let base_paths = ["m/84'/0'/0'", "m/44'/0'/0'", "m/44'/0'/0'/1"]
+            .map(|base_path| DerivationPath::from_str(base_path).unwrap());
+        let mut finds = vec![
+            find("m/44'/0'/0'/0/10", &wpkh),
+            find("m/44'/0'/0'/1/0", &pkh),
+            find("m/44'/0'/0'/0/2", &wpkh),
+            find("m/84'/0'/0'/0/7", &wpkh),
+            find("m/44'/0'/0'/0/2", &pkh),
+            find("m", &wpkh),
+        ];
+        sort_finds(&mut finds, &base_paths);
+        let expected = vec![
+            find("m", &wpkh),
+            find("m/84'/0'/0'/0/7", &wpkh),
+            find("m/44'/0'/0'/0/2", &pkh),
+            find("m/44'/0'/0'/0/2", &wpkh),
+            find("m/44'/0'/0'/0/10", &wpkh),
+            find("m/44'/0'/0'/1/0", &pkh),
+        ];
+        assert_eq!(finds, expected);
+    }
+}
@@ -0,0 +1,466 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use bitcoin::{bip32::DerivationPath, PublicKey};
+use getset::Getters;
+use miniscript::Descriptor;
+use num_format::{Locale, ToFormattedString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mempool::MempoolActivity,
+    metrics::{MetricsSnapshot, Phase},
+    path_pairs::{PathDescriptorPair, PathScanResultDescriptorTrio},
+};
+
+/// A summary of how much of the exploration path space has been searched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct SearchCoverage {
+    total_paths: usize,
+    paths_processed: usize,
+    last_processed_path: Option<String>,
+    completed: bool,
+}
+
+impl SearchCoverage {
+    pub fn new(
+        total_paths: usize,
+        paths_processed: usize,
+        last_processed_path: Option<String>,
+        completed: bool,
+    ) -> Self {
+        SearchCoverage {
+            total_paths,
+            paths_processed,
+            last_processed_path,
+            completed,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchCoverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Paths searched: {} of {}",
+            self.paths_processed.to_formatted_string(&Locale::en),
+            self.total_paths.to_formatted_string(&Locale::en)
+        )?;
+        if let Some(last_processed_path) = &self.last_processed_path {
+            write!(f, "\nLast searched path: {}", last_processed_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single find in a report. Amounts are only known when details have been fetched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct ReportedFind {
+    path: String,
+    descriptor: String,
+    amount_sats: Option<u64>,
+    /// Unconfirmed transactions spending or paying to the find, when the mempool was checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mempool: Option<MempoolActivity>,
+}
+
+impl ReportedFind {
+    /// Turns finds into reported finds, using the details of finds (with amounts) when fetched,
+    /// along with their mempool activity when checked. The finds whose details are missing, as
+    /// when fetching them failed partway, follow without an amount.
+    pub fn from_finds(
+        finds: &[PathDescriptorPair],
+        detailed_finds: Option<&[PathScanResultDescriptorTrio]>,
+        mempool_activity: Option<&[MempoolActivity]>,
+    ) -> Vec<Self> {
+        let detailed_finds = detailed_finds.unwrap_or_default();
+        let mut reported_finds = detailed_finds
+            .iter()
+            .enumerate()
+            .map(|(index, detail)| ReportedFind {
+                path: detail.0.to_string(),
+                descriptor: detail.get_descriptor_string(),
+                amount_sats: Some(detail.1.total_amount.to_sat()),
+                mempool: mempool_activity
+                    .and_then(|mempool_activity| mempool_activity.get(index).cloned()),
+            })
+            .collect::<Vec<_>>();
+        reported_finds.extend(
+            finds
+                .iter()
+                .filter(|find| {
+                    !detailed_finds
+                        .iter()
+                        .any(|detail| detail.0 == find.0 && detail.2 == find.1)
+                })
+                .map(|find| ReportedFind {
+                    path: find.0.to_string(),
+                    descriptor: find.get_descriptor_string(),
+                    amount_sats: None,
+                    mempool: None,
+                }),
+        );
+        reported_finds
+    }
+}
+
+/// The report of a run that finished, see `Retriever::run`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct RunReport {
+    coverage: Option<SearchCoverage>,
+    finds: Vec<ReportedFind>,
+    /// The amount of the finds whose details were fetched.
+    total_amount_sats: u64,
+}
+
+impl RunReport {
+    pub fn new(
+        coverage: Option<SearchCoverage>,
+        finds: &[PathDescriptorPair],
+        detailed_finds: Option<&[PathScanResultDescriptorTrio]>,
+        mempool_activity: Option<&[MempoolActivity]>,
+    ) -> Self {
+        let finds = ReportedFind::from_finds(finds, detailed_finds, mempool_activity);
+        RunReport {
+            coverage,
+            total_amount_sats: finds.iter().filter_map(|find| find.amount_sats).sum(),
+            finds,
+        }
+    }
+}
+
+/// The number and amount of the finds sharing a descriptor type or a base path.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct FindTotals {
+    finds: usize,
+    amount_sats: u64,
+}
+
+/// A summary of a search: how much was searched and found, broken down by descriptor type and
+/// by base path, and how long each phase of the run took, see `Retriever::scan_report`. Amounts
+/// are only known once the details of the finds are fetched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct ScanReport {
+    paths_explored: u64,
+    total_paths: u64,
+    /// The scriptpubkeys of the explored paths looked up in the unspent scriptpubkey set.
+    scripts_checked: u64,
+    finds: usize,
+    total_amount_sats: u64,
+    per_descriptor: BTreeMap<String, FindTotals>,
+    /// The finds of standalone keys are under m.
+    per_base_path: BTreeMap<String, FindTotals>,
+    phase_timings: Vec<(Phase, Duration)>,
+    elapsed: Duration,
+}
+
+impl ScanReport {
+    pub fn new(
+        metrics: &MetricsSnapshot,
+        base_paths: &[DerivationPath],
+        finds: &[PathDescriptorPair],
+        detailed_finds: Option<&[PathScanResultDescriptorTrio]>,
+    ) -> Self {
+        let finds: Vec<(&DerivationPath, &Descriptor<PublicKey>, u64)> = match detailed_finds {
+            Some(detailed_finds) => detailed_finds
+                .iter()
+                .map(|detail| (&detail.0, &detail.2, detail.1.total_amount.to_sat()))
+                .collect(),
+            None => finds.iter().map(|find| (&find.0, &find.1, 0)).collect(),
+        };
+        let mut per_descriptor = BTreeMap::<String, FindTotals>::new();
+        let mut per_base_path = BTreeMap::<String, FindTotals>::new();
+        for (path, descriptor, amount_sats) in finds.iter() {
+            let base_path = base_paths
+                .iter()
+                .filter(|base_path| path.as_ref().starts_with(base_path.as_ref()))
+                .max_by_key(|base_path| base_path.len())
+                .map_or_else(|| DerivationPath::master().to_string(), ToString::to_string);
+            for totals in [
+                per_descriptor
+                    .entry(format!("{:?}", descriptor.desc_type()))
+                    .or_default(),
+                per_base_path.entry(base_path).or_default(),
+            ] {
+                totals.finds += 1;
+                totals.amount_sats += amount_sats;
+            }
+        }
+        ScanReport {
+            paths_explored: metrics.paths_searched,
+            total_paths: metrics.total_paths,
+            scripts_checked: metrics.scripts_checked,
+            finds: finds.len(),
+            total_amount_sats: finds.iter().map(|(_, _, amount_sats)| amount_sats).sum(),
+            per_descriptor,
+            per_base_path,
+            phase_timings: metrics.phase_timings.clone(),
+            elapsed: metrics.elapsed,
+        }
+    }
+}
+
+impl std::fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "\nSummary of the search")?;
+        writeln!(
+            f,
+            "Paths explored: {} of {}",
+            self.paths_explored.to_formatted_string(&Locale::en),
+            self.total_paths.to_formatted_string(&Locale::en)
+        )?;
+        writeln!(
+            f,
+            "ScriptPubKeys checked: {}",
+            self.scripts_checked.to_formatted_string(&Locale::en)
+        )?;
+        writeln!(
+            f,
+            "Finds: {}, amount(satoshis): {}",
+            self.finds,
+            self.total_amount_sats.to_formatted_string(&Locale::en)
+        )?;
+        for (breakdown, totals) in [
+            ("descriptor", &self.per_descriptor),
+            ("base path", &self.per_base_path),
+        ] {
+            for (key, totals) in totals.iter() {
+                writeln!(
+                    f,
+                    "  {} {}: {} finds, amount(satoshis): {}",
+                    breakdown,
+                    key,
+                    totals.finds,
+                    totals.amount_sats.to_formatted_string(&Locale::en)
+                )?;
+            }
+        }
+        for (phase, duration) in self.phase_timings.iter() {
+            writeln!(f, "{:?} phase took {:.1?}", phase, duration)?;
+        }
+        write!(f, "Elapsed: {:.1?}", self.elapsed)
+    }
+}
+
+/// What a search is expected to cost before it is run, see `Retriever::estimate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct SearchEstimate {
+    /// The paths of the exploration path, those of the sweep included.
+    total_paths: u64,
+    /// The scriptpubkeys of the selected descriptors checked for each path.
+    scripts_per_path: u64,
+    /// The scriptpubkeys the set is expected to hold, from the dump in data dir, the populated
+    /// set, or the size of the mainnet utxo set otherwise.
+    set_script_pubkeys: u64,
+    projected_memory_bytes: u64,
+    /// The paths derived and checked per second, measured on a sample of the exploration.
+    paths_per_second: u64,
+    estimated_search_seconds: u64,
+}
+
+impl SearchEstimate {
+    pub fn new(
+        total_paths: u64,
+        scripts_per_path: u64,
+        set_script_pubkeys: u64,
+        projected_memory_bytes: u64,
+        paths_per_second: u64,
+    ) -> Self {
+        SearchEstimate {
+            total_paths,
+            scripts_per_path,
+            set_script_pubkeys,
+            projected_memory_bytes,
+            paths_per_second,
+            estimated_search_seconds: total_paths
+                .checked_div(paths_per_second)
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn total_scripts(&self) -> u64 {
+        self.total_paths * self.scripts_per_path
+    }
+}
+
+impl std::fmt::Display for SearchEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "
+Estimate of the search"
+        )?;
+        writeln!(
+            f,
+            "Paths to explore: {}, {} ScriptPubKeys each, {} in total",
+            self.total_paths.to_formatted_string(&Locale::en),
+            self.scripts_per_path,
+            self.total_scripts().to_formatted_string(&Locale::en)
+        )?;
+        writeln!(
+            f,
+            "ScriptPubKeys in the set: {}, projected memory: {} MB",
+            self.set_script_pubkeys.to_formatted_string(&Locale::en),
+            (self.projected_memory_bytes / (1024 * 1024)).to_formatted_string(&Locale::en)
+        )?;
+        writeln!(
+            f,
+            "Measured rate: {} paths per second",
+            self.paths_per_second.to_formatted_string(&Locale::en)
+        )?;
+        write!(
+            f,
+            "Estimated search time: {:?}, besides populating the set",
+            Duration::from_secs(self.estimated_search_seconds)
+        )
+    }
+}
+
+/// The report of a run that did not finish, holding everything found before it stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct PartialReport {
+    partial: bool,
+    cause: String,
+    coverage: Option<SearchCoverage>,
+    finds: Vec<ReportedFind>,
+}
+
+impl PartialReport {
+    pub fn new(
+        cause: &str,
+        coverage: Option<SearchCoverage>,
+        finds: &[PathDescriptorPair],
+        detailed_finds: Option<&[PathScanResultDescriptorTrio]>,
+        mempool_activity: Option<&[MempoolActivity]>,
+    ) -> Self {
+        PartialReport {
+            partial: true,
+            cause: cause.to_string(),
+            coverage,
+            finds: ReportedFind::from_finds(finds, detailed_finds, mempool_activity),
+        }
+    }
+}
+
+impl std::fmt::Display for PartialReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "\n*** PARTIAL RESULTS: the run did not finish ({}). ***",
+            self.cause
+        )?;
+        match &self.coverage {
+            Some(coverage) => writeln!(f, "{}", coverage)?,
+            None => writeln!(f, "The search phase was not started.")?,
+        }
+        for (index, find) in self.finds.iter().enumerate() {
+            write!(
+                f,
+                "\nResult {}\nPath: {}\nDescriptor: {}",
+                index + 1,
+                find.path,
+                find.descriptor
+            )?;
+            if let Some(amount_sats) = find.amount_sats {
+                write!(
+                    f,
+                    "\nAmount(satoshis): {}",
+                    amount_sats.to_formatted_string(&Locale::en)
+                )?;
+            }
+            if let Some(mempool) = &find.mempool {
+                write!(f, "{}", mempool)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn scan_report_works_01() {
+        let pubkey = PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let find = |path: &str, descriptor: Descriptor<PublicKey>| {
+            PathDescriptorPair::new(DerivationPath::from_str(path).unwrap(), descriptor)
+        };
+        let finds = [
+            find("m/84h/0h/0h/0/1", Descriptor::new_wpkh(pubkey).unwrap()),
+            find("m/84h/0h/0h/1/0", Descriptor::new_wpkh(pubkey).unwrap()),
+            find("m/0/3", Descriptor::new_pkh(pubkey).unwrap()),
+            find("m", Descriptor::new_pkh(pubkey).unwrap()),
+        ];
+        let metrics = crate::metrics::Metrics::new().snapshot();
+        let base_paths = [
+            DerivationPath::from_str("m/84h/0h/0h").unwrap(),
+            DerivationPath::from_str("m/0").unwrap(),
+        ];
+        let report = ScanReport::new(&metrics, &base_paths, &finds, None);
+        assert_eq!(*report.get_finds(), 4);
+        assert_eq!(*report.get_total_amount_sats(), 0);
+        assert_eq!(
+            report
+                .get_per_descriptor()
+                .values()
+                .map(|totals| totals.finds)
+                .collect::<Vec<_>>(),
+            vec![2, 2]
+        );
+        assert_eq!(
+            report
+                .get_per_base_path()
+                .iter()
+                .map(|(base_path, totals)| (base_path.as_str(), totals.finds))
+                .collect::<Vec<_>>(),
+            vec![("m", 1), ("m/0", 1), ("m/84'/0'/0'", 2)]
+        );
+    }
+
+    #[test]
+    fn reported_finds_works_01() {
+        let pubkey = PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let finds = ["m/84h/0h/0h/0/1", "m/84h/0h/0h/0/2"].map(|path| {
+            PathDescriptorPair::new(
+                DerivationPath::from_str(path).unwrap(),
+                Descriptor::new_wpkh(pubkey).unwrap(),
+            )
+        });
+        let detail = PathScanResultDescriptorTrio::new(
+            finds[1].0.clone(),
+            bitcoincore_rpc::json::ScanTxOutResult {
+                success: Some(true),
+                tx_outs: Some(1_000),
+                height: Some(840_000),
+                best_block_hash: None,
+                unspents: vec![],
+                total_amount: bitcoin::Amount::from_sat(5_000),
+            },
+            finds[1].1.clone(),
+        );
+        let reported_finds = ReportedFind::from_finds(&finds, Some(&[detail]), None);
+        assert_eq!(
+            reported_finds
+                .iter()
+                .map(|find| (find.get_path().as_str(), *find.get_amount_sats()))
+                .collect::<Vec<_>>(),
+            vec![("m/84'/0'/0'/0/2", Some(5_000)), ("m/84'/0'/0'/0/1", None)]
+        );
+    }
+}
@@ -3,28 +3,94 @@ use std::{
     path::PathBuf,
     str::FromStr,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
-use bitcoin::{bip32::DerivationPath, key::Secp256k1};
+use base64::Engine;
+use bitcoin::{
+    bip32::DerivationPath,
+    consensus::encode::serialize_hex,
+    hashes::{hash160, sha256, Hash},
+    psbt::Psbt,
+    Address, Block, FeeRate, Network, OutPoint, PublicKey, ScriptBuf, Transaction, Txid,
+};
+use bitcoincore_rpc::jsonrpc::serde_json;
+use futures_core::Stream;
 use getset::Getters;
 use itertools::Itertools;
 use miniscript::Descriptor;
 use num_format::{Locale, ToFormattedString};
-use tokio::sync::mpsc;
-use tracing::{error, info, warn};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use strum::IntoEnumIterator;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, field::Empty, info, instrument, warn, Span};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::{
-    client::BitcoincoreRpcClient,
+    chainstate::ChainstateReader,
+    client::{scan_in_progress_policy::ScanInProgressPolicy, BitcoincoreRpcClient},
     covered_descriptors::CoveredDescriptors,
-    data::defaults::DEFAULT_SELECTED_DESCRIPTORS,
+    data::defaults::{
+        DEFAULT_SELECTED_DESCRIPTORS, DEFAULT_SILENT_PAYMENTS_BIRTH_HEIGHT, DERIVATION_BATCH_SIZE,
+        DETAILS_SCAN_RETRY_DELAY_SECONDS, DETAILS_SCAN_RETRY_ROUNDS, DISK_USPK_SET_FILE_NAME,
+        DUMP_FETCH_TIMEOUT_SECONDS, ESTIMATE_SAMPLE_PATHS, EVENT_LOG_PATHS_SCANNED_INTERVAL,
+        INVERTED_SEARCH_MAX_PATHS, SCANTXOUTSET_BATCH_SIZE, SCANTXOUTSET_STATUS_POLL_SECONDS,
+        SEARCH_CHECKPOINT_INTERVAL_SECONDS, SORTED_MERGE_DIR_NAME, SORTED_MERGE_RUN_ITEMS,
+        SPILL_USPK_SET_FILE_NAME, SWEEP_CONFIRMATION_POLL_SECONDS,
+        USPK_SET_CHAINSTATE_EXPECTED_ITEMS,
+    },
+    details_checkpoint::DetailsCheckpoint,
+    dump::UtxoDump,
+    dump_fetch::fetch_dump,
     error::RetrieverError,
-    explorer::Explorer,
-    path_pairs::{PathDescriptorPair, PathScanResultDescriptorTrio},
+    event_log::{EventLog, LoggedEvent, RetrieverEvent},
+    explorer::{
+        auxiliaries::SECP,
+        exploration_path::ExplorationPath,
+        explorer_key::XprivCache,
+        explorer_setting::ExplorerSetting,
+        sanity::{check_exploration_sanity, estimate_runtime_seconds, ExplorationWarning},
+        Explorer,
+    },
+    export::{
+        private_descriptor::{private_descriptors_of_finds, FoundAccountDescriptor},
+        sparrow::SparrowWalletExport,
+        wif::{wifs_of_finds, FoundWif},
+    },
+    finds_stream::FindSubscribers,
+    mempool::MempoolActivity,
+    metrics::{Metrics, Phase},
+    offline::scan_coins_for_finds,
+    path_pairs::{
+        sort_detailed_finds, sort_finds, ExplorerFind, PathDescriptorPair, PathParentKeyPair,
+        PathScanResultDescriptorTrio,
+    },
+    report::{PartialReport, ReportedFind, RunReport, ScanReport, SearchCoverage, SearchEstimate},
+    run_history::{RunHistory, RunRecord},
+    search_checkpoint::SearchCheckpoint,
     setting::RetrieverSetting,
+    silent_payments::{SilentPaymentFind, SilentPaymentKeys},
+    sorted_merge::ExternalSorter,
+    sweep::{
+        build_sweep_psbts, finalize_sweep_psbt, sign_sweep_psbt, sweep_inputs_of_finds,
+        SweepOptions,
+    },
+    uspk_cache::USPK_SET_CACHE_FILE_NAME,
     uspk_set::{UnspentScriptPubKeysSet, UspkSetStatus},
+    uspk_store::{digest_script_pubkey, MemoryUspkSet, UspkSetBackend, UspkStore},
+    watchlist::{Watchlist, WatchlistFind},
 };
 
+/// What the descriptors of the derived keys are checked against: the unspent scriptpubkeys in a
+/// search, the set collecting them before an inverted search reads the utxo set, or the sorter
+/// writing them to disk before a sorted merge search does.
+enum SearchTarget<'a> {
+    UspkSet(&'a UspkStore),
+    Collect(&'a Mutex<MemoryUspkSet>),
+    Sort(&'a Mutex<ExternalSorter>),
+}
+
 #[derive(Debug, Clone, Default, Getters)]
 #[get = "pub"]
 pub struct Retriever {
@@ -33,8 +99,49 @@ pub struct Retriever {
     uspk_set: UnspentScriptPubKeysSet,
     data_dir: String,
     finds: Arc<Mutex<Vec<PathDescriptorPair>>>,
+    /// The streams of `finds_stream`, fed each find as it is discovered.
+    find_subscribers: FindSubscribers,
     detailed_finds: Option<Vec<PathScanResultDescriptorTrio>>,
+    /// The mempool activity of each detailed find, in the same order, when checked.
+    mempool_activity: Option<Vec<MempoolActivity>>,
+    /// The silent payments to the seed, when searched.
+    silent_payment_finds: Option<Vec<SilentPaymentFind>>,
+    /// The scriptpubkeys checked instead of exploring a seed, in watchlist mode.
+    watchlist: Option<Watchlist>,
+    watchlist_finds: Option<Vec<WatchlistFind>>,
     select_descriptors: hashbrown::HashSet<CoveredDescriptors>,
+    /// Whether paths under a BIP43 purpose are only searched in the descriptor of its script type.
+    narrow_by_purpose: bool,
+    /// The number of threads deriving and searching the explored paths, 0 for one per core.
+    derivation_threads: usize,
+    /// Whether the derived scriptpubkeys are collected before the utxo set is read, so the set
+    /// only keeps the coins they match. Never in watchlist mode, where nothing is derived.
+    inverted_search: bool,
+    /// Whether the derived scriptpubkeys are sorted to disk and merged with the sorted utxo set
+    /// instead, for machines the utxo set does not fit in the memory of.
+    sorted_merge_search: bool,
+    cancellation_token: CancellationToken,
+    /// The progress of the run, shared with the set and readable by embedders.
+    metrics: Arc<Metrics>,
+    search_coverage: Option<SearchCoverage>,
+    /// The number of explored paths below each base path an interrupted search had searched,
+    /// which its resumed search skips.
+    resume_cursors: Vec<usize>,
+    /// The finds of every `search_with_explorer`, tagged with their explorer.
+    explorer_finds: Vec<ExplorerFind>,
+    explorers_searched: usize,
+    event_log: EventLog,
+    chainstate_dir: Option<String>,
+    dump_height: Option<u64>,
+    scan_in_progress_policy: ScanInProgressPolicy,
+    check_mempool: bool,
+    /// Where to download the dump file from instead of asking bitcoincore for it, with its sha256.
+    dump_url: Option<String>,
+    dump_sha256: Option<String>,
+    /// Whether the retriever runs without bitcoincore, see `new_offline`.
+    offline: bool,
+    /// The network of the explored keys, mainnet for a default retriever.
+    network: Option<Network>,
 }
 
 impl Retriever {
@@ -42,17 +149,151 @@ impl Retriever {
         info!("Creation of retriever started.");
         let client_setting = setting.get_client_setting();
         let explorer_setting = setting.get_explorer_setting();
-        let client = BitcoincoreRpcClient::new(client_setting).await?;
-        let explorer = Arc::new(Explorer::new(explorer_setting)?);
-        let uspk_set = UnspentScriptPubKeysSet::new();
+        let (data_dir, event_log) = Self::open_data_dir(&setting)?;
+        let client = BitcoincoreRpcClient::new(client_setting)
+            .await
+            .inspect_err(|err| event_log.log_error("client_creation", err))?
+            .with_event_log(event_log.clone());
+        let required_height = match setting.get_chainstate_dir() {
+            Some(_) => None,
+            None => *setting.get_dump_height(),
+        };
+        client
+            .preflight(*explorer_setting.get_network(), required_height)
+            .await
+            .inspect_err(|err| event_log.log_error("preflight", err))?;
+        Self::build(setting, client, data_dir, event_log, false)
+    }
+
+    /// Creates a retriever that never connects to bitcoincore, for air-gapped machines. The
+    /// utxo set is read from utxo_dump.dat in data dir (or from the chainstate dir), and so are
+    /// the details of finds.
+    pub async fn new_offline(setting: RetrieverSetting) -> Result<Self, RetrieverError> {
+        info!("Creation of offline retriever started.");
+        let (data_dir, event_log) = Self::open_data_dir(&setting)?;
+        if setting.get_chainstate_dir().is_none()
+            && setting.get_dump_url().is_none()
+            && !PathBuf::from(&data_dir).join("utxo_dump.dat").exists()
+        {
+            error!("Offline runs need a dump file (utxo_dump.dat) in data dir.");
+            let err = RetrieverError::NoDumpFileInDataDir;
+            event_log.log_error("client_creation", &err);
+            return Err(err);
+        }
+        if setting.get_dump_height().is_some() {
+            warn!("Dump height is ignored as no dump is requested offline.");
+        }
+        Self::build(
+            setting,
+            BitcoincoreRpcClient::default(),
+            data_dir,
+            event_log,
+            true,
+        )
+    }
+
+    /// Canonicalizes data dir and opens the event log in it, if enabled.
+    fn open_data_dir(setting: &RetrieverSetting) -> Result<(String, EventLog), RetrieverError> {
         let data_dir = fs::canonicalize(setting.get_data_dir())?
             .to_string_lossy()
             .to_string();
+        let event_log = if setting.is_event_log_enabled() {
+            EventLog::open(&data_dir)?
+        } else {
+            EventLog::default()
+        };
+        Ok((data_dir, event_log))
+    }
+
+    /// Builds the retriever around the given client, which is a placeholder when offline.
+    fn build(
+        setting: RetrieverSetting,
+        client: BitcoincoreRpcClient,
+        data_dir: String,
+        event_log: EventLog,
+        offline: bool,
+    ) -> Result<Self, RetrieverError> {
+        let explorer_setting = setting.get_explorer_setting();
+        let network = *explorer_setting.get_network();
+        let watchlist = match (
+            setting.get_script_pubkey_watchlist(),
+            setting.get_address_watchlist(),
+        ) {
+            (None, None) => None,
+            (script_pubkey_file, addresses) => Some(Watchlist::new(
+                script_pubkey_file.as_deref(),
+                addresses.as_deref().unwrap_or_default(),
+                *explorer_setting.get_network(),
+            )?),
+        };
+        // Nothing is derived in watchlist mode, so no seed is needed.
+        let explorer = match watchlist {
+            Some(_) => Arc::new(Explorer::default()),
+            None => Arc::new(Explorer::new(explorer_setting)?),
+        };
+        let metrics = Arc::new(Metrics::new().with_event_log(event_log.clone()));
+        let mut uspk_set = UnspentScriptPubKeysSet::new()
+            .with_event_log(event_log.clone())
+            .with_metrics(metrics.clone());
+        if setting.get_uspk_set_backend() == UspkSetBackend::Disk {
+            uspk_set =
+                uspk_set.with_disk_backend(PathBuf::from(&data_dir).join(DISK_USPK_SET_FILE_NAME));
+        }
+        if let Some(max_memory_mb) = setting.get_max_memory_mb() {
+            uspk_set = uspk_set.with_memory_cap(
+                *max_memory_mb as usize * 1024 * 1024,
+                PathBuf::from(&data_dir).join(SPILL_USPK_SET_FILE_NAME),
+            );
+        }
+        if setting.is_uspk_set_cache_enabled() {
+            uspk_set = uspk_set.with_cache(PathBuf::from(&data_dir).join(USPK_SET_CACHE_FILE_NAME));
+        }
+        if setting.is_silent_payments_enabled() {
+            uspk_set = uspk_set.with_silent_payment_outputs(
+                setting
+                    .get_silent_payments_birth_height()
+                    .unwrap_or(DEFAULT_SILENT_PAYMENTS_BIRTH_HEIGHT),
+            );
+        }
+        if setting.is_keeping_coins_enabled() {
+            uspk_set = uspk_set.with_unspent_coins();
+        }
+        let sorted_merge_search = setting.is_sorted_merge_search_enabled() && watchlist.is_none();
+        if sorted_merge_search && setting.is_inverted_search_enabled() {
+            warn!("Inverted search is ignored as the search is a sorted merge.");
+        }
+        if sorted_merge_search
+            && (setting.is_keeping_coins_enabled() || setting.is_silent_payments_enabled())
+        {
+            warn!("The coins and the outputs of silent payments are not kept in a sorted merge search.");
+        }
+        let inverted_search =
+            setting.is_inverted_search_enabled() && watchlist.is_none() && !sorted_merge_search;
         let finds = Arc::new(Mutex::new(vec![]));
         let select_descriptors = match setting.get_selected_descriptors() {
             Some(select_descriptors) => hashbrown::HashSet::from_iter(select_descriptors.clone()),
             None => hashbrown::HashSet::from_iter(DEFAULT_SELECTED_DESCRIPTORS.to_vec()),
         };
+        info!(
+            "Estimated time for searching the exploration path: ~{} minutes.",
+            (1 + estimate_runtime_seconds(
+                explorer.get_exploration_path(),
+                select_descriptors.len()
+            ) / 60)
+                .to_formatted_string(&Locale::en)
+        );
+        for warning in
+            check_exploration_sanity(explorer.get_exploration_path(), select_descriptors.len())
+        {
+            warn!("{}", warning);
+        }
+        if setting.get_chainstate_dir().is_some() && setting.get_dump_height().is_some() {
+            warn!("Dump height is ignored as the chainstate is read directly.");
+        }
+        event_log.log(LoggedEvent::RunStarted {
+            total_paths: explorer.get_exploration_path().size(),
+            selected_descriptors: select_descriptors.len(),
+        });
         info!("Creation of retriever finished successfully.");
         Ok(Retriever {
             client,
@@ -60,44 +301,378 @@ impl Retriever {
             uspk_set,
             data_dir,
             finds,
+            find_subscribers: FindSubscribers::default(),
             detailed_finds: None,
+            mempool_activity: None,
+            silent_payment_finds: None,
+            watchlist,
+            watchlist_finds: None,
             select_descriptors,
+            narrow_by_purpose: setting.is_narrowing_by_purpose_enabled(),
+            derivation_threads: setting.get_derivation_thread_count(),
+            inverted_search,
+            sorted_merge_search,
+            cancellation_token: CancellationToken::new(),
+            metrics,
+            search_coverage: None,
+            resume_cursors: vec![],
+            explorer_finds: vec![],
+            explorers_searched: 0,
+            event_log,
+            chainstate_dir: setting.get_chainstate_dir().clone(),
+            dump_height: *setting.get_dump_height(),
+            scan_in_progress_policy: setting.get_scan_in_progress_policy(),
+            check_mempool: setting.is_mempool_check_enabled(),
+            dump_url: setting.get_dump_url().clone(),
+            dump_sha256: setting.get_dump_sha256().clone(),
+            offline,
+            network: Some(network),
         })
     }
 
+    /// A receiver of the progress of the run from now on: the dump and the population of the set
+    /// starting or advancing, the search advancing, finds and the end of each phase. A receiver
+    /// lagging more than `RETRIEVER_EVENTS_CAPACITY` events behind misses the oldest of them.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<RetrieverEvent> {
+        self.event_log.subscribe()
+    }
+
+    /// A stream of the finds of the next search, or of the running one, yielding each as soon as
+    /// it is discovered, so an application can show them before the search ends. It ends with
+    /// the search, finished or cancelled. Finds of the search before a resume are not yielded;
+    /// they are in `finds` already.
+    pub fn finds_stream(&self) -> impl Stream<Item = PathDescriptorPair> + Send + Unpin {
+        self.find_subscribers.subscribe()
+    }
+
+    /// Cancels the running phase. Finds collected so far are kept and can be reported with
+    /// `write_partial_report`.
+    pub fn cancel(&self) {
+        warn!("Cancellation of the retriever requested.");
+        self.cancellation_token.cancel();
+    }
+
+    /// Makes the retriever stop when the given token is cancelled, so an application embedding it
+    /// can cancel every phase with a token of its own: the creation of the dump, the population
+    /// of the set, the search and the calls fetching the details of finds. A cancelled phase
+    /// returns `RetrieverError::Cancelled` rather than ending the process, and the retriever,
+    /// its keys zeroized when dropped, can still report the finds so far. `replace_explorer`
+    /// starts the next search with a new token.
+    pub fn set_cancellation_token(&mut self, cancellation_token: CancellationToken) {
+        self.cancellation_token = cancellation_token;
+    }
+
+    /// Replaces the explorer with one built from the given setting and clears the finds and
+    /// coverage of the previous search, so a new search can run against the populated set.
+    pub fn replace_explorer(
+        &mut self,
+        explorer_setting: ExplorerSetting,
+    ) -> Result<(), RetrieverError> {
+        self.start_search_with(Explorer::new(explorer_setting)?);
+        Ok(())
+    }
+
+    /// Replaces the exploration of the explorer, keeping its key, and clears the finds and
+    /// coverage of the previous search like `replace_explorer`, so the search space can be
+    /// narrowed or widened and searched again against the populated set. Without base paths, the
+    /// current ones are kept, which below xpubs are their origins and cannot be changed.
+    pub fn replace_exploration(
+        &mut self,
+        base_paths: Option<Vec<String>>,
+        exploration_path: &str,
+        exploration_depth: u32,
+        sweep: bool,
+    ) -> Result<(), RetrieverError> {
+        if self.inverted_search || self.sorted_merge_search {
+            error!("The set only holds the coins of the exploration it was populated for.");
+            return Err(RetrieverError::UspkSetNarrowedToExplorer);
+        }
+        let base_paths = base_paths.unwrap_or_else(|| {
+            self.explorer
+                .get_exploration_path()
+                .get_base_paths()
+                .iter()
+                .map(|base_path| base_path.to_string())
+                .collect()
+        });
+        let exploration_path =
+            ExplorationPath::new(Some(base_paths), exploration_path, exploration_depth, sweep)?;
+        let explorer = self.explorer.with_exploration_path(exploration_path)?;
+        self.start_search_with(explorer);
+        Ok(())
+    }
+
+    /// Makes the explorer the one of the next search, clearing what the previous one left.
+    fn start_search_with(&mut self, explorer: Explorer) {
+        for warning in check_exploration_sanity(
+            explorer.get_exploration_path(),
+            self.select_descriptors.len(),
+        ) {
+            warn!("{}", warning);
+        }
+        self.explorer = Arc::new(explorer);
+        self.finds.lock().unwrap().clear();
+        self.detailed_finds = None;
+        self.mempool_activity = None;
+        self.silent_payment_finds = None;
+        self.search_coverage = None;
+        self.metrics.set_finds(0);
+        self.cancellation_token = CancellationToken::new();
+        self.event_log.log(LoggedEvent::RunStarted {
+            total_paths: self.explorer.get_exploration_path().size(),
+            selected_descriptors: self.select_descriptors.len(),
+        });
+    }
+
+    /// Searches the populated set with another explorer, e.g. of another mnemonic, passphrase or
+    /// exploration path, so the set is built once for all of them. The explorer, finds and
+    /// coverage of the retriever are left as they were; the finds of the explorer are returned
+    /// tagged with it, and added to `explorer_finds`, those of a cancelled search included.
+    ///
+    /// An inverted or sorted merge search leaves only the coins of the explorer of the retriever
+    /// in the set, so neither can be searched again with another one.
+    pub async fn search_with_explorer(
+        &mut self,
+        explorer: &Explorer,
+    ) -> Result<Vec<ExplorerFind>, RetrieverError> {
+        if self.inverted_search || self.sorted_merge_search {
+            error!("The set only holds the coins of the explorer it was populated for.");
+            return Err(RetrieverError::UspkSetNarrowedToExplorer);
+        }
+        let own_explorer = std::mem::replace(&mut self.explorer, Arc::new(explorer.clone()));
+        let own_finds = std::mem::take(&mut self.finds);
+        let own_coverage = self.search_coverage.take();
+        let own_cursors = std::mem::take(&mut self.resume_cursors);
+        let searched = self.search_the_uspk_set().await;
+        let finds = std::mem::replace(&mut self.finds, own_finds);
+        self.explorer = own_explorer;
+        self.search_coverage = own_coverage;
+        self.resume_cursors = own_cursors;
+        self.metrics
+            .set_finds(self.finds.lock().unwrap().len() as u64);
+        let explorer_index = self.explorers_searched;
+        self.explorers_searched += 1;
+        let fingerprint = explorer.get_key().get_fingerprint(&SECP);
+        let explorer_finds = finds
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|find| ExplorerFind {
+                explorer_index,
+                fingerprint,
+                label: explorer.get_label().clone(),
+                find,
+            })
+            .collect::<Vec<_>>();
+        info!(
+            "Explorer {} ({}) found {} matches.",
+            explorer_index,
+            fingerprint,
+            explorer_finds.len()
+        );
+        self.explorer_finds.extend(explorer_finds.iter().cloned());
+        searched?;
+        Ok(explorer_finds)
+    }
+
+    /// Applies a new block to the populated unspent scriptpubkey set: adds the scriptpubkeys of
+    /// its outputs and, when the set keeps the coins, removes those whose last coin it spends. A
+    /// block that does not extend the last applied one, after a reorg or missed blocks, marks
+    /// the set stale (see `UnspentScriptPubKeysSet::is_stale`), as it may then lack coins the
+    /// reorg brought back, and it should be populated again. Returns the number of new
+    /// scriptpubkeys.
+    pub fn apply_block(&mut self, block: &Block) -> Result<usize, RetrieverError> {
+        if self.uspk_set.get_status() != UspkSetStatus::Ready {
+            return Err(RetrieverError::UnspentScriptPublicKeySetIsNotPopulated);
+        }
+        let new_scripts = self.uspk_set.apply_block(block)?;
+        info!(
+            "Block {} applied to the unspent scriptpubkey set, {} new scriptpubkeys.",
+            block.block_hash(),
+            new_scripts
+        );
+        self.event_log.log(LoggedEvent::BlockApplied {
+            block_hash: block.block_hash().to_string(),
+            new_scripts,
+        });
+        Ok(new_scripts)
+    }
+
+    /// Returns warnings about unlikely combinations in the exploration configuration.
+    pub fn check_exploration_sanity(&self) -> Vec<ExplorationWarning> {
+        check_exploration_sanity(
+            self.explorer.get_exploration_path(),
+            self.select_descriptors.len(),
+        )
+    }
+
     pub async fn check_for_dump_in_data_dir_or_create_dump_file(
         &mut self,
     ) -> Result<(), RetrieverError> {
+        let _phase = self.metrics.enter_phase(Phase::Dump);
         let data_dir_path = PathBuf::from_str(&self.data_dir).unwrap();
         let mut dump_file_path = data_dir_path.clone();
         dump_file_path.extend(["utxo_dump.dat"]);
+        if self.chainstate_dir.is_some() {
+            info!("Chainstate is read directly, no dump file is needed.");
+            return Ok(());
+        }
         info!("Searching for the dump file in datadir.");
         if dump_file_path.exists() {
             info!("Dump file found in datadir.");
+            self.event_log.log(LoggedEvent::DumpFound {
+                path: dump_file_path.to_string_lossy().to_string(),
+            });
             Ok(())
+        } else if let Some(dump_url) = self.dump_url.clone() {
+            info!("Dump file was not found in datadir, fetching it.");
+            if !data_dir_path.exists() {
+                fs::create_dir_all(data_dir_path)?;
+            }
+            let Some(dump_sha256) = self.dump_sha256.clone() else {
+                error!("A dump url is set without the sha256 of the dump file.");
+                let err = RetrieverError::InvalidDumpChecksum;
+                self.event_log.log_error("dump", &err);
+                return Err(err);
+            };
+            let url = dump_url.clone();
+            let fetch = tokio::task::spawn_blocking(move || {
+                fetch_dump(
+                    &url,
+                    &dump_sha256,
+                    &dump_file_path,
+                    Duration::from_secs(DUMP_FETCH_TIMEOUT_SECONDS),
+                )
+            });
+            // The blocking download cannot be interrupted, it is left to end on its own.
+            tokio::select! {
+                fetched = fetch => fetched?
+                    .inspect_err(|err| self.event_log.log_error("dump", err))?,
+                _ = self.cancellation_token.cancelled() => {
+                    warn!("Fetching the dump file cancelled.");
+                    return Err(RetrieverError::Cancelled);
+                }
+            };
+            self.event_log
+                .log(LoggedEvent::DumpFetched { url: dump_url });
+            Ok(())
+        } else if self.offline {
+            error!("Dump file was not found in datadir and cannot be created offline.");
+            let err = RetrieverError::NoDumpFileInDataDir;
+            self.event_log.log_error("dump", &err);
+            Err(err)
         } else {
             info!("Dump file was not found in datadir.");
             if !data_dir_path.exists() {
                 info!("Creating the full datadir path.");
                 fs::create_dir_all(data_dir_path)?;
             }
-            let _dump_result = self.client.dump_utxo_set(&self.data_dir).await?;
-            Ok(())
+            self.event_log.log(LoggedEvent::DumpStarted {
+                height: self.dump_height,
+            });
+            let dump_result = match self.dump_height {
+                Some(height) => {
+                    self.client
+                        .dump_utxo_set_at_height(&self.data_dir, height, &self.cancellation_token)
+                        .await
+                }
+                None => {
+                    self.client
+                        .dump_utxo_set(&self.data_dir, &self.cancellation_token)
+                        .await
+                }
+            };
+            match dump_result {
+                Ok(_) => Ok(()),
+                // The node may have completed an earlier attempt whose response was lost.
+                Err(err) if dump_file_path.exists() => {
+                    warn!(
+                        "Dump request failed ({:?}), but the dump file was written, using it.",
+                        err
+                    );
+                    Ok(())
+                }
+                Err(err) => {
+                    self.event_log.log_error("dump", &err);
+                    Err(err)
+                }
+            }
         }
     }
 
+    #[instrument(
+        name = "populate_set",
+        skip_all,
+        fields(
+            inverted = self.inverted_search,
+            sorted_merge = self.sorted_merge_search,
+            coins_inserted = Empty,
+            duration_ms = Empty,
+        )
+    )]
     pub async fn populate_uspk_set(&mut self) -> Result<(), RetrieverError> {
         if self.uspk_set.get_status() == UspkSetStatus::Empty {
+            let _phase = self.metrics.enter_phase(Phase::Population);
+            if self.inverted_search {
+                self.collect_derived_script_pubkeys()
+                    .await
+                    .inspect_err(|err| self.event_log.log_error("population", err))?;
+            }
+            let derived = match self.sorted_merge_search {
+                true => Some(
+                    self.sort_derived_script_pubkeys()
+                        .await
+                        .inspect_err(|err| self.event_log.log_error("population", err))?,
+                ),
+                false => None,
+            };
+            if let Some(chainstate_dir) = &self.chainstate_dir {
+                info!("Reading the chainstate to populate the Unspent ScriptPubKey set.");
+                let populated = match derived {
+                    Some(derived) => {
+                        self.uspk_set
+                            .merge_with_chainstate(
+                                chainstate_dir,
+                                derived,
+                                &self.cancellation_token,
+                            )
+                            .await
+                    }
+                    None => {
+                        self.uspk_set
+                            .populate_with_chainstate(chainstate_dir, &self.cancellation_token)
+                            .await
+                    }
+                };
+                return populated.inspect_err(|err| self.event_log.log_error("population", err));
+            }
             info!("Searching for the dump file to populate the Unspent ScriptPubKey set.");
             let dump_file_path_str = format!("{}/utxo_dump.dat", self.data_dir);
             let dump_file_path = PathBuf::from_str(&dump_file_path_str).unwrap();
             if !dump_file_path.exists() {
                 error!("Dump file (utxo_dump.dat) does not exist in data dir.");
-                return Err(RetrieverError::NoDumpFileInDataDir);
+                let err = RetrieverError::NoDumpFileInDataDir;
+                self.event_log.log_error("population", &err);
+                return Err(err);
             }
             info!("Dump file found.");
-            let _ = tokio::join!({ self.uspk_set.populate_with_dump_file(&dump_file_path_str) });
-            Ok(())
+            match derived {
+                Some(derived) => {
+                    self.uspk_set
+                        .merge_with_dump_file(
+                            &dump_file_path_str,
+                            derived,
+                            &self.cancellation_token,
+                        )
+                        .await
+                }
+                None => {
+                    self.uspk_set
+                        .populate_with_dump_file(&dump_file_path_str, &self.cancellation_token)
+                        .await
+                }
+            }
+            .inspect_err(|err| self.event_log.log_error("population", err))
         } else if self.uspk_set.get_status() == UspkSetStatus::Populating {
             Err(RetrieverError::PopulatingUSPKSetInProgress)
         } else {
@@ -105,39 +680,111 @@ impl Retriever {
         }
     }
 
+    /// Derives the scriptpubkeys of every explored path and makes the unspent scriptpubkey set
+    /// keep only those, for an inverted search. The search that follows the population then
+    /// derives them again, checking them against the far smaller set.
+    async fn collect_derived_script_pubkeys(&mut self) -> Result<(), RetrieverError> {
+        let total_paths = self.explorer.get_exploration_path().size();
+        if total_paths > INVERTED_SEARCH_MAX_PATHS {
+            error!(
+                "Inverted search needs at most {} paths, but the exploration has {}.",
+                INVERTED_SEARCH_MAX_PATHS.to_formatted_string(&Locale::en),
+                total_paths.to_formatted_string(&Locale::en)
+            );
+            return Err(RetrieverError::ExplorationTooLargeForInvertedSearch);
+        }
+        info!("Collecting the derived scriptpubkeys for the inverted search.");
+        let script_pubkeys = Mutex::new(MemoryUspkSet::new(
+            total_paths * self.select_descriptors.len(),
+        ));
+        let (tx, mut rx) = mpsc::channel(1024);
+        self.create_derivation_path_stream(tx).await?;
+        self.derive_path_stream(&mut rx, &SearchTarget::Collect(&script_pubkeys))
+            .await?;
+        let script_pubkeys = script_pubkeys.into_inner().unwrap();
+        info!(
+            script_pubkeys = script_pubkeys.len(),
+            "Derived scriptpubkeys collected."
+        );
+        self.uspk_set = std::mem::take(&mut self.uspk_set)
+            .with_kept_script_pubkeys(UspkStore::Memory(Arc::new(script_pubkeys)));
+        Ok(())
+    }
+
+    /// Derives the scriptpubkeys of every explored path and sorts them into runs in the data dir,
+    /// for a sorted merge search, which merges them with the sorted utxo set. As with an inverted
+    /// search, the search that follows derives them again, against the unspent ones only.
+    async fn sort_derived_script_pubkeys(&mut self) -> Result<ExternalSorter, RetrieverError> {
+        info!("Sorting the derived scriptpubkeys to disk for the sorted merge search.");
+        let derived = Mutex::new(ExternalSorter::new(
+            &PathBuf::from(&self.data_dir).join(SORTED_MERGE_DIR_NAME),
+            "derived",
+            SORTED_MERGE_RUN_ITEMS,
+        )?);
+        let (tx, mut rx) = mpsc::channel(1024);
+        self.create_derivation_path_stream(tx).await?;
+        self.derive_path_stream(&mut rx, &SearchTarget::Sort(&derived))
+            .await?;
+        let derived = derived.into_inner().unwrap();
+        info!(
+            script_pubkeys = derived.items(),
+            "Derived scriptpubkeys sorted."
+        );
+        Ok(derived)
+    }
+
+    /// Walks the exploration tree below each base path, the last step varying fastest, and sends
+    /// each path with the key of its parent, the xpub when the last child is normal and the xpriv
+    /// otherwise. The xprivs along the path are cached, so a parent is derived only from the first
+    /// child it does not share with the previous one.
     pub async fn create_derivation_path_stream(
         &self,
-        sender: mpsc::Sender<DerivationPath>,
+        sender: mpsc::Sender<PathParentKeyPair>,
     ) -> Result<(), RetrieverError> {
         let explorer = self.explorer.clone();
         let bases = explorer.get_exploration_path().get_base_paths().to_owned();
         let num_explore_paths = self.explorer.get_exploration_path().size();
         let total_paths = num_explore_paths;
         let mut sent_paths = 0;
-        tokio::spawn(async move {
+        let cancellation_token = self.cancellation_token.clone();
+        let resume_cursors = self.resume_cursors.clone();
+        tokio::task::spawn_blocking(move || {
             info!(
                 "Creation of an iterator for total {} paths started.",
                 total_paths.to_formatted_string(&Locale::en)
             );
-            let explore_paths_iter = explorer
-                .get_exploration_path()
-                .clone()
-                .get_explore()
-                .to_owned()
-                .iter()
-                .map(|step| step.to_owned())
-                .multi_cartesian_product();
-            for explore_path in explore_paths_iter {
-                for base in bases.iter() {
-                    sender
-                        .send(
-                            base.extend(
-                                DerivationPath::from_str(&format!("m/{}", explore_path.join("/")))
-                                    .unwrap(),
-                            ),
-                        )
-                        .await
-                        .unwrap();
+            let mut xpriv_cache = explorer
+                .get_key()
+                .get_master_xpriv()
+                .map(|master_xpriv| XprivCache::new(*master_xpriv));
+            for (base_index, base) in bases.iter().enumerate() {
+                let explore_paths_iter = explorer
+                    .get_exploration_path()
+                    .get_explore()
+                    .iter()
+                    .map(|step| step.to_owned())
+                    .multi_cartesian_product()
+                    .skip(resume_cursors.get(base_index).copied().unwrap_or(0));
+                for explore_path in explore_paths_iter {
+                    let path = base.extend(
+                        DerivationPath::from_str(&format!("m/{}", explore_path.join("/"))).unwrap(),
+                    );
+                    let parent_key = match (xpriv_cache.as_mut(), path.as_ref().split_last()) {
+                        (Some(xpriv_cache), Some((child, parent))) => Some(
+                            xpriv_cache
+                                .derive_parent_key(&SECP, &DerivationPath::from(parent), *child)
+                                .unwrap(),
+                        ),
+                        _ => None,
+                    };
+                    if cancellation_token.is_cancelled()
+                        || sender
+                            .blocking_send(PathParentKeyPair::new(path, parent_key))
+                            .is_err()
+                    {
+                        info!("Sending paths to processing stopped.");
+                        return;
+                    }
                     sent_paths += 1;
                     if sent_paths % 1000 == 0 {
                         info!(
@@ -152,135 +799,879 @@ impl Retriever {
         Ok(())
     }
 
+    /// Receives the paths in batches, each of which the worker pool derives and searches in
+    /// parallel. Cancellation is checked between batches.
     pub async fn process_derivation_path_stream(
         &mut self,
-        receiver: &mut mpsc::Receiver<DerivationPath>,
+        receiver: &mut mpsc::Receiver<PathParentKeyPair>,
     ) -> Result<(), RetrieverError> {
-        let secp = Secp256k1::new();
-        let select_descriptors = self.select_descriptors.clone();
         let uspk_set = self.uspk_set.get_immutable_inner_set();
-        let mut paths_received = 0;
-        while let Some(path) = receiver.recv().await {
-            paths_received += 1;
-            if paths_received % 1000 == 0 {
-                info!(
-                    "Total paths received to process: {}",
-                    paths_received.to_formatted_string(&Locale::en)
-                );
+        self.derive_path_stream(receiver, &SearchTarget::UspkSet(&uspk_set))
+            .await
+    }
+
+    /// Derives the keys of the received paths and checks their descriptors against the target.
+    /// Only searches of the unspent scriptpubkey set are logged and tracked in the coverage.
+    #[instrument(
+        name = "derive",
+        skip_all,
+        fields(
+            total_paths = self.explorer.get_exploration_path().size(),
+            paths_searched = Empty,
+            scripts_checked = Empty,
+            finds = Empty,
+            duration_ms = Empty,
+        )
+    )]
+    async fn derive_path_stream(
+        &mut self,
+        receiver: &mut mpsc::Receiver<PathParentKeyPair>,
+        target: &SearchTarget<'_>,
+    ) -> Result<(), RetrieverError> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.derivation_threads)
+            .build()?;
+        let mut batch = Vec::with_capacity(DERIVATION_BATCH_SIZE);
+        let select_descriptors = self.select_descriptors.clone();
+        let is_search = matches!(target, SearchTarget::UspkSet(_));
+        let total_paths = self.explorer.get_exploration_path().size();
+        let cancellation_token = self.cancellation_token.clone();
+        let resumed_paths = match is_search {
+            true => self.resume_cursors.iter().sum(),
+            false => 0,
+        };
+        let mut paths_received = resumed_paths;
+        let mut last_processed_path = None;
+        let mut last_checkpoint = Instant::now();
+        let _phase = is_search.then(|| self.metrics.enter_phase(Phase::Search));
+        if is_search {
+            self.metrics
+                .start_search(total_paths as u64, resumed_paths as u64);
+            self.search_coverage =
+                Some(SearchCoverage::new(total_paths, resumed_paths, None, false));
+            self.event_log
+                .log(LoggedEvent::SearchStarted { total_paths });
+        }
+        // The standalone keys are searched before any path, so a resumed search has searched them.
+        let standalone_keys = match resumed_paths {
+            0 => self.explorer.get_standalone_keys().to_vec(),
+            _ => vec![],
+        };
+        for (label, pubkey) in standalone_keys.iter() {
+            if self.search_descriptors_of_key(
+                &DerivationPath::master(),
+                *pubkey,
+                &pubkey.pubkey_hash().to_raw_hash(),
+                &select_descriptors,
+                target,
+            )? {
+                warn!("The find of standalone key {} is at path m.", label);
             }
-            let pubkey = self
-                .explorer
-                .get_master_xpriv()
-                .derive_priv(&secp, &path)
-                .unwrap()
-                .to_keypair(&secp)
-                .public_key();
-            if select_descriptors.contains(&CoveredDescriptors::P2pk) {
-                let desc = Descriptor::new_pk(pubkey);
-                let desc_pubkey = desc.script_pubkey();
-                let target = desc_pubkey.as_bytes();
-                if uspk_set.contains(target) {
-                    warn!("Found a UTXO match for ScriptPubKey.");
-                    self.finds
-                        .lock()
-                        .unwrap()
-                        .push(PathDescriptorPair::new(path.to_owned(), desc));
+        }
+        loop {
+            batch.clear();
+            tokio::select! {
+                received = receiver.recv_many(&mut batch, DERIVATION_BATCH_SIZE) => if received == 0 {
+                    break;
+                },
+                _ = cancellation_token.cancelled() => {
+                    warn!("Search of the unspent scriptpubkey set cancelled.");
+                    if is_search {
+                        self.event_log.log(LoggedEvent::SearchCancelled {
+                            scanned: paths_received,
+                        });
+                        self.search_coverage = Some(SearchCoverage::new(
+                            total_paths,
+                            paths_received,
+                            last_processed_path.as_ref().map(DerivationPath::to_string),
+                            false,
+                        ));
+                        self.find_subscribers.close();
+                        self.sort_finds();
+                        self.save_search_checkpoint(paths_received)?;
+                    }
+                    return Err(RetrieverError::Cancelled);
                 }
             }
-            if select_descriptors.contains(&CoveredDescriptors::P2pkh) {
-                let desc = Descriptor::new_pkh(pubkey)
-                    .map_err(RetrieverError::from)
-                    .unwrap();
-                let desc_pubkey = desc.script_pubkey();
-                let target = desc_pubkey.as_bytes();
-                if uspk_set.contains(target) {
-                    warn!("Found a UTXO match for ScriptPubKey.");
-                    self.finds
-                        .lock()
-                        .unwrap()
-                        .push(PathDescriptorPair::new(path.to_owned(), desc));
-                }
+            self.search_batch(&pool, &batch, &select_descriptors, target)
+                .inspect_err(|err| self.event_log.log_error("search", err))?;
+            let previously_received = paths_received;
+            paths_received += batch.len();
+            if is_search {
+                self.metrics.set_paths_searched(paths_received as u64);
             }
-            if select_descriptors.contains(&CoveredDescriptors::P2wpkh) {
-                let desc = Descriptor::new_wpkh(pubkey)
-                    .map_err(RetrieverError::from)
-                    .unwrap();
-                let desc_pubkey = desc.script_pubkey();
-                let target = desc_pubkey.as_bytes();
-                if uspk_set.contains(target) {
-                    warn!("Found a UTXO match for ScriptPubKey.");
-                    self.finds
-                        .lock()
-                        .unwrap()
-                        .push(PathDescriptorPair::new(path.to_owned(), desc));
-                }
+            if paths_received / 1000 > previously_received / 1000 {
+                info!(paths = paths_received, "Paths received to process.");
             }
-            if select_descriptors.contains(&CoveredDescriptors::P2shwpkh) {
-                let desc = Descriptor::new_sh_wpkh(pubkey)
-                    .map_err(RetrieverError::from)
-                    .unwrap();
-                let desc_pubkey = desc.script_pubkey();
-                let target = desc_pubkey.as_bytes();
-                if uspk_set.contains(target) {
-                    warn!("Found a UTXO match for ScriptPubKey.");
-                    self.finds
-                        .lock()
-                        .unwrap()
-                        .push(PathDescriptorPair::new(path.to_owned(), desc));
-                }
+            if is_search
+                && paths_received / EVENT_LOG_PATHS_SCANNED_INTERVAL
+                    > previously_received / EVENT_LOG_PATHS_SCANNED_INTERVAL
+            {
+                self.event_log.log(LoggedEvent::PathsScanned {
+                    scanned: paths_received,
+                    total: total_paths,
+                });
             }
-            if select_descriptors.contains(&CoveredDescriptors::P2tr) {
-                let desc = Descriptor::new_tr(pubkey, None)
-                    .map_err(RetrieverError::from)
-                    .unwrap();
-                let desc_pubkey = desc.script_pubkey();
-                let target = desc_pubkey.as_bytes();
-                if uspk_set.contains(target) {
-                    warn!("Found a UTXO match for ScriptPubKey.");
-                    self.finds
-                        .lock()
-                        .unwrap()
-                        .push(PathDescriptorPair::new(path.to_owned(), desc));
-                }
+            last_processed_path = batch.last().map(|PathParentKeyPair(path, _)| path.clone());
+            if is_search
+                && last_checkpoint.elapsed().as_secs() >= SEARCH_CHECKPOINT_INTERVAL_SECONDS
+            {
+                self.save_search_checkpoint(paths_received)?;
+                last_checkpoint = Instant::now();
             }
         }
+        if is_search {
+            self.sort_finds();
+            self.save_search_checkpoint(paths_received)?;
+            self.resume_cursors.clear();
+            self.search_coverage = Some(SearchCoverage::new(
+                total_paths,
+                paths_received,
+                last_processed_path.as_ref().map(DerivationPath::to_string),
+                true,
+            ));
+            self.event_log.log(LoggedEvent::SearchFinished {
+                scanned: paths_received,
+                finds: self.finds.lock().unwrap().len(),
+            });
+            self.find_subscribers.close();
+        }
         Ok(())
     }
 
+    /// Derives the keys of a batch of paths on the worker pool, those of normal children of xpubs
+    /// on the GPU if built with the gpu feature and one is usable, and checks their descriptors
+    /// against the target.
+    #[instrument(name = "match", level = "debug", skip_all, fields(paths = batch.len()))]
+    fn search_batch(
+        &self,
+        pool: &rayon::ThreadPool,
+        batch: &[PathParentKeyPair],
+        select_descriptors: &hashbrown::HashSet<CoveredDescriptors>,
+        target: &SearchTarget<'_>,
+    ) -> Result<(), RetrieverError> {
+        pool.install(|| {
+            let derived = derive_batch_on_gpu(batch);
+            batch.par_iter().enumerate().try_for_each(
+                |(position, PathParentKeyPair(path, parent_key))| {
+                    let (pubkey, key_hash) = match derived.get(position).copied().flatten() {
+                        Some(derived) => derived,
+                        None => {
+                            let pubkey = PublicKey::new(
+                                self.explorer
+                                    .get_key()
+                                    .derive_public_key_from_parent(&SECP, path, parent_key.as_ref())
+                                    .unwrap(),
+                            );
+                            (pubkey, pubkey.pubkey_hash().to_raw_hash())
+                        }
+                    };
+                    self.search_descriptors_of_key(
+                        path,
+                        pubkey,
+                        &key_hash,
+                        select_descriptors,
+                        target,
+                    )?;
+                    self.search_script_descriptors(path, pubkey, target)
+                },
+            )
+        })
+    }
+
+    /// Estimates the cost of searching with the explorer without running the search: the paths
+    /// and scriptpubkeys to check, the memory the set takes on its backend, and the time the
+    /// search takes at the rate a sample of the paths is derived and checked at here.
+    pub fn estimate(&self) -> Result<SearchEstimate, RetrieverError> {
+        let dump_file_path = PathBuf::from(&self.data_dir).join("utxo_dump.dat");
+        let populated_set = self.uspk_set.get_immutable_inner_set();
+        let set_script_pubkeys = if !populated_set.is_empty() {
+            populated_set.len()
+        } else if dump_file_path.exists() {
+            UtxoDump::open(dump_file_path.to_str().unwrap())?.utxo_set_size as usize
+        } else {
+            USPK_SET_CHAINSTATE_EXPECTED_ITEMS
+        };
+        let sample = self.sample_paths(ESTIMATE_SAMPLE_PATHS);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.derivation_threads)
+            .build()?;
+        let collected = Mutex::new(MemoryUspkSet::new(
+            sample.len() * self.select_descriptors.len(),
+        ));
+        let start = Instant::now();
+        for batch in sample.chunks(DERIVATION_BATCH_SIZE) {
+            self.search_batch(
+                &pool,
+                batch,
+                &self.select_descriptors,
+                &SearchTarget::Collect(&collected),
+            )?;
+        }
+        let paths_per_second = match sample.len() {
+            0 => 0,
+            sampled => (sampled as f64 / start.elapsed().as_secs_f64().max(1e-6)) as u64,
+        };
+        let estimate = SearchEstimate::new(
+            self.explorer.get_exploration_path().size() as u64,
+            self.select_descriptors.len() as u64,
+            set_script_pubkeys as u64,
+            self.uspk_set.projected_memory_bytes(set_script_pubkeys) as u64,
+            paths_per_second,
+        );
+        info!("{}", estimate);
+        Ok(estimate)
+    }
+
+    /// The first paths of the exploration, each with the key of its parent, as the search walks
+    /// them.
+    fn sample_paths(&self, count: usize) -> Vec<PathParentKeyPair> {
+        let mut xpriv_cache = self
+            .explorer
+            .get_key()
+            .get_master_xpriv()
+            .map(|master_xpriv| XprivCache::new(*master_xpriv));
+        let exploration_path = self.explorer.get_exploration_path();
+        exploration_path
+            .get_base_paths()
+            .iter()
+            .flat_map(|base| {
+                exploration_path
+                    .get_explore()
+                    .iter()
+                    .map(|step| step.to_owned())
+                    .multi_cartesian_product()
+                    .map(move |explore_path| {
+                        base.extend(
+                            DerivationPath::from_str(&format!("m/{}", explore_path.join("/")))
+                                .unwrap(),
+                        )
+                    })
+            })
+            .take(count)
+            .map(|path| {
+                let parent_key = match (xpriv_cache.as_mut(), path.as_ref().split_last()) {
+                    (Some(xpriv_cache), Some((child, parent))) => Some(
+                        xpriv_cache
+                            .derive_parent_key(&SECP, &DerivationPath::from(parent), *child)
+                            .unwrap(),
+                    ),
+                    _ => None,
+                };
+                PathParentKeyPair::new(path, parent_key)
+            })
+            .collect()
+    }
+
+    /// Records a find for each selected descriptor of the key whose scriptpubkey is unspent and
+    /// tells whether there was any. Uncompressed keys have no segwit or taproot descriptors, and
+    /// paths under a BIP43 purpose only have the descriptor of its script type when narrowing.
+    /// The scriptpubkeys are built from the key and its given hash160, and the descriptors only
+    /// for finds.
+    fn search_descriptors_of_key(
+        &self,
+        path: &DerivationPath,
+        pubkey: PublicKey,
+        key_hash: &hash160::Hash,
+        select_descriptors: &hashbrown::HashSet<CoveredDescriptors>,
+        target: &SearchTarget<'_>,
+    ) -> Result<bool, RetrieverError> {
+        let mut found = false;
+        let purpose_descriptor = match self.narrow_by_purpose {
+            true => CoveredDescriptors::from_purpose(path),
+            false => None,
+        };
+        for descriptor_type in CoveredDescriptors::iter() {
+            if !select_descriptors.contains(&descriptor_type)
+                || purpose_descriptor
+                    .as_ref()
+                    .is_some_and(|purpose_descriptor| *purpose_descriptor != descriptor_type)
+                || !pubkey.compressed
+                    && !matches!(
+                        descriptor_type,
+                        CoveredDescriptors::P2pk
+                            | CoveredDescriptors::P2pkh
+                            | CoveredDescriptors::BareMulti
+                            | CoveredDescriptors::P2shpk
+                    )
+            {
+                continue;
+            }
+            found |= self.check_script_pubkey(
+                path,
+                descriptor_type.script_pubkey(&SECP, &pubkey, key_hash),
+                || descriptor_type.to_descriptor(pubkey).unwrap(),
+                target,
+                "ScriptPubKey",
+            )?;
+        }
+        Ok(found)
+    }
+
+    /// Records a find for each descriptor of the multisig, of the miniscript policy, of the Green
+    /// wallet, of the timelocked scripts and of the Lightning channel scripts of the key whose
+    /// scriptpubkey is unspent. The other keys of the multisig and the policy are derived at the
+    /// path below its base path, and Green service keys at the last step of the path.
+    fn search_script_descriptors(
+        &self,
+        path: &DerivationPath,
+        pubkey: PublicKey,
+        target: &SearchTarget<'_>,
+    ) -> Result<(), RetrieverError> {
+        if let Some(Ok(descriptors)) = self
+            .explorer
+            .get_green()
+            .as_ref()
+            .map(|green| green.get_descriptors(pubkey, path))
+        {
+            for desc in descriptors {
+                self.check_descriptor(path, desc, target, "a Green ScriptPubKey")?;
+            }
+        }
+        if let Some(Ok(descriptors)) = self
+            .explorer
+            .get_timelocks()
+            .as_ref()
+            .map(|timelocks| timelocks.get_descriptors(pubkey))
+        {
+            for desc in descriptors {
+                self.check_descriptor(path, desc, target, "a timelocked ScriptPubKey")?;
+            }
+        }
+        if let Some(Ok(descriptors)) = self
+            .explorer
+            .get_lightning_node()
+            .map(|lightning_node| lightning_node.get_descriptors(pubkey, path))
+        {
+            for desc in descriptors {
+                self.check_descriptor(path, desc, target, "a Lightning channel ScriptPubKey")?;
+            }
+        }
+        let multisig = self.explorer.get_multisig();
+        let miniscript_policy = self.explorer.get_miniscript_policy();
+        if multisig.is_none() && miniscript_policy.is_none() {
+            return Ok(());
+        }
+        let Some(relative_path) = self
+            .explorer
+            .get_exploration_path()
+            .get_base_paths()
+            .iter()
+            .filter(|base_path| path.as_ref().starts_with(base_path.as_ref()))
+            .max_by_key(|base_path| base_path.len())
+            .map(|base_path| DerivationPath::from(&path.as_ref()[base_path.len()..]))
+        else {
+            return Ok(());
+        };
+        if let Some(Ok(descriptors)) = multisig
+            .as_ref()
+            .map(|multisig| multisig.get_descriptors(pubkey, &relative_path))
+        {
+            for desc in descriptors {
+                self.check_descriptor(path, desc, target, "a multisig ScriptPubKey")?;
+            }
+        }
+        if let Some(Ok(descriptors)) = miniscript_policy
+            .as_ref()
+            .map(|miniscript_policy| miniscript_policy.get_descriptors(pubkey, &relative_path))
+        {
+            for desc in descriptors {
+                self.check_descriptor(path, desc, target, "a miniscript policy ScriptPubKey")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the descriptor of the key at the path against the target: records a find if its
+    /// scriptpubkey is unspent, telling whether it is, or collects its scriptpubkey. Fails when
+    /// the sorter cannot write the scriptpubkey to disk.
+    fn check_descriptor(
+        &self,
+        path: &DerivationPath,
+        desc: Descriptor<PublicKey>,
+        target: &SearchTarget<'_>,
+        label: &str,
+    ) -> Result<bool, RetrieverError> {
+        let script_pubkey = desc.script_pubkey();
+        self.check_script_pubkey(path, script_pubkey, || desc, target, label)
+    }
+
+    /// Same as `check_descriptor`, for a scriptpubkey built without its descriptor, which is
+    /// only built when it is a find.
+    fn check_script_pubkey(
+        &self,
+        path: &DerivationPath,
+        script_pubkey: ScriptBuf,
+        desc: impl FnOnce() -> Descriptor<PublicKey>,
+        target: &SearchTarget<'_>,
+        label: &str,
+    ) -> Result<bool, RetrieverError> {
+        match target {
+            SearchTarget::UspkSet(uspk_set) => {
+                self.metrics.add_script_checked();
+                if !uspk_set.contains(script_pubkey.as_bytes()) {
+                    return Ok(false);
+                }
+                warn!("Found a UTXO match for {}.", label);
+                self.record_find(PathDescriptorPair::new(path.to_owned(), desc()));
+                Ok(true)
+            }
+            SearchTarget::Collect(script_pubkeys) => {
+                script_pubkeys
+                    .lock()
+                    .unwrap()
+                    .insert_all(&[script_pubkey.as_bytes()]);
+                Ok(false)
+            }
+            SearchTarget::Sort(derived) => {
+                derived.lock().unwrap().push(
+                    digest_script_pubkey(script_pubkey.as_bytes()),
+                    script_pubkey.as_bytes(),
+                )?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Puts the finds in a deterministic order, as the workers push them in whatever order their
+    /// batches finish.
+    fn sort_finds(&self) {
+        sort_finds(
+            &mut self.finds.lock().unwrap(),
+            self.explorer.get_exploration_path().get_base_paths(),
+        );
+    }
+
+    fn record_find(&self, find: PathDescriptorPair) {
+        self.event_log.log(LoggedEvent::FindDiscovered {
+            path: find.0.to_string(),
+            descriptor: find.get_descriptor_string(),
+        });
+        self.find_subscribers.publish(&find);
+        self.finds.lock().unwrap().push(find);
+        self.metrics.add_find();
+    }
+
+    /// Resumes the search from the checkpoint an interrupted search of the explored key left in
+    /// data dir, with its finds, or searches from the start if there is none of the same
+    /// exploration. A search that had finished is not repeated.
+    pub async fn resume(&mut self) -> Result<(), RetrieverError> {
+        let fingerprint = self.explorer.get_key().get_fingerprint(&SECP);
+        match SearchCheckpoint::load(&self.data_dir, &fingerprint)? {
+            Some(checkpoint) if checkpoint.is_of_same_search(&self.new_search_checkpoint(0)) => {
+                info!(
+                    "Resuming the search after {} paths.",
+                    checkpoint
+                        .get_searched_paths()
+                        .to_formatted_string(&Locale::en)
+                );
+                *self.finds.lock().unwrap() = checkpoint.get_finds()?;
+                self.metrics
+                    .set_finds(self.finds.lock().unwrap().len() as u64);
+                self.resume_cursors = checkpoint.get_cursors().to_vec();
+            }
+            Some(_) => {
+                warn!("The search checkpoint is of another exploration, searching from the start.")
+            }
+            None => info!("No search checkpoint found, searching from the start."),
+        }
+        self.search_the_uspk_set().await
+    }
+
+    /// A checkpoint of the search once the given number of paths of the stream is searched.
+    fn new_search_checkpoint(&self, searched_paths: usize) -> SearchCheckpoint {
+        SearchCheckpoint::new(
+            (**self.explorer.get_exploration_path()).clone(),
+            self.selected_descriptor_names(),
+            self.script_settings_digest(),
+            searched_paths,
+            &self.finds.lock().unwrap(),
+        )
+    }
+
+    /// The names of the selected descriptors, sorted so they do not depend on the order of the
+    /// setting.
+    fn selected_descriptor_names(&self) -> Vec<String> {
+        let mut descriptors = self
+            .select_descriptors
+            .iter()
+            .map(|descriptor| format!("{:?}", descriptor))
+            .collect::<Vec<_>>();
+        descriptors.sort();
+        descriptors
+    }
+
+    /// A digest of the settings that decide, besides the selected descriptors, which
+    /// scriptpubkeys the paths are searched for: the multisig, miniscript policy, Green wallet,
+    /// timelocks and Lightning node, the standalone keys and the narrowing by purpose.
+    fn script_settings_digest(&self) -> String {
+        let settings = format!(
+            "{:?}{:?}{:?}{:?}{:?}{:?}{}",
+            self.explorer.get_multisig(),
+            self.explorer.get_miniscript_policy(),
+            self.explorer.get_green(),
+            self.explorer.get_timelocks(),
+            self.explorer.get_lightning_node(),
+            self.explorer
+                .get_standalone_keys()
+                .iter()
+                .map(|(_, pubkey)| pubkey)
+                .collect::<Vec<_>>(),
+            self.narrow_by_purpose,
+        );
+        hex::encode(&sha256::Hash::hash(settings.as_bytes())[..8])
+    }
+
+    fn save_search_checkpoint(&self, searched_paths: usize) -> Result<(), RetrieverError> {
+        self.new_search_checkpoint(searched_paths).save(
+            &self.data_dir,
+            &self.explorer.get_key().get_fingerprint(&SECP),
+        )
+    }
+
     pub async fn search_the_uspk_set(&mut self) -> Result<(), RetrieverError> {
         let (tx, mut rx) = mpsc::channel(1024);
-        let _ = tokio::join!(self.create_derivation_path_stream(tx));
-        let _ = tokio::join!(self.process_derivation_path_stream(&mut rx));
-        Ok(())
+        self.create_derivation_path_stream(tx).await?;
+        self.process_derivation_path_stream(&mut rx).await
     }
 
+    /// Fetches the amounts and outputs of the finds from bitcoincore, or reads them from the local
+    /// utxo set when offline. With the coins kept while populating, they are read from those
+    /// instead, and only the found outputs are verified with bitcoincore when online.
+    #[instrument(
+        name = "details",
+        skip_all,
+        fields(source = Empty, finds = Empty, total_sats = Empty, duration_ms = Empty)
+    )]
     pub async fn get_details_of_finds_from_bitcoincore(&mut self) -> Result<(), RetrieverError> {
+        let _phase = self.metrics.enter_phase(Phase::Details);
         // if self.finds.lock().unwrap().is_empty() {
         //     return Err(RetrieverError::NoSearchHasBeenPerformed);
         // } else
+        self.sort_finds();
         if self.finds.lock().unwrap().is_empty() {
             println!("No UTXO match were found in the explored paths.");
             Ok(())
+        } else if let Some(unspent_coins) = self.uspk_set.get_unspent_coins() {
+            Span::current().record("source", "kept_coins");
+            let finds = self.finds.lock().unwrap().clone();
+            info!(
+                finds = finds.len(),
+                "Reading the details of finds from the coins kept with the set."
+            );
+            let mut detailed_finds = unspent_coins.details_of_finds(&finds);
+            if !self.offline {
+                detailed_finds = self
+                    .drop_spent_unspents(detailed_finds)
+                    .await
+                    .inspect_err(|err| self.event_log.log_error("details", err))?;
+            }
+            self.record_detailed_finds(detailed_finds).await;
+            Ok(())
+        } else if self.offline {
+            Span::current().record("source", "dump");
+            let finds = self.finds.lock().unwrap().clone();
+            let detailed_finds = self
+                .read_details_of_finds_offline(finds)
+                .await
+                .inspect_err(|err| self.event_log.log_error("details", err))?;
+            self.record_detailed_finds(detailed_finds).await;
+            Ok(())
         } else {
-            let path_scan_request_pairs = self
-                .finds
-                .lock()
-                .unwrap()
+            Span::current().record("source", "scantxoutset");
+            let finds = self.finds.lock().unwrap().clone();
+            let mut checkpoint = DetailsCheckpoint::load(&self.data_dir)?;
+            let path_scan_request_pairs = finds
                 .iter()
+                .filter(|find| checkpoint.get(find).is_none())
                 .map(|item| item.to_path_scan_request_descriptor_trio())
-                .collect();
-            self.detailed_finds = Some(self.client.scan_utxo_set(path_scan_request_pairs).await?);
+                .collect::<Vec<_>>();
+            if !checkpoint.is_empty() {
+                info!(
+                    "Resuming the details of finds, {} of {} finds were already scanned.",
+                    finds.len() - path_scan_request_pairs.len(),
+                    finds.len()
+                );
+            }
+            if !path_scan_request_pairs.is_empty() {
+                self.settle_utxo_set_scan_in_progress()
+                    .await
+                    .inspect_err(|err| self.event_log.log_error("details", err))?;
+            }
+            let mut unscanned = path_scan_request_pairs;
+            let mut retry_round = 0;
+            while !unscanned.is_empty() {
+                let mut failed = vec![];
+                let mut last_error = None;
+                for batch in unscanned.chunks(SCANTXOUTSET_BATCH_SIZE) {
+                    let scan = tokio::select! {
+                        scan = self.client.scan_utxo_set(batch.to_vec()) => scan
+                            .inspect_err(|err| self.event_log.log_error("details", err))?,
+                        _ = self.cancellation_token.cancelled() => {
+                            warn!("Fetching the details of finds cancelled.");
+                            let _ = self.client.abort_utxo_set_scan().await;
+                            return Err(RetrieverError::Cancelled);
+                        }
+                    };
+                    if !scan.results.is_empty() {
+                        checkpoint.extend(&scan.results);
+                        checkpoint.save(&self.data_dir)?;
+                    }
+                    if let Some(err) = scan.error {
+                        self.event_log.log_error("details", &err);
+                        failed.extend(scan.unscanned);
+                        last_error = Some(err);
+                    }
+                }
+                unscanned = failed;
+                let Some(err) = last_error else {
+                    break;
+                };
+                retry_round += 1;
+                if retry_round > DETAILS_SCAN_RETRY_ROUNDS
+                    || !matches!(err, RetrieverError::RpcRetriesExhausted)
+                {
+                    warn!(
+                        "The details of {} finds could not be fetched, keeping the details of the \
+                        {} scanned ones.",
+                        unscanned.len(),
+                        checkpoint.len()
+                    );
+                    let mut detailed_finds = checkpoint.details_of_finds(&finds);
+                    sort_detailed_finds(
+                        &mut detailed_finds,
+                        self.explorer.get_exploration_path().get_base_paths(),
+                    );
+                    self.detailed_finds = Some(detailed_finds);
+                    return Err(err);
+                }
+                warn!(
+                    "Scanning the utxo set for {} finds failed, retrying them in {} s ({} of {}).",
+                    unscanned.len(),
+                    DETAILS_SCAN_RETRY_DELAY_SECONDS,
+                    retry_round,
+                    DETAILS_SCAN_RETRY_ROUNDS
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(DETAILS_SCAN_RETRY_DELAY_SECONDS)) => {}
+                    _ = self.cancellation_token.cancelled() => {
+                        warn!("Fetching the details of finds cancelled.");
+                        return Err(RetrieverError::Cancelled);
+                    }
+                }
+                self.settle_utxo_set_scan_in_progress()
+                    .await
+                    .inspect_err(|err| self.event_log.log_error("details", err))?;
+            }
+            let detailed_finds = checkpoint.details_of_finds(&finds);
+            let detailed_finds = self
+                .drop_spent_unspents(detailed_finds)
+                .await
+                .inspect_err(|err| self.event_log.log_error("details", err))?;
+            DetailsCheckpoint::remove(&self.data_dir)?;
+            self.record_detailed_finds(detailed_finds).await;
             Ok(())
         }
     }
 
+    /// Keeps the details of finds, checking the mempool for their outputs when online and
+    /// enabled.
+    async fn record_detailed_finds(
+        &mut self,
+        mut detailed_finds: Vec<PathScanResultDescriptorTrio>,
+    ) {
+        sort_detailed_finds(
+            &mut detailed_finds,
+            self.explorer.get_exploration_path().get_base_paths(),
+        );
+        let total_sats = detailed_finds
+            .iter()
+            .map(|detail| detail.1.total_amount.to_sat())
+            .sum();
+        Span::current().record("total_sats", total_sats);
+        self.event_log.log(LoggedEvent::DetailsFetched {
+            finds: detailed_finds.len(),
+            total_sats,
+        });
+        if self.check_mempool && !self.offline {
+            self.mempool_activity = match self.check_mempool_for_finds(&detailed_finds).await {
+                Ok(mempool_activity) => Some(mempool_activity),
+                Err(err) => {
+                    warn!("Checking the mempool for finds failed: {:?}", err);
+                    None
+                }
+            };
+        }
+        self.detailed_finds = Some(detailed_finds);
+    }
+
+    /// Reads the details of finds from the dump file or the chainstate in one pass over its coins.
+    async fn read_details_of_finds_offline(
+        &self,
+        finds: Vec<PathDescriptorPair>,
+    ) -> Result<Vec<PathScanResultDescriptorTrio>, RetrieverError> {
+        info!(
+            "Reading the details of {} finds from the local utxo set.",
+            finds.len()
+        );
+        let chainstate_dir = self.chainstate_dir.clone();
+        let dump_file_path = format!("{}/utxo_dump.dat", self.data_dir);
+        let cancellation_token = self.cancellation_token.clone();
+        tokio::task::spawn_blocking(move || match chainstate_dir {
+            Some(chainstate_dir) => scan_coins_for_finds(
+                ChainstateReader::open(&chainstate_dir)?,
+                None,
+                &finds,
+                &cancellation_token,
+            ),
+            None => {
+                let dump = UtxoDump::open(&dump_file_path)?;
+                let block_hash = dump.block_hash;
+                scan_coins_for_finds(dump, Some(block_hash), &finds, &cancellation_token)
+            }
+        })
+        .await?
+    }
+
+    /// Drops the unspents of the detailed finds that were spent in blocks found since they were
+    /// scanned, looking them up over REST or in parallel over the client pool.
+    async fn drop_spent_unspents(
+        &self,
+        mut detailed_finds: Vec<PathScanResultDescriptorTrio>,
+    ) -> Result<Vec<PathScanResultDescriptorTrio>, RetrieverError> {
+        let out_points = detailed_finds
+            .iter()
+            .flat_map(|detail| {
+                detail
+                    .1
+                    .unspents
+                    .iter()
+                    .map(|utxo| OutPoint::new(utxo.txid, utxo.vout))
+            })
+            .collect::<Vec<_>>();
+        if out_points.is_empty() {
+            return Ok(detailed_finds);
+        }
+        info!(
+            "Verifying {} found outputs with bitcoincore.",
+            out_points.len()
+        );
+        let mut are_unspent = self.client.are_unspent(out_points).await?.into_iter();
+        let mut spent = 0;
+        for detail in detailed_finds.iter_mut() {
+            detail.1.unspents.retain(|_| {
+                let is_unspent = are_unspent.next().unwrap_or_default();
+                if !is_unspent {
+                    spent += 1;
+                }
+                is_unspent
+            });
+            detail.1.total_amount = detail.1.unspents.iter().map(|utxo| utxo.amount).sum();
+        }
+        if spent > 0 {
+            warn!(
+                "{} found outputs were spent since the scan and are left out.",
+                spent
+            );
+        }
+        Ok(detailed_finds)
+    }
+
+    /// Looks for mempool transactions spending the unspents of the detailed finds or paying to
+    /// their scriptpubkeys.
+    async fn check_mempool_for_finds(
+        &self,
+        detailed_finds: &[PathScanResultDescriptorTrio],
+    ) -> Result<Vec<MempoolActivity>, RetrieverError> {
+        info!("Checking the mempool of bitcoincore for finds.");
+        let mut mempool_activity = vec![MempoolActivity::default(); detailed_finds.len()];
+        let unspents = detailed_finds
+            .iter()
+            .enumerate()
+            .flat_map(|(index, detail)| {
+                detail
+                    .1
+                    .unspents
+                    .iter()
+                    .map(move |utxo| (index, OutPoint::new(utxo.txid, utxo.vout), utxo.amount))
+            })
+            .collect::<Vec<_>>();
+        if !unspents.is_empty() {
+            let spenders = self
+                .client
+                .get_mempool_spenders(unspents.iter().map(|unspent| unspent.1).collect())
+                .await?;
+            for ((index, _, amount), spender) in unspents.iter().zip(spenders) {
+                if let Some(txid) = spender {
+                    mempool_activity[*index].spending_txids.push(txid);
+                    mempool_activity[*index].spending_sats += amount.to_sat();
+                }
+            }
+        }
+        let script_pubkeys = detailed_finds
+            .iter()
+            .enumerate()
+            .map(|(index, detail)| (detail.2.script_pubkey(), index))
+            .collect::<hashbrown::HashMap<_, _>>();
+        let incoming = self
+            .client
+            .get_mempool_outputs_to(script_pubkeys.keys().cloned().collect())
+            .await?;
+        for (out_point, tx_out) in incoming {
+            let index = script_pubkeys[&tx_out.script_pubkey];
+            mempool_activity[index].incoming_txids.push(out_point.txid);
+            mempool_activity[index].incoming_sats += tx_out.value.to_sat();
+        }
+        let active = mempool_activity
+            .iter()
+            .filter(|activity| !activity.is_empty())
+            .count();
+        if active > 0 {
+            warn!(
+                "{} finds have unconfirmed transactions in the mempool.",
+                active
+            );
+        }
+        Ok(mempool_activity)
+    }
+
+    /// Makes sure bitcoincore is not running another utxo set scan, which would make the next
+    /// scantxoutset fail, by waiting for it to finish or aborting it.
+    async fn settle_utxo_set_scan_in_progress(&self) -> Result<(), RetrieverError> {
+        while let Some(progress) = self.client.get_utxo_set_scan_progress().await? {
+            match self.scan_in_progress_policy {
+                ScanInProgressPolicy::Wait => info!(
+                    "Bitcoincore is already scanning the utxo set ({:.0}%), waiting for it to finish.",
+                    progress
+                ),
+                ScanInProgressPolicy::Abort => {
+                    warn!(
+                        "Bitcoincore is already scanning the utxo set ({:.0}%), aborting it.",
+                        progress
+                    );
+                    self.client.abort_utxo_set_scan().await?;
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(SCANTXOUTSET_STATUS_POLL_SECONDS)) => {}
+                _ = self.cancellation_token.cancelled() => return Err(RetrieverError::Cancelled),
+            }
+        }
+        Ok(())
+    }
+
     pub fn print_detailed_finds_on_console(&self) -> Result<(), RetrieverError> {
         if self.detailed_finds.is_none() {
             return Err(RetrieverError::DetailsHaveNotBeenFetched);
         };
         for (index, detail) in self.detailed_finds.as_ref().unwrap().iter().enumerate() {
+            let mempool_activity = self
+                .mempool_activity
+                .as_ref()
+                .map(|mempool_activity| mempool_activity[index].to_string())
+                .unwrap_or_default();
             let info = format!(
-                "\nResult {}\nPath: {}\nAmount(satoshis): {}\nDescriptor: {}",
+                "\nResult {}\nPath: {}\nAmount(satoshis): {}{}\nDescriptor: {}",
                 index + 1,
                 detail.0,
                 detail
@@ -288,13 +1679,180 @@ impl Retriever {
                     .total_amount
                     .to_sat()
                     .to_formatted_string(&Locale::en),
-                detail.2
+                mempool_activity,
+                detail.get_descriptor_string()
             );
             println!("{info}");
         }
         Ok(())
     }
 
+    /// Searches the taproot outputs kept while populating for silent payments to the master key
+    /// of the explorer. The utxo set holds no inputs, so each block creating kept outputs is
+    /// fetched with the prevouts of its transactions to compute their shared secrets.
+    pub async fn search_silent_payments(&mut self) -> Result<(), RetrieverError> {
+        let Some(outputs) = self.uspk_set.get_silent_payment_outputs() else {
+            return Ok(());
+        };
+        if self.offline {
+            error!("Silent payments cannot be searched offline, as blocks are fetched from bitcoincore.");
+            return Err(RetrieverError::UnavailableOffline);
+        }
+        let Some(master_xpriv) = self.explorer.get_key().get_master_xpriv() else {
+            error!("Silent payments cannot be searched without the private scan key.");
+            return Err(RetrieverError::SilentPaymentsUnavailable);
+        };
+        let keys = SilentPaymentKeys::from_master_xpriv(master_xpriv)?;
+        let heights = outputs.get_heights();
+        info!(
+            "Searching {} unspent taproot outputs in {} blocks for silent payments.",
+            outputs.len().to_formatted_string(&Locale::en),
+            heights.len().to_formatted_string(&Locale::en)
+        );
+        let mut silent_payment_finds = vec![];
+        for height in heights {
+            if self.cancellation_token.is_cancelled() {
+                return Err(RetrieverError::Cancelled);
+            }
+            let unspent_outputs = outputs.get_outputs_at(height);
+            let transactions = self
+                .client
+                .get_block_transactions_with_prevouts(height as u64)
+                .await?;
+            for (tx, prevouts) in transactions {
+                let txid = tx.txid();
+                if !unspent_outputs
+                    .iter()
+                    .any(|output| output.out_point.txid == txid)
+                {
+                    continue;
+                }
+                for find in keys.scan_transaction(&tx, &prevouts, height, unspent_outputs)? {
+                    warn!("Found an unspent silent payment at {}.", find.out_point);
+                    silent_payment_finds.push(find);
+                }
+            }
+        }
+        info!(
+            "Silent payments search finished with {} finds.",
+            silent_payment_finds.len()
+        );
+        self.silent_payment_finds = Some(silent_payment_finds);
+        Ok(())
+    }
+
+    pub fn print_silent_payment_finds_on_console(&self) {
+        let Some(silent_payment_finds) = self.silent_payment_finds.as_ref() else {
+            return;
+        };
+        if silent_payment_finds.is_empty() {
+            println!("No silent payments were found.");
+        }
+        for (index, find) in silent_payment_finds.iter().enumerate() {
+            println!(
+                "\nSilent payment {}\nOutpoint: {}\nHeight: {}\nAmount(satoshis): {}\nOutput: k = {}{}\nSpend key tweak: {}",
+                index + 1,
+                find.out_point,
+                find.height,
+                find.amount_sats.to_formatted_string(&Locale::en),
+                find.k,
+                if find.change { ", change" } else { "" },
+                find.tweak
+            );
+        }
+    }
+
+    /// Checks the scriptpubkeys of the watchlist against the populated unspent scriptpubkey set.
+    pub fn search_watchlist(&mut self) {
+        let Some(watchlist) = self.watchlist.as_ref() else {
+            return;
+        };
+        info!(
+            "Searching {} scriptpubkeys of the watchlist.",
+            watchlist.len().to_formatted_string(&Locale::en)
+        );
+        let watchlist_finds = watchlist.search(&self.uspk_set.get_immutable_inner_set());
+        for find in watchlist_finds.iter() {
+            warn!(
+                "Found a UTXO match for watchlist ScriptPubKey {}.",
+                find.script_pubkey.to_hex_string()
+            );
+        }
+        info!(
+            "Watchlist search finished with {} finds.",
+            watchlist_finds.len()
+        );
+        self.watchlist_finds = Some(watchlist_finds);
+    }
+
+    /// Fetches the amounts of the watchlist finds from bitcoincore. They are left unknown
+    /// offline.
+    pub async fn get_details_of_watchlist_finds_from_bitcoincore(
+        &mut self,
+    ) -> Result<(), RetrieverError> {
+        let Some(watchlist_finds) = self.watchlist_finds.as_ref() else {
+            return Ok(());
+        };
+        if watchlist_finds.is_empty() {
+            return Ok(());
+        }
+        if self.offline {
+            warn!("The amounts of watchlist finds are not fetched offline.");
+            return Ok(());
+        }
+        let script_pubkeys = watchlist_finds
+            .iter()
+            .map(|find| find.script_pubkey.to_owned())
+            .collect::<Vec<_>>();
+        self.settle_utxo_set_scan_in_progress().await?;
+        let unspents = tokio::select! {
+            unspents = self.client.scan_utxo_set_for_script_pubkeys(script_pubkeys) => unspents?,
+            _ = self.cancellation_token.cancelled() => {
+                warn!("Fetching the amounts of watchlist finds cancelled.");
+                let _ = self.client.abort_utxo_set_scan().await;
+                return Err(RetrieverError::Cancelled);
+            }
+        };
+        for find in self.watchlist_finds.as_mut().unwrap().iter_mut() {
+            find.amount_sats = Some(
+                unspents
+                    .iter()
+                    .filter(|utxo| utxo.script_pub_key == find.script_pubkey)
+                    .map(|utxo| utxo.amount.to_sat())
+                    .sum(),
+            );
+        }
+        Ok(())
+    }
+
+    pub fn print_watchlist_finds_on_console(&self) {
+        let Some(watchlist_finds) = self.watchlist_finds.as_ref() else {
+            return;
+        };
+        if watchlist_finds.is_empty() {
+            println!("None of the watchlist scriptpubkeys is unspent.");
+        }
+        for (index, find) in watchlist_finds.iter().enumerate() {
+            let amount = find
+                .amount_sats
+                .map(|amount_sats| {
+                    format!(
+                        "\nAmount(satoshis): {}",
+                        amount_sats.to_formatted_string(&Locale::en)
+                    )
+                })
+                .unwrap_or_default();
+            println!(
+                "\nWatchlist result {}\nScriptPubKey: {}\nAddress: {}{}\nDescriptor: {}",
+                index + 1,
+                find.script_pubkey.to_hex_string(),
+                find.address.as_deref().unwrap_or("none"),
+                amount,
+                find.descriptor
+            );
+        }
+    }
+
     pub fn get_detailed_finds(&self) -> Result<Vec<PathScanResultDescriptorTrio>, RetrieverError> {
         if self.detailed_finds.is_none() {
             Err(RetrieverError::DetailsHaveNotBeenFetched)
@@ -302,14 +1860,483 @@ impl Retriever {
             Ok(self.detailed_finds.as_ref().unwrap().to_owned())
         }
     }
+
+    /// Runs the phases in order: finds or creates the dump and populates the set, unless it is
+    /// populated already, searches it, fetches the details of finds and searches for silent
+    /// payments, if enabled. In watchlist mode the watchlist is searched instead, and its finds
+    /// are in `watchlist_finds`. A failing phase stops the run with its error, and the finds so
+    /// far can still be reported with `write_partial_report`.
+    pub async fn run(&mut self) -> Result<RunReport, RetrieverError> {
+        self.run_phases(false).await
+    }
+
+    /// Same as `run`, resuming the search from its checkpoint, see `resume`.
+    pub async fn run_resumed(&mut self) -> Result<RunReport, RetrieverError> {
+        self.run_phases(true).await
+    }
+
+    async fn run_phases(&mut self, resume: bool) -> Result<RunReport, RetrieverError> {
+        if self.uspk_set.get_status() == UspkSetStatus::Empty {
+            self.check_for_dump_in_data_dir_or_create_dump_file()
+                .await?;
+            self.populate_uspk_set().await?;
+        }
+        if self.watchlist.is_some() {
+            self.search_watchlist();
+            self.get_details_of_watchlist_finds_from_bitcoincore()
+                .await?;
+            return Ok(self.report());
+        }
+        match resume {
+            true => self.resume().await?,
+            false => self.search_the_uspk_set().await?,
+        }
+        self.get_details_of_finds_from_bitcoincore().await?;
+        self.search_silent_payments().await?;
+        if let Err(err) = self.record_run() {
+            warn!("Recording the run in the run history failed: {:?}", err);
+        }
+        Ok(self.report())
+    }
+
+    /// Records the exploration and descriptors of the last search, how much of it was covered and
+    /// its finds in the run history of data dir, see `RunHistory`. `run` records every run it
+    /// finishes, a run stopped by an error can be recorded with this.
+    pub fn record_run(&self) -> Result<RunRecord, RetrieverError> {
+        RunHistory::open(&self.data_dir)?.record(RunRecord::new(
+            &self.explorer.get_key().get_fingerprint(&SECP),
+            self.explorer.get_label().clone(),
+            (**self.explorer.get_exploration_path()).clone(),
+            self.selected_descriptor_names(),
+            self.search_coverage.clone(),
+            ReportedFind::from_finds(
+                &self.finds.lock().unwrap(),
+                self.detailed_finds.as_deref(),
+                self.mempool_activity.as_deref(),
+            ),
+        ))
+    }
+
+    /// The report of the finds so far, with their amounts once their details are fetched.
+    pub fn report(&self) -> RunReport {
+        RunReport::new(
+            self.search_coverage.clone(),
+            &self.finds.lock().unwrap(),
+            self.detailed_finds.as_deref(),
+            self.mempool_activity.as_deref(),
+        )
+    }
+
+    /// The totals of the last search, broken down by descriptor type and base path, with how
+    /// long each phase of the run took.
+    pub fn scan_report(&self) -> ScanReport {
+        ScanReport::new(
+            &self.metrics.snapshot(),
+            self.explorer.get_exploration_path().get_base_paths(),
+            &self.finds.lock().unwrap(),
+            self.detailed_finds.as_deref(),
+        )
+    }
+
+    /// Prints the scan report and writes it into data dir.
+    pub fn write_scan_report(&self) -> Result<PathBuf, RetrieverError> {
+        let report = self.scan_report();
+        println!("{report}");
+        let report_path = PathBuf::from_str(&self.data_dir)
+            .unwrap()
+            .join(self.output_file_name("scan_report.json"));
+        fs::write(&report_path, serde_json::to_string_pretty(&report)?)?;
+        info!("Scan report written to {}.", report_path.display());
+        Ok(report_path)
+    }
+
+    /// Prints and writes (as partial_report.json in data dir) the finds collected so far by a run
+    /// that was cancelled or failed, together with how much of the path space was searched.
+    pub fn write_partial_report(&self, cause: &str) -> Result<PathBuf, RetrieverError> {
+        let finds = self.finds.lock().unwrap().clone();
+        let report = PartialReport::new(
+            cause,
+            self.search_coverage.clone(),
+            &finds,
+            self.detailed_finds.as_deref(),
+            self.mempool_activity.as_deref(),
+        );
+        println!("{report}");
+        let report_path = PathBuf::from_str(&self.data_dir)
+            .unwrap()
+            .join(self.output_file_name("partial_report.json"));
+        fs::write(&report_path, serde_json::to_string_pretty(&report)?)?;
+        warn!("Partial report written to {}.", report_path.display());
+        Ok(report_path)
+    }
+
+    /// Prefixes the name of a file written into data dir with the label of the explored seed, so
+    /// the files of the seeds of a batch or of BIP85 children do not overwrite each other.
+    fn output_file_name(&self, file_name: &str) -> String {
+        match self.explorer.get_label() {
+            Some(label) => format!("{}_{}", label, file_name),
+            None => file_name.to_string(),
+        }
+    }
+
+    /// Whether the last search covered the whole exploration path.
+    pub fn is_search_complete(&self) -> bool {
+        self.search_coverage
+            .as_ref()
+            .is_some_and(|coverage| *coverage.get_completed())
+    }
+
+    /// Creates a watch-only descriptor wallet with the given name on bitcoincore and imports the
+    /// descriptor of each find into it, labelled with its path, so the funds can be tracked and
+    /// spent (with the keys from elsewhere) from bitcoincore. Once details are fetched, the node
+    /// rescans each descriptor from the block of its oldest output only, otherwise from genesis.
+    /// Returns the number of imported descriptors.
+    pub async fn export_finds_to_core_wallet(&self, name: &str) -> Result<usize, RetrieverError> {
+        if self.offline {
+            error!("Finds cannot be exported to bitcoincore offline.");
+            return Err(RetrieverError::UnavailableOffline);
+        }
+        let finds = self.finds.lock().unwrap().clone();
+        if finds.is_empty() {
+            info!("No finds to export to a bitcoincore wallet.");
+            return Ok(0);
+        }
+        let oldest_heights = finds
+            .iter()
+            .map(|find| {
+                self.detailed_finds.as_ref().and_then(|detailed_finds| {
+                    detailed_finds
+                        .iter()
+                        .find(|detail| detail.0 == find.0 && detail.2 == find.1)
+                        .and_then(|detail| detail.1.unspents.iter().map(|utxo| utxo.height).min())
+                })
+            })
+            .collect::<Vec<_>>();
+        let block_times = self
+            .client
+            .get_block_times(oldest_heights.iter().flatten().copied().collect())
+            .await?;
+        let mut block_times = block_times.into_iter();
+        let imports = finds
+            .iter()
+            .zip(oldest_heights)
+            .map(|(find, oldest_height)| {
+                let timestamp = oldest_height
+                    .and_then(|_| block_times.next())
+                    .unwrap_or_default();
+                serde_json::json!({
+                    "desc": find.get_descriptor_string(),
+                    "timestamp": timestamp,
+                    "label": find.0.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let results = self
+            .client
+            .import_descriptors_into_new_wallet(name, imports)
+            .await
+            .inspect_err(|err| self.event_log.log_error("wallet_export", err))?;
+        let failed = results
+            .iter()
+            .filter(|result| result["success"].as_bool() != Some(true))
+            .inspect(|result| warn!("Importing a descriptor failed: {}", result["error"]))
+            .count();
+        if failed > 0 {
+            error!(
+                "{} of {} descriptors could not be imported.",
+                failed,
+                finds.len()
+            );
+            return Err(RetrieverError::WalletImportFailed);
+        }
+        info!(
+            "{} finds imported into bitcoincore wallet {}.",
+            finds.len(),
+            name
+        );
+        Ok(finds.len())
+    }
+
+    /// Writes a Sparrow importable wallet file into the data dir for each account in which
+    /// funds were found, and returns the paths of the written files.
+    pub fn export_sparrow_wallets(&self) -> Result<Vec<PathBuf>, RetrieverError> {
+        let finds = self.finds.lock().unwrap().clone();
+        if finds.is_empty() {
+            info!("No finds to export as Sparrow wallets.");
+            return Ok(vec![]);
+        }
+        let Some(master_xpriv) = self.explorer.get_key().get_master_xpriv() else {
+            error!("Sparrow wallets cannot be exported without the master key.");
+            return Err(RetrieverError::UnavailableWatchOnly);
+        };
+        let mut exports =
+            SparrowWalletExport::from_finds(master_xpriv, &finds, self.detailed_finds.as_deref())?;
+        if !self.is_search_complete() {
+            exports
+                .iter_mut()
+                .for_each(SparrowWalletExport::mark_partial);
+        }
+        let mut export_paths = vec![];
+        for export in exports {
+            let export_path = PathBuf::from_str(&self.data_dir)
+                .unwrap()
+                .join(self.output_file_name(&export.file_name()));
+            fs::write(&export_path, serde_json::to_string_pretty(&export)?)?;
+            info!(
+                "Sparrow wallet export written to {}.",
+                export_path.display()
+            );
+            export_paths.push(export_path);
+        }
+        Ok(export_paths)
+    }
+
+    /// The private keys of the finds in WIF, or BIP38 encrypted with the password if given, to
+    /// sweep their funds with other wallet software. Nothing calls this unless asked to, as
+    /// anyone reading the keys can spend the funds.
+    pub fn export_wifs(&self, password: Option<&str>) -> Result<Vec<FoundWif>, RetrieverError> {
+        let finds = self.finds.lock().unwrap().clone();
+        if finds.is_empty() {
+            info!("No finds to export the private keys of.");
+            return Ok(vec![]);
+        }
+        let Some(master_xpriv) = self.explorer.get_key().get_master_xpriv() else {
+            error!("Private keys cannot be exported without the master key.");
+            return Err(RetrieverError::UnavailableWatchOnly);
+        };
+        warn!("Exporting the private keys of the finds, which spend their funds.");
+        wifs_of_finds(master_xpriv, &finds, password)
+    }
+
+    /// Builds the unsigned PSBTs sweeping the unspent outputs of the detailed finds to the
+    /// destination address at the fee rate in sat/vB, one for all of them unless the options
+    /// split them per find, to be signed by a wallet holding the keys. The transactions of legacy
+    /// outputs, which their signers need, are fetched from bitcoincore unless offline.
+    pub async fn build_sweep_psbts(
+        &self,
+        destination: &str,
+        fee_rate_sat_per_vb: u64,
+        options: &SweepOptions,
+    ) -> Result<Vec<Psbt>, RetrieverError> {
+        let Some(detailed_finds) = self.detailed_finds.as_deref() else {
+            error!("The details of the finds must be fetched before sweeping them.");
+            return Err(RetrieverError::DetailsHaveNotBeenFetched);
+        };
+        let network = self.network.unwrap_or(Network::Bitcoin);
+        let destination = Address::from_str(destination)
+            .ok()
+            .and_then(|address| address.require_network(network).ok())
+            .ok_or_else(|| {
+                error!("Invalid sweep address for {}: {}", network, destination);
+                RetrieverError::InvalidSweepAddress
+            })?;
+        let fee_rate =
+            FeeRate::from_sat_per_vb(fee_rate_sat_per_vb).ok_or(RetrieverError::InvalidFeeRate)?;
+        let mut inputs = sweep_inputs_of_finds(self.explorer.get_key(), detailed_finds)?;
+        let heights = detailed_finds
+            .iter()
+            .flat_map(|detail| detail.1.unspents.iter())
+            .map(|utxo| utxo.height)
+            .collect::<Vec<_>>();
+        let legacy = inputs
+            .iter()
+            .zip(heights)
+            .enumerate()
+            .filter(|(_, (input, _))| input.is_legacy())
+            .map(|(index, (input, height))| (index, (input.get_outpoint().txid, height)))
+            .collect::<Vec<_>>();
+        if !legacy.is_empty() && self.offline {
+            warn!("The transactions of legacy outputs cannot be fetched offline, signers may need them.");
+        } else if !legacy.is_empty() {
+            let previous_txs = self
+                .client
+                .get_transactions_at_heights(legacy.iter().map(|(_, txid)| *txid).collect())
+                .await?;
+            for ((index, _), previous_tx) in legacy.into_iter().zip(previous_txs) {
+                inputs[index] = inputs[index].clone().with_previous_tx(previous_tx);
+            }
+        }
+        build_sweep_psbts(&inputs, &destination, fee_rate, options)
+    }
+
+    /// The path in data dir of a file of a sweep, numbered when the finds are swept apart.
+    fn sweep_file_path(&self, index: usize, count: usize, extension: &str) -> PathBuf {
+        let file_name = match count {
+            1 => format!("sweep.{}", extension),
+            _ => format!("sweep_{}.{}", index, extension),
+        };
+        PathBuf::from_str(&self.data_dir)
+            .unwrap()
+            .join(self.output_file_name(&file_name))
+    }
+
+    /// Writes the sweep PSBTs of `build_sweep_psbts` in base64 to sweep.psbt in data dir, or
+    /// sweep_0.psbt and on for several, which wallets such as Sparrow or bitcoincore's
+    /// walletprocesspsbt can load and sign.
+    pub async fn write_sweep_psbts(
+        &self,
+        destination: &str,
+        fee_rate_sat_per_vb: u64,
+        options: &SweepOptions,
+    ) -> Result<Vec<PathBuf>, RetrieverError> {
+        let psbts = self
+            .build_sweep_psbts(destination, fee_rate_sat_per_vb, options)
+            .await?;
+        let mut psbt_paths = vec![];
+        for (index, psbt) in psbts.iter().enumerate() {
+            let psbt_path = self.sweep_file_path(index, psbts.len(), "psbt");
+            fs::write(
+                &psbt_path,
+                base64::engine::general_purpose::STANDARD.encode(psbt.serialize()),
+            )?;
+            info!("Sweep PSBT written to {}.", psbt_path.display());
+            psbt_paths.push(psbt_path);
+        }
+        Ok(psbt_paths)
+    }
+
+    /// Signs a sweep PSBT, such as of `build_sweep_psbts`, with the keys the explorer derives from
+    /// its master key, and finalizes it into a raw transaction. Fails if any input is left
+    /// unsigned, e.g. one of a standalone key or a multisig short of signatures.
+    pub fn sign_sweep(&self, mut psbt: Psbt) -> Result<Transaction, RetrieverError> {
+        let Some(master_xpriv) = self.explorer.get_key().get_master_xpriv() else {
+            error!("The sweep cannot be signed without the master key.");
+            return Err(RetrieverError::UnavailableWatchOnly);
+        };
+        sign_sweep_psbt(&mut psbt, master_xpriv)?;
+        finalize_sweep_psbt(psbt)
+    }
+
+    /// Builds and signs the sweeps of the detailed finds, and writes the raw transactions in hex
+    /// to sweep.tx in data dir, or numbered like the PSBTs, ready for `sendrawtransaction` or
+    /// `broadcast_sweep`. Nothing is written unless all of them are signed.
+    pub async fn write_signed_sweeps(
+        &self,
+        destination: &str,
+        fee_rate_sat_per_vb: u64,
+        options: &SweepOptions,
+    ) -> Result<Vec<Transaction>, RetrieverError> {
+        let transactions = self
+            .build_sweep_psbts(destination, fee_rate_sat_per_vb, options)
+            .await?
+            .into_iter()
+            .map(|psbt| self.sign_sweep(psbt))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (index, transaction) in transactions.iter().enumerate() {
+            let transaction_path = self.sweep_file_path(index, transactions.len(), "tx");
+            fs::write(&transaction_path, serialize_hex(transaction))?;
+            info!(
+                "Signed sweep {} written to {}.",
+                transaction.txid(),
+                transaction_path.display()
+            );
+        }
+        Ok(transactions)
+    }
+
+    /// Broadcasts a signed sweep through bitcoincore and, unless no confirmations are asked for,
+    /// waits until it has them, which the cancellation token interrupts. Returns the txid.
+    pub async fn broadcast_sweep(
+        &self,
+        transaction: &Transaction,
+        confirmations: u64,
+    ) -> Result<Txid, RetrieverError> {
+        if self.offline {
+            error!("The sweep cannot be broadcast offline.");
+            return Err(RetrieverError::UnavailableOffline);
+        }
+        let from_height = self.client.get_block_count().await?;
+        let txid = self.client.broadcast(transaction).await?;
+        if confirmations > 0 {
+            info!("Waiting for {} confirmations of {}.", confirmations, txid);
+            let height = self
+                .client
+                .wait_for_confirmations(
+                    txid,
+                    from_height,
+                    confirmations,
+                    Duration::from_secs(SWEEP_CONFIRMATION_POLL_SECONDS),
+                    &self.cancellation_token,
+                )
+                .await?;
+            info!("Sweep {} confirmed in block {}.", txid, height);
+        }
+        Ok(txid)
+    }
+
+    /// The private ranged descriptor of each account with finds, covering the branch of its
+    /// finds, to restore the account in a descriptor wallet. Like `export_wifs`, only called when
+    /// asked to, as the descriptors spend the funds.
+    pub fn export_private_descriptors(
+        &self,
+    ) -> Result<Vec<FoundAccountDescriptor>, RetrieverError> {
+        let finds = self.finds.lock().unwrap().clone();
+        if finds.is_empty() {
+            info!("No finds to export the private descriptors of.");
+            return Ok(vec![]);
+        }
+        let Some(master_xpriv) = self.explorer.get_key().get_master_xpriv() else {
+            error!("Private descriptors cannot be exported without the master key.");
+            return Err(RetrieverError::UnavailableWatchOnly);
+        };
+        warn!("Exporting the private descriptors of the accounts with finds, which spend their funds.");
+        private_descriptors_of_finds(master_xpriv, &finds)
+    }
 }
 
 impl Zeroize for Retriever {
     fn zeroize(&mut self) {
         self.client.zeroize();
-        // self.explorer.as_ref().zeroize();
+        // The explorer may be shared with a running search, whose master key is erased once the
+        // last of them drops it, see `ExplorerKey`.
+        drop(std::mem::take(&mut self.explorer));
         self.data_dir.zeroize();
     }
 }
 
+impl Drop for Retriever {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 impl ZeroizeOnDrop for Retriever {}
+
+/// The keys and hash160s of the paths of the batch derived on the GPU, none for those left to the
+/// CPU.
+#[cfg(feature = "gpu")]
+fn derive_batch_on_gpu(batch: &[PathParentKeyPair]) -> Vec<Option<(PublicKey, hash160::Hash)>> {
+    crate::gpu::derive_batch(batch)
+}
+
+#[cfg(not(feature = "gpu"))]
+fn derive_batch_on_gpu(_batch: &[PathParentKeyPair]) -> Vec<Option<(PublicKey, hash160::Hash)>> {
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_script_pubkey_works_01() {
+        let dir = std::env::temp_dir().join("check_script_pubkey_works_01");
+        let derived = Mutex::new(ExternalSorter::new(&dir, "derived", 1).unwrap());
+        // Without its directory, the sorter cannot write the run of the first scriptpubkey.
+        fs::remove_dir(&dir).unwrap();
+        let desc = Descriptor::new_pkh(
+            PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let checked = Retriever::default().check_descriptor(
+            &DerivationPath::master(),
+            desc,
+            &SearchTarget::Sort(&derived),
+            "ScriptPubKey",
+        );
+        assert!(matches!(checked, Err(RetrieverError::IoError(_))));
+    }
+}
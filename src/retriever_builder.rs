@@ -0,0 +1,222 @@
+use bitcoin::Network;
+
+use crate::{
+    covered_descriptors::CoveredDescriptors,
+    error::RetrieverError,
+    explorer::seed_type::SeedType,
+    retriever::Retriever,
+    retriever_phases::{NeedsDump, PhasedRetriever},
+    setting::RetrieverSetting,
+};
+
+/// Builds a retriever step by step, for applications embedding the crate, instead of filling in
+/// a `RetrieverSetting` with its positional `new`. Anything not set keeps the default of the
+/// setting: bitcoincore on its default local url and port, the default exploration of the
+/// network, the default descriptors, and the current directory as data dir.
+#[derive(Debug, Clone)]
+pub struct RetrieverBuilder {
+    setting: RetrieverSetting,
+    offline: bool,
+}
+
+impl Default for RetrieverBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetrieverBuilder {
+    pub fn new() -> Self {
+        RetrieverBuilder {
+            setting: RetrieverSetting::new(
+                None,
+                None,
+                String::new(),
+                None,
+                String::new(),
+                String::new(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                ".".to_string(),
+            ),
+            offline: false,
+        }
+    }
+
+    /// Starts from the given setting, e.g. one read from a config file.
+    pub fn from_setting(setting: RetrieverSetting) -> Self {
+        RetrieverBuilder {
+            setting,
+            offline: false,
+        }
+    }
+
+    pub fn rpc_url(mut self, rpc_url: &str) -> Self {
+        self.setting
+            .set_bitcoincore_rpc_url(Some(rpc_url.to_string()));
+        self
+    }
+
+    pub fn rpc_port(mut self, rpc_port: u16) -> Self {
+        self.setting
+            .set_bitcoincore_rpc_port(Some(rpc_port.to_string()));
+        self
+    }
+
+    pub fn rpc_cookie_path(mut self, cookie_path: &str) -> Self {
+        self.setting
+            .set_bitcoincore_rpc_cookie_path(cookie_path.to_string());
+        self
+    }
+
+    /// Authenticates with rpcuser and rpcpassword instead of the cookie file.
+    pub fn rpc_user_and_password(mut self, user: &str, password: &str) -> Self {
+        self.setting
+            .set_bitcoincore_rpc_user(Some(user.to_string()));
+        self.setting
+            .set_bitcoincore_rpc_password(Some(password.to_string()));
+        self
+    }
+
+    pub fn rpc_timeout_seconds(mut self, timeout_seconds: u64) -> Self {
+        self.setting
+            .set_bitcoincore_rpc_timeout_seconds(Some(timeout_seconds));
+        self
+    }
+
+    /// Explores the keys of a BIP39 mnemonic and its passphrase, empty if none.
+    pub fn mnemonic(mut self, mnemonic: &str, passphrase: &str) -> Self {
+        self.setting.set_mnemonic(mnemonic.to_string());
+        self.setting.set_passphrase(passphrase.to_string());
+        self
+    }
+
+    /// Sets how the mnemonic is read, e.g. as an Electrum seed, if not a BIP39 mnemonic.
+    pub fn seed_type(mut self, seed_type: SeedType) -> Self {
+        self.setting.set_seed_type(Some(seed_type));
+        self
+    }
+
+    /// Explores the keys of an extended private key instead of a mnemonic.
+    pub fn xpriv(mut self, xpriv: &str) -> Self {
+        self.setting.set_seed_type(Some(SeedType::Xpriv));
+        self.setting.set_xpriv(Some(xpriv.to_string()));
+        self
+    }
+
+    /// Explores the non-hardened paths below extended public keys, without any private key.
+    pub fn xpubs(mut self, xpubs: Vec<String>) -> Self {
+        self.setting.set_seed_type(Some(SeedType::Xpubs));
+        self.setting.set_xpubs(Some(xpubs));
+        self
+    }
+
+    /// Explores the keys of a bip32 seed or BIP39 entropy in hex instead of a mnemonic.
+    pub fn seed_hex(mut self, seed_hex: &str) -> Self {
+        self.setting.set_seed_type(Some(SeedType::Hex));
+        self.setting.set_seed_hex(Some(seed_hex.to_string()));
+        self
+    }
+
+    pub fn base_derivation_paths(mut self, base_derivation_paths: Vec<String>) -> Self {
+        self.setting
+            .set_base_derivation_paths(Some(base_derivation_paths));
+        self
+    }
+
+    pub fn exploration_path(mut self, exploration_path: &str) -> Self {
+        self.setting
+            .set_exploration_path(Some(exploration_path.to_string()));
+        self
+    }
+
+    pub fn exploration_depth(mut self, exploration_depth: u32) -> Self {
+        self.setting.set_exploration_depth(Some(exploration_depth));
+        self
+    }
+
+    pub fn sweep(mut self, sweep: bool) -> Self {
+        self.setting.set_sweep(Some(sweep));
+        self
+    }
+
+    pub fn selected_descriptors(mut self, selected_descriptors: Vec<CoveredDescriptors>) -> Self {
+        self.setting
+            .set_selected_descriptors(Some(selected_descriptors));
+        self
+    }
+
+    pub fn network(mut self, network: Network) -> Self {
+        self.setting.set_network(Some(network));
+        self
+    }
+
+    pub fn data_dir(mut self, data_dir: &str) -> Self {
+        self.setting.set_data_dir(data_dir.to_string());
+        self
+    }
+
+    /// Changes any other part of the setting, such as the backend of the set or the dump height.
+    pub fn configure(mut self, configure: impl FnOnce(&mut RetrieverSetting)) -> Self {
+        configure(&mut self.setting);
+        self
+    }
+
+    /// Builds a retriever that never connects to bitcoincore, see `Retriever::new_offline`.
+    pub fn offline(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+
+    /// The setting built so far.
+    pub fn setting(&self) -> &RetrieverSetting {
+        &self.setting
+    }
+
+    /// Creates the retriever, connecting to bitcoincore unless offline.
+    pub async fn build(self) -> Result<Retriever, RetrieverError> {
+        match self.offline {
+            true => Retriever::new_offline(self.setting).await,
+            false => Retriever::new(self.setting).await,
+        }
+    }
+
+    /// Creates the retriever in its first phase, whose phases then only run in order.
+    pub async fn build_phased(self) -> Result<PhasedRetriever<NeedsDump>, RetrieverError> {
+        Ok(PhasedRetriever::new(self.build().await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retriever_builder_works_01() {
+        let builder = RetrieverBuilder::new()
+            .rpc_url("http://10.0.0.2")
+            .rpc_port(18443)
+            .rpc_user_and_password("retriever", "secret")
+            .xpriv("tprv8ZgxMBicQKsPd7Uf69XL1XwhmjHopUGep8GuEiJDZmbQz6o58LninorQAfcKZWARbtRtfnLcJ5MQ2AtHcQJCCRUcMRvmDUjyEmNUWwx8UbK")
+            .exploration_depth(5)
+            .selected_descriptors(vec![CoveredDescriptors::P2wpkh])
+            .network(Network::Regtest)
+            .configure(|setting| {
+                setting.set_dump_height(Some(100));
+            });
+        let setting = builder.setting();
+        let client_setting = setting.get_client_setting();
+        assert_eq!(client_setting.get_rpc_url(), "http://10.0.0.2");
+        assert_eq!(client_setting.get_rpc_port(), "18443");
+        assert_eq!(setting.get_dump_height(), &Some(100));
+        assert_eq!(setting.get_data_dir(), ".");
+        let explorer_setting = setting.get_explorer_setting();
+        assert_eq!(*explorer_setting.get_network(), Network::Regtest);
+        assert_eq!(*explorer_setting.get_exploration_depth(), 5);
+        assert_eq!(*explorer_setting.get_seed_type(), SeedType::Xpriv);
+    }
+}
@@ -0,0 +1,188 @@
+use std::marker::PhantomData;
+
+use bitcoin::Block;
+
+use crate::{
+    error::RetrieverError, path_pairs::PathScanResultDescriptorTrio, retriever::Retriever,
+};
+
+/// The retriever is created, and the dump file may still have to be found or created.
+#[derive(Debug)]
+pub struct NeedsDump;
+
+/// The dump file is in data dir, or the chainstate is read instead.
+#[derive(Debug)]
+pub struct DumpReady;
+
+/// The unspent scriptpubkey set is populated and can be searched.
+#[derive(Debug)]
+pub struct SetPopulated;
+
+/// The explored paths are searched, and the finds can be detailed.
+#[derive(Debug)]
+pub struct Searched;
+
+/// The details of the finds are fetched.
+#[derive(Debug)]
+pub struct Detailed;
+
+/// A retriever that only offers the methods of the phase it is in, each of which moves it to
+/// the next phase when it succeeds, so that calling them out of order, such as searching before
+/// the set is populated, does not compile. The `Retriever` methods return a `RetrieverError`
+/// for those at run time instead.
+///
+/// A failed phase hands the retriever back in the phase it was in, in `PhaseFailed`, so it can
+/// be retried, or its finds so far reported after a cancelled search.
+#[derive(Debug)]
+pub struct PhasedRetriever<P> {
+    retriever: Retriever,
+    phase: PhantomData<P>,
+}
+
+/// A phase that failed, with the retriever in the phase it was in, boxed as it is large.
+#[derive(Debug)]
+pub struct PhaseFailed<P> {
+    pub retriever: Box<PhasedRetriever<P>>,
+    pub error: RetrieverError,
+}
+
+impl<P> From<PhaseFailed<P>> for RetrieverError {
+    fn from(value: PhaseFailed<P>) -> Self {
+        value.error
+    }
+}
+
+impl<P> PhasedRetriever<P> {
+    /// The retriever, to read what it found so far or report it in any phase.
+    pub fn retriever(&self) -> &Retriever {
+        &self.retriever
+    }
+
+    pub fn into_inner(self) -> Retriever {
+        self.retriever
+    }
+
+    pub fn cancel(&self) {
+        self.retriever.cancel();
+    }
+
+    /// Moves to the next phase if the phase succeeded, and stays otherwise.
+    fn advance<N>(
+        self,
+        result: Result<(), RetrieverError>,
+    ) -> Result<PhasedRetriever<N>, PhaseFailed<P>> {
+        match result {
+            Ok(()) => Ok(PhasedRetriever {
+                retriever: self.retriever,
+                phase: PhantomData,
+            }),
+            Err(error) => Err(PhaseFailed {
+                retriever: Box::new(self),
+                error,
+            }),
+        }
+    }
+}
+
+impl PhasedRetriever<NeedsDump> {
+    /// Starts the phases of a newly created retriever.
+    pub fn new(retriever: Retriever) -> Self {
+        PhasedRetriever {
+            retriever,
+            phase: PhantomData,
+        }
+    }
+
+    /// See `Retriever::check_for_dump_in_data_dir_or_create_dump_file`.
+    pub async fn prepare_dump(
+        mut self,
+    ) -> Result<PhasedRetriever<DumpReady>, PhaseFailed<NeedsDump>> {
+        let result = self
+            .retriever
+            .check_for_dump_in_data_dir_or_create_dump_file()
+            .await;
+        self.advance(result)
+    }
+}
+
+impl PhasedRetriever<DumpReady> {
+    /// See `Retriever::populate_uspk_set`.
+    pub async fn populate_uspk_set(
+        mut self,
+    ) -> Result<PhasedRetriever<SetPopulated>, PhaseFailed<DumpReady>> {
+        let result = self.retriever.populate_uspk_set().await;
+        self.advance(result)
+    }
+}
+
+impl PhasedRetriever<SetPopulated> {
+    /// See `Retriever::search_the_uspk_set`.
+    pub async fn search_the_uspk_set(
+        mut self,
+    ) -> Result<PhasedRetriever<Searched>, PhaseFailed<SetPopulated>> {
+        let result = self.retriever.search_the_uspk_set().await;
+        self.advance(result)
+    }
+
+    /// See `Retriever::resume`.
+    pub async fn resume(mut self) -> Result<PhasedRetriever<Searched>, PhaseFailed<SetPopulated>> {
+        let result = self.retriever.resume().await;
+        self.advance(result)
+    }
+
+    /// See `Retriever::apply_block`.
+    pub fn apply_block(&mut self, block: &Block) -> Result<usize, RetrieverError> {
+        self.retriever.apply_block(block)
+    }
+}
+
+impl PhasedRetriever<Searched> {
+    /// See `Retriever::get_details_of_finds_from_bitcoincore`.
+    pub async fn get_details_of_finds(
+        mut self,
+    ) -> Result<PhasedRetriever<Detailed>, PhaseFailed<Searched>> {
+        let result = self.retriever.get_details_of_finds_from_bitcoincore().await;
+        self.advance(result)
+    }
+}
+
+impl PhasedRetriever<Detailed> {
+    /// The finds with their details, none if nothing was found.
+    pub fn detailed_finds(&self) -> &[PathScanResultDescriptorTrio] {
+        self.retriever
+            .detailed_finds()
+            .as_deref()
+            .unwrap_or_default()
+    }
+
+    pub fn print_detailed_finds_on_console(&self) -> Result<(), RetrieverError> {
+        self.retriever.print_detailed_finds_on_console()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn phased_retriever_works_01() {
+        // Without a dump file in data dir, the set cannot be populated.
+        let retriever = PhasedRetriever::<DumpReady> {
+            retriever: Retriever::default(),
+            phase: PhantomData,
+        };
+        let failed = retriever.populate_uspk_set().await.unwrap_err();
+        assert!(matches!(failed.error, RetrieverError::NoDumpFileInDataDir));
+        assert!(failed
+            .retriever
+            .retriever()
+            .finds()
+            .lock()
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            RetrieverError::from(failed),
+            RetrieverError::NoDumpFileInDataDir
+        ));
+    }
+}
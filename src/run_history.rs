@@ -0,0 +1,355 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bitcoin::{
+    bip32::Fingerprint,
+    hashes::{sha256, Hash, HashEngine},
+};
+use bitcoincore_rpc::jsonrpc::serde_json;
+use getset::Getters;
+use hashbrown::HashMap;
+use num_format::{Locale, ToFormattedString};
+use redb::{Database, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    data::defaults::RUN_HISTORY_FILE_NAME,
+    error::RetrieverError,
+    explorer::exploration_path::ExplorationPath,
+    report::{ReportedFind, SearchCoverage},
+};
+
+/// The runs by their id, each as the json of its record.
+const RUNS_TABLE: TableDefinition<u64, &[u8]> = TableDefinition::new("runs");
+
+/// What a run searched and found: the exploration and descriptors it searched with, how much of
+/// the exploration it covered and its finds, with their amounts when their details were fetched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct RunRecord {
+    /// Assigned in the order the runs are recorded, starting at 1.
+    id: u64,
+    /// Seconds since the unix epoch when the run was recorded.
+    timestamp: u64,
+    /// A hash of the key fingerprint, the exploration and the descriptors, shared by the runs
+    /// that searched the same path space of the same key.
+    settings_fingerprint: String,
+    key_fingerprint: String,
+    label: Option<String>,
+    exploration: ExplorationPath,
+    descriptors: Vec<String>,
+    coverage: Option<SearchCoverage>,
+    finds: Vec<ReportedFind>,
+}
+
+impl RunRecord {
+    /// A record of a run yet to be given its id by `RunHistory::record`.
+    pub fn new(
+        key_fingerprint: &Fingerprint,
+        label: Option<String>,
+        exploration: ExplorationPath,
+        descriptors: Vec<String>,
+        coverage: Option<SearchCoverage>,
+        finds: Vec<ReportedFind>,
+    ) -> Self {
+        let mut engine = sha256::Hash::engine();
+        engine.input(key_fingerprint.as_bytes());
+        engine.input(&serde_json::to_vec(&exploration).unwrap_or_default());
+        for descriptor in descriptors.iter() {
+            engine.input(descriptor.as_bytes());
+        }
+        let settings_fingerprint = hex::encode(&sha256::Hash::from_engine(engine)[..8]);
+        RunRecord {
+            id: 0,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            settings_fingerprint,
+            key_fingerprint: key_fingerprint.to_string(),
+            label,
+            exploration,
+            descriptors,
+            coverage,
+            finds,
+        }
+    }
+
+    /// The finds of this run that the earlier run did not have, those it had that this run has
+    /// not, and those of both whose amounts differ.
+    pub fn diff(&self, earlier: &RunRecord) -> RunDiff {
+        let key = |find: &ReportedFind| (find.get_path().clone(), find.get_descriptor().clone());
+        let earlier_finds = earlier
+            .finds
+            .iter()
+            .map(|find| (key(find), find))
+            .collect::<HashMap<_, _>>();
+        let finds = self
+            .finds
+            .iter()
+            .map(|find| (key(find), find))
+            .collect::<HashMap<_, _>>();
+        let mut amount_changed = vec![];
+        let mut added = vec![];
+        for find in self.finds.iter() {
+            match earlier_finds.get(&key(find)) {
+                Some(earlier_find) if earlier_find.get_amount_sats() != find.get_amount_sats() => {
+                    amount_changed.push(((*earlier_find).clone(), find.clone()))
+                }
+                Some(_) => {}
+                None => added.push(find.clone()),
+            }
+        }
+        RunDiff {
+            from: earlier.id,
+            to: self.id,
+            same_settings: self.settings_fingerprint == earlier.settings_fingerprint,
+            paths_processed: (earlier.paths_processed(), self.paths_processed()),
+            added,
+            removed: earlier
+                .finds
+                .iter()
+                .filter(|find| !finds.contains_key(&key(find)))
+                .cloned()
+                .collect(),
+            amount_changed,
+        }
+    }
+
+    fn paths_processed(&self) -> usize {
+        self.coverage
+            .as_ref()
+            .map_or(0, |coverage| *coverage.get_paths_processed())
+    }
+}
+
+impl fmt::Display for RunRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Run {} at {} of key {}",
+            self.id, self.timestamp, self.key_fingerprint
+        )?;
+        if let Some(label) = &self.label {
+            write!(f, " ({})", label)?;
+        }
+        write!(
+            f,
+            ", settings {}: {} finds",
+            self.settings_fingerprint,
+            self.finds.len()
+        )?;
+        if let Some(coverage) = &self.coverage {
+            write!(
+                f,
+                ", {} of {} paths searched",
+                coverage
+                    .get_paths_processed()
+                    .to_formatted_string(&Locale::en),
+                coverage.get_total_paths().to_formatted_string(&Locale::en)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// How the finds of a run differ from those of an earlier one, see `RunRecord::diff`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[get = "pub with_prefix"]
+pub struct RunDiff {
+    from: u64,
+    to: u64,
+    /// Whether both runs searched the same path space of the same key.
+    same_settings: bool,
+    /// The paths searched by the earlier run and by the later one.
+    paths_processed: (usize, usize),
+    added: Vec<ReportedFind>,
+    removed: Vec<ReportedFind>,
+    /// The finds of both runs, as of the earlier run and as of the later one.
+    amount_changed: Vec<(ReportedFind, ReportedFind)>,
+}
+
+impl fmt::Display for RunDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let amount = |find: &ReportedFind| match find.get_amount_sats() {
+            Some(sats) => format!("{} sats", sats.to_formatted_string(&Locale::en)),
+            None => "unknown amount".to_string(),
+        };
+        write!(f, "Run {} against run {}", self.to, self.from)?;
+        if !self.same_settings {
+            write!(f, " (different settings)")?;
+        }
+        write!(
+            f,
+            "\nPaths searched: {} -> {}",
+            self.paths_processed.0.to_formatted_string(&Locale::en),
+            self.paths_processed.1.to_formatted_string(&Locale::en)
+        )?;
+        for find in self.added.iter() {
+            write!(
+                f,
+                "\n+ {} {} ({})",
+                find.get_path(),
+                find.get_descriptor(),
+                amount(find)
+            )?;
+        }
+        for find in self.removed.iter() {
+            write!(
+                f,
+                "\n- {} {} ({})",
+                find.get_path(),
+                find.get_descriptor(),
+                amount(find)
+            )?;
+        }
+        for (earlier, later) in self.amount_changed.iter() {
+            write!(
+                f,
+                "\n~ {} {} ({} -> {})",
+                later.get_path(),
+                later.get_descriptor(),
+                amount(earlier),
+                amount(later)
+            )?;
+        }
+        if self.added.is_empty() && self.removed.is_empty() && self.amount_changed.is_empty() {
+            write!(f, "\nNo differences in finds.")?;
+        }
+        Ok(())
+    }
+}
+
+/// The records of the runs made with a data dir, kept in a database in it, so a recovery made in
+/// stages can list what each stage searched and compare their finds.
+pub struct RunHistory {
+    path: PathBuf,
+    database: Database,
+}
+
+impl fmt::Debug for RunHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunHistory")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl RunHistory {
+    /// Opens the run history of the data dir, creating it if there is none.
+    pub fn open(data_dir: &str) -> Result<Self, RetrieverError> {
+        let path = Path::new(data_dir).join(RUN_HISTORY_FILE_NAME);
+        let database = Database::create(&path)?;
+        let write = database.begin_write()?;
+        write.open_table(RUNS_TABLE)?;
+        write.commit()?;
+        Ok(RunHistory { path, database })
+    }
+
+    /// Adds the record, giving it the id after that of the last recorded run, and returns it.
+    pub fn record(&self, mut record: RunRecord) -> Result<RunRecord, RetrieverError> {
+        let write = self.database.begin_write()?;
+        {
+            let mut table = write.open_table(RUNS_TABLE)?;
+            record.id = table.last()?.map_or(0, |(id, _)| id.value()) + 1;
+            table.insert(record.id, serde_json::to_vec(&record)?.as_slice())?;
+        }
+        write.commit()?;
+        info!("Run {} recorded in {}.", record.id, self.path.display());
+        Ok(record)
+    }
+
+    /// The recorded runs, oldest first.
+    pub fn runs(&self) -> Result<Vec<RunRecord>, RetrieverError> {
+        let read = self.database.begin_read()?;
+        let table = read.open_table(RUNS_TABLE)?;
+        table
+            .iter()?
+            .map(|entry| Ok(serde_json::from_slice(entry?.1.value())?))
+            .collect()
+    }
+
+    pub fn run(&self, id: u64) -> Result<Option<RunRecord>, RetrieverError> {
+        let read = self.database.begin_read()?;
+        let table = read.open_table(RUNS_TABLE)?;
+        let record = table.get(id)?;
+        Ok(match record {
+            Some(record) => Some(serde_json::from_slice(record.value())?),
+            None => None,
+        })
+    }
+
+    /// How the finds of the later run differ from those of the earlier one.
+    pub fn diff(&self, earlier: u64, later: u64) -> Result<RunDiff, RetrieverError> {
+        match (self.run(earlier)?, self.run(later)?) {
+            (Some(earlier), Some(later)) => Ok(later.diff(&earlier)),
+            _ => Err(RetrieverError::NoSuchRun),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn run_history_works_01() {
+        let data_dir = std::env::temp_dir().join("retriever_run_history");
+        let _ = std::fs::remove_dir_all(&data_dir);
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let data_dir = data_dir.to_string_lossy().to_string();
+        let exploration =
+            ExplorationPath::new(Some(vec!["m/84'/0'/0'".to_string()]), "0..1/*", 4, false)
+                .unwrap();
+        let fingerprint = Fingerprint::from_str("01020304").unwrap();
+        let find = |path: &str, amount_sats: u64| -> ReportedFind {
+            serde_json::from_value(serde_json::json!({
+                "path": path,
+                "descriptor": "wpkh(...)",
+                "amount_sats": amount_sats,
+            }))
+            .unwrap()
+        };
+        let record = |searched: usize, finds: Vec<ReportedFind>| {
+            RunRecord::new(
+                &fingerprint,
+                None,
+                exploration.clone(),
+                vec!["P2wpkh".to_string()],
+                Some(SearchCoverage::new(10, searched, None, searched == 10)),
+                finds,
+            )
+        };
+        let history = RunHistory::open(&data_dir).unwrap();
+        let first = history
+            .record(record(5, vec![find("m/84'/0'/0'/0/1", 1_000)]))
+            .unwrap();
+        let second = history
+            .record(record(
+                10,
+                vec![find("m/84'/0'/0'/0/1", 600), find("m/84'/0'/0'/1/3", 2_000)],
+            ))
+            .unwrap();
+        assert_eq!((*first.get_id(), *second.get_id()), (1, 2));
+        assert_eq!(
+            first.get_settings_fingerprint(),
+            second.get_settings_fingerprint()
+        );
+        drop(history);
+        let history = RunHistory::open(&data_dir).unwrap();
+        assert_eq!(history.runs().unwrap(), vec![first, second]);
+        let diff = history.diff(1, 2).unwrap();
+        assert!(diff.get_same_settings());
+        assert_eq!(*diff.get_paths_processed(), (5, 10));
+        assert_eq!(diff.get_added(), &vec![find("m/84'/0'/0'/1/3", 2_000)]);
+        assert!(diff.get_removed().is_empty());
+        assert_eq!(diff.get_amount_changed().len(), 1);
+        assert!(history.diff(1, 3).is_err());
+    }
+}
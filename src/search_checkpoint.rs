@@ -0,0 +1,194 @@
+use std::{fs, path::PathBuf, str::FromStr};
+
+use bitcoin::bip32::{DerivationPath, Fingerprint};
+use bitcoincore_rpc::jsonrpc::serde_json;
+use miniscript::Descriptor;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    error::RetrieverError, explorer::exploration_path::ExplorationPath,
+    path_pairs::PathDescriptorPair,
+};
+
+/// How far the search of an exploration got, and its finds. It is written into data dir every
+/// minute of the search, when the search is cancelled and when it finishes, so `resume` can go
+/// on from the paths an interrupted search had not reached. The paths of the stream are searched
+/// batch after batch in the order they are generated, so all the paths below a base path up to
+/// its cursor have been searched. Each explored key has a checkpoint of its own, and one of a
+/// finished search lets a resumed batch of seeds skip the seeds that were searched already.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchCheckpoint {
+    /// The exploration, selected descriptors and digest of the script settings the checkpoint is
+    /// of, which a resumed search must share. The script settings are those deciding which
+    /// scriptpubkeys a path is searched for besides the descriptors, see
+    /// `Retriever::script_settings_digest`.
+    exploration: ExplorationPath,
+    descriptors: Vec<String>,
+    #[serde(default)]
+    scripts: String,
+    /// The number of explored paths searched below each base path, in the order of the base paths.
+    cursors: Vec<usize>,
+    finds: Vec<CheckpointedFind>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CheckpointedFind {
+    path: String,
+    descriptor: String,
+}
+
+impl SearchCheckpoint {
+    /// A checkpoint of the search of the exploration once the given number of paths of the
+    /// stream has been searched.
+    pub fn new(
+        exploration: ExplorationPath,
+        descriptors: Vec<String>,
+        scripts: String,
+        searched_paths: usize,
+        finds: &[PathDescriptorPair],
+    ) -> Self {
+        let paths_per_base = exploration.num_of_paths_per_base();
+        let cursors = (0..exploration.get_base_paths().len())
+            .map(|base_index| {
+                searched_paths
+                    .saturating_sub(base_index * paths_per_base)
+                    .min(paths_per_base)
+            })
+            .collect();
+        SearchCheckpoint {
+            exploration,
+            descriptors,
+            scripts,
+            cursors,
+            finds: finds
+                .iter()
+                .map(|find| CheckpointedFind {
+                    path: find.0.to_string(),
+                    descriptor: find.get_descriptor_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn file_path(data_dir: &str, fingerprint: &Fingerprint) -> PathBuf {
+        PathBuf::from(data_dir).join(format!("search_checkpoint_{}.json", fingerprint))
+    }
+
+    /// Loads the checkpoint of the key of the fingerprint in data dir, if there is one.
+    pub fn load(data_dir: &str, fingerprint: &Fingerprint) -> Result<Option<Self>, RetrieverError> {
+        let file_path = Self::file_path(data_dir, fingerprint);
+        if !file_path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(&file_path)?)?))
+    }
+
+    pub fn save(&self, data_dir: &str, fingerprint: &Fingerprint) -> Result<(), RetrieverError> {
+        fs::write(
+            Self::file_path(data_dir, fingerprint),
+            serde_json::to_vec(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Whether this is a checkpoint of the same search as the other one, which it can resume.
+    pub fn is_of_same_search(&self, other: &SearchCheckpoint) -> bool {
+        self.exploration == other.exploration
+            && self.descriptors == other.descriptors
+            && self.scripts == other.scripts
+    }
+
+    pub fn get_cursors(&self) -> &[usize] {
+        &self.cursors
+    }
+
+    pub fn get_searched_paths(&self) -> usize {
+        self.cursors.iter().sum()
+    }
+
+    pub fn get_finds(&self) -> Result<Vec<PathDescriptorPair>, RetrieverError> {
+        let finds = self
+            .finds
+            .iter()
+            .map(|find| {
+                Ok(PathDescriptorPair::new(
+                    DerivationPath::from_str(&find.path)?,
+                    Descriptor::from_str(&find.descriptor)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, RetrieverError>>()?;
+        info!(
+            "Search checkpoint with {} searched paths and {} finds loaded.",
+            self.get_searched_paths(),
+            finds.len()
+        );
+        Ok(finds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{key::Secp256k1, secp256k1::SecretKey};
+
+    use super::*;
+
+    #[test]
+    fn search_checkpoint_works_01() {
+        let data_dir = std::env::temp_dir().join("retriever_search_checkpoint");
+        fs::create_dir_all(&data_dir).unwrap();
+        let data_dir = data_dir.to_string_lossy().to_string();
+        let exploration = ExplorationPath::new(
+            Some(vec!["m/84'/0'/0'".to_string(), "m/44'/0'/0'".to_string()]),
+            "0..1/*",
+            4,
+            false,
+        )
+        .unwrap();
+        let find = PathDescriptorPair::new(
+            DerivationPath::from_str("m/84'/0'/0'/1/2").unwrap(),
+            Descriptor::new_wpkh(
+                SecretKey::from_slice(&[1u8; 32])
+                    .unwrap()
+                    .public_key(&Secp256k1::new())
+                    .into(),
+            )
+            .unwrap(),
+        );
+        let fingerprint = Fingerprint::from([1u8, 2, 3, 4]);
+        assert_eq!(
+            SearchCheckpoint::load(&data_dir, &fingerprint).unwrap(),
+            None
+        );
+        // Each base path has 2 * 5 paths, so the first is done and the second half done.
+        let checkpoint = SearchCheckpoint::new(
+            exploration.clone(),
+            vec!["P2wpkh".to_string()],
+            "multisig".to_string(),
+            15,
+            std::slice::from_ref(&find),
+        );
+        assert_eq!(checkpoint.get_cursors(), &[10, 5]);
+        checkpoint.save(&data_dir, &fingerprint).unwrap();
+        let loaded = SearchCheckpoint::load(&data_dir, &fingerprint)
+            .unwrap()
+            .unwrap();
+        assert!(loaded.is_of_same_search(&SearchCheckpoint::new(
+            exploration.clone(),
+            vec!["P2wpkh".to_string()],
+            "multisig".to_string(),
+            0,
+            &[]
+        )));
+        // Other script settings search other scriptpubkeys below the same paths.
+        assert!(!loaded.is_of_same_search(&SearchCheckpoint::new(
+            exploration,
+            vec!["P2wpkh".to_string()],
+            "timelocks".to_string(),
+            0,
+            &[]
+        )));
+        assert_eq!(loaded.get_finds().unwrap(), vec![find]);
+        fs::remove_file(SearchCheckpoint::file_path(&data_dir, &fingerprint)).unwrap();
+    }
+}
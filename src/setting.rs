@@ -1,43 +1,289 @@
+use std::{fs, ops::RangeInclusive, sync::Arc};
+
 use config::Config;
-use getset::Getters;
+use getset::{Getters, Setters};
 use serde::{Deserialize, Serialize};
-use tracing::info;
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use tracing::{error, info};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::{
-    client::client_setting::ClientSetting,
+    client::{client_setting::ClientSetting, scan_in_progress_policy::ScanInProgressPolicy},
     covered_descriptors::CoveredDescriptors,
     data::{
         defaults::{
-            DEFAULT_BITCOINCORE_RPC_PORT, DEFAULT_BITCOINCORE_RPC_TIMEOUT_SECONDS,
-            DEFAULT_BITCOINCORE_RPC_URL, DEFAULT_EXPLORATION_DEPTH, DEFAULT_EXPLORATION_PATH,
-            DEFAULT_NETWORK, DEFAULT_SWEEP,
+            DEFAULT_BIP85_WORDS, DEFAULT_BITCOINCORE_RPC_POOL_SIZE, DEFAULT_BITCOINCORE_RPC_PORT,
+            DEFAULT_BITCOINCORE_RPC_RETRIES, DEFAULT_BITCOINCORE_RPC_RETRY_BACKOFF_MILLIS,
+            DEFAULT_BITCOINCORE_RPC_TIMEOUT_SECONDS, DEFAULT_BITCOINCORE_RPC_URL,
+            DEFAULT_BRAINWALLET_HASH, DEFAULT_CHECK_MEMPOOL, DEFAULT_CORRECT_TYPOS,
+            DEFAULT_DERIVATION_THREADS, DEFAULT_EVENT_LOG, DEFAULT_EXPLORATION_DEPTH,
+            DEFAULT_EXPLORATION_PATH, DEFAULT_GREEN_SUBACCOUNTS, DEFAULT_HWI_COMMAND,
+            DEFAULT_INVERTED_SEARCH, DEFAULT_KEEP_COINS, DEFAULT_NARROW_DESCRIPTORS_BY_PURPOSE,
+            DEFAULT_NETWORK, DEFAULT_SCAN_IN_PROGRESS_POLICY, DEFAULT_SEED_TYPE,
+            DEFAULT_SILENT_PAYMENTS, DEFAULT_SORTED_MERGE_SEARCH, DEFAULT_SWEEP,
+            DEFAULT_USPK_SET_BACKEND, DEFAULT_USPK_SET_CACHE,
         },
         wallets_info::WalletsInfo,
     },
     error::RetrieverError,
-    explorer::explorer_setting::ExplorerSetting,
+    explorer::{
+        auxiliaries::from_input_str_to_mnemonic,
+        bip85::parse_bip85_indexes,
+        brainwallet::BrainwalletHash,
+        explorer_setting::ExplorerSetting,
+        green::{
+            GreenWallet, GREEN_EXPLORATION_PATH, GREEN_MAINNET_CSV_BLOCKS, GREEN_TESTNET_CSV_BLOCKS,
+        },
+        lightning::{LightningNode, LIGHTNING_EXPLORATION_PATH},
+        missing_words::{from_mnemonic_with_unknown_words_to_candidates, has_unknown_words},
+        multisig::MultisigPreset,
+        seed_type::SeedType,
+        timelock::TimelockTemplate,
+        typo_correction::from_mnemonic_with_typos_to_candidates,
+        word_order::from_mnemonic_with_swaps_to_candidates,
+    },
+    uspk_store::UspkSetBackend,
 };
 
-#[derive(Debug, Serialize, Deserialize, Getters, Clone)]
+#[derive(Debug, Serialize, Deserialize, Getters, Setters, Clone)]
 #[get = "pub with_prefix"]
 pub struct RetrieverSetting {
+    #[getset(set = "pub")]
     bitcoincore_rpc_url: Option<String>,
+    #[getset(set = "pub")]
     bitcoincore_rpc_port: Option<String>,
-    // Must be entered.
+    // Must be entered, unless bitcoincore_rpc_user and bitcoincore_rpc_password are.
+    #[serde(default)]
+    #[getset(set = "pub")]
     bitcoincore_rpc_cookie_path: String,
+    #[getset(set = "pub")]
     bitcoincore_rpc_timeout_seconds: Option<u64>,
     // Must be entered.
+    #[getset(set = "pub")]
     mnemonic: String,
     // Must be entered.
+    #[getset(set = "pub")]
     passphrase: String,
+    #[getset(set = "pub")]
     base_derivation_paths: Option<Vec<String>>,
+    #[getset(set = "pub")]
     exploration_path: Option<String>,
+    #[getset(set = "pub")]
     selected_descriptors: Option<Vec<CoveredDescriptors>>,
+    #[getset(set = "pub")]
     sweep: Option<bool>,
+    #[getset(set = "pub")]
     exploration_depth: Option<u32>,
+    #[getset(set = "pub")]
     network: Option<bitcoin::Network>,
+    #[getset(set = "pub")]
     data_dir: String,
+    /// Whether to write the JSON Lines event log (events.jsonl) into data dir.
+    #[getset(set = "pub")]
+    event_log: Option<bool>,
+    /// Bitcoin Core's chainstate directory, read directly instead of dumping the utxo set.
+    #[getset(set = "pub")]
+    chainstate_dir: Option<String>,
+    #[getset(set = "pub")]
+    bitcoincore_rpc_user: Option<String>,
+    #[getset(set = "pub")]
+    bitcoincore_rpc_password: Option<String>,
+    /// A SOCKS5 proxy (host:port) for the rpc connection, e.g. Tor.
+    #[getset(set = "pub")]
+    bitcoincore_rpc_proxy: Option<String>,
+    #[getset(set = "pub")]
+    bitcoincore_rpc_proxy_user: Option<String>,
+    #[getset(set = "pub")]
+    bitcoincore_rpc_proxy_password: Option<String>,
+    /// A PEM encoded CA certificate to trust for https rpc urls.
+    #[getset(set = "pub")]
+    bitcoincore_rpc_tls_ca_cert_path: Option<String>,
+    /// Accepts self-signed or otherwise invalid certificates for https rpc urls.
+    #[getset(set = "pub")]
+    bitcoincore_rpc_tls_accept_invalid_certs: Option<bool>,
+    /// Urls (with port) of further nodes to fail over to when the node in use becomes unreachable.
+    #[getset(set = "pub")]
+    bitcoincore_rpc_fallback_urls: Option<Vec<String>>,
+    /// The block height as of which the utxo set is dumped, instead of the chain tip.
+    #[getset(set = "pub")]
+    dump_height: Option<u64>,
+    /// What to do when bitcoincore is already scanning the utxo set before fetching details.
+    #[getset(set = "pub")]
+    scan_in_progress: Option<ScanInProgressPolicy>,
+    /// How often rpc calls are retried after connection errors or timeouts.
+    #[getset(set = "pub")]
+    bitcoincore_rpc_retries: Option<u32>,
+    /// The wait before the first retry, doubled for every further one.
+    #[getset(set = "pub")]
+    bitcoincore_rpc_retry_backoff_millis: Option<u64>,
+    /// The number of rpc connections for calls made in parallel, e.g. verifying finds.
+    #[getset(set = "pub")]
+    bitcoincore_rpc_pool_size: Option<usize>,
+    /// The zmq endpoint bitcoind publishes raw blocks on (-zmqpubrawblock), for daemon mode.
+    #[getset(set = "pub")]
+    bitcoincore_zmq_rawblock: Option<String>,
+    /// The url (including the port) of the REST interface of the node (-rest=1).
+    #[getset(set = "pub")]
+    bitcoincore_rest_url: Option<String>,
+    /// Whether to look for mempool transactions spending or paying to finds.
+    #[getset(set = "pub")]
+    check_mempool: Option<bool>,
+    /// An http(s) url to download utxo_dump.dat from when it is not in data dir.
+    #[getset(set = "pub")]
+    dump_url: Option<String>,
+    /// The sha256 (hex) the downloaded dump file must have.
+    #[getset(set = "pub")]
+    dump_sha256: Option<String>,
+    /// Whether the mnemonic is a BIP39 mnemonic or an Electrum seed, or the seed is a SLIP-39
+    /// backup, an extended private key, extended public keys or hex.
+    #[getset(set = "pub")]
+    seed_type: Option<SeedType>,
+    /// The mnemonic shares of a SLIP-39 backup, used instead of the mnemonic.
+    #[getset(set = "pub")]
+    slip39_shares: Option<Vec<String>>,
+    /// An extended private key used instead of the mnemonic.
+    #[getset(set = "pub")]
+    xpriv: Option<String>,
+    /// Extended public keys, optionally with their origin, scanned instead of a seed.
+    #[getset(set = "pub")]
+    xpubs: Option<Vec<String>>,
+    /// A bip32 seed or BIP39 entropy in hex, used instead of the mnemonic.
+    #[getset(set = "pub")]
+    seed_hex: Option<String>,
+    /// A standard SeedQR as digits, or a CompactSeedQR in hex, used instead of the mnemonic.
+    #[getset(set = "pub")]
+    seed_qr: Option<String>,
+    /// The BIP85 indexes (e.g. "0..9") whose child mnemonics are explored instead of the seed.
+    #[getset(set = "pub")]
+    bip85_indexes: Option<String>,
+    /// The number of words of the BIP85 child mnemonics.
+    #[getset(set = "pub")]
+    bip85_words: Option<u32>,
+    /// Mnemonics with their passphrases, scanned one after another against the same utxo set
+    /// instead of the mnemonic and passphrase.
+    #[getset(set = "pub")]
+    seeds: Option<Vec<BatchSeed>>,
+    /// A file with a candidate passphrase per line, each of which is tried with the mnemonic.
+    #[getset(set = "pub")]
+    passphrase_candidates_file: Option<String>,
+    /// Whether a BIP39 mnemonic that does not parse is explored with its typos corrected.
+    #[getset(set = "pub")]
+    correct_typos: Option<bool>,
+    /// The swaps of two words by which the written order of a BIP39 mnemonic may be wrong.
+    #[getset(set = "pub")]
+    word_swaps: Option<usize>,
+    /// BIP38 encrypted private keys, e.g. of paper wallets, searched besides the seed.
+    #[getset(set = "pub")]
+    bip38_keys: Option<Vec<String>>,
+    /// The password of the BIP38 keys.
+    #[getset(set = "pub")]
+    bip38_password: Option<String>,
+    /// A file of candidate brainwallet passphrases, one per line, searched besides the seed.
+    #[getset(set = "pub")]
+    brainwallet_file: Option<String>,
+    /// How the brainwallet passphrases are hashed into keys, each of them is tried.
+    #[getset(set = "pub")]
+    brainwallet_hashes: Option<Vec<BrainwalletHash>>,
+    /// The salt of WarpWallet brainwallets, often an email address.
+    #[getset(set = "pub")]
+    warpwallet_salt: Option<String>,
+    /// The HWI executable through which xpubs are fetched from a hardware wallet.
+    #[getset(set = "pub")]
+    hwi_command: Option<String>,
+    /// The fingerprint of the hardware wallet, needed if several are connected.
+    #[getset(set = "pub")]
+    hwi_fingerprint: Option<String>,
+    /// The xpubs of the other cosigners of a multisig wallet the explored keys belong to.
+    #[getset(set = "pub")]
+    multisig_cosigners: Option<Vec<String>>,
+    /// The keys needed to spend from the multisig wallet.
+    #[getset(set = "pub")]
+    multisig_threshold: Option<usize>,
+    /// A miniscript policy holding the explored key as `key`, compiled for each explored key.
+    #[getset(set = "pub")]
+    miniscript_policy: Option<String>,
+    /// Whether to search the utxo set for silent payments (BIP352) to the seed.
+    #[getset(set = "pub")]
+    silent_payments: Option<bool>,
+    /// The height from which outputs may be silent payments to the seed, e.g. its birthday.
+    #[getset(set = "pub")]
+    silent_payments_birth_height: Option<u32>,
+    /// The wallet software whose multisig conventions give the base path, exploration path, seed
+    /// type and script, unless set.
+    #[getset(set = "pub")]
+    multisig_preset: Option<MultisigPreset>,
+    /// The Green service xpub, to recover a Green 2-of-2 wallet.
+    #[getset(set = "pub")]
+    green_service_xpub: Option<String>,
+    /// The Green subaccounts to search.
+    #[getset(set = "pub")]
+    green_subaccounts: Option<Vec<u32>>,
+    /// The CSV timeouts of the Green subaccounts, in blocks.
+    #[getset(set = "pub")]
+    green_csv_blocks: Option<Vec<u32>>,
+    /// The timelocked scripts to search the explored keys in.
+    #[getset(set = "pub")]
+    timelock_templates: Option<Vec<TimelockTemplate>>,
+    /// The lock values of the timelocked scripts, each a value or an inclusive range.
+    #[getset(set = "pub")]
+    timelock_values: Option<Vec<String>>,
+    /// The Lightning node the seed is of, to search the outputs of its closed channels.
+    #[getset(set = "pub")]
+    lightning_node: Option<LightningNode>,
+    /// A file of scriptpubkeys in hex, checked against the utxo set instead of exploring a seed.
+    #[getset(set = "pub")]
+    script_pubkey_watchlist: Option<String>,
+    /// Addresses checked against the utxo set instead of exploring a seed.
+    #[getset(set = "pub")]
+    address_watchlist: Option<Vec<String>>,
+    /// Whether paths under the BIP43 purposes 44', 49', 84' and 86' are only searched in the
+    /// selected descriptor of their script type.
+    #[getset(set = "pub")]
+    narrow_descriptors_by_purpose: Option<bool>,
+    /// The number of threads deriving and searching the explored paths.
+    #[getset(set = "pub")]
+    derivation_threads: Option<usize>,
+    /// Whether the unspent scriptpubkeys are kept in memory or on disk.
+    #[getset(set = "pub")]
+    uspk_backend: Option<UspkSetBackend>,
+    /// Whether the derived scriptpubkeys are collected first and the utxo set is then read once,
+    /// keeping only the coins they match.
+    #[getset(set = "pub")]
+    inverted_search: Option<bool>,
+    /// Whether the set populated from a dump is cached in the data dir and read back by the next
+    /// runs on the same dump.
+    #[getset(set = "pub")]
+    uspk_set_cache: Option<bool>,
+    /// The most memory in megabytes the unspent scriptpubkeys take in memory, past which they
+    /// spill to a temporary database on disk.
+    #[getset(set = "pub")]
+    max_memory_mb: Option<u64>,
+    /// Whether the outpoints and amounts of the coins are kept with the set, so the details of
+    /// finds are read from them instead of scanning the utxo set of bitcoincore.
+    #[getset(set = "pub")]
+    keep_coins: Option<bool>,
+    /// Whether the derived scriptpubkeys and those of the utxo set are sorted into files in the
+    /// data dir and merged, so neither has to fit in memory, instead of searching a set of the
+    /// utxo set. Never in watchlist mode.
+    #[getset(set = "pub")]
+    sorted_merge_search: Option<bool>,
+}
+
+/// A mnemonic and its passphrase, one of the seeds of a batch.
+#[derive(Debug, Serialize, Deserialize, Getters, Clone, Zeroize, ZeroizeOnDrop)]
+#[get = "pub with_prefix"]
+pub struct BatchSeed {
+    mnemonic: String,
+    #[serde(default)]
+    passphrase: String,
+}
+
+impl BatchSeed {
+    pub fn new(mnemonic: String, passphrase: String) -> Self {
+        BatchSeed {
+            mnemonic,
+            passphrase,
+        }
+    }
 }
 
 impl Zeroize for RetrieverSetting {
@@ -53,6 +299,67 @@ impl Zeroize for RetrieverSetting {
         self.exploration_path.zeroize();
         self.sweep.zeroize();
         self.exploration_depth.zeroize();
+        self.event_log.zeroize();
+        self.chainstate_dir.zeroize();
+        self.bitcoincore_rpc_user.zeroize();
+        self.bitcoincore_rpc_password.zeroize();
+        self.bitcoincore_rpc_proxy.zeroize();
+        self.bitcoincore_rpc_proxy_user.zeroize();
+        self.bitcoincore_rpc_proxy_password.zeroize();
+        self.bitcoincore_rpc_tls_ca_cert_path.zeroize();
+        self.bitcoincore_rpc_tls_accept_invalid_certs.zeroize();
+        self.bitcoincore_rpc_fallback_urls.zeroize();
+        self.dump_height.zeroize();
+        self.scan_in_progress = None;
+        self.bitcoincore_rpc_retries.zeroize();
+        self.bitcoincore_rpc_retry_backoff_millis.zeroize();
+        self.bitcoincore_rpc_pool_size.zeroize();
+        self.bitcoincore_zmq_rawblock.zeroize();
+        self.bitcoincore_rest_url.zeroize();
+        self.check_mempool.zeroize();
+        self.dump_url.zeroize();
+        self.dump_sha256.zeroize();
+        self.seed_type = None;
+        self.slip39_shares.zeroize();
+        self.xpriv.zeroize();
+        self.xpubs.zeroize();
+        self.seed_hex.zeroize();
+        self.seed_qr.zeroize();
+        self.bip85_indexes.zeroize();
+        self.bip85_words.zeroize();
+        self.seeds.zeroize();
+        self.passphrase_candidates_file.zeroize();
+        self.correct_typos.zeroize();
+        self.word_swaps.zeroize();
+        self.bip38_keys.zeroize();
+        self.bip38_password.zeroize();
+        self.brainwallet_file.zeroize();
+        self.brainwallet_hashes = None;
+        self.warpwallet_salt.zeroize();
+        self.hwi_command.zeroize();
+        self.hwi_fingerprint.zeroize();
+        self.multisig_cosigners.zeroize();
+        self.multisig_threshold.zeroize();
+        self.miniscript_policy.zeroize();
+        self.silent_payments.zeroize();
+        self.silent_payments_birth_height.zeroize();
+        self.multisig_preset = None;
+        self.green_service_xpub.zeroize();
+        self.green_subaccounts.zeroize();
+        self.green_csv_blocks.zeroize();
+        self.timelock_templates = None;
+        self.timelock_values.zeroize();
+        self.lightning_node = None;
+        self.script_pubkey_watchlist.zeroize();
+        self.address_watchlist.zeroize();
+        self.narrow_descriptors_by_purpose.zeroize();
+        self.derivation_threads.zeroize();
+        self.uspk_backend = None;
+        self.inverted_search.zeroize();
+        self.uspk_set_cache.zeroize();
+        self.max_memory_mb.zeroize();
+        self.keep_coins.zeroize();
+        self.sorted_merge_search.zeroize();
         self.network = Some(bitcoin::Network::Signet);
         info!("Zeroizing retriever setting finished.");
     }
@@ -93,6 +400,67 @@ impl RetrieverSetting {
             exploration_depth,
             network,
             data_dir,
+            event_log: None,
+            chainstate_dir: None,
+            bitcoincore_rpc_user: None,
+            bitcoincore_rpc_password: None,
+            bitcoincore_rpc_proxy: None,
+            bitcoincore_rpc_proxy_user: None,
+            bitcoincore_rpc_proxy_password: None,
+            bitcoincore_rpc_tls_ca_cert_path: None,
+            bitcoincore_rpc_tls_accept_invalid_certs: None,
+            bitcoincore_rpc_fallback_urls: None,
+            dump_height: None,
+            scan_in_progress: None,
+            bitcoincore_rpc_retries: None,
+            bitcoincore_rpc_retry_backoff_millis: None,
+            bitcoincore_rpc_pool_size: None,
+            bitcoincore_zmq_rawblock: None,
+            bitcoincore_rest_url: None,
+            check_mempool: None,
+            dump_url: None,
+            dump_sha256: None,
+            seed_type: None,
+            slip39_shares: None,
+            xpriv: None,
+            xpubs: None,
+            seed_hex: None,
+            seed_qr: None,
+            bip85_indexes: None,
+            bip85_words: None,
+            seeds: None,
+            passphrase_candidates_file: None,
+            correct_typos: None,
+            word_swaps: None,
+            bip38_keys: None,
+            bip38_password: None,
+            brainwallet_file: None,
+            brainwallet_hashes: None,
+            warpwallet_salt: None,
+            hwi_command: None,
+            hwi_fingerprint: None,
+            multisig_cosigners: None,
+            multisig_threshold: None,
+            miniscript_policy: None,
+            silent_payments: None,
+            silent_payments_birth_height: None,
+            multisig_preset: None,
+            green_service_xpub: None,
+            green_subaccounts: None,
+            green_csv_blocks: None,
+            timelock_templates: None,
+            timelock_values: None,
+            lightning_node: None,
+            script_pubkey_watchlist: None,
+            address_watchlist: None,
+            narrow_descriptors_by_purpose: None,
+            derivation_threads: None,
+            uspk_backend: None,
+            inverted_search: None,
+            uspk_set_cache: None,
+            max_memory_mb: None,
+            keep_coins: None,
+            sorted_merge_search: None,
         }
     }
 
@@ -117,36 +485,301 @@ impl RetrieverSetting {
             Some(timeout_seconds) => *timeout_seconds,
             None => DEFAULT_BITCOINCORE_RPC_TIMEOUT_SECONDS,
         };
-        ClientSetting::new(rpc_url, rpc_port, cookie_path, timeout_seconds)
+        let mut client_setting =
+            ClientSetting::new(rpc_url, rpc_port, cookie_path, timeout_seconds);
+        if let Some(proxy) = self.get_bitcoincore_rpc_proxy() {
+            client_setting = client_setting.with_socks5_proxy(
+                proxy,
+                self.get_bitcoincore_rpc_proxy_user().to_owned(),
+                self.get_bitcoincore_rpc_proxy_password().to_owned(),
+            );
+        }
+        client_setting = client_setting.with_tls(
+            self.get_bitcoincore_rpc_tls_ca_cert_path().to_owned(),
+            self.get_bitcoincore_rpc_tls_accept_invalid_certs()
+                .unwrap_or_default(),
+        );
+        client_setting = client_setting.with_retry(
+            self.get_bitcoincore_rpc_retries()
+                .unwrap_or(DEFAULT_BITCOINCORE_RPC_RETRIES),
+            self.get_bitcoincore_rpc_retry_backoff_millis()
+                .unwrap_or(DEFAULT_BITCOINCORE_RPC_RETRY_BACKOFF_MILLIS),
+        );
+        client_setting = client_setting.with_pool_size(
+            self.get_bitcoincore_rpc_pool_size()
+                .unwrap_or(DEFAULT_BITCOINCORE_RPC_POOL_SIZE),
+        );
+        if let Some(rest_url) = self.get_bitcoincore_rest_url() {
+            client_setting = client_setting.with_rest_url(rest_url);
+        }
+        if let Some(fallback_urls) = self.get_bitcoincore_rpc_fallback_urls() {
+            client_setting = client_setting.with_fallback_urls(fallback_urls.to_owned());
+        }
+        match self.get_bitcoincore_rpc_user() {
+            Some(rpc_user) => client_setting.with_user_pass(
+                rpc_user,
+                self.get_bitcoincore_rpc_password()
+                    .as_deref()
+                    .unwrap_or_default(),
+            ),
+            None => client_setting,
+        }
     }
 
-    pub fn get_explorer_setting(&self) -> ExplorerSetting {
-        let mnemonic = self.get_mnemonic().to_owned();
-        let passphrase = self.get_passphrase().to_owned();
-        let base_derivation_paths = match self.get_base_derivation_paths() {
-            Some(base_derivation_paths) => base_derivation_paths.to_owned(),
-            None => WalletsInfo::get_all_unique_preset_wallet_base_paths().to_owned(),
-        };
+    pub fn get_scan_in_progress_policy(&self) -> ScanInProgressPolicy {
+        match self.get_scan_in_progress() {
+            Some(scan_in_progress) => *scan_in_progress,
+            None => DEFAULT_SCAN_IN_PROGRESS_POLICY,
+        }
+    }
+
+    pub fn is_mempool_check_enabled(&self) -> bool {
+        match self.get_check_mempool() {
+            Some(check_mempool) => *check_mempool,
+            None => DEFAULT_CHECK_MEMPOOL,
+        }
+    }
+
+    pub fn is_narrowing_by_purpose_enabled(&self) -> bool {
+        match self.get_narrow_descriptors_by_purpose() {
+            Some(narrow_descriptors_by_purpose) => *narrow_descriptors_by_purpose,
+            None => DEFAULT_NARROW_DESCRIPTORS_BY_PURPOSE,
+        }
+    }
+
+    pub fn get_uspk_set_backend(&self) -> UspkSetBackend {
+        match self.get_uspk_backend() {
+            Some(uspk_backend) => *uspk_backend,
+            None => DEFAULT_USPK_SET_BACKEND,
+        }
+    }
+
+    pub fn is_inverted_search_enabled(&self) -> bool {
+        match self.get_inverted_search() {
+            Some(inverted_search) => *inverted_search,
+            None => DEFAULT_INVERTED_SEARCH,
+        }
+    }
+
+    pub fn is_uspk_set_cache_enabled(&self) -> bool {
+        match self.get_uspk_set_cache() {
+            Some(uspk_set_cache) => *uspk_set_cache,
+            None => DEFAULT_USPK_SET_CACHE,
+        }
+    }
+
+    pub fn is_keeping_coins_enabled(&self) -> bool {
+        match self.get_keep_coins() {
+            Some(keep_coins) => *keep_coins,
+            None => DEFAULT_KEEP_COINS,
+        }
+    }
+
+    pub fn is_sorted_merge_search_enabled(&self) -> bool {
+        match self.get_sorted_merge_search() {
+            Some(sorted_merge_search) => *sorted_merge_search,
+            None => DEFAULT_SORTED_MERGE_SEARCH,
+        }
+    }
+
+    pub fn get_derivation_thread_count(&self) -> usize {
+        self.get_derivation_threads()
+            .unwrap_or(DEFAULT_DERIVATION_THREADS)
+    }
 
-        let exploration_path = match self.get_exploration_path() {
-            Some(exploration_path) => exploration_path.to_owned(),
-            None => DEFAULT_EXPLORATION_PATH.to_string(),
+    pub fn is_silent_payments_enabled(&self) -> bool {
+        match self.get_silent_payments() {
+            Some(silent_payments) => *silent_payments,
+            None => DEFAULT_SILENT_PAYMENTS,
+        }
+    }
+
+    /// The BIP85 indexes to explore the child mnemonics of, if set.
+    pub fn get_bip85_index_range(&self) -> Result<Option<RangeInclusive<u32>>, RetrieverError> {
+        self.get_bip85_indexes()
+            .as_deref()
+            .map(parse_bip85_indexes)
+            .transpose()
+    }
+
+    pub fn is_event_log_enabled(&self) -> bool {
+        match self.get_event_log() {
+            Some(event_log) => *event_log,
+            None => DEFAULT_EVENT_LOG,
+        }
+    }
+
+    /// The settings of the explorers of a run over several seeds or BIP85 children, labelled after
+    /// them, in the order they are explored. None if the seed is explored itself only. They are
+    /// made as the run goes, as mnemonics with unknown words may stand for many thousands.
+    pub fn get_batch_explorer_settings(
+        &self,
+    ) -> Result<Option<Box<dyn Iterator<Item = ExplorerSetting>>>, RetrieverError> {
+        let bip85_indexes = self.get_bip85_index_range()?;
+        let passphrase_candidates = self.read_passphrase_candidates()?;
+        let explorer_settings = match self.get_seeds() {
+            Some(seeds) if !seeds.is_empty() => seeds
+                .iter()
+                .enumerate()
+                .map(|(index, seed)| {
+                    self.get_explorer_setting()
+                        .with_seed(
+                            seed.get_mnemonic().to_owned(),
+                            seed.get_passphrase().to_owned(),
+                        )
+                        .with_label(Some(format!("seed_{}", index + 1)))
+                })
+                .collect::<Vec<_>>(),
+            _ => vec![self.get_explorer_setting().with_seed(
+                self.get_mnemonic().to_owned(),
+                self.get_passphrase().to_owned(),
+            )],
         };
+        let correct_typos = self.get_correct_typos().unwrap_or(DEFAULT_CORRECT_TYPOS);
+        let word_swaps = *self.get_word_swaps();
+        let mut with_candidates = false;
+        for setting in explorer_settings.iter() {
+            with_candidates |=
+                from_mnemonic_to_candidates(setting, correct_typos, word_swaps)?.is_some();
+        }
+        if self.get_seeds().as_ref().is_none_or(Vec::is_empty)
+            && !with_candidates
+            && bip85_indexes.is_none()
+            && passphrase_candidates.is_none()
+        {
+            return Ok(None);
+        }
+        let mut explorer_settings: Box<dyn Iterator<Item = ExplorerSetting>> =
+            Box::new(explorer_settings.into_iter().flat_map(
+                move |setting| -> Box<dyn Iterator<Item = ExplorerSetting>> {
+                    let Some(candidates) =
+                        from_mnemonic_to_candidates(&setting, correct_typos, word_swaps)
+                            .expect("checked above")
+                    else {
+                        return Box::new(std::iter::once(setting));
+                    };
+                    Box::new(candidates.map(move |(candidate_label, mnemonic)| {
+                        let label = join_labels(setting.get_label(), candidate_label);
+                        setting
+                            .clone()
+                            .with_seed(mnemonic, setting.get_passphrase().to_owned())
+                            .with_label(Some(label))
+                    }))
+                },
+            ));
+        if let Some(passphrase_candidates) = passphrase_candidates {
+            let passphrase_candidates = Arc::new(Zeroizing::new(passphrase_candidates));
+            explorer_settings = Box::new(explorer_settings.flat_map(move |setting| {
+                let passphrase_candidates = passphrase_candidates.clone();
+                (0..passphrase_candidates.len()).map(move |line| {
+                    let label =
+                        join_labels(setting.get_label(), format!("passphrase_{}", line + 1));
+                    setting
+                        .clone()
+                        .with_passphrase(passphrase_candidates[line].to_owned())
+                        .with_label(Some(label))
+                })
+            }));
+        }
+        if let Some(bip85_indexes) = bip85_indexes {
+            explorer_settings = Box::new(explorer_settings.flat_map(move |setting| {
+                bip85_indexes
+                    .clone()
+                    .map(move |bip85_index| setting.clone().with_bip85_index(Some(bip85_index)))
+            }));
+        }
+        // The standalone keys are the same in every run, so they are searched in the first one.
+        Ok(Some(Box::new(explorer_settings.enumerate().map(
+            |(run, setting)| match run {
+                0 => setting,
+                _ => setting.without_standalone_keys(),
+            },
+        ))))
+    }
 
-        let exploration_depth = match self.get_exploration_depth() {
-            Some(exploration_depth) => *exploration_depth,
-            None => DEFAULT_EXPLORATION_DEPTH,
+    /// The passphrases of the candidates file, one per line, if one is set. An empty line stands
+    /// for no passphrase.
+    fn read_passphrase_candidates(&self) -> Result<Option<Vec<String>>, RetrieverError> {
+        let Some(file_path) = self.get_passphrase_candidates_file() else {
+            return Ok(None);
         };
+        let mut content = fs::read_to_string(file_path).inspect_err(|err| {
+            error!("Reading the passphrase candidates file failed: {}", err);
+        })?;
+        let candidates = content
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect::<Vec<_>>();
+        content.zeroize();
+        if candidates.is_empty() {
+            error!("The passphrase candidates file is empty.");
+            return Err(RetrieverError::NoPassphraseCandidates);
+        }
+        info!("{} passphrase candidates read.", candidates.len());
+        Ok(Some(candidates))
+    }
 
+    /// The explorer setting of the mnemonic and passphrase, or of the first seed of a batch.
+    pub fn get_explorer_setting(&self) -> ExplorerSetting {
+        let (mnemonic, passphrase) = match self.get_seeds().as_ref().and_then(|seeds| seeds.first())
+        {
+            Some(seed) => (
+                seed.get_mnemonic().to_owned(),
+                seed.get_passphrase().to_owned(),
+            ),
+            None => (
+                self.get_mnemonic().to_owned(),
+                self.get_passphrase().to_owned(),
+            ),
+        };
         let network = match self.get_network() {
             Some(network) => *network,
             None => DEFAULT_NETWORK,
         };
+        let multisig_preset = *self.get_multisig_preset();
+        let lightning_node = *self.get_lightning_node();
+        let green_subaccounts = self
+            .get_green_subaccounts()
+            .to_owned()
+            .unwrap_or(DEFAULT_GREEN_SUBACCOUNTS.to_vec());
+        let base_derivation_paths = match (self.get_base_derivation_paths(), multisig_preset) {
+            (Some(base_derivation_paths), _) => base_derivation_paths.to_owned(),
+            (None, Some(multisig_preset)) => {
+                vec![multisig_preset.get_base_path(network).to_string()]
+            }
+            (None, None) if self.get_green_service_xpub().is_some() => {
+                GreenWallet::get_base_paths(&green_subaccounts)
+            }
+            (None, None) if lightning_node.is_some() => lightning_node
+                .map(|lightning_node| lightning_node.get_base_paths(network))
+                .unwrap_or_default(),
+            (None, None) => WalletsInfo::get_all_unique_preset_wallet_base_paths().to_owned(),
+        };
+        let green_csv_blocks = match (self.get_green_csv_blocks(), network) {
+            (Some(green_csv_blocks), _) => green_csv_blocks.to_owned(),
+            (None, bitcoin::Network::Bitcoin) => GREEN_MAINNET_CSV_BLOCKS.to_vec(),
+            (None, _) => GREEN_TESTNET_CSV_BLOCKS.to_vec(),
+        };
+
+        let exploration_path = match (self.get_exploration_path(), multisig_preset) {
+            (Some(exploration_path), _) => exploration_path.to_owned(),
+            (None, Some(multisig_preset)) => multisig_preset.get_exploration_path().to_string(),
+            (None, None) if self.get_green_service_xpub().is_some() => {
+                GREEN_EXPLORATION_PATH.to_string()
+            }
+            (None, None) if lightning_node.is_some() => LIGHTNING_EXPLORATION_PATH.to_string(),
+            (None, None) => DEFAULT_EXPLORATION_PATH.to_string(),
+        };
+
+        let exploration_depth = match self.get_exploration_depth() {
+            Some(exploration_depth) => *exploration_depth,
+            None => DEFAULT_EXPLORATION_DEPTH,
+        };
         let sweep = match self.get_sweep() {
             Some(sweep) => *sweep,
             None => DEFAULT_SWEEP,
         };
-        ExplorerSetting::new(
+        let explorer_setting = ExplorerSetting::new(
             mnemonic,
             passphrase,
             base_derivation_paths,
@@ -155,5 +788,131 @@ impl RetrieverSetting {
             network,
             sweep,
         )
+        .with_seed_type(
+            self.get_seed_type()
+                .unwrap_or(match (multisig_preset, lightning_node) {
+                    (Some(multisig_preset), _) => multisig_preset.get_seed_type(),
+                    (None, Some(lightning_node)) => lightning_node.get_seed_type(),
+                    (None, None) => DEFAULT_SEED_TYPE,
+                }),
+        )
+        .with_slip39_shares(self.get_slip39_shares().to_owned().unwrap_or_default())
+        .with_xpriv(self.get_xpriv().to_owned().unwrap_or_default())
+        .with_xpubs(self.get_xpubs().to_owned().unwrap_or_default())
+        .with_seed_hex(self.get_seed_hex().to_owned().unwrap_or_default())
+        .with_seed_qr(self.get_seed_qr().to_owned().unwrap_or_default())
+        .with_bip85_words(self.get_bip85_words().unwrap_or(DEFAULT_BIP85_WORDS))
+        .with_bip38_keys(
+            self.get_bip38_keys().to_owned().unwrap_or_default(),
+            self.get_bip38_password().to_owned().unwrap_or_default(),
+        )
+        .with_brainwallets(
+            self.get_brainwallet_file().to_owned().unwrap_or_default(),
+            self.get_brainwallet_hashes()
+                .to_owned()
+                .unwrap_or(vec![DEFAULT_BRAINWALLET_HASH]),
+            self.get_warpwallet_salt().to_owned().unwrap_or_default(),
+        )
+        .with_hwi(
+            self.get_hwi_command()
+                .to_owned()
+                .unwrap_or(DEFAULT_HWI_COMMAND.to_string()),
+            self.get_hwi_fingerprint().to_owned().unwrap_or_default(),
+        )
+        .with_multisig(
+            self.get_multisig_cosigners().to_owned().unwrap_or_default(),
+            self.get_multisig_threshold().unwrap_or_default(),
+        )
+        .with_miniscript_policy(self.get_miniscript_policy().to_owned().unwrap_or_default())
+        .with_multisig_preset(multisig_preset)
+        .with_green(
+            self.get_green_service_xpub().to_owned().unwrap_or_default(),
+            green_subaccounts,
+            green_csv_blocks,
+        )
+        .with_timelocks(
+            self.get_timelock_templates().to_owned().unwrap_or_default(),
+            self.get_timelock_values().to_owned().unwrap_or_default(),
+        )
+        .with_lightning_node(lightning_node);
+        // The batch explores every candidate of the mnemonic, a single explorer the first one.
+        let correct_typos = self.get_correct_typos().unwrap_or(DEFAULT_CORRECT_TYPOS);
+        match from_mnemonic_to_candidates(&explorer_setting, correct_typos, *self.get_word_swaps())
+            .ok()
+            .flatten()
+            .and_then(|mut candidates| candidates.next())
+        {
+            Some((_, mnemonic)) => {
+                let passphrase = explorer_setting.get_passphrase().to_owned();
+                explorer_setting.with_seed(mnemonic, passphrase)
+            }
+            None => explorer_setting,
+        }
+    }
+}
+
+/// Candidate mnemonics, each with the label of its run.
+type MnemonicCandidates = Box<dyn Iterator<Item = (String, String)>>;
+
+/// The mnemonics explored in place of the mnemonic of the setting, labelled after what was filled
+/// in, reordered or corrected, if it has unknown words, if its words may be swapped, or if it does
+/// not parse and typos are corrected.
+fn from_mnemonic_to_candidates(
+    setting: &ExplorerSetting,
+    correct_typos: bool,
+    word_swaps: Option<usize>,
+) -> Result<Option<MnemonicCandidates>, RetrieverError> {
+    let mnemonic = setting.get_mnemonic();
+    let is_bip39 = *setting.get_seed_type() == SeedType::Bip39;
+    if has_unknown_words(mnemonic) {
+        if !is_bip39 {
+            error!("Unknown words can only be recovered in BIP39 mnemonics.");
+            return Err(RetrieverError::InvalidUnknownWords);
+        }
+        let candidates = from_mnemonic_with_unknown_words_to_candidates(mnemonic)?;
+        return Ok(Some(Box::new(candidates.map(|(filling, mnemonic)| {
+            (format!("words_{}", filling.join("_")), mnemonic)
+        }))));
+    }
+    if let Some(word_swaps) = word_swaps {
+        if !is_bip39 {
+            error!("Swapped words can only be recovered in BIP39 mnemonics.");
+            return Err(RetrieverError::InvalidWordSwaps);
+        }
+        let candidates = from_mnemonic_with_swaps_to_candidates(mnemonic, word_swaps)?;
+        return Ok(Some(Box::new(candidates.map(|(moves, mnemonic)| {
+            let label = if moves.is_empty() {
+                "as_written".to_string()
+            } else {
+                moves
+                    .iter()
+                    .fold("reordered".to_string(), |label, (from, to)| {
+                        format!("{}_{}to{}", label, from, to)
+                    })
+            };
+            (label, mnemonic)
+        }))));
+    }
+    if !correct_typos || !is_bip39 || from_input_str_to_mnemonic(mnemonic).is_ok() {
+        return Ok(None);
+    }
+    let candidates = from_mnemonic_with_typos_to_candidates(mnemonic)?;
+    Ok(Some(Box::new(candidates.into_iter().map(
+        |(corrections, mnemonic)| {
+            let corrections = corrections
+                .iter()
+                .map(|(position, word)| format!("{}_{}", position, word))
+                .collect::<Vec<_>>()
+                .join("_");
+            (format!("corrected_{}", corrections), mnemonic)
+        },
+    ))))
+}
+
+/// Appends the part to the label of the seed, if it has one.
+fn join_labels(label: &Option<String>, part: String) -> String {
+    match label {
+        Some(label) => format!("{}_{}", label, part),
+        None => part,
     }
 }
@@ -0,0 +1,492 @@
+use std::str::FromStr;
+
+use bitcoin::{
+    bip32::{DerivationPath, Xpriv},
+    consensus::serialize,
+    hashes::{hash160, sha256, Hash, HashEngine},
+    key::{Parity, Secp256k1},
+    script::Instruction,
+    secp256k1::{PublicKey, Scalar, SecretKey, XOnlyPublicKey},
+    Block, OutPoint, Script, ScriptBuf, Transaction, TxIn,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{chainstate::ChainstateCoin, coin_arena::CoinHeader, error::RetrieverError};
+
+const INPUTS_TAG: &str = "BIP0352/Inputs";
+const SHARED_SECRET_TAG: &str = "BIP0352/SharedSecret";
+const LABEL_TAG: &str = "BIP0352/Label";
+/// The x coordinate of the key without known private key that BIP341 suggests as the internal
+/// key of outputs spendable by script only. Inputs revealing it have no key to share a secret
+/// with.
+const NUMS_INTERNAL_KEY: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+/// The first byte of the annex, the optional last element of a taproot witness.
+const TAPROOT_ANNEX_PREFIX: u8 = 0x50;
+
+/// The hash of the data under the tag, as BIP340 defines tagged hashes.
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    for part in data {
+        engine.input(part);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// An unspent taproot output, kept while the utxo set is read as it may be a silent payment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SilentPaymentOutput {
+    pub out_point: OutPoint,
+    pub amount_sats: u64,
+    pub output_key: XOnlyPublicKey,
+}
+
+/// The unspent taproot outputs created at or after the birth height, by the height of their
+/// block. The utxo set holds no inputs, so the blocks at these heights are fetched later to
+/// compute the shared secrets of their transactions.
+#[derive(Debug, Clone, Default)]
+pub struct SilentPaymentOutputs {
+    birth_height: u32,
+    by_height: hashbrown::HashMap<u32, Vec<SilentPaymentOutput>>,
+}
+
+impl SilentPaymentOutputs {
+    pub fn new(birth_height: u32) -> Self {
+        SilentPaymentOutputs {
+            birth_height,
+            by_height: hashbrown::HashMap::new(),
+        }
+    }
+
+    /// Keeps the coin if it is a taproot output recent enough.
+    pub fn insert_coin(&mut self, coin: &ChainstateCoin) {
+        self.insert(
+            coin.height,
+            coin.out_point,
+            coin.amount_sats,
+            &coin.script_pubkey,
+        );
+    }
+
+    /// Keeps the coin if a taproot output, its scriptpubkey decoded apart.
+    pub fn insert_decoded_coin(&mut self, coin: &CoinHeader, script_pubkey: &[u8]) {
+        self.insert(
+            coin.height,
+            coin.out_point,
+            coin.amount_sats,
+            Script::from_bytes(script_pubkey),
+        );
+    }
+
+    /// Keeps the taproot outputs of a new block.
+    pub fn insert_block(&mut self, height: u32, block: &Block) {
+        for tx in block.txdata.iter() {
+            let txid = tx.txid();
+            for (vout, txout) in tx.output.iter().enumerate() {
+                self.insert(
+                    height,
+                    OutPoint::new(txid, vout as u32),
+                    txout.value.to_sat(),
+                    &txout.script_pubkey,
+                );
+            }
+        }
+    }
+
+    fn insert(&mut self, height: u32, out_point: OutPoint, amount_sats: u64, script: &Script) {
+        if height < self.birth_height || !script.is_p2tr() {
+            return;
+        }
+        let Ok(output_key) = XOnlyPublicKey::from_slice(&script.as_bytes()[2..34]) else {
+            return;
+        };
+        self.by_height
+            .entry(height)
+            .or_default()
+            .push(SilentPaymentOutput {
+                out_point,
+                amount_sats,
+                output_key,
+            });
+    }
+
+    /// The heights holding kept outputs, in ascending order.
+    pub fn get_heights(&self) -> Vec<u32> {
+        let mut heights = self.by_height.keys().copied().collect::<Vec<_>>();
+        heights.sort_unstable();
+        heights
+    }
+
+    pub fn get_outputs_at(&self, height: u32) -> &[SilentPaymentOutput] {
+        self.by_height
+            .get(&height)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_height.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_height.is_empty()
+    }
+}
+
+/// The public key an input contributes to the shared secret, given the scriptpubkey it spends.
+/// Only p2pkh, p2wpkh, p2sh-p2wpkh and p2tr inputs have one, as BIP352 lays down.
+fn get_input_public_key(input: &TxIn, prevout: &Script) -> Option<PublicKey> {
+    if prevout.is_p2tr() {
+        let mut witness = input.witness.iter().collect::<Vec<_>>();
+        if witness.len() > 1
+            && witness
+                .last()
+                .is_some_and(|last| last.first() == Some(&TAPROOT_ANNEX_PREFIX))
+        {
+            witness.pop();
+        }
+        // A script path spend ends with the control block, which holds the internal key.
+        if witness.len() > 1
+            && witness
+                .last()
+                .is_some_and(|control_block| control_block.get(1..33) == Some(&NUMS_INTERNAL_KEY))
+        {
+            return None;
+        }
+        let output_key = XOnlyPublicKey::from_slice(&prevout.as_bytes()[2..34]).ok()?;
+        return Some(output_key.public_key(Parity::Even));
+    }
+    let compressed_key = |key: &[u8]| {
+        (key.len() == 33)
+            .then(|| PublicKey::from_slice(key).ok())
+            .flatten()
+    };
+    if prevout.is_p2wpkh() {
+        return input.witness.nth(1).and_then(compressed_key);
+    }
+    if prevout.is_p2sh() {
+        let redeem_script = match input.script_sig.instructions().next()? {
+            Ok(Instruction::PushBytes(push)) => ScriptBuf::from(push.as_bytes().to_vec()),
+            _ => return None,
+        };
+        if redeem_script.is_p2wpkh() && input.witness.len() == 2 {
+            return input.witness.nth(1).and_then(compressed_key);
+        }
+        return None;
+    }
+    if prevout.is_p2pkh() {
+        // The key is the last 33 bytes of the scriptsig hashing to the spent hash, which also
+        // holds for malleated scriptsigs.
+        let key_hash = &prevout.as_bytes()[3..23];
+        return input
+            .script_sig
+            .as_bytes()
+            .windows(33)
+            .rev()
+            .find(|key| hash160::Hash::hash(key).as_byte_array()[..] == *key_hash)
+            .and_then(compressed_key);
+    }
+    None
+}
+
+/// The tweak of a transaction, the sum of the keys of its eligible inputs times the hash of that
+/// sum and the smallest outpoint it spends. With the scan key, it gives the shared secret of the
+/// silent payments the transaction makes. Transactions without eligible inputs, or spending
+/// outputs of future segwit versions, make none.
+pub fn get_transaction_tweak(tx: &Transaction, prevouts: &[ScriptBuf]) -> Option<PublicKey> {
+    if tx.is_coinbase()
+        || prevouts.len() != tx.input.len()
+        || prevouts.iter().any(|prevout| {
+            prevout
+                .witness_version()
+                .is_some_and(|version| version.to_num() > 1)
+        })
+    {
+        return None;
+    }
+    let input_keys = tx
+        .input
+        .iter()
+        .zip(prevouts)
+        .filter_map(|(input, prevout)| get_input_public_key(input, prevout))
+        .collect::<Vec<_>>();
+    if input_keys.is_empty() {
+        return None;
+    }
+    let input_keys_sum = PublicKey::combine_keys(&input_keys.iter().collect::<Vec<_>>()).ok()?;
+    let smallest_outpoint = tx
+        .input
+        .iter()
+        .map(|input| serialize(&input.previous_output))
+        .min()?;
+    let input_hash = tagged_hash(
+        INPUTS_TAG,
+        &[&smallest_outpoint, &input_keys_sum.serialize()],
+    );
+    input_keys_sum
+        .mul_tweak(
+            &Secp256k1::verification_only(),
+            &Scalar::from_be_bytes(input_hash).ok()?,
+        )
+        .ok()
+}
+
+/// A silent payment found among the unspent outputs. It is spent with the spend private key
+/// plus the tweak.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SilentPaymentFind {
+    pub out_point: OutPoint,
+    pub amount_sats: u64,
+    pub height: u32,
+    /// The index of the output among the silent payments of its transaction.
+    pub k: u32,
+    /// Whether the output is change, i.e. paid to the address with the change label.
+    pub change: bool,
+    /// The tweak (hex) to add to the spend private key.
+    pub tweak: String,
+}
+
+/// The scan and spend keys of the silent payment address of a wallet, derived at the BIP352
+/// paths of its first account.
+pub struct SilentPaymentKeys {
+    scan_key: SecretKey,
+    spend_key: PublicKey,
+    change_label: Scalar,
+}
+
+impl SilentPaymentKeys {
+    pub fn from_master_xpriv(master_xpriv: &Xpriv) -> Result<Self, RetrieverError> {
+        let secp = Secp256k1::new();
+        let coin_type = match master_xpriv.network {
+            bitcoin::Network::Bitcoin => 0,
+            _ => 1,
+        };
+        let derive = |branch: u32| -> Result<SecretKey, RetrieverError> {
+            let path =
+                DerivationPath::from_str(&format!("m/352'/{}'/0'/{}'/0", coin_type, branch))?;
+            Ok(master_xpriv.derive_priv(&secp, &path)?.private_key)
+        };
+        let scan_key = derive(1)?;
+        let mut spend_private_key = derive(0)?;
+        let spend_key = spend_private_key.public_key(&secp);
+        spend_private_key.non_secure_erase();
+        // The change label m = 0, which every wallet scans for.
+        let change_label = Scalar::from(SecretKey::from_slice(&tagged_hash(
+            LABEL_TAG,
+            &[&scan_key.secret_bytes(), &0u32.to_be_bytes()],
+        ))?);
+        Ok(SilentPaymentKeys {
+            scan_key,
+            spend_key,
+            change_label,
+        })
+    }
+
+    /// Looks for silent payments to the keys among the outputs of the transaction, and returns
+    /// those still unspent. Outputs are tried for k = 0, 1, ... as long as one matches, against
+    /// all outputs of the transaction, so spent silent payments do not hide the later ones.
+    pub fn scan_transaction(
+        &self,
+        tx: &Transaction,
+        prevouts: &[ScriptBuf],
+        height: u32,
+        unspent_outputs: &[SilentPaymentOutput],
+    ) -> Result<Vec<SilentPaymentFind>, RetrieverError> {
+        let Some(tweak) = get_transaction_tweak(tx, prevouts) else {
+            return Ok(vec![]);
+        };
+        let secp = Secp256k1::new();
+        let txid = tx.txid();
+        let shared_secret = tweak.mul_tweak(&secp, &Scalar::from(self.scan_key))?;
+        let output_keys = tx
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(_, txout)| txout.script_pubkey.is_p2tr())
+            .filter_map(|(vout, txout)| {
+                XOnlyPublicKey::from_slice(&txout.script_pubkey.as_bytes()[2..34])
+                    .ok()
+                    .map(|output_key| (vout as u32, output_key))
+            })
+            .collect::<Vec<_>>();
+        let mut finds = vec![];
+        for k in 0..output_keys.len() as u32 {
+            let shared_tweak = SecretKey::from_slice(&tagged_hash(
+                SHARED_SECRET_TAG,
+                &[&shared_secret.serialize(), &k.to_be_bytes()],
+            ))?;
+            let mut found = None;
+            for (change, key_tweak) in [
+                (false, shared_tweak),
+                (true, shared_tweak.add_tweak(&self.change_label)?),
+            ] {
+                let output_key = self
+                    .spend_key
+                    .combine(&key_tweak.public_key(&secp))?
+                    .x_only_public_key()
+                    .0;
+                if let Some((vout, _)) = output_keys.iter().find(|(_, key)| *key == output_key) {
+                    found = Some((*vout, change, key_tweak));
+                    break;
+                }
+            }
+            let Some((vout, change, mut key_tweak)) = found else {
+                break;
+            };
+            let out_point = OutPoint::new(txid, vout);
+            if let Some(output) = unspent_outputs
+                .iter()
+                .find(|output| output.out_point == out_point)
+            {
+                finds.push(SilentPaymentFind {
+                    out_point,
+                    amount_sats: output.amount_sats,
+                    height,
+                    k,
+                    change,
+                    tweak: hex::encode(key_tweak.secret_bytes()),
+                });
+            }
+            key_tweak.non_secure_erase();
+        }
+        Ok(finds)
+    }
+}
+
+impl Drop for SilentPaymentKeys {
+    fn drop(&mut self) {
+        self.scan_key.non_secure_erase();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, key::TweakedPublicKey, transaction::Version, Amount,
+        Sequence, TxOut, Txid, Witness,
+    };
+
+    use super::*;
+
+    #[test]
+    fn silent_payments_works_01() {
+        let secp = Secp256k1::new();
+        let master_xpriv = Xpriv::new_master(bitcoin::Network::Bitcoin, &[1u8; 64]).unwrap();
+        let keys = SilentPaymentKeys::from_master_xpriv(&master_xpriv).unwrap();
+        let spend_private_key = master_xpriv
+            .derive_priv(
+                &secp,
+                &DerivationPath::from_str("m/352'/0'/0'/0'/0").unwrap(),
+            )
+            .unwrap()
+            .private_key;
+        // The sender pays to the address from the private key of its p2wpkh input.
+        let input_private_key = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let input_key = input_private_key.public_key(&secp);
+        let prevout =
+            ScriptBuf::new_p2wpkh(&bitcoin::PublicKey::new(input_key).wpubkey_hash().unwrap());
+        let previous_output = OutPoint::new(Txid::from_byte_array([7u8; 32]), 1);
+        let input_hash = tagged_hash(
+            INPUTS_TAG,
+            &[&serialize(&previous_output), &input_key.serialize()],
+        );
+        let shared_secret = keys
+            .scan_key
+            .public_key(&secp)
+            .mul_tweak(
+                &secp,
+                &Scalar::from(
+                    input_private_key
+                        .mul_tweak(&Scalar::from_be_bytes(input_hash).unwrap())
+                        .unwrap(),
+                ),
+            )
+            .unwrap();
+        let output_key = |k: u32, label: Option<Scalar>| {
+            let mut tweak = SecretKey::from_slice(&tagged_hash(
+                SHARED_SECRET_TAG,
+                &[&shared_secret.serialize(), &k.to_be_bytes()],
+            ))
+            .unwrap();
+            if let Some(label) = label {
+                tweak = tweak.add_tweak(&label).unwrap();
+            }
+            keys.spend_key
+                .combine(&tweak.public_key(&secp))
+                .unwrap()
+                .x_only_public_key()
+                .0
+        };
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::from_slice(&[vec![0u8; 71], input_key.serialize().to_vec()]),
+            }],
+            output: [output_key(0, None), output_key(1, Some(keys.change_label))]
+                .iter()
+                .map(|key| TxOut {
+                    value: Amount::from_sat(10_000),
+                    script_pubkey: ScriptBuf::new_p2tr_tweaked(
+                        TweakedPublicKey::dangerous_assume_tweaked(*key),
+                    ),
+                })
+                .collect(),
+        };
+        let mut outputs = SilentPaymentOutputs::new(100);
+        for (vout, txout) in tx.output.iter().enumerate() {
+            outputs.insert_coin(&ChainstateCoin {
+                out_point: OutPoint::new(tx.txid(), vout as u32),
+                height: 120,
+                is_coinbase: false,
+                amount_sats: txout.value.to_sat(),
+                script_pubkey: txout.script_pubkey.clone(),
+            });
+        }
+        assert_eq!(outputs.get_heights(), vec![120]);
+        let finds = keys
+            .scan_transaction(
+                &tx,
+                std::slice::from_ref(&prevout),
+                120,
+                outputs.get_outputs_at(120),
+            )
+            .unwrap();
+        assert_eq!(finds.len(), 2);
+        assert_eq!((finds[0].k, finds[0].change), (0, false));
+        assert_eq!((finds[1].k, finds[1].change), (1, true));
+        // The spend private key plus the tweak spends the output.
+        for find in finds.iter() {
+            let tweak = SecretKey::from_slice(&hex::decode(&find.tweak).unwrap()).unwrap();
+            let private_key = spend_private_key.add_tweak(&Scalar::from(tweak)).unwrap();
+            assert_eq!(
+                private_key.x_only_public_key(&secp).0,
+                outputs.get_outputs_at(120)[find.out_point.vout as usize].output_key
+            );
+        }
+        // A spent first payment does not hide the second.
+        let finds = keys
+            .scan_transaction(&tx, &[prevout], 120, &outputs.get_outputs_at(120)[1..])
+            .unwrap();
+        assert_eq!(finds.len(), 1);
+        assert_eq!(finds[0].k, 1);
+        // Outputs below the birth height are not kept.
+        let mut outputs = SilentPaymentOutputs::new(200);
+        outputs.insert_coin(&ChainstateCoin {
+            out_point: OutPoint::new(tx.txid(), 0),
+            height: 120,
+            is_coinbase: false,
+            amount_sats: 10_000,
+            script_pubkey: tx.output[0].script_pubkey.clone(),
+        });
+        assert!(outputs.is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{error::RetrieverError, uspk_store::ScriptPubKeyDigest};
+
+/// Scriptpubkey digests, each with a payload such as the scriptpubkey itself, sorted by digest
+/// without holding them all in memory. Records are buffered up to a number of them, then sorted
+/// and written to a run file in a directory, and the runs are merged when read back.
+///
+/// A run file is the records one after another: the digest, the length of the payload as a
+/// little endian u16, and the payload.
+#[derive(Debug)]
+pub struct ExternalSorter {
+    dir: PathBuf,
+    name: String,
+    run_items: usize,
+    /// The digest of each buffered record, where its payload starts and its length.
+    records: Vec<(ScriptPubKeyDigest, usize, u16)>,
+    payloads: Vec<u8>,
+    runs: Vec<PathBuf>,
+    items: u64,
+}
+
+impl ExternalSorter {
+    /// A sorter writing its runs, named after it, in the directory, which is created if missing.
+    pub fn new(dir: &Path, name: &str, run_items: usize) -> Result<Self, RetrieverError> {
+        fs::create_dir_all(dir)?;
+        Ok(ExternalSorter {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            run_items,
+            records: vec![],
+            payloads: vec![],
+            runs: vec![],
+            items: 0,
+        })
+    }
+
+    pub fn push(
+        &mut self,
+        digest: ScriptPubKeyDigest,
+        payload: &[u8],
+    ) -> Result<(), RetrieverError> {
+        self.records
+            .push((digest, self.payloads.len(), payload.len() as u16));
+        self.payloads.extend_from_slice(payload);
+        self.items += 1;
+        if self.records.len() >= self.run_items {
+            self.write_run()?;
+        }
+        Ok(())
+    }
+
+    /// The directory the runs are written in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The number of records pushed, duplicates included.
+    pub fn items(&self) -> u64 {
+        self.items
+    }
+
+    /// Sorts the buffered records into a new run, writing each distinct record once.
+    fn write_run(&mut self) -> Result<(), RetrieverError> {
+        let payloads = &self.payloads;
+        let payload_of = |&(_, start, len): &(ScriptPubKeyDigest, usize, u16)| {
+            &payloads[start..start + len as usize]
+        };
+        self.records
+            .sort_unstable_by(|a, b| a.0.cmp(&b.0).then_with(|| payload_of(a).cmp(payload_of(b))));
+        self.records
+            .dedup_by(|a, b| a.0 == b.0 && payload_of(a) == payload_of(b));
+        let path = self
+            .dir
+            .join(format!("{}.{}.run", self.name, self.runs.len()));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.runs.push(path);
+        for record in self.records.iter() {
+            writer.write_all(&record.0)?;
+            writer.write_all(&record.2.to_le_bytes())?;
+            writer.write_all(payload_of(record))?;
+        }
+        writer.flush()?;
+        self.records.clear();
+        self.payloads.clear();
+        Ok(())
+    }
+
+    /// Writes the last run and merges the runs, which are removed once the records are dropped.
+    pub fn finish(mut self) -> Result<SortedRecords, RetrieverError> {
+        if !self.records.is_empty() || self.runs.is_empty() {
+            self.write_run()?;
+        }
+        SortedRecords::open(std::mem::take(&mut self.runs))
+    }
+}
+
+impl Drop for ExternalSorter {
+    fn drop(&mut self) {
+        for run in self.runs.iter() {
+            let _ = fs::remove_file(run);
+        }
+    }
+}
+
+/// The records of the runs of an `ExternalSorter` in the order of their digests, read by merging
+/// the runs. A record in several runs is read once from each.
+#[derive(Debug)]
+pub struct SortedRecords {
+    runs: Vec<PathBuf>,
+    readers: Vec<BufReader<File>>,
+    /// The next record of each run that is not exhausted, with the index of the run.
+    heads: BinaryHeap<Reverse<(ScriptPubKeyDigest, Vec<u8>, usize)>>,
+}
+
+impl SortedRecords {
+    fn open(runs: Vec<PathBuf>) -> Result<Self, RetrieverError> {
+        let mut records = SortedRecords {
+            readers: vec![],
+            heads: BinaryHeap::with_capacity(runs.len()),
+            runs,
+        };
+        for run in records.runs.iter() {
+            records.readers.push(BufReader::new(File::open(run)?));
+        }
+        for index in 0..records.readers.len() {
+            records.read_head(index)?;
+        }
+        Ok(records)
+    }
+
+    /// Reads the next record of the run into the heads, if it has one.
+    fn read_head(&mut self, index: usize) -> Result<(), RetrieverError> {
+        let reader = &mut self.readers[index];
+        let mut digest = ScriptPubKeyDigest::default();
+        match reader.read_exact(&mut digest) {
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            read => read?,
+        }
+        let mut len = [0u8; 2];
+        reader.read_exact(&mut len)?;
+        let mut payload = vec![0u8; u16::from_le_bytes(len) as usize];
+        reader.read_exact(&mut payload)?;
+        self.heads.push(Reverse((digest, payload, index)));
+        Ok(())
+    }
+}
+
+impl Iterator for SortedRecords {
+    type Item = Result<(ScriptPubKeyDigest, Vec<u8>), RetrieverError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((digest, payload, index)) = self.heads.pop()?;
+        Some(self.read_head(index).map(|_| (digest, payload)))
+    }
+}
+
+impl Drop for SortedRecords {
+    fn drop(&mut self) {
+        self.readers.clear();
+        for run in self.runs.iter() {
+            let _ = fs::remove_file(run);
+        }
+    }
+}
+
+/// The payloads of the derived records whose digest is also among the unspent records, walking
+/// both in order, so neither has to fit in memory.
+pub fn merge_join(
+    derived: SortedRecords,
+    mut unspent: SortedRecords,
+) -> Result<Vec<Vec<u8>>, RetrieverError> {
+    let mut matches = vec![];
+    let mut current = unspent.next().transpose()?;
+    for record in derived {
+        let (digest, payload) = record?;
+        while current
+            .as_ref()
+            .is_some_and(|(unspent_digest, _)| *unspent_digest < digest)
+        {
+            current = unspent.next().transpose()?;
+        }
+        if current
+            .as_ref()
+            .is_some_and(|(unspent_digest, _)| *unspent_digest == digest)
+        {
+            matches.push(payload);
+        }
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::uspk_store::digest_script_pubkey;
+
+    use super::*;
+
+    #[test]
+    fn sorted_merge_works_01() {
+        let dir = std::env::temp_dir().join("sorted_merge_works_01");
+        let mut derived = ExternalSorter::new(&dir, "derived", 7).unwrap();
+        for i in (0u32..50).rev() {
+            derived
+                .push(digest_script_pubkey(&i.to_le_bytes()), &i.to_le_bytes())
+                .unwrap();
+        }
+        assert_eq!(derived.items(), 50);
+        let mut unspent = ExternalSorter::new(&dir, "unspent", 10).unwrap();
+        // Every third number from 30 on, twice, in runs of their own and the same.
+        for i in (30u32..200).step_by(3).chain((30u32..200).step_by(3)) {
+            unspent
+                .push(digest_script_pubkey(&i.to_le_bytes()), &[])
+                .unwrap();
+        }
+        let derived = derived.finish().unwrap();
+        let unspent = unspent.finish().unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 8 + 12);
+        let mut matches = merge_join(derived, unspent).unwrap();
+        matches.sort();
+        let mut expected = (30u32..50)
+            .step_by(3)
+            .map(|i| i.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(matches, expected);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+}
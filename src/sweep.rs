@@ -0,0 +1,558 @@
+use bitcoin::{
+    absolute::LockTime,
+    bip32::{DerivationPath, Fingerprint, Xpriv},
+    key::{Keypair, TapTweak},
+    psbt::Psbt,
+    secp256k1::Message,
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot,
+    transaction::Version,
+    Address, Amount, FeeRate, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+    Weight, Witness,
+};
+use getset::Getters;
+use miniscript::{
+    descriptor::{DefiniteDescriptorKey, DescriptorPublicKey, SinglePub, SinglePubKey},
+    psbt::{PsbtExt, PsbtInputExt},
+    translate_hash_clone, Descriptor, TranslatePk, Translator,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    error::RetrieverError,
+    explorer::{auxiliaries::SECP, explorer_key::ExplorerKey},
+    path_pairs::PathScanResultDescriptorTrio,
+};
+
+/// An unspent output of a find, with what a PSBT needs to spend it.
+#[derive(Debug, Clone, Getters)]
+#[get = "pub with_prefix"]
+pub struct SweepInput {
+    outpoint: OutPoint,
+    txout: TxOut,
+    /// The descriptor of the find, its explored key with the fingerprint and path it is derived
+    /// at, so signers can find it.
+    descriptor: Descriptor<DefiniteDescriptorKey>,
+    /// The transaction of the output, which signers of legacy inputs need to check its amount.
+    previous_tx: Option<Transaction>,
+}
+
+impl SweepInput {
+    pub fn new(
+        outpoint: OutPoint,
+        txout: TxOut,
+        descriptor: Descriptor<DefiniteDescriptorKey>,
+    ) -> Self {
+        SweepInput {
+            outpoint,
+            txout,
+            descriptor,
+            previous_tx: None,
+        }
+    }
+
+    pub fn with_previous_tx(mut self, previous_tx: Transaction) -> Self {
+        self.previous_tx = Some(previous_tx);
+        self
+    }
+
+    /// Whether the output is spent without a witness, e.g. p2pkh.
+    pub fn is_legacy(&self) -> bool {
+        self.descriptor.desc_type().segwit_version().is_none()
+    }
+}
+
+/// Whether the finds are swept together or apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SweepGrouping {
+    /// One transaction spending all the finds into one output.
+    #[default]
+    Consolidated,
+    /// A transaction for each find, so the finds are not linked together on chain.
+    PerFind,
+}
+
+/// The options of a sweep, by default a final consolidating transaction with no locktime.
+#[derive(Debug, Clone, Copy, Getters)]
+#[get = "pub with_prefix"]
+pub struct SweepOptions {
+    /// Signals BIP125 replaceability, so the fee can be bumped later.
+    replaceable: bool,
+    grouping: SweepGrouping,
+    /// The locktime of the transactions, e.g. the current height against fee sniping, or the
+    /// height a timelocked find can be spent at.
+    lock_time: LockTime,
+}
+
+impl Default for SweepOptions {
+    fn default() -> Self {
+        SweepOptions {
+            replaceable: false,
+            grouping: SweepGrouping::default(),
+            lock_time: LockTime::ZERO,
+        }
+    }
+}
+
+impl SweepOptions {
+    pub fn replaceable(mut self, replaceable: bool) -> Self {
+        self.replaceable = replaceable;
+        self
+    }
+
+    pub fn grouping(mut self, grouping: SweepGrouping) -> Self {
+        self.grouping = grouping;
+        self
+    }
+
+    pub fn lock_time(mut self, lock_time: LockTime) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// The sequence of the inputs, which must not be final for the locktime to apply.
+    fn sequence(&self) -> Sequence {
+        match self.replaceable {
+            true => Sequence::ENABLE_RBF_NO_LOCKTIME,
+            false => Sequence::ENABLE_LOCKTIME_NO_RBF,
+        }
+    }
+}
+
+/// The inputs spending the unspent outputs of the detailed finds. The keys of the explorer are
+/// given the origin of their path; others, such as standalone keys or the keys of cosigners, have
+/// none.
+pub fn sweep_inputs_of_finds(
+    explorer_key: &ExplorerKey,
+    detailed_finds: &[PathScanResultDescriptorTrio],
+) -> Result<Vec<SweepInput>, RetrieverError> {
+    let fingerprint = explorer_key.get_fingerprint(&SECP);
+    let mut inputs = vec![];
+    for detail in detailed_finds {
+        let mut translator = OriginTranslator {
+            explored_key: explorer_key.derive_public_key(&SECP, &detail.0).ok(),
+            origin: (fingerprint, detail.0.clone()),
+        };
+        let descriptor = detail
+            .2
+            .translate_pk(&mut translator)
+            .map_err(|_| RetrieverError::InvalidSweep)?
+            .at_derivation_index(0)
+            .map_err(|_| RetrieverError::InvalidSweep)?;
+        for utxo in detail.1.unspents.iter() {
+            inputs.push(SweepInput::new(
+                OutPoint::new(utxo.txid, utxo.vout),
+                TxOut {
+                    value: utxo.amount,
+                    script_pubkey: utxo.script_pub_key.clone(),
+                },
+                descriptor.clone(),
+            ));
+        }
+    }
+    Ok(inputs)
+}
+
+/// Builds the unsigned PSBTs sweeping the inputs to the destination as grouped by the options:
+/// one for all of them, or one for the inputs of each find, leaving out finds too small to pay
+/// their own fee.
+pub fn build_sweep_psbts(
+    inputs: &[SweepInput],
+    destination: &Address,
+    fee_rate: FeeRate,
+    options: &SweepOptions,
+) -> Result<Vec<Psbt>, RetrieverError> {
+    if options.grouping == SweepGrouping::Consolidated {
+        return Ok(vec![build_sweep_psbt(
+            inputs,
+            destination,
+            fee_rate,
+            options,
+        )?]);
+    }
+    let mut psbts = vec![];
+    // The inputs of a find are next to each other and share its descriptor.
+    for find_inputs in inputs.chunk_by(|a, b| a.descriptor == b.descriptor) {
+        match build_sweep_psbt(find_inputs, destination, fee_rate, options) {
+            Err(RetrieverError::SweepBelowDust) => {
+                warn!(
+                    "The find of {} is left out of the sweep.",
+                    find_inputs[0].descriptor
+                )
+            }
+            psbt => psbts.push(psbt?),
+        }
+    }
+    if psbts.is_empty() && !inputs.is_empty() {
+        return Err(RetrieverError::SweepBelowDust);
+    }
+    Ok(psbts)
+}
+
+/// Builds an unsigned PSBT spending all the inputs to the destination, less the fee at the fee
+/// rate for the largest the transaction can be once signed. Each input carries its utxo, the
+/// derivation of its explored key and its scripts, for a signer to sign it.
+pub fn build_sweep_psbt(
+    inputs: &[SweepInput],
+    destination: &Address,
+    fee_rate: FeeRate,
+    options: &SweepOptions,
+) -> Result<Psbt, RetrieverError> {
+    if inputs.is_empty() {
+        error!("There are no unspent outputs among the finds to sweep.");
+        return Err(RetrieverError::InvalidSweep);
+    }
+    let mut transaction = Transaction {
+        version: Version::TWO,
+        lock_time: options.lock_time,
+        input: inputs
+            .iter()
+            .map(|input| TxIn {
+                previous_output: input.outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: options.sequence(),
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: destination.script_pubkey(),
+        }],
+    };
+    let mut satisfaction_weight = 0;
+    for input in inputs {
+        satisfaction_weight += input.descriptor.max_weight_to_satisfy()?;
+    }
+    // A transaction with any witness has the segwit marker and flag, and a witness count for
+    // each of its inputs, which the satisfaction weights leave out.
+    if inputs.iter().any(|input| !input.is_legacy()) {
+        satisfaction_weight += 2 + inputs.len();
+    }
+    let weight = transaction.weight() + Weight::from_wu(satisfaction_weight as u64);
+    let fee = fee_rate
+        .fee_wu(weight)
+        .ok_or(RetrieverError::InvalidFeeRate)?;
+    let total = inputs.iter().map(|input| input.txout.value).sum::<Amount>();
+    let value = total
+        .checked_sub(fee)
+        .filter(|value| *value >= destination.script_pubkey().dust_value())
+        .ok_or_else(|| {
+            error!(
+                "The finds hold {} sats, too few to pay the fee of {} sats.",
+                total.to_sat(),
+                fee.to_sat()
+            );
+            RetrieverError::SweepBelowDust
+        })?;
+    transaction.output[0].value = value;
+    let mut psbt = Psbt::from_unsigned_tx(transaction).map_err(|_| RetrieverError::InvalidSweep)?;
+    for (psbt_input, input) in psbt.inputs.iter_mut().zip(inputs) {
+        if input.descriptor.script_pubkey() != input.txout.script_pubkey {
+            error!(
+                "The descriptor of {} does not pay to its scriptpubkey.",
+                input.outpoint
+            );
+            return Err(RetrieverError::InvalidSweep);
+        }
+        match input.is_legacy() {
+            true => psbt_input.non_witness_utxo = input.previous_tx.clone(),
+            false => psbt_input.witness_utxo = Some(input.txout.clone()),
+        }
+        psbt_input
+            .update_with_descriptor_unchecked(&input.descriptor)
+            .map_err(|_| RetrieverError::InvalidSweep)?;
+    }
+    info!(
+        "Sweep of {} sats in {} inputs to {} built, paying {} sats of fees.",
+        total.to_sat(),
+        inputs.len(),
+        destination,
+        fee.to_sat()
+    );
+    Ok(psbt)
+}
+
+/// Signs the inputs of a sweep PSBT whose keys derive from the master key, by their bip32 or
+/// taproot origins, with ECDSA or a taproot key-path signature, and returns the number of inputs
+/// signed. Inputs of other keys, such as cosigners of a multisig, are left for their signers.
+pub fn sign_sweep_psbt(psbt: &mut Psbt, master_xpriv: &Xpriv) -> Result<usize, RetrieverError> {
+    let mut signed = match psbt.sign(master_xpriv, &SECP) {
+        Ok(signing_keys) => signing_keys,
+        Err((_, signing_errors)) => {
+            for (index, err) in signing_errors {
+                error!("Error while signing input {} of the sweep: {}", index, err);
+            }
+            return Err(RetrieverError::SweepSigningFailed);
+        }
+    }
+    .into_values()
+    .filter(|keys| !keys.is_empty())
+    .count();
+    for index in 0..psbt.inputs.len() {
+        if sign_taproot_key_path(psbt, index, master_xpriv)? {
+            signed += 1;
+        }
+    }
+    info!(
+        "Signed {} of the {} inputs of the sweep.",
+        signed,
+        psbt.inputs.len()
+    );
+    Ok(signed)
+}
+
+/// Signs the key path of a taproot input if its internal key derives from the master key.
+fn sign_taproot_key_path(
+    psbt: &mut Psbt,
+    index: usize,
+    master_xpriv: &Xpriv,
+) -> Result<bool, RetrieverError> {
+    let input = &psbt.inputs[index];
+    let Some(internal_key) = input.tap_internal_key else {
+        return Ok(false);
+    };
+    let Some((_, (fingerprint, path))) = input.tap_key_origins.get(&internal_key) else {
+        return Ok(false);
+    };
+    if *fingerprint != master_xpriv.fingerprint(&SECP) {
+        return Ok(false);
+    }
+    let keypair =
+        Keypair::from_secret_key(&SECP, &master_xpriv.derive_priv(&SECP, path)?.private_key);
+    if keypair.x_only_public_key().0 != internal_key {
+        return Ok(false);
+    }
+    // The key-path sighash commits to the outputs spent by all the inputs.
+    let prevouts = (0..psbt.inputs.len())
+        .map(|index| psbt.spend_utxo(index).cloned())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| {
+            error!("Error while signing input {} of the sweep: {}", index, err);
+            RetrieverError::SweepSigningFailed
+        })?;
+    let sighash = SighashCache::new(&psbt.unsigned_tx)
+        .taproot_key_spend_signature_hash(index, &Prevouts::All(&prevouts), TapSighashType::Default)
+        .map_err(|_| RetrieverError::SweepSigningFailed)?;
+    let tweaked_keypair = keypair
+        .tap_tweak(&SECP, psbt.inputs[index].tap_merkle_root)
+        .to_inner();
+    psbt.inputs[index].tap_key_sig = Some(taproot::Signature {
+        sig: SECP.sign_schnorr(&Message::from(sighash), &tweaked_keypair),
+        hash_ty: TapSighashType::Default,
+    });
+    Ok(true)
+}
+
+/// Finalizes the signed inputs of a sweep PSBT into their scriptsigs and witnesses, and extracts
+/// the transaction once its signatures check out, ready to broadcast.
+pub fn finalize_sweep_psbt(mut psbt: Psbt) -> Result<Transaction, RetrieverError> {
+    if let Err(errs) = psbt.finalize_mut(&SECP) {
+        for err in errs {
+            error!("Error while finalizing the sweep: {}", err);
+        }
+        return Err(RetrieverError::SweepNotFinalized);
+    }
+    psbt.extract(&SECP).map_err(|err| {
+        error!("Error while extracting the sweep transaction: {}", err);
+        RetrieverError::SweepNotFinalized
+    })
+}
+
+/// Gives the explored key of a find the origin of its path.
+struct OriginTranslator {
+    explored_key: Option<bitcoin::secp256k1::PublicKey>,
+    origin: (Fingerprint, DerivationPath),
+}
+
+impl Translator<PublicKey, DescriptorPublicKey, ()> for OriginTranslator {
+    fn pk(&mut self, pk: &PublicKey) -> Result<DescriptorPublicKey, ()> {
+        Ok(DescriptorPublicKey::Single(SinglePub {
+            origin: (self.explored_key == Some(pk.inner)).then(|| self.origin.clone()),
+            key: SinglePubKey::FullKey(*pk),
+        }))
+    }
+
+    translate_hash_clone!(PublicKey, DescriptorPublicKey, ());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{bip32::Xpriv, hashes::Hash, Network, Txid};
+    use bitcoincore_rpc::json::{ScanTxOutResult, Utxo};
+
+    use super::*;
+
+    #[test]
+    fn sweep_works_01() {
+        let master_xpriv = Xpriv::new_master(Network::Regtest, &[7u8; 32]).unwrap();
+        let explorer_key = ExplorerKey::Xpriv(master_xpriv);
+        let path = DerivationPath::from_str("m/84'/1'/0'/0/3").unwrap();
+        let public_key = PublicKey::new(explorer_key.derive_public_key(&SECP, &path).unwrap());
+        let descriptor = Descriptor::new_wpkh(public_key).unwrap();
+        let utxo = |vout, sats| Utxo {
+            txid: Txid::all_zeros(),
+            vout,
+            script_pub_key: descriptor.script_pubkey(),
+            descriptor: String::new(),
+            amount: Amount::from_sat(sats),
+            height: 100,
+        };
+        let detailed_finds = [PathScanResultDescriptorTrio::new(
+            path.clone(),
+            ScanTxOutResult {
+                success: Some(true),
+                tx_outs: None,
+                height: Some(100),
+                best_block_hash: None,
+                unspents: vec![utxo(0, 50_000), utxo(1, 20_000)],
+                total_amount: Amount::from_sat(70_000),
+            },
+            descriptor.clone(),
+        )];
+        let inputs = sweep_inputs_of_finds(&explorer_key, &detailed_finds).unwrap();
+        assert_eq!(inputs.len(), 2);
+        let destination = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+            .unwrap()
+            .require_network(Network::Regtest)
+            .unwrap();
+        let psbt = build_sweep_psbt(
+            &inputs,
+            &destination,
+            FeeRate::from_sat_per_vb(10).unwrap(),
+            &SweepOptions::default(),
+        )
+        .unwrap();
+        // Two p2wpkh inputs and a p2wpkh output weigh at most 710 WU, a fee of 1775 sats.
+        assert_eq!(psbt.unsigned_tx.output[0].value, Amount::from_sat(68_225));
+        assert_eq!(
+            psbt.inputs[0].bip32_derivation[&public_key.inner],
+            (master_xpriv.fingerprint(&SECP), path)
+        );
+        assert_eq!(
+            psbt.inputs[1].witness_utxo.as_ref().unwrap().value,
+            Amount::from_sat(20_000)
+        );
+        assert!(matches!(
+            build_sweep_psbt(
+                &inputs,
+                &destination,
+                FeeRate::from_sat_per_vb(400).unwrap(),
+                &SweepOptions::default()
+            ),
+            Err(RetrieverError::SweepBelowDust)
+        ));
+    }
+
+    #[test]
+    fn sign_sweep_works_01() {
+        let master_xpriv = Xpriv::new_master(Network::Regtest, &[7u8; 32]).unwrap();
+        let explorer_key = ExplorerKey::Xpriv(master_xpriv);
+        let detailed_finds = [
+            detail(&explorer_key, "m/84'/1'/0'/0/3", 0, 30_000, |key| {
+                Descriptor::new_wpkh(key).unwrap()
+            }),
+            detail(&explorer_key, "m/86'/1'/0'/0/5", 1, 30_000, |key| {
+                Descriptor::new_tr(key, None).unwrap()
+            }),
+        ];
+        let inputs = sweep_inputs_of_finds(&explorer_key, &detailed_finds).unwrap();
+        let destination = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+            .unwrap()
+            .require_network(Network::Regtest)
+            .unwrap();
+        let fee_rate = FeeRate::from_sat_per_vb(10).unwrap();
+        let psbt =
+            build_sweep_psbt(&inputs, &destination, fee_rate, &SweepOptions::default()).unwrap();
+        // Another seed signs none of the inputs, so the sweep cannot be finalized.
+        let other_xpriv = Xpriv::new_master(Network::Regtest, &[8u8; 32]).unwrap();
+        let mut unsigned = psbt.clone();
+        assert_eq!(sign_sweep_psbt(&mut unsigned, &other_xpriv).unwrap(), 0);
+        assert!(matches!(
+            finalize_sweep_psbt(unsigned),
+            Err(RetrieverError::SweepNotFinalized)
+        ));
+        let mut signed = psbt;
+        assert_eq!(sign_sweep_psbt(&mut signed, &master_xpriv).unwrap(), 2);
+        assert!(signed.inputs[1].tap_key_sig.is_some());
+        let transaction = finalize_sweep_psbt(signed).unwrap();
+        assert!(transaction
+            .input
+            .iter()
+            .all(|txin| !txin.witness.is_empty()));
+        // The fee covers the signed transaction at the fee rate.
+        let fee = Amount::from_sat(60_000) - transaction.output[0].value;
+        assert!(fee >= fee_rate.fee_wu(transaction.weight()).unwrap());
+    }
+
+    #[test]
+    fn sweep_options_works_01() {
+        let explorer_key =
+            ExplorerKey::Xpriv(Xpriv::new_master(Network::Regtest, &[7u8; 32]).unwrap());
+        let wpkh = |key| Descriptor::new_wpkh(key).unwrap();
+        let detailed_finds = [
+            detail(&explorer_key, "m/84'/1'/0'/0/0", 0, 40_000, wpkh),
+            detail(&explorer_key, "m/84'/1'/0'/0/1", 1, 2_000, wpkh),
+        ];
+        let inputs = sweep_inputs_of_finds(&explorer_key, &detailed_finds).unwrap();
+        let destination = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+            .unwrap()
+            .require_network(Network::Regtest)
+            .unwrap();
+        let lock_time = LockTime::from_height(800).unwrap();
+        let options = SweepOptions::default()
+            .replaceable(true)
+            .grouping(SweepGrouping::PerFind)
+            .lock_time(lock_time);
+        let fee_rate = FeeRate::from_sat_per_vb(5).unwrap();
+        let psbts = build_sweep_psbts(&inputs, &destination, fee_rate, &options).unwrap();
+        assert_eq!(psbts.len(), 2);
+        assert!(psbts
+            .iter()
+            .all(|psbt| psbt.unsigned_tx.lock_time == lock_time
+                && psbt.unsigned_tx.is_explicitly_rbf()));
+        // The small find cannot pay its own fee at a higher rate, and is left out.
+        let fee_rate = FeeRate::from_sat_per_vb(20).unwrap();
+        let psbts = build_sweep_psbts(&inputs, &destination, fee_rate, &options).unwrap();
+        assert_eq!(psbts.len(), 1);
+        assert_eq!(psbts[0].unsigned_tx.input.len(), 1);
+        let consolidated =
+            build_sweep_psbts(&inputs, &destination, fee_rate, &SweepOptions::default()).unwrap();
+        assert_eq!(consolidated.len(), 1);
+        assert_eq!(consolidated[0].unsigned_tx.input.len(), 2);
+        assert!(!consolidated[0].unsigned_tx.is_explicitly_rbf());
+    }
+
+    /// A find of the explored key at the path, holding one output of the given amount.
+    fn detail(
+        explorer_key: &ExplorerKey,
+        path: &str,
+        vout: u32,
+        sats: u64,
+        descriptor: fn(PublicKey) -> Descriptor<PublicKey>,
+    ) -> PathScanResultDescriptorTrio {
+        let path = DerivationPath::from_str(path).unwrap();
+        let public_key = PublicKey::new(explorer_key.derive_public_key(&SECP, &path).unwrap());
+        let descriptor = descriptor(public_key);
+        PathScanResultDescriptorTrio::new(
+            path,
+            ScanTxOutResult {
+                success: Some(true),
+                tx_outs: None,
+                height: Some(100),
+                best_block_hash: None,
+                unspents: vec![Utxo {
+                    txid: Txid::all_zeros(),
+                    vout,
+                    script_pub_key: descriptor.script_pubkey(),
+                    descriptor: String::new(),
+                    amount: Amount::from_sat(sats),
+                    height: 100,
+                }],
+                total_amount: Amount::from_sat(sats),
+            },
+            descriptor,
+        )
+    }
+}
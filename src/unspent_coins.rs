@@ -0,0 +1,261 @@
+use bitcoin::{Amount, Block, BlockHash, OutPoint};
+use bitcoincore_rpc::json::Utxo;
+
+use crate::{
+    coin_arena::CoinHeader,
+    offline::scan_result_of_unspents,
+    path_pairs::{PathDescriptorPair, PathScanResultDescriptorTrio},
+    uspk_store::{digest_script_pubkey, ScriptPubKeyDigest},
+};
+
+/// A coin of the utxo set, all but its scriptpubkey, which it is kept under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnspentCoin {
+    pub out_point: OutPoint,
+    pub amount_sats: u64,
+    pub height: u32,
+}
+
+/// The coins of the utxo set by the digest of their scriptpubkey, as the in-memory set keeps
+/// it, kept while the set is populated. The details of finds are then read from them, as
+/// scantxoutset would report them as of the dump or the chainstate, without scanning the utxo
+/// set of bitcoincore again for hours.
+#[derive(Debug, Clone, Default)]
+pub struct UnspentCoins {
+    by_script_pubkey: hashbrown::HashMap<ScriptPubKeyDigest, Vec<UnspentCoin>>,
+    /// The number of coins read from the dump or the chainstate, and the block of the dump.
+    coins_read: u64,
+    block_hash: Option<BlockHash>,
+}
+
+impl UnspentCoins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert_decoded_coin(&mut self, coin: &CoinHeader, script_pubkey: &[u8]) {
+        self.by_script_pubkey
+            .entry(digest_script_pubkey(script_pubkey))
+            .or_default()
+            .push(UnspentCoin {
+                out_point: coin.out_point,
+                amount_sats: coin.amount_sats,
+                height: coin.height,
+            });
+    }
+
+    /// Keeps the outputs of a new block and removes the coins its inputs spend, including the
+    /// outputs spent within the block. Returns the digests of the scriptpubkeys left without
+    /// coins, which the set may drop. The coins are not indexed by outpoint, to not grow them
+    /// by a third, so the spends are removed in one pass over all coins.
+    pub fn insert_block(&mut self, height: u32, block: &Block) -> Vec<ScriptPubKeyDigest> {
+        for tx in block.txdata.iter() {
+            let txid = tx.txid();
+            for (vout, txout) in tx.output.iter().enumerate() {
+                if txout.script_pubkey.is_op_return() {
+                    continue;
+                }
+                self.by_script_pubkey
+                    .entry(digest_script_pubkey(txout.script_pubkey.as_bytes()))
+                    .or_default()
+                    .push(UnspentCoin {
+                        out_point: OutPoint::new(txid, vout as u32),
+                        amount_sats: txout.value.to_sat(),
+                        height,
+                    });
+                self.coins_read += 1;
+            }
+        }
+        let spent: hashbrown::HashSet<OutPoint> = block
+            .txdata
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .flat_map(|tx| tx.input.iter().map(|txin| txin.previous_output))
+            .collect();
+        let mut emptied = vec![];
+        if !spent.is_empty() {
+            let mut coins_spent = 0;
+            self.by_script_pubkey.retain(|digest, coins| {
+                let coins_before = coins.len();
+                coins.retain(|coin| !spent.contains(&coin.out_point));
+                coins_spent += coins_before - coins.len();
+                if coins.is_empty() {
+                    emptied.push(*digest);
+                }
+                !coins.is_empty()
+            });
+            self.coins_read = self.coins_read.saturating_sub(coins_spent as u64);
+        }
+        self.block_hash = Some(block.block_hash());
+        emptied
+    }
+
+    /// Records where the coins were read from once the set is populated.
+    pub fn set_source(&mut self, coins_read: u64, block_hash: Option<BlockHash>) {
+        self.coins_read = coins_read;
+        self.block_hash = block_hash;
+    }
+
+    pub fn get(&self, script_pubkey: &[u8]) -> &[UnspentCoin] {
+        self.by_script_pubkey
+            .get(&digest_script_pubkey(script_pubkey))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The details of the finds, shaped like the results of scantxoutset.
+    pub fn details_of_finds(
+        &self,
+        finds: &[PathDescriptorPair],
+    ) -> Vec<PathScanResultDescriptorTrio> {
+        finds
+            .iter()
+            .map(|find| {
+                let script_pubkey = find.1.script_pubkey();
+                let unspents = self
+                    .get(script_pubkey.as_bytes())
+                    .iter()
+                    .map(|coin| Utxo {
+                        txid: coin.out_point.txid,
+                        vout: coin.out_point.vout,
+                        script_pub_key: script_pubkey.clone(),
+                        descriptor: find.get_descriptor_string(),
+                        amount: Amount::from_sat(coin.amount_sats),
+                        height: coin.height as u64,
+                    })
+                    .collect();
+                PathScanResultDescriptorTrio::new(
+                    find.0.clone(),
+                    scan_result_of_unspents(unspents, self.coins_read, self.block_hash),
+                    find.1.clone(),
+                )
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_script_pubkey.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_script_pubkey.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        absolute::LockTime,
+        bip32::DerivationPath,
+        block::{self, Header},
+        hashes::Hash,
+        key::Secp256k1,
+        secp256k1::SecretKey,
+        transaction::Version,
+        CompactTarget, ScriptBuf, Transaction, TxIn, TxMerkleNode, TxOut, Txid,
+    };
+    use miniscript::Descriptor;
+
+    use super::*;
+
+    #[test]
+    fn unspent_coins_works_01() {
+        let find = PathDescriptorPair::new(
+            DerivationPath::from_str("m/84'/0'/0'/0/1").unwrap(),
+            Descriptor::new_wpkh(
+                SecretKey::from_slice(&[1u8; 32])
+                    .unwrap()
+                    .public_key(&Secp256k1::new())
+                    .into(),
+            )
+            .unwrap(),
+        );
+        let coin = |vout, amount_sats| CoinHeader {
+            out_point: OutPoint::new(Txid::from_str(&"ab".repeat(32)).unwrap(), vout),
+            height: 840_000,
+            is_coinbase: false,
+            amount_sats,
+        };
+        let mut coins = UnspentCoins::new();
+        coins.insert_decoded_coin(&coin(0, 1_000), find.1.script_pubkey().as_bytes());
+        coins.insert_decoded_coin(&coin(1, 5_000), &[0x51]);
+        coins.insert_decoded_coin(&coin(2, 2_000), find.1.script_pubkey().as_bytes());
+        coins.set_source(3, None);
+        assert_eq!(coins.len(), 3);
+        let details = coins.details_of_finds(std::slice::from_ref(&find));
+        assert_eq!(details[0].1.unspents.len(), 2);
+        assert_eq!(details[0].1.total_amount, Amount::from_sat(3_000));
+        assert_eq!(details[0].1.tx_outs, Some(3));
+        assert_eq!(details[0].1.unspents[1].vout, 2);
+    }
+
+    #[test]
+    fn unspent_coins_works_02() {
+        let funding_txid = Txid::from_str(&"ab".repeat(32)).unwrap();
+        let coin = |vout, amount_sats| CoinHeader {
+            out_point: OutPoint::new(funding_txid, vout),
+            height: 840_000,
+            is_coinbase: false,
+            amount_sats,
+        };
+        let spent_script_pubkey = ScriptBuf::from_bytes(vec![0x51]);
+        let kept_script_pubkey = ScriptBuf::from_bytes(vec![0x52]);
+        let new_script_pubkey = ScriptBuf::from_bytes(vec![0x53]);
+        let mut coins = UnspentCoins::new();
+        coins.insert_decoded_coin(&coin(0, 1_000), spent_script_pubkey.as_bytes());
+        coins.insert_decoded_coin(&coin(1, 2_000), kept_script_pubkey.as_bytes());
+        coins.insert_decoded_coin(&coin(2, 3_000), kept_script_pubkey.as_bytes());
+        coins.set_source(3, None);
+        let txin = |out_point| TxIn {
+            previous_output: out_point,
+            ..Default::default()
+        };
+        let txout = |script_pubkey: &ScriptBuf, amount_sats| TxOut {
+            value: Amount::from_sat(amount_sats),
+            script_pubkey: script_pubkey.clone(),
+        };
+        let coinbase = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![txin(OutPoint::null())],
+            output: vec![txout(&new_script_pubkey, 4_000)],
+        };
+        let spend = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![txin(coin(0, 0).out_point), txin(coin(2, 0).out_point)],
+            output: vec![txout(&new_script_pubkey, 3_500)],
+        };
+        let block = Block {
+            header: Header {
+                version: block::Version::TWO,
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 0,
+                bits: CompactTarget::from_consensus(0),
+                nonce: 0,
+            },
+            txdata: vec![coinbase, spend],
+        };
+        let emptied = coins.insert_block(840_001, &block);
+        assert_eq!(
+            emptied,
+            vec![digest_script_pubkey(spent_script_pubkey.as_bytes())]
+        );
+        assert!(coins.get(spent_script_pubkey.as_bytes()).is_empty());
+        assert_eq!(
+            coins.get(kept_script_pubkey.as_bytes()),
+            &[UnspentCoin {
+                out_point: coin(1, 0).out_point,
+                amount_sats: 2_000,
+                height: 840_000,
+            }]
+        );
+        assert_eq!(coins.get(new_script_pubkey.as_bytes()).len(), 2);
+        assert_eq!(coins.len(), 3);
+        assert_eq!(coins.coins_read, 3);
+        assert_eq!(coins.block_hash, Some(block.block_hash()));
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use bitcoin::{hashes::Hash, BlockHash};
+use tracing::info;
+
+use crate::{error::RetrieverError, uspk_store::MemoryUspkSet};
+
+pub const USPK_SET_CACHE_FILE_NAME: &str = "uspk_set.cache";
+const USPK_SET_CACHE_MAGIC_BYTES: [u8; 8] = *b"uspkset\x00";
+const USPK_SET_CACHE_VERSION: u16 = 2;
+
+/// Reads the in-memory set a previous run cached at the path, if it was populated from a dump of
+/// the same utxo set. A dump is told apart by its base block hash and number of coins, as its
+/// file carries no txoutset hash; the utxo set at a block is the same whichever node dumps it.
+pub fn load(
+    cache_path: &Path,
+    block_hash: &BlockHash,
+    utxo_set_size: u64,
+) -> Result<Option<MemoryUspkSet>, RetrieverError> {
+    if !cache_path.exists() {
+        return Ok(None);
+    }
+    let mut reader = BufReader::new(File::open(cache_path)?);
+    let mut magic_bytes = [0u8; 8];
+    reader.read_exact(&mut magic_bytes)?;
+    let mut version = [0u8; 2];
+    reader.read_exact(&mut version)?;
+    if magic_bytes != USPK_SET_CACHE_MAGIC_BYTES
+        || u16::from_le_bytes(version) != USPK_SET_CACHE_VERSION
+    {
+        return Err(RetrieverError::InvalidUspkSetCache);
+    }
+    let mut cached_block_hash = [0u8; 32];
+    reader.read_exact(&mut cached_block_hash)?;
+    let mut cached_utxo_set_size = [0u8; 8];
+    reader.read_exact(&mut cached_utxo_set_size)?;
+    if cached_block_hash != *block_hash.as_byte_array()
+        || u64::from_le_bytes(cached_utxo_set_size) != utxo_set_size
+    {
+        info!("The cached unspent scriptpubkey set is of another dump.");
+        return Ok(None);
+    }
+    Ok(Some(MemoryUspkSet::read_from(&mut reader)?))
+}
+
+/// Caches the set populated from the dump at the path. It is written next to the cache and then
+/// renamed over it, so a run stopped while writing leaves the previous cache intact.
+pub fn save(
+    cache_path: &Path,
+    block_hash: &BlockHash,
+    utxo_set_size: u64,
+    set: &MemoryUspkSet,
+) -> Result<(), RetrieverError> {
+    let partial_path = cache_path.with_extension("cache.part");
+    let mut writer = BufWriter::new(File::create(&partial_path)?);
+    writer.write_all(&USPK_SET_CACHE_MAGIC_BYTES)?;
+    writer.write_all(&USPK_SET_CACHE_VERSION.to_le_bytes())?;
+    writer.write_all(block_hash.as_byte_array())?;
+    writer.write_all(&utxo_set_size.to_le_bytes())?;
+    set.write_to(&mut writer)?;
+    writer
+        .into_inner()
+        .map_err(|err| err.into_error())?
+        .sync_all()?;
+    fs::rename(&partial_path, cache_path)?;
+    info!(
+        "Unspent scriptpubkey set cached at {}.",
+        cache_path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uspk_set_cache_works_01() {
+        let cache_path = std::env::temp_dir().join("uspk_set_cache_works_01.cache");
+        let block_hash = BlockHash::from_byte_array([7u8; 32]);
+        let mut set = MemoryUspkSet::new(2);
+        set.insert_all(&[vec![0x51], vec![0x52]]);
+        save(&cache_path, &block_hash, 2, &set).unwrap();
+        let cached = load(&cache_path, &block_hash, 2).unwrap().unwrap();
+        assert!(cached.contains(&[0x51]) && cached.contains(&[0x52]));
+        assert!(!cached.contains(&[0x53]));
+        // Another dump, whether of another block or with another number of coins.
+        assert!(load(&cache_path, &block_hash, 3).unwrap().is_none());
+        assert!(load(&cache_path, &BlockHash::all_zeros(), 2)
+            .unwrap()
+            .is_none());
+        fs::write(&cache_path, b"not a cache").unwrap();
+        assert!(load(&cache_path, &block_hash, 2).is_err());
+        fs::remove_file(cache_path).unwrap();
+    }
+}
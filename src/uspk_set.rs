@@ -1,17 +1,54 @@
 use std::{
-    sync::{Arc, Mutex},
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
     time::Instant,
 };
 
-use num_format::{Locale, ToFormattedString};
-use tracing::info;
+use bitcoin::{Block, BlockHash};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 
-use crate::error::RetrieverError;
+use crate::{
+    chainstate::ChainstateReader,
+    coin_arena::{CoinArena, CoinSource},
+    data::defaults::{
+        DECODED_COINS_BATCHES_AHEAD, DECODED_COINS_BATCH_SIZE, SORTED_MERGE_RUN_ITEMS,
+        USPK_SET_CHAINSTATE_EXPECTED_ITEMS, USPK_SET_INSERT_BATCH_SIZE,
+    },
+    dump::UtxoDump,
+    error::RetrieverError,
+    event_log::{EventLog, LoggedEvent},
+    metrics::Metrics,
+    silent_payments::SilentPaymentOutputs,
+    sorted_merge::{merge_join, ExternalSorter},
+    unspent_coins::UnspentCoins,
+    uspk_cache,
+    uspk_store::{digest_script_pubkey, DiskUspkSet, MemoryUspkSet, SpillingUspkSet, UspkStore},
+};
 
 #[derive(Debug, Clone)]
 pub struct UnspentScriptPubKeysSet {
-    set: Arc<hashbrown::HashSet<Vec<u8>>>,
+    set: UspkStore,
+    /// The file of the set when it is kept on disk rather than in memory.
+    disk_path: Option<PathBuf>,
+    /// The file the set populated from a dump is cached in for the next runs, if enabled.
+    cache_path: Option<PathBuf>,
+    /// The most bytes the in-memory set takes, and the file it spills the rest into, if capped.
+    memory_cap: Option<(usize, PathBuf)>,
     status: Arc<Mutex<Vec<UspkSetStatus>>>,
+    event_log: EventLog,
+    metrics: Arc<Metrics>,
+    /// The taproot outputs kept for the silent payments search, if enabled.
+    silent_payment_outputs: Option<Arc<SilentPaymentOutputs>>,
+    /// The coins of the scriptpubkeys, kept for the details of finds, if enabled.
+    unspent_coins: Option<Arc<UnspentCoins>>,
+    /// The derived scriptpubkeys in an inverted search, the only ones the set keeps.
+    keep_only: Option<UspkStore>,
+    /// The block the set is current as of, that of the dump or the last one applied, if known.
+    tip: Option<BlockHash>,
+    /// Whether a block was applied that does not extend the tip, after a reorg or missed blocks.
+    stale: bool,
 }
 
 impl Default for UnspentScriptPubKeysSet {
@@ -22,20 +59,166 @@ impl Default for UnspentScriptPubKeysSet {
 
 impl UnspentScriptPubKeysSet {
     pub fn new() -> Self {
-        let set: hashbrown::HashSet<Vec<u8>> = hashbrown::HashSet::new();
         UnspentScriptPubKeysSet {
-            set: Arc::new(set),
+            set: UspkStore::default(),
+            disk_path: None,
+            cache_path: None,
+            memory_cap: None,
             status: Arc::new(Mutex::new(vec![UspkSetStatus::Empty])),
+            event_log: EventLog::default(),
+            metrics: Arc::default(),
+            silent_payment_outputs: None,
+            unspent_coins: None,
+            keep_only: None,
+            tip: None,
+            stale: false,
+        }
+    }
+
+    /// Makes the set record its population milestones in the given event log.
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = event_log;
+        self
+    }
+
+    /// Makes the set count the coins it inserts in the given metrics.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Makes the set keep the scriptpubkeys in a database at the path instead of in memory.
+    pub fn with_disk_backend(mut self, disk_path: PathBuf) -> Self {
+        self.disk_path = Some(disk_path);
+        self
+    }
+
+    /// Makes the set read itself from the cache at the path when populated from the dump it was
+    /// cached for, and cache itself there otherwise.
+    pub fn with_cache(mut self, cache_path: PathBuf) -> Self {
+        self.cache_path = Some(cache_path);
+        self
+    }
+
+    /// Makes the in-memory set take at most the given bytes of memory, and keep the rest of the
+    /// scriptpubkeys in a temporary database at the path.
+    pub fn with_memory_cap(mut self, max_memory_bytes: usize, spill_path: PathBuf) -> Self {
+        self.memory_cap = Some((max_memory_bytes, spill_path));
+        self
+    }
+
+    /// The cache of the set, unless the set is more than the unspent scriptpubkeys in memory,
+    /// as with the outputs of the silent payments search, or less, as in an inverted search.
+    /// A capped set is not cached either, as reading the cache would take all of it to memory.
+    fn usable_cache_path(&self) -> Option<PathBuf> {
+        match (
+            &self.cache_path,
+            &self.disk_path,
+            &self.memory_cap,
+            &self.silent_payment_outputs,
+            &self.unspent_coins,
+            &self.keep_only,
+        ) {
+            (Some(cache_path), None, None, None, None, None) => Some(cache_path.clone()),
+            _ => None,
         }
     }
+
+    /// Makes the set keep only the unspent scriptpubkeys among the given ones, so populating it
+    /// takes as little memory as the derived scriptpubkeys of an inverted search do.
+    pub fn with_kept_script_pubkeys(mut self, script_pubkeys: UspkStore) -> Self {
+        self.keep_only = Some(script_pubkeys);
+        self
+    }
+
+    /// An empty store of the backend of the set, its bloom filter sized for the expected number
+    /// of scriptpubkeys, or for the kept ones if fewer.
+    fn create_store(&self, expected_items: usize) -> Result<UspkStore, RetrieverError> {
+        let expected_items = match self.keep_only.as_ref() {
+            Some(keep_only) => expected_items.min(keep_only.len()),
+            None => expected_items,
+        };
+        Ok(match (self.disk_path.as_ref(), self.memory_cap.as_ref()) {
+            (Some(disk_path), _) => {
+                UspkStore::Disk(Arc::new(DiskUspkSet::create(disk_path, expected_items)?))
+            }
+            (None, Some((max_memory_bytes, spill_path))) => UspkStore::Spilling(Arc::new(
+                SpillingUspkSet::new(*max_memory_bytes, expected_items, spill_path.clone()),
+            )),
+            (None, None) => UspkStore::Memory(Arc::new(MemoryUspkSet::new(expected_items))),
+        })
+    }
+
+    /// The bytes of memory the set is projected to take once populated with the expected number
+    /// of scriptpubkeys, on its backend.
+    pub fn projected_memory_bytes(&self, expected_items: usize) -> usize {
+        let expected_items = match self.keep_only.as_ref() {
+            Some(keep_only) => expected_items.min(keep_only.len()),
+            None => expected_items,
+        };
+        match (self.disk_path.as_ref(), self.memory_cap.as_ref()) {
+            (Some(_), _) => DiskUspkSet::projected_memory_bytes(expected_items),
+            (None, Some((max_memory_bytes, _))) => {
+                SpillingUspkSet::projected_memory_bytes(*max_memory_bytes, expected_items)
+            }
+            (None, None) => MemoryUspkSet::projected_memory_bytes(expected_items),
+        }
+    }
+
+    /// Makes the set also keep the unspent taproot outputs created from the birth height on, for
+    /// the silent payments search. They take far more memory than their scriptpubkeys.
+    pub fn with_silent_payment_outputs(mut self, birth_height: u32) -> Self {
+        self.silent_payment_outputs = Some(Arc::new(SilentPaymentOutputs::new(birth_height)));
+        self
+    }
+
+    /// Makes the set also keep the outpoint, amount and height of each coin by its scriptpubkey,
+    /// about a hundred bytes a coin on top of the set, or of each kept coin in an inverted search.
+    pub fn with_unspent_coins(mut self) -> Self {
+        self.unspent_coins = Some(Arc::new(UnspentCoins::new()));
+        self
+    }
+
+    /// Populates the set from the dump file. A cancellation stops it between two batches of
+    /// coins, leaving the set empty.
     pub async fn populate_with_dump_file(
         &mut self,
         dump_file_path: &str,
+        cancellation_token: &CancellationToken,
     ) -> Result<(), RetrieverError> {
         let creation_start = Instant::now();
         let status = self.status.clone();
-        let (set_sender, set_receiver) = tokio::sync::oneshot::channel();
-        let mut dump = txoutset::Dump::new(dump_file_path, txoutset::ComputeAddresses::No)?;
+        let dump = UtxoDump::open(dump_file_path)?;
+        let (block_hash, utxo_set_size) = (dump.block_hash, dump.utxo_set_size);
+        let cache_path = self.usable_cache_path();
+        if let Some(cache_path) = cache_path.clone() {
+            let cached = tokio::task::spawn_blocking(move || {
+                uspk_cache::load(&cache_path, &block_hash, utxo_set_size)
+            })
+            .await?;
+            match cached {
+                Ok(Some(set)) => {
+                    info!(
+                        script_pubkeys = set.len(),
+                        duration_ms = creation_start.elapsed().as_millis() as u64,
+                        "UTXO database read from the cache."
+                    );
+                    self.set = UspkStore::Memory(Arc::new(set));
+                    self.tip = Some(block_hash);
+                    self.status.lock().unwrap()[0] = UspkSetStatus::Ready;
+                    self.event_log.log(LoggedEvent::PopulationFinished {
+                        total_utxos: utxo_set_size,
+                        seconds: creation_start.elapsed().as_secs(),
+                    });
+                    return Ok(());
+                }
+                Ok(None) => info!("No cached unspent scriptpubkey set for the dump."),
+                Err(err) => warn!(
+                    "Reading the cached unspent scriptpubkey set failed ({:?}), reading the dump.",
+                    err
+                ),
+            }
+        }
         // Loop information.
         let step_size = 100000u64;
         let mut average_step_time_in_micros = 0u128;
@@ -44,67 +227,442 @@ impl UnspentScriptPubKeysSet {
         let mut steps_done = 0u128;
         let mut steps_remaining = (total_loops / step_size) as u128;
         let mut step_start_time = Instant::now();
+        let event_log = self.event_log.clone();
+        let mut silent_payment_outputs = self.silent_payment_outputs.as_deref().cloned();
+        let mut unspent_coins = self.unspent_coins.as_deref().cloned();
+        let mut set = self.create_store(total_loops as usize)?;
+        let keep_only = self.keep_only.clone();
+        let cancellation_token = cancellation_token.clone();
+        let metrics = self.metrics.clone();
+        event_log.log(LoggedEvent::PopulationStarted {
+            total_utxos: total_loops,
+        });
         // Loop.
-        tokio::task::spawn_blocking(move || {
+        let (set, silent_payment_outputs, unspent_coins) = tokio::task::spawn_blocking(move || {
+            status.lock().unwrap()[0] = UspkSetStatus::Populating;
+            metrics.start_population(total_loops);
+            let (arenas, recycled) = decode_in_background(dump);
+            let mut pending =
+                Vec::with_capacity(USPK_SET_INSERT_BATCH_SIZE / DECODED_COINS_BATCH_SIZE);
+            for arena in arenas {
+                let arena =
+                    arena.inspect_err(|_| status.lock().unwrap()[0] = UspkSetStatus::Empty)?;
+                if cancellation_token.is_cancelled() {
+                    warn!("Population of the unspent scriptpubkey set cancelled.");
+                    status.lock().unwrap()[0] = UspkSetStatus::Empty;
+                    return Err(RetrieverError::Cancelled);
+                }
+                if let Some(silent_payment_outputs) = silent_payment_outputs.as_mut() {
+                    for (coin, script_pubkey) in arena.iter() {
+                        silent_payment_outputs.insert_decoded_coin(coin, script_pubkey);
+                    }
+                }
+                if let Some(unspent_coins) = unspent_coins.as_mut() {
+                    for (coin, script_pubkey) in arena.iter().filter(|(_, script_pubkey)| {
+                        keep_only
+                            .as_ref()
+                            .is_none_or(|keep_only| keep_only.contains(script_pubkey))
+                    }) {
+                        unspent_coins.insert_decoded_coin(coin, script_pubkey);
+                    }
+                }
+                let coins = arena.len() as u64;
+                metrics.add_coins_inserted(coins);
+                pending.push(arena);
+                if pending.iter().map(CoinArena::len).sum::<usize>() >= USPK_SET_INSERT_BATCH_SIZE {
+                    insert_arenas(&mut set, &mut pending, keep_only.as_ref(), &recycled)?;
+                }
+                // Loop info stuff.
+                let steps_before = loops_done / step_size;
+                loops_done += coins;
+                if loops_done / step_size > steps_before {
+                    steps_done += 1;
+                    steps_remaining = steps_remaining.saturating_sub(1);
+                    average_step_time_in_micros = (step_start_time.elapsed().as_micros()
+                        + (steps_done - 1) * average_step_time_in_micros)
+                        / steps_done;
+                    let remaining_time_in_milis = average_step_time_in_micros * steps_remaining;
+                    info!(
+                        utxos = loops_done,
+                        total_utxos = total_loops,
+                        eta_minutes = 1 + remaining_time_in_milis / 60_000_000,
+                        "Utxos moved to database."
+                    );
+                    event_log.log(LoggedEvent::PopulationProgress {
+                        done: loops_done,
+                        total: total_loops,
+                    });
+                    step_start_time = Instant::now();
+                }
+            }
+            insert_arenas(&mut set, &mut pending, keep_only.as_ref(), &recycled)?;
+            status.lock().unwrap()[0] = UspkSetStatus::Ready;
+            if let Some(unspent_coins) = unspent_coins.as_mut() {
+                unspent_coins.set_source(loops_done, Some(block_hash));
+            }
+            Ok::<_, RetrieverError>((set, silent_payment_outputs, unspent_coins))
+        })
+        .await??;
+        self.set = set;
+        self.silent_payment_outputs = silent_payment_outputs.map(Arc::new);
+        self.unspent_coins = unspent_coins.map(Arc::new);
+        self.tip = Some(block_hash);
+        info!(
+            utxos = total_loops,
+            duration_ms = creation_start.elapsed().as_millis() as u64,
+            "UTXO database populated."
+        );
+        // A failed write only costs the next run the reading of the dump.
+        if let (Some(cache_path), UspkStore::Memory(set)) = (cache_path, &self.set) {
+            let set = set.clone();
+            if let Err(err) = tokio::task::spawn_blocking(move || {
+                uspk_cache::save(&cache_path, &block_hash, utxo_set_size, &set)
+            })
+            .await?
+            {
+                warn!("Caching the unspent scriptpubkey set failed: {:?}", err);
+            }
+        }
+        self.event_log.log(LoggedEvent::PopulationFinished {
+            total_utxos: total_loops,
+            seconds: creation_start.elapsed().as_secs(),
+        });
+        Ok(())
+    }
+
+    /// Populates the set by reading Bitcoin Core's chainstate database directly. The number of
+    /// coins is not known beforehand, so progress is reported as a count only. A cancellation
+    /// stops it as it does the population from a dump.
+    pub async fn populate_with_chainstate(
+        &mut self,
+        chainstate_dir: &str,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), RetrieverError> {
+        let creation_start = Instant::now();
+        let status = self.status.clone();
+        let event_log = self.event_log.clone();
+        let chainstate = ChainstateReader::open(chainstate_dir)?;
+        let mut silent_payment_outputs = self.silent_payment_outputs.as_deref().cloned();
+        let mut unspent_coins = self.unspent_coins.as_deref().cloned();
+        let step_size = 100000u64;
+        let mut set = self.create_store(USPK_SET_CHAINSTATE_EXPECTED_ITEMS)?;
+        let keep_only = self.keep_only.clone();
+        let cancellation_token = cancellation_token.clone();
+        let metrics = self.metrics.clone();
+        event_log.log(LoggedEvent::PopulationStarted { total_utxos: 0 });
+        let (set, silent_payment_outputs, unspent_coins) = tokio::task::spawn_blocking(move || {
             status.lock().unwrap()[0] = UspkSetStatus::Populating;
-            let mut set = hashbrown::HashSet::new();
-            loop {
-                match dump.next() {
-                    Some(txout) => {
-                        set.insert(txout.script_pubkey.as_bytes().to_vec());
-                        // Loop info stuff.
-                        loops_done += 1;
-                        if loops_done % step_size == 0 {
-                            steps_done += 1;
-                            steps_remaining -= 1;
-                            average_step_time_in_micros = (step_start_time.elapsed().as_micros()
-                                + (steps_done - 1) * average_step_time_in_micros)
-                                / steps_done;
-                            let remaining_time_in_milis =
-                                average_step_time_in_micros * steps_remaining;
-                            info!(
-                                "Utxos moved to database: {} of {}",
-                                loops_done.to_formatted_string(&Locale::en),
-                                total_loops.to_formatted_string(&Locale::en)
-                            );
-                            info!(
-                                "Estimated time to completion: ~{} minutes.",
-                                (1 + remaining_time_in_milis / 60_000_000)
-                                    .to_formatted_string(&Locale::en)
-                            );
-                            step_start_time = Instant::now();
-                        }
+            metrics.start_population(0);
+            let (arenas, recycled) = decode_in_background(chainstate);
+            let mut pending =
+                Vec::with_capacity(USPK_SET_INSERT_BATCH_SIZE / DECODED_COINS_BATCH_SIZE);
+            let mut loops_done = 0u64;
+            for arena in arenas {
+                let arena =
+                    arena.inspect_err(|_| status.lock().unwrap()[0] = UspkSetStatus::Empty)?;
+                if cancellation_token.is_cancelled() {
+                    warn!("Population of the unspent scriptpubkey set cancelled.");
+                    status.lock().unwrap()[0] = UspkSetStatus::Empty;
+                    return Err(RetrieverError::Cancelled);
+                }
+                if let Some(silent_payment_outputs) = silent_payment_outputs.as_mut() {
+                    for (coin, script_pubkey) in arena.iter() {
+                        silent_payment_outputs.insert_decoded_coin(coin, script_pubkey);
                     }
-                    None => {
-                        let _ = set_sender.send(set);
-                        status.lock().unwrap()[0] = UspkSetStatus::Ready;
-                        break;
+                }
+                if let Some(unspent_coins) = unspent_coins.as_mut() {
+                    for (coin, script_pubkey) in arena.iter().filter(|(_, script_pubkey)| {
+                        keep_only
+                            .as_ref()
+                            .is_none_or(|keep_only| keep_only.contains(script_pubkey))
+                    }) {
+                        unspent_coins.insert_decoded_coin(coin, script_pubkey);
                     }
                 }
+                let coins = arena.len() as u64;
+                metrics.add_coins_inserted(coins);
+                pending.push(arena);
+                if pending.iter().map(CoinArena::len).sum::<usize>() >= USPK_SET_INSERT_BATCH_SIZE {
+                    insert_arenas(&mut set, &mut pending, keep_only.as_ref(), &recycled)?;
+                }
+                let steps_before = loops_done / step_size;
+                loops_done += coins;
+                if loops_done / step_size > steps_before {
+                    info!(
+                        utxos = loops_done,
+                        "Utxos moved to database from chainstate."
+                    );
+                    event_log.log(LoggedEvent::PopulationProgress {
+                        done: loops_done,
+                        total: 0,
+                    });
+                }
+            }
+            insert_arenas(&mut set, &mut pending, keep_only.as_ref(), &recycled)?;
+            status.lock().unwrap()[0] = UspkSetStatus::Ready;
+            if let Some(unspent_coins) = unspent_coins.as_mut() {
+                unspent_coins.set_source(loops_done, None);
             }
+            Ok::<_, RetrieverError>((set, silent_payment_outputs, unspent_coins))
+        })
+        .await??;
+        let total_utxos = set.len() as u64;
+        self.set = set;
+        self.silent_payment_outputs = silent_payment_outputs.map(Arc::new);
+        self.unspent_coins = unspent_coins.map(Arc::new);
+        info!(
+            script_pubkeys = total_utxos,
+            duration_ms = creation_start.elapsed().as_millis() as u64,
+            "UTXO database populated from chainstate."
+        );
+        self.event_log.log(LoggedEvent::PopulationFinished {
+            total_utxos,
+            seconds: creation_start.elapsed().as_secs(),
+        });
+        Ok(())
+    }
+
+    /// Populates the set with the derived scriptpubkeys that are unspent in the dump file, found
+    /// by sorting the scriptpubkeys of the dump into runs on disk and merging them against the
+    /// sorted derived ones. Neither is held in memory, only the derived scriptpubkeys found.
+    /// The outputs of the silent payments search and the coins are not kept.
+    pub async fn merge_with_dump_file(
+        &mut self,
+        dump_file_path: &str,
+        derived: ExternalSorter,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), RetrieverError> {
+        let dump = UtxoDump::open(dump_file_path)?;
+        let (block_hash, total_coins) = (dump.block_hash, dump.utxo_set_size);
+        self.merge_with_coins(dump, total_coins, derived, cancellation_token)
+            .await?;
+        self.tip = Some(block_hash);
+        Ok(())
+    }
+
+    /// Same as `merge_with_dump_file`, reading Bitcoin Core's chainstate database directly.
+    pub async fn merge_with_chainstate(
+        &mut self,
+        chainstate_dir: &str,
+        derived: ExternalSorter,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), RetrieverError> {
+        let chainstate = ChainstateReader::open(chainstate_dir)?;
+        self.merge_with_coins(chainstate, 0, derived, cancellation_token)
+            .await
+    }
+
+    /// Sorts the scriptpubkeys of the coins, 0 of them if not known beforehand, into runs next to
+    /// those of the derived ones, and keeps the derived scriptpubkeys among them.
+    async fn merge_with_coins<S>(
+        &mut self,
+        coins: S,
+        total_coins: u64,
+        derived: ExternalSorter,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), RetrieverError>
+    where
+        S: CoinSource + Send + 'static,
+    {
+        let creation_start = Instant::now();
+        let status = self.status.clone();
+        let event_log = self.event_log.clone();
+        let cancellation_token = cancellation_token.clone();
+        let metrics = self.metrics.clone();
+        let step_size = 100000u64;
+        event_log.log(LoggedEvent::PopulationStarted {
+            total_utxos: total_coins,
         });
+        let (set, coins_read) = tokio::task::spawn_blocking(move || {
+            status.lock().unwrap()[0] = UspkSetStatus::Populating;
+            metrics.start_population(total_coins);
+            let mut unspent =
+                ExternalSorter::new(derived.dir(), "unspent", SORTED_MERGE_RUN_ITEMS)?;
+            let (arenas, recycled) = decode_in_background(coins);
+            let mut coins_read = 0u64;
+            for arena in arenas {
+                let arena =
+                    arena.inspect_err(|_| status.lock().unwrap()[0] = UspkSetStatus::Empty)?;
+                if cancellation_token.is_cancelled() {
+                    warn!("Population of the unspent scriptpubkey set cancelled.");
+                    status.lock().unwrap()[0] = UspkSetStatus::Empty;
+                    return Err(RetrieverError::Cancelled);
+                }
+                for (_, script_pubkey) in arena.iter() {
+                    unspent.push(digest_script_pubkey(script_pubkey), &[])?;
+                }
+                let coins = arena.len() as u64;
+                metrics.add_coins_inserted(coins);
+                let _ = recycled.send(arena);
+                let steps_before = coins_read / step_size;
+                coins_read += coins;
+                if coins_read / step_size > steps_before {
+                    info!(utxos = coins_read, "Utxos sorted to disk.");
+                    event_log.log(LoggedEvent::PopulationProgress {
+                        done: coins_read,
+                        total: total_coins,
+                    });
+                }
+            }
+            info!("Merging the sorted utxos with the sorted derived scriptpubkeys.");
+            let found = merge_join(derived.finish()?, unspent.finish()?)
+                .inspect_err(|_| status.lock().unwrap()[0] = UspkSetStatus::Empty)?;
+            let mut set = MemoryUspkSet::new(found.len());
+            set.insert_all(&found);
+            status.lock().unwrap()[0] = UspkSetStatus::Ready;
+            Ok::<_, RetrieverError>((set, coins_read))
+        })
+        .await??;
         info!(
-            "UTXO database of {} unspent scripts populated in ~{} mins.",
-            total_loops.to_formatted_string(&Locale::en),
-            1 + creation_start.elapsed().as_secs() / 60
+            script_pubkeys = set.len(),
+            utxos = coins_read,
+            duration_ms = creation_start.elapsed().as_millis() as u64,
+            "Derived scriptpubkeys found among the utxos."
         );
-        self.set = Arc::new(set_receiver.await.unwrap());
+        self.set = UspkStore::Memory(Arc::new(set));
+        self.event_log.log(LoggedEvent::PopulationFinished {
+            total_utxos: coins_read,
+            seconds: creation_start.elapsed().as_secs(),
+        });
         Ok(())
     }
 
-    pub fn get_immutable_inner_set(&self) -> Arc<hashbrown::HashSet<Vec<u8>>> {
+    pub fn get_immutable_inner_set(&self) -> UspkStore {
         self.set.clone()
     }
 
+    pub fn get_silent_payment_outputs(&self) -> Option<Arc<SilentPaymentOutputs>> {
+        self.silent_payment_outputs.clone()
+    }
+
+    pub fn get_unspent_coins(&self) -> Option<Arc<UnspentCoins>> {
+        self.unspent_coins.clone()
+    }
+
     pub fn get_status(&self) -> UspkSetStatus {
         self.status.lock().unwrap()[0]
     }
 
+    /// Whether blocks were applied out of order, so the set may lack coins a reorg brought back
+    /// and keep outputs of disconnected blocks until populated again.
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Adds the scriptpubkeys of the outputs of a new block, so the set stays current without a
+    /// new dump. When the coins are kept, the scriptpubkeys whose last coin the block spends are
+    /// removed too, from an in-memory set; otherwise the set keeps no outpoints and only grows,
+    /// and finds are verified against the node when their details are fetched. A block that
+    /// does not extend the last one makes the set stale. Returns the number of scriptpubkeys
+    /// that were new to the set. Taproot outputs are kept for the silent payments search too.
+    pub fn apply_block(&mut self, block: &Block) -> Result<usize, RetrieverError> {
+        let block_hash = block.block_hash();
+        if self.tip == Some(block_hash) {
+            return Ok(0);
+        }
+        if let Some(tip) = self.tip.filter(|tip| *tip != block.header.prev_blockhash) {
+            if !self.stale {
+                warn!(
+                    "Block {} does not extend block {}, after a reorg or missed blocks. The \
+                     unspent scriptpubkey set is stale until populated again.",
+                    block_hash, tip
+                );
+            }
+            self.stale = true;
+        }
+        self.tip = Some(block_hash);
+        if let (Some(silent_payment_outputs), Ok(height)) = (
+            self.silent_payment_outputs.as_mut(),
+            block.bip34_block_height(),
+        ) {
+            Arc::make_mut(silent_payment_outputs).insert_block(height as u32, block);
+        }
+        let spent_script_pubkeys = match (self.unspent_coins.as_mut(), block.bip34_block_height()) {
+            (Some(unspent_coins), Ok(height)) => {
+                Arc::make_mut(unspent_coins).insert_block(height as u32, block)
+            }
+            _ => vec![],
+        };
+        let new_script_pubkeys = self.set.insert_all(
+            &block
+                .txdata
+                .iter()
+                .flat_map(|tx| tx.output.iter())
+                .filter(|txout| !txout.script_pubkey.is_op_return())
+                .filter(|txout| {
+                    self.keep_only
+                        .as_ref()
+                        .is_none_or(|keep_only| keep_only.contains(txout.script_pubkey.as_bytes()))
+                })
+                .map(|txout| txout.script_pubkey.as_bytes())
+                .collect::<Vec<_>>(),
+        )?;
+        let removed = self.set.remove_digests(&spent_script_pubkeys);
+        if removed > 0 {
+            info!(
+                "{} scriptpubkeys spent by block {} removed from the set.",
+                removed, block_hash
+            );
+        }
+        Ok(new_script_pubkeys)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.set.is_empty()
     }
 }
 
+/// Decodes the coins into arenas on a thread of their own, which hands them over one after
+/// another, so the next coins are decoded while the previous ones are inserted into the set.
+/// Arenas sent back through the returned sender are refilled instead of new ones allocated. The
+/// thread stops after the first error, or once the coins are no longer wanted.
+fn decode_in_background<S>(
+    mut coins: S,
+) -> (
+    mpsc::Receiver<Result<CoinArena, RetrieverError>>,
+    mpsc::Sender<CoinArena>,
+)
+where
+    S: CoinSource + Send + 'static,
+{
+    let (sender, receiver) = mpsc::sync_channel(DECODED_COINS_BATCHES_AHEAD);
+    let (recycler, recycled) = mpsc::channel::<CoinArena>();
+    thread::spawn(move || loop {
+        let mut arena = recycled
+            .try_recv()
+            .unwrap_or_else(|_| CoinArena::with_capacity(DECODED_COINS_BATCH_SIZE));
+        let (arena, more) = match arena.refill(&mut coins, DECODED_COINS_BATCH_SIZE) {
+            Ok(more) => (Ok(arena), more),
+            Err(err) => (Err(err), false),
+        };
+        if sender.send(arena).is_err() || !more {
+            return;
+        }
+    });
+    (receiver, recycler)
+}
+
+/// Inserts the scriptpubkeys of the arenas into the set, only those among the kept ones if
+/// given, straight from the arenas, and sends the arenas back to be refilled.
+fn insert_arenas(
+    set: &mut UspkStore,
+    arenas: &mut Vec<CoinArena>,
+    keep_only: Option<&UspkStore>,
+    recycler: &mpsc::Sender<CoinArena>,
+) -> Result<(), RetrieverError> {
+    let script_pubkeys = arenas
+        .iter()
+        .flat_map(CoinArena::iter)
+        .map(|(_, script_pubkey)| script_pubkey)
+        .filter(|script_pubkey| keep_only.is_none_or(|keep_only| keep_only.contains(script_pubkey)))
+        .collect::<Vec<_>>();
+    set.insert_all(&script_pubkeys)?;
+    for arena in arenas.drain(..) {
+        let _ = recycler.send(arena);
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UspkSetStatus {
     Empty,
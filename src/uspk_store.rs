@@ -0,0 +1,786 @@
+use std::{
+    fmt,
+    hash::{BuildHasher, BuildHasherDefault, Hasher, RandomState},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+};
+
+use fastbloom::{AtomicBloomFilter, BloomFilter};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use serde::{Deserialize, Serialize};
+use siphasher::sip128::{Hasher128, SipHasher13};
+use tracing::{info, warn};
+
+use crate::error::RetrieverError;
+
+/// The table of the unspent scriptpubkeys, with no values.
+const USPK_TABLE: TableDefinition<&[u8], ()> = TableDefinition::new("unspent_script_pubkeys");
+/// The share of lookups of absent scriptpubkeys the bloom filters let through to the set.
+const BLOOM_FALSE_POSITIVE_RATE: f64 = 0.01;
+/// The bytes of the digest the in-memory set keeps of each scriptpubkey.
+const SCRIPT_PUBKEY_DIGEST_LEN: usize = 16;
+/// The seed of the in-memory prefilter, fixed so a filter read back from the cache of a previous
+/// run hashes scriptpubkeys as it did when it was built.
+const MEMORY_PREFILTER_SEED: u128 = 0;
+/// The page cache redb keeps in memory, well below what the in-memory set needs.
+const DISK_USPK_SET_CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+/// Where the unspent scriptpubkeys are kept once populated.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum UspkSetBackend {
+    /// A hash set in memory, the fastest, which needs well over 8 GB of memory for mainnet.
+    Memory,
+    /// A database in the data dir, behind a bloom filter of about 10 bits per scriptpubkey in
+    /// memory, so only the few lookups the filter lets through read the disk.
+    Disk,
+}
+
+/// The unspent scriptpubkeys, in one of the backends.
+#[derive(Debug, Clone)]
+pub enum UspkStore {
+    Memory(Arc<MemoryUspkSet>),
+    Disk(Arc<DiskUspkSet>),
+    /// In memory up to a cap, and on disk past it.
+    Spilling(Arc<SpillingUspkSet>),
+}
+
+impl Default for UspkStore {
+    fn default() -> Self {
+        UspkStore::Memory(Arc::new(MemoryUspkSet::new(0)))
+    }
+}
+
+impl UspkStore {
+    pub fn contains(&self, script_pubkey: &[u8]) -> bool {
+        match self {
+            UspkStore::Memory(set) => set.contains(script_pubkey),
+            UspkStore::Disk(set) => set.contains(script_pubkey),
+            UspkStore::Spilling(set) => set.contains(script_pubkey),
+        }
+    }
+
+    /// Adds the scriptpubkeys and returns the number of them that were new to the store.
+    pub fn insert_all<S: AsRef<[u8]> + Sync>(
+        &mut self,
+        script_pubkeys: &[S],
+    ) -> Result<usize, RetrieverError> {
+        match self {
+            UspkStore::Memory(set) => Ok(Arc::make_mut(set).insert_all(script_pubkeys)),
+            UspkStore::Disk(set) => set.insert_all(script_pubkeys),
+            UspkStore::Spilling(set) => Arc::make_mut(set).insert_all(script_pubkeys),
+        }
+    }
+
+    /// Removes the scriptpubkeys of the digests, as `digest_script_pubkey` makes them, and
+    /// returns the number removed. Only the in-memory set can, the others keep the scriptpubkeys
+    /// themselves and cannot find them by digest.
+    pub fn remove_digests(&mut self, digests: &[ScriptPubKeyDigest]) -> usize {
+        match self {
+            UspkStore::Memory(set) if !digests.is_empty() => {
+                Arc::make_mut(set).remove_digests(digests)
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            UspkStore::Memory(set) => set.len(),
+            UspkStore::Disk(set) => set.len(),
+            UspkStore::Spilling(set) => set.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl FromIterator<Vec<u8>> for UspkStore {
+    fn from_iter<T: IntoIterator<Item = Vec<u8>>>(iter: T) -> Self {
+        let script_pubkeys = iter.into_iter().collect::<Vec<_>>();
+        let mut set = MemoryUspkSet::new(script_pubkeys.len());
+        set.insert_all(&script_pubkeys);
+        UspkStore::Memory(Arc::new(set))
+    }
+}
+
+/// The keyed SipHash-1-3 of a scriptpubkey, 128 bits long.
+pub(crate) type ScriptPubKeyDigest = [u8; SCRIPT_PUBKEY_DIGEST_LEN];
+
+/// The key of the digests of scriptpubkeys, drawn once per process, so no scriptpubkeys can be
+/// crafted ahead of time to collide in the sets.
+static SCRIPT_PUBKEY_DIGEST_KEY: LazyLock<[u8; 16]> = LazyLock::new(|| {
+    let random_state = RandomState::new();
+    let mut key = [0u8; 16];
+    key[..8].copy_from_slice(&random_state.hash_one(0u8).to_le_bytes());
+    key[8..].copy_from_slice(&random_state.hash_one(1u8).to_le_bytes());
+    key
+});
+
+pub(crate) fn digest_script_pubkey(script_pubkey: &[u8]) -> ScriptPubKeyDigest {
+    digest_script_pubkey_with_key(&SCRIPT_PUBKEY_DIGEST_KEY, script_pubkey)
+}
+
+fn digest_script_pubkey_with_key(key: &[u8; 16], script_pubkey: &[u8]) -> ScriptPubKeyDigest {
+    let mut hasher = SipHasher13::new_with_key(key);
+    hasher.write(script_pubkey);
+    hasher.finish128().as_bytes()
+}
+
+/// Splits a digest into the index it is kept under in the table of the in-memory set and the
+/// check kept along with it.
+fn split_digest(digest: &ScriptPubKeyDigest) -> (u64, u64) {
+    let (index, check) = digest.split_at(8);
+    (
+        u64::from_le_bytes(index.try_into().unwrap()),
+        u64::from_le_bytes(check.try_into().unwrap()),
+    )
+}
+
+/// Hashes the indices of the in-memory set to themselves, as they are keyed hashes already.
+#[derive(Default)]
+struct DigestIndexHasher(u64);
+
+impl Hasher for DigestIndexHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ *byte as u64;
+        }
+    }
+
+    fn write_u64(&mut self, index: u64) {
+        self.0 = index;
+    }
+}
+
+type DigestIndexTable<V> = hashbrown::HashMap<u64, V, BuildHasherDefault<DigestIndexHasher>>;
+
+/// Unspent scriptpubkeys in a hash table in memory, behind a bloom filter built along with it.
+/// Nearly every derived scriptpubkey misses, and the filter, a fraction of the size of the set,
+/// rules most misses out without hashing the scriptpubkey into the set and reading its far
+/// larger table.
+///
+/// The set keeps a 16 byte keyed SipHash digest of each scriptpubkey inline rather than its
+/// bytes on the heap, which takes less than half the memory. The table is indexed by the first
+/// 8 bytes of the digest and holds the other 8 as a check. Scriptpubkeys whose index is taken by
+/// another scriptpubkey with a different check are not lost: their checks go into a small table
+/// of collisions, which lookups of a taken index with a different check go through.
+#[derive(Clone)]
+pub struct MemoryUspkSet {
+    /// The key of the digests, kept with the set so a set read back from a cache digests as it
+    /// did when it was built.
+    key: [u8; 16],
+    set: DigestIndexTable<u64>,
+    collisions: DigestIndexTable<Vec<u64>>,
+    prefilter: BloomFilter,
+}
+
+impl fmt::Debug for MemoryUspkSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryUspkSet")
+            .field("len", &self.set.len())
+            .finish()
+    }
+}
+
+impl MemoryUspkSet {
+    /// An empty set whose filter is sized for the expected number of scriptpubkeys. The filter
+    /// still has no false negatives past that number, only more false positives.
+    pub fn new(expected_items: usize) -> Self {
+        MemoryUspkSet {
+            key: *SCRIPT_PUBKEY_DIGEST_KEY,
+            set: DigestIndexTable::default(),
+            collisions: DigestIndexTable::default(),
+            prefilter: BloomFilter::with_false_pos(BLOOM_FALSE_POSITIVE_RATE)
+                .seed(&MEMORY_PREFILTER_SEED)
+                .expected_items(expected_items.max(1)),
+        }
+    }
+
+    /// Writes the prefilter, the key and the digests, little endian and each preceded by its
+    /// length, the prefilter by its number of hashes too.
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), RetrieverError> {
+        writer.write_all(&self.prefilter.num_hashes().to_le_bytes())?;
+        writer.write_all(&(self.prefilter.as_slice().len() as u64).to_le_bytes())?;
+        for word in self.prefilter.iter() {
+            writer.write_all(&word.to_le_bytes())?;
+        }
+        writer.write_all(&self.key)?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        let collided = self
+            .collisions
+            .iter()
+            .flat_map(|(index, checks)| checks.iter().map(move |check| (index, check)));
+        for (index, check) in self.set.iter().chain(collided) {
+            writer.write_all(&index.to_le_bytes())?;
+            writer.write_all(&check.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads back a set written by `write_to`.
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, RetrieverError> {
+        let mut num_hashes = [0u8; 4];
+        reader.read_exact(&mut num_hashes)?;
+        let num_words = read_u64(reader)? as usize;
+        let mut words = Vec::with_capacity(num_words);
+        for _ in 0..num_words {
+            words.push(read_u64(reader)?);
+        }
+        if words.is_empty() {
+            return Err(RetrieverError::InvalidUspkSetCache);
+        }
+        let prefilter = BloomFilter::from_vec(words)
+            .seed(&MEMORY_PREFILTER_SEED)
+            .hashes(u32::from_le_bytes(num_hashes));
+        let mut key = [0u8; 16];
+        reader.read_exact(&mut key)?;
+        let num_digests = read_u64(reader)? as usize;
+        let mut set = MemoryUspkSet {
+            key,
+            set: DigestIndexTable::with_capacity_and_hasher(num_digests, Default::default()),
+            collisions: DigestIndexTable::default(),
+            prefilter,
+        };
+        let mut digest = [0u8; SCRIPT_PUBKEY_DIGEST_LEN];
+        for _ in 0..num_digests {
+            reader.read_exact(&mut digest)?;
+            set.insert_digest(&digest);
+        }
+        Ok(set)
+    }
+
+    pub fn contains(&self, script_pubkey: &[u8]) -> bool {
+        self.prefilter.contains(script_pubkey)
+            && self.contains_digest(&digest_script_pubkey_with_key(&self.key, script_pubkey))
+    }
+
+    fn contains_digest(&self, digest: &ScriptPubKeyDigest) -> bool {
+        let (index, check) = split_digest(digest);
+        match self.set.get(&index) {
+            Some(stored_check) if *stored_check == check => true,
+            Some(_) => self
+                .collisions
+                .get(&index)
+                .is_some_and(|checks| checks.contains(&check)),
+            None => false,
+        }
+    }
+
+    /// Adds the digest and returns whether it was new to the set.
+    fn insert_digest(&mut self, digest: &ScriptPubKeyDigest) -> bool {
+        let (index, check) = split_digest(digest);
+        match self.set.entry(index) {
+            hashbrown::hash_map::Entry::Vacant(entry) => {
+                entry.insert(check);
+                true
+            }
+            hashbrown::hash_map::Entry::Occupied(entry) if *entry.get() == check => false,
+            hashbrown::hash_map::Entry::Occupied(_) => {
+                let checks = self.collisions.entry(index).or_default();
+                if checks.contains(&check) {
+                    return false;
+                }
+                checks.push(check);
+                true
+            }
+        }
+    }
+
+    /// Removes the digest and returns whether it was in the set. A check in the collisions takes
+    /// the place of a removed one in the table.
+    fn remove_digest(&mut self, digest: &ScriptPubKeyDigest) -> bool {
+        let (index, check) = split_digest(digest);
+        let Some(stored_check) = self.set.get_mut(&index) else {
+            return false;
+        };
+        if *stored_check != check {
+            let Some(checks) = self.collisions.get_mut(&index) else {
+                return false;
+            };
+            let Some(position) = checks.iter().position(|collided| *collided == check) else {
+                return false;
+            };
+            checks.swap_remove(position);
+            if checks.is_empty() {
+                self.collisions.remove(&index);
+            }
+            return true;
+        }
+        match self.collisions.get_mut(&index) {
+            Some(checks) => {
+                *stored_check = checks.pop().unwrap();
+                if checks.is_empty() {
+                    self.collisions.remove(&index);
+                }
+            }
+            None => {
+                self.set.remove(&index);
+            }
+        }
+        true
+    }
+
+    /// Removes the scriptpubkeys of the digests, as `digest_script_pubkey` makes them, and
+    /// returns the number removed. A set read from the cache of another run is keyed otherwise
+    /// and keeps them. They stay in the prefilter, which only lets more lookups through.
+    pub fn remove_digests(&mut self, digests: &[ScriptPubKeyDigest]) -> usize {
+        if self.key != *SCRIPT_PUBKEY_DIGEST_KEY {
+            return 0;
+        }
+        digests
+            .iter()
+            .filter(|digest| self.remove_digest(digest))
+            .count()
+    }
+
+    /// Adds the scriptpubkeys and returns the number of them that were new to the set. They are
+    /// digested on the rayon pool before going into the set, which only one thread can fill.
+    pub fn insert_all<S: AsRef<[u8]> + Sync>(&mut self, script_pubkeys: &[S]) -> usize {
+        let digests = script_pubkeys
+            .par_iter()
+            .map(|script_pubkey| digest_script_pubkey_with_key(&self.key, script_pubkey.as_ref()))
+            .collect::<Vec<_>>();
+        let mut inserted = 0;
+        for (script_pubkey, digest) in script_pubkeys.iter().zip(digests) {
+            self.prefilter.insert(script_pubkey.as_ref());
+            if self.insert_digest(&digest) {
+                inserted += 1;
+            }
+        }
+        inserted
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len() + self.collisions.values().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// The bytes a set of the expected number of scriptpubkeys is projected to take, its table
+    /// grown to the power of two buckets that holds them and its prefilter sized for them. The
+    /// table of collisions is left out, as it is empty in all but about one in a thousand sets
+    /// of mainnet's size, and holds a check or two in those.
+    pub fn projected_memory_bytes(expected_items: usize) -> usize {
+        let buckets = match expected_items {
+            0 => 0,
+            1..=3 => 4,
+            expected_items => (expected_items * 8 / 7).next_power_of_two(),
+        };
+        buckets * (SCRIPT_PUBKEY_DIGEST_LEN + 1) + projected_bloom_filter_bytes(expected_items)
+    }
+
+    /// The most digests a set whose prefilter is sized for the expected number of scriptpubkeys
+    /// holds in the given bytes of memory. The table of the set has a power of two buckets of a
+    /// digest and a control byte each, and is full at 7/8 of them.
+    fn capacity_within(max_memory_bytes: usize, expected_items: usize) -> usize {
+        let prefilter_bytes = BloomFilter::with_false_pos(BLOOM_FALSE_POSITIVE_RATE)
+            .expected_items(expected_items.max(1))
+            .as_slice()
+            .len()
+            * 8;
+        let max_buckets =
+            max_memory_bytes.saturating_sub(prefilter_bytes) / (SCRIPT_PUBKEY_DIGEST_LEN + 1);
+        match max_buckets {
+            0..=7 => 0,
+            max_buckets => (1 << max_buckets.ilog2()) / 8 * 7,
+        }
+    }
+}
+
+/// The bytes of a bloom filter of the sets sized for the expected number of scriptpubkeys, from
+/// the optimal number of bits for its false positive rate, without allocating one.
+fn projected_bloom_filter_bytes(expected_items: usize) -> usize {
+    let bits = expected_items.max(1) as f64 * -BLOOM_FALSE_POSITIVE_RATE.ln()
+        / std::f64::consts::LN_2.powi(2);
+    bits.ceil() as usize / 8
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, RetrieverError> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Unspent scriptpubkeys in memory until the set takes as much memory as it is allowed, and in
+/// a temporary database on disk, behind its own bloom filter, from then on. Mainnet is read with
+/// a fraction of the memory it needs in memory, instead of running out of memory halfway, and
+/// only the lookups both filters let through are slowed down by the disk.
+#[derive(Debug, Clone)]
+pub struct SpillingUspkSet {
+    memory: MemoryUspkSet,
+    /// The most scriptpubkeys the memory set takes before its table outgrows the cap.
+    memory_capacity: usize,
+    spill_path: PathBuf,
+    /// Created once the memory set is full.
+    spill: Option<Arc<DiskUspkSet>>,
+    expected_items: usize,
+}
+
+impl SpillingUspkSet {
+    /// An empty set taking at most the given bytes of memory, besides the bloom filter and page
+    /// cache of the database it spills into at the path, sized for the expected number of
+    /// scriptpubkeys.
+    pub fn new(max_memory_bytes: usize, expected_items: usize, spill_path: PathBuf) -> Self {
+        let memory_capacity = MemoryUspkSet::capacity_within(max_memory_bytes, expected_items);
+        let mut memory = MemoryUspkSet::new(expected_items.min(memory_capacity));
+        memory.set.reserve(expected_items.min(memory_capacity));
+        SpillingUspkSet {
+            memory,
+            memory_capacity,
+            spill_path,
+            spill: None,
+            expected_items,
+        }
+    }
+
+    pub fn contains(&self, script_pubkey: &[u8]) -> bool {
+        self.memory.contains(script_pubkey)
+            || self
+                .spill
+                .as_ref()
+                .is_some_and(|spill| spill.contains(script_pubkey))
+    }
+
+    /// Adds the scriptpubkeys and returns the number of them that were new to the set. Those
+    /// the memory set has no room for go to the database.
+    pub fn insert_all<S: AsRef<[u8]> + Sync>(
+        &mut self,
+        script_pubkeys: &[S],
+    ) -> Result<usize, RetrieverError> {
+        let mut script_pubkeys = script_pubkeys
+            .iter()
+            .map(|script_pubkey| script_pubkey.as_ref())
+            .filter(|script_pubkey| !self.memory.contains(script_pubkey))
+            .collect::<Vec<_>>();
+        let room = self.memory_capacity.saturating_sub(self.memory.len());
+        let spilled = script_pubkeys.split_off(room.min(script_pubkeys.len()));
+        let mut inserted = self.memory.insert_all(&script_pubkeys);
+        if spilled.is_empty() {
+            return Ok(inserted);
+        }
+        if self.spill.is_none() {
+            warn!(
+                "The unspent scriptpubkey set reached its memory cap at {} scriptpubkeys, the rest go to disk.",
+                self.memory.len()
+            );
+            self.spill = Some(Arc::new(DiskUspkSet::create_temporary(
+                &self.spill_path,
+                self.expected_items.saturating_sub(self.memory.len()),
+            )?));
+        }
+        inserted += self.spill.as_ref().unwrap().insert_all(&spilled)?;
+        Ok(inserted)
+    }
+
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.spill.as_ref().map_or(0, |spill| spill.len())
+    }
+
+    /// The bytes a set capped at the given bytes is projected to take for the expected number of
+    /// scriptpubkeys, counting the filter and page cache of its spill as if it held them all.
+    pub fn projected_memory_bytes(max_memory_bytes: usize, expected_items: usize) -> usize {
+        match MemoryUspkSet::projected_memory_bytes(expected_items) {
+            memory_bytes if memory_bytes <= max_memory_bytes => memory_bytes,
+            _ => max_memory_bytes + DiskUspkSet::projected_memory_bytes(expected_items),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Unspent scriptpubkeys in a redb database on disk. A bloom filter in memory answers most
+/// lookups of absent scriptpubkeys, which nearly all lookups of a search are.
+pub struct DiskUspkSet {
+    path: PathBuf,
+    database: Database,
+    bloom: AtomicBloomFilter,
+    /// Whether the database is removed once the set is dropped.
+    temporary: bool,
+}
+
+impl fmt::Debug for DiskUspkSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiskUspkSet")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl DiskUspkSet {
+    /// Creates an empty set at the path, replacing any set a previous run left there, as the
+    /// bloom filter is not kept. The filter is sized for the expected number of scriptpubkeys.
+    pub fn create(path: &Path, expected_items: usize) -> Result<Self, RetrieverError> {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let database = redb::Builder::new()
+            .set_cache_size(DISK_USPK_SET_CACHE_BYTES)
+            .create(path)?;
+        let write = database.begin_write()?;
+        write.open_table(USPK_TABLE)?;
+        write.commit()?;
+        info!(
+            "Unspent scriptpubkey set created on disk at {}.",
+            path.display()
+        );
+        Ok(DiskUspkSet {
+            path: path.to_owned(),
+            database,
+            bloom: AtomicBloomFilter::with_false_pos(BLOOM_FALSE_POSITIVE_RATE)
+                .expected_items(expected_items.max(1)),
+            temporary: false,
+        })
+    }
+
+    /// The bytes of memory a set of the expected number of scriptpubkeys is projected to take,
+    /// its bloom filter and the page cache of the database, which keeps the rest on disk.
+    pub fn projected_memory_bytes(expected_items: usize) -> usize {
+        projected_bloom_filter_bytes(expected_items) + DISK_USPK_SET_CACHE_BYTES
+    }
+
+    /// Creates an empty set at the path as `create` does, which is removed once dropped.
+    pub fn create_temporary(path: &Path, expected_items: usize) -> Result<Self, RetrieverError> {
+        let mut set = Self::create(path, expected_items)?;
+        set.temporary = true;
+        Ok(set)
+    }
+
+    /// Adds the scriptpubkeys in a single write transaction and returns the number of them
+    /// that were new to the set.
+    pub fn insert_all<S: AsRef<[u8]>>(
+        &self,
+        script_pubkeys: &[S],
+    ) -> Result<usize, RetrieverError> {
+        let write = self.database.begin_write()?;
+        let mut inserted = 0;
+        {
+            let mut table = write.open_table(USPK_TABLE)?;
+            for script_pubkey in script_pubkeys.iter().map(AsRef::as_ref) {
+                if self.bloom.contains(script_pubkey) && table.get(script_pubkey)?.is_some() {
+                    continue;
+                }
+                table.insert(script_pubkey, ())?;
+                self.bloom.insert(script_pubkey);
+                inserted += 1;
+            }
+        }
+        write.commit()?;
+        Ok(inserted)
+    }
+
+    /// Whether the scriptpubkey is in the set. A failing read of the database leaves the search
+    /// unable to tell finds from misses, so it panics.
+    pub fn contains(&self, script_pubkey: &[u8]) -> bool {
+        self.bloom.contains(script_pubkey)
+            && self
+                .read(script_pubkey)
+                .expect("Reading the unspent scriptpubkey set on disk failed.")
+    }
+
+    fn read(&self, script_pubkey: &[u8]) -> Result<bool, RetrieverError> {
+        let read = self.database.begin_read()?;
+        let table = read.open_table(USPK_TABLE)?;
+        Ok(table.get(script_pubkey)?.is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.count()
+            .expect("Reading the unspent scriptpubkey set on disk failed.") as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn count(&self) -> Result<u64, RetrieverError> {
+        let read = self.database.begin_read()?;
+        let table = read.open_table(USPK_TABLE)?;
+        Ok(table.len()?)
+    }
+}
+
+impl Drop for DiskUspkSet {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_uspk_set_works_01() {
+        let mut set = MemoryUspkSet::new(2);
+        assert_eq!(set.insert_all(&[vec![0x51], vec![0x52], vec![0x51]]), 2);
+        // Past the expected number of scriptpubkeys, nothing is missed.
+        assert_eq!(
+            set.insert_all(&(0u32..1000).map(|i| i.to_le_bytes()).collect::<Vec<_>>()),
+            1000
+        );
+        assert!(set.contains(&[0x51]));
+        assert!((0u32..1000).all(|i| set.contains(&i.to_le_bytes())));
+        assert!(!set.contains(&[0x53]));
+        assert_eq!(set.len(), 1002);
+        let mut bytes = vec![];
+        set.write_to(&mut bytes).unwrap();
+        let read = MemoryUspkSet::read_from(&mut bytes.as_slice()).unwrap();
+        assert!(read.contains(&[0x51]));
+        assert!((0u32..1000).all(|i| read.contains(&i.to_le_bytes())));
+        assert!(!read.contains(&[0x53]));
+        assert_eq!(read.len(), 1002);
+    }
+
+    #[test]
+    fn memory_uspk_set_works_02() {
+        let mut set = MemoryUspkSet::new(4);
+        let digest = |index: u64, check: u64| {
+            let mut digest = [0u8; SCRIPT_PUBKEY_DIGEST_LEN];
+            digest[..8].copy_from_slice(&index.to_le_bytes());
+            digest[8..].copy_from_slice(&check.to_le_bytes());
+            digest
+        };
+        assert!(set.insert_digest(&digest(7, 1)));
+        // Another digest under the same index goes to the collisions.
+        assert!(set.insert_digest(&digest(7, 2)));
+        assert!(!set.insert_digest(&digest(7, 2)));
+        assert!(set.insert_digest(&digest(8, 2)));
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.collisions.len(), 1);
+        for set in [set.clone(), {
+            let mut bytes = vec![];
+            set.write_to(&mut bytes).unwrap();
+            MemoryUspkSet::read_from(&mut bytes.as_slice()).unwrap()
+        }] {
+            assert!(set.contains_digest(&digest(7, 1)));
+            assert!(set.contains_digest(&digest(7, 2)));
+            assert!(set.contains_digest(&digest(8, 2)));
+            assert!(!set.contains_digest(&digest(7, 3)));
+            assert!(!set.contains_digest(&digest(9, 1)));
+            assert_eq!(set.len(), 3);
+        }
+    }
+
+    #[test]
+    fn memory_uspk_set_works_03() {
+        let mut set = MemoryUspkSet::new(4);
+        let digest = |index: u64, check: u64| {
+            let mut digest = [0u8; SCRIPT_PUBKEY_DIGEST_LEN];
+            digest[..8].copy_from_slice(&index.to_le_bytes());
+            digest[8..].copy_from_slice(&check.to_le_bytes());
+            digest
+        };
+        for (index, check) in [(7, 1), (7, 2), (7, 3), (8, 1)] {
+            set.insert_digest(&digest(index, check));
+        }
+        // A collided check takes the place of the removed one in the table.
+        assert!(set.remove_digest(&digest(7, 1)));
+        assert!(!set.remove_digest(&digest(7, 1)));
+        assert!(set.contains_digest(&digest(7, 2)));
+        assert!(set.contains_digest(&digest(7, 3)));
+        assert!(set.remove_digest(&digest(7, 3)));
+        assert!(set.collisions.is_empty());
+        assert!(!set.remove_digest(&digest(9, 1)));
+        assert_eq!(set.len(), 2);
+        set.insert_all(&[[0x51], [0x52]]);
+        assert_eq!(
+            set.remove_digests(&[digest_script_pubkey(&[0x51]), digest(8, 1), digest(8, 2)]),
+            2
+        );
+        assert!(!set.contains(&[0x51]));
+        assert!(set.contains(&[0x52]));
+        assert!(set.contains_digest(&digest(7, 2)));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn projected_memory_bytes_works_01() {
+        let mut set = MemoryUspkSet::new(1000);
+        set.insert_all(&(0u32..1000).map(|i| i.to_le_bytes()).collect::<Vec<_>>());
+        // The table of 1000 digests has 2048 buckets, full at 1792 of them.
+        assert_eq!(set.set.capacity(), 1792);
+        let prefilter_bytes = set.prefilter.as_slice().len() * 8;
+        let projected_prefilter_bytes = MemoryUspkSet::projected_memory_bytes(1000) - 2048 * 17;
+        assert!(projected_prefilter_bytes.abs_diff(prefilter_bytes) < prefilter_bytes / 10);
+        let memory_bytes = MemoryUspkSet::projected_memory_bytes(1000);
+        assert_eq!(
+            SpillingUspkSet::projected_memory_bytes(memory_bytes, 1000),
+            memory_bytes
+        );
+        assert_eq!(
+            SpillingUspkSet::projected_memory_bytes(10_000, 1000),
+            10_000 + DiskUspkSet::projected_memory_bytes(1000)
+        );
+    }
+
+    #[test]
+    fn disk_uspk_set_works_01() {
+        let path = std::env::temp_dir().join("disk_uspk_set_works_01.redb");
+        let mut store = UspkStore::Disk(Arc::new(DiskUspkSet::create(&path, 100).unwrap()));
+        assert!(store.is_empty());
+        assert_eq!(
+            store
+                .insert_all(&[vec![0x51], vec![0x52], vec![0x51]])
+                .unwrap(),
+            2
+        );
+        assert_eq!(store.insert_all(&[vec![0x52], vec![0x53]]).unwrap(), 1);
+        assert_eq!(store.len(), 3);
+        assert!(store.contains(&[0x51]));
+        assert!(store.contains(&[0x53]));
+        assert!(!store.contains(&[0x54]));
+        drop(store);
+        let store = DiskUspkSet::create(&path, 100).unwrap();
+        assert!(store.is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn spilling_uspk_set_works_01() {
+        let path = std::env::temp_dir().join("spilling_uspk_set_works_01.redb");
+        // Room for the prefilter and a table of 64 buckets, 56 scriptpubkeys.
+        let max_memory_bytes = MemoryUspkSet::new(1000).prefilter.as_slice().len() * 8 + 64 * 17;
+        let mut store = UspkStore::Spilling(Arc::new(SpillingUspkSet::new(
+            max_memory_bytes,
+            1000,
+            path.clone(),
+        )));
+        assert_eq!(
+            store
+                .insert_all(&(0u32..50).map(|i| i.to_le_bytes()).collect::<Vec<_>>())
+                .unwrap(),
+            50
+        );
+        assert!(!path.exists());
+        assert_eq!(
+            store
+                .insert_all(&(0u32..1000).map(|i| i.to_le_bytes()).collect::<Vec<_>>())
+                .unwrap(),
+            950
+        );
+        assert!(path.exists());
+        let UspkStore::Spilling(set) = &store else {
+            unreachable!()
+        };
+        assert_eq!(set.memory.len(), 56);
+        assert_eq!(store.len(), 1000);
+        assert!((0u32..1000).all(|i| store.contains(&i.to_le_bytes())));
+        assert!(!store.contains(&[0x51]));
+        drop(store);
+        assert!(!path.exists());
+    }
+}
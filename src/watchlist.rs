@@ -0,0 +1,194 @@
+use std::{fs, str::FromStr};
+
+use bitcoin::{address::NetworkUnchecked, Address, ScriptBuf};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    descriptor_checksum::raw_descriptor_with_checksum, error::RetrieverError, uspk_store::UspkStore,
+};
+
+/// Scriptpubkeys computed elsewhere or of known addresses, checked against the unspent
+/// scriptpubkey set directly instead of deriving any key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchlist {
+    network: bitcoin::Network,
+    script_pubkeys: Vec<ScriptBuf>,
+}
+
+/// A scriptpubkey of the watchlist found unspent, with its address if it has one, its raw()
+/// descriptor with checksum, and its amount once its details are fetched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchlistFind {
+    pub script_pubkey: ScriptBuf,
+    pub address: Option<String>,
+    pub descriptor: String,
+    pub amount_sats: Option<u64>,
+}
+
+/// Parses scriptpubkeys in hex, one per line. Blank lines and lines starting with # are
+/// skipped, and the first invalid line is reported with its number.
+fn parse_script_pubkeys(content: &str) -> Result<Vec<ScriptBuf>, RetrieverError> {
+    let mut script_pubkeys = vec![];
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match ScriptBuf::from_hex(line) {
+            Ok(script_pubkey) if !script_pubkey.is_empty() => script_pubkeys.push(script_pubkey),
+            _ => {
+                error!(
+                    "Line {} of the watchlist is no scriptpubkey in hex: {}",
+                    index + 1,
+                    line
+                );
+                return Err(RetrieverError::InvalidWatchlist);
+            }
+        }
+    }
+    Ok(script_pubkeys)
+}
+
+/// The scriptpubkeys of addresses, which must be of the network.
+fn from_addresses_to_script_pubkeys(
+    addresses: &[String],
+    network: bitcoin::Network,
+) -> Result<Vec<ScriptBuf>, RetrieverError> {
+    addresses
+        .iter()
+        .map(|address| {
+            Address::<NetworkUnchecked>::from_str(address.trim())
+                .ok()
+                .and_then(|address| address.require_network(network).ok())
+                .map(|address| address.script_pubkey())
+                .ok_or_else(|| {
+                    error!("{} is no {} address.", address, network);
+                    RetrieverError::InvalidWatchlist
+                })
+        })
+        .collect()
+}
+
+impl Watchlist {
+    /// Reads the file of scriptpubkeys in hex, one per line, if any, and adds the scriptpubkeys
+    /// of the addresses.
+    pub fn new(
+        script_pubkey_file: Option<&str>,
+        addresses: &[String],
+        network: bitcoin::Network,
+    ) -> Result<Self, RetrieverError> {
+        let mut script_pubkeys = match script_pubkey_file {
+            Some(file_path) => {
+                let content = fs::read_to_string(file_path).inspect_err(|err| {
+                    error!("Reading the scriptpubkey watchlist failed: {}", err);
+                })?;
+                parse_script_pubkeys(&content)?
+            }
+            None => vec![],
+        };
+        script_pubkeys.extend(from_addresses_to_script_pubkeys(addresses, network)?);
+        if script_pubkeys.is_empty() {
+            error!("The watchlist is empty.");
+            return Err(RetrieverError::InvalidWatchlist);
+        }
+        Ok(Watchlist {
+            network,
+            script_pubkeys,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.script_pubkeys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.script_pubkeys.is_empty()
+    }
+
+    /// The scriptpubkeys of the watchlist in the unspent scriptpubkey set, each once.
+    pub fn search(&self, uspk_set: &UspkStore) -> Vec<WatchlistFind> {
+        let mut finds: Vec<WatchlistFind> = vec![];
+        for script_pubkey in self.script_pubkeys.iter() {
+            if !uspk_set.contains(script_pubkey.as_bytes()) {
+                continue;
+            }
+            let find = WatchlistFind {
+                script_pubkey: script_pubkey.to_owned(),
+                address: Address::from_script(script_pubkey, self.network)
+                    .ok()
+                    .map(|address| address.to_string()),
+                descriptor: raw_descriptor_with_checksum(script_pubkey).unwrap(),
+                amount_sats: None,
+            };
+            if !finds.contains(&find) {
+                finds.push(find);
+            }
+        }
+        finds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchlist_works_01() {
+        let script_pubkeys = parse_script_pubkeys(
+            "# p2wpkh\n0014751e76e8199196d454941c45d1b3a323f1433bd6\n\n  76a914751e76e8199196d454941c45d1b3a323f1433bd688ac\n0014751e76e8199196d454941c45d1b3a323f1433bd6\n",
+        )
+        .unwrap();
+        assert_eq!(script_pubkeys.len(), 3);
+        let watchlist = Watchlist {
+            network: bitcoin::Network::Bitcoin,
+            script_pubkeys,
+        };
+        let uspk_set = UspkStore::from_iter([watchlist.script_pubkeys[0].to_bytes(), vec![0x51]]);
+        assert_eq!(
+            watchlist.search(&uspk_set),
+            vec![WatchlistFind {
+                script_pubkey: ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6")
+                    .unwrap(),
+                address: Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string()),
+                descriptor: "raw(0014751e76e8199196d454941c45d1b3a323f1433bd6)#t687veyk"
+                    .to_string(),
+                amount_sats: None,
+            }]
+        );
+        assert!(matches!(
+            parse_script_pubkeys("0014751e\nnot hex"),
+            Err(RetrieverError::InvalidWatchlist)
+        ));
+    }
+
+    #[test]
+    fn watchlist_works_02() {
+        let watchlist = Watchlist::new(
+            None,
+            &[
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string(),
+                "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".to_string(),
+            ],
+            bitcoin::Network::Bitcoin,
+        )
+        .unwrap();
+        assert_eq!(
+            watchlist.script_pubkeys[0].to_hex_string(),
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6"
+        );
+        assert!(watchlist.script_pubkeys[1].is_p2pkh());
+        assert!(matches!(
+            Watchlist::new(
+                None,
+                &["bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string()],
+                bitcoin::Network::Testnet
+            ),
+            Err(RetrieverError::InvalidWatchlist)
+        ));
+        assert!(matches!(
+            Watchlist::new(None, &[], bitcoin::Network::Bitcoin),
+            Err(RetrieverError::InvalidWatchlist)
+        ));
+    }
+}
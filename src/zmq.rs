@@ -0,0 +1,73 @@
+use tracing::{error, info};
+use zeromq::{Socket, SocketRecv, SubSocket};
+
+use crate::error::RetrieverError;
+
+/// A ZeroMQ subscriber to the notifications bitcoind publishes, e.g. with -zmqpubrawblock.
+pub struct ZmqSubscriber {
+    socket: SubSocket,
+}
+
+impl ZmqSubscriber {
+    /// Connects to a publisher such as tcp://127.0.0.1:28332 and subscribes to the topic.
+    pub async fn connect(endpoint: &str, topic: &str) -> Result<Self, RetrieverError> {
+        if !endpoint.starts_with("tcp://") {
+            error!("Only tcp:// zmq endpoints are supported.");
+            return Err(RetrieverError::InvalidZmqEndpoint);
+        }
+        let mut socket = SubSocket::new();
+        socket.connect(endpoint).await?;
+        socket.subscribe(topic).await?;
+        info!("Subscribed to {} notifications at {}.", topic, endpoint);
+        Ok(ZmqSubscriber { socket })
+    }
+
+    /// Receives the next message as its frames.
+    pub async fn recv(&mut self) -> Result<Vec<Vec<u8>>, RetrieverError> {
+        let message = self.socket.recv().await?;
+        Ok(message
+            .into_vec()
+            .into_iter()
+            .map(|frame| frame.to_vec())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use zeromq::{PubSocket, SocketSend, ZmqMessage};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn zmq_subscriber_works_01() {
+        let mut publisher = PubSocket::new();
+        let endpoint = publisher.bind("tcp://127.0.0.1:0").await.unwrap();
+        let mut subscriber = ZmqSubscriber::connect(&endpoint.to_string(), "rawblock")
+            .await
+            .unwrap();
+        // The subscription reaches the publisher some time after connecting, and a publisher
+        // drops the messages of topics nobody subscribes to, so they are sent until one arrives.
+        let publishing = tokio::spawn(async move {
+            loop {
+                for topic in ["hashblock", "rawblock"] {
+                    let mut message = ZmqMessage::from(topic);
+                    message.push_back(vec![7u8; 300].into());
+                    message.push_back(vec![0u8; 4].into());
+                    publisher.send(message).await.unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        let frames = tokio::time::timeout(Duration::from_secs(10), subscriber.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        publishing.abort();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0], b"rawblock");
+        assert_eq!(frames[1], vec![7u8; 300]);
+    }
+}